@@ -1,5 +1,6 @@
 use std::ops;
 use std::rc::Rc;
+use std::hash::{Hash, Hasher};
 use super::value::{Value, ty_int};
 use crate::{vli, UTCDateTime};
 use crate::BsonResult;
@@ -41,13 +42,18 @@ impl Array {
         Array(data)
     }
 
-    pub fn iter(&self) -> Iter {
+    pub fn iter(&self) -> Iter<'_> {
         Iter {
             arr: self,
             index: 0,
         }
     }
 
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&Value> {
+        self.0.get(index)
+    }
+
     pub fn push(&mut self, elm: Value) {
         self.0.push(elm)
     }
@@ -86,9 +92,9 @@ impl Array {
                     result.push(ty_int::BOOLEAN);
 
                     if *bl {
-                        result.push(0x00);
-                    } else {
                         result.push(0x01);
+                    } else {
+                        result.push(0x00);
                     }
                 }
 
@@ -142,6 +148,12 @@ impl Array {
                     vli::encode(&mut result, ts as i64)?;
                 }
 
+                Value::Timestamp { time, inc } => {
+                    result.push(ty_int::TIMESTAMP);  // not standard, big endian
+                    result.extend_from_slice(&time.to_be_bytes());
+                    result.extend_from_slice(&inc.to_be_bytes());
+                }
+
             }
         }
 
@@ -255,6 +267,13 @@ impl Array {
                     arr.0.push(Value::UTCDateTime(Rc::new(UTCDateTime::new(integer))));
                 }
 
+                ty_int::TIMESTAMP => {
+                    let (time, inc) = crate::value::timestamp_from_bytes(&bytes[ptr..])?;
+                    ptr += 8;
+
+                    arr.0.push(Value::Timestamp { time, inc });
+                }
+
                 _ => return Err(BsonErr::ParseError(parse_error_reason::UNEXPECTED_DOCUMENT_FLAG.into())),
             }
 
@@ -283,6 +302,25 @@ impl ops::IndexMut<usize> for Array {
 
 }
 
+/// The arrays are equal if the elements are equal in order.
+impl PartialEq for Array {
+
+    fn eq(&self, other: &Array) -> bool {
+        self.0 == other.0
+    }
+
+}
+
+impl Eq for Array {}
+
+impl Hash for Array {
+
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+
+}
+
 impl Default for Array {
 
     fn default() -> Self {
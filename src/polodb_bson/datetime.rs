@@ -1,6 +1,8 @@
 use std::time::{SystemTime, UNIX_EPOCH};
 
-#[derive(Debug, Clone)]
+/// Milliseconds since the Unix epoch,
+/// serialized with the type code of BSON's UTC datetime.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct UTCDateTime {
     timestamp: u64,
 }
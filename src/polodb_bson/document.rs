@@ -1,7 +1,8 @@
 use std::rc::Rc;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use super::value::{Value, ty_int};
-use super::linked_hash_map::{LinkedHashMap, Iter};
+use super::linked_hash_map::{LinkedHashMap, Iter, Keys};
 use crate::{vli, UTCDateTime};
 use crate::BsonResult;
 use crate::error::{BsonErr, parse_error_reason};
@@ -15,6 +16,7 @@ pub struct Document {
 
 impl Document {
 
+    /// A document with an `ObjectId` generated by `id_maker` as `_id`.
     pub fn new(id_maker: &mut ObjectIdMaker) -> Document {
         let id = id_maker.mk_object_id();
         let mut result = Document {
@@ -30,21 +32,57 @@ impl Document {
         }
     }
 
+    /// A document with the `_id` supplied, e.g. the key from an external system.
+    ///
+    /// The `_id` is kept when the document is inserted,
+    /// an `ObjectId` is generated only for the document without `_id`.
+    pub fn with_id(id: Value) -> Document {
+        let mut result = Document::new_without_id();
+        result.map.insert("_id".to_string(), id);
+        result
+    }
+
     #[inline]
     pub fn insert(&mut self, key: String, value: Value) -> Option<Value> {
         self.map.insert(key, value)
     }
 
+    /// Remove the field and return its value,
+    /// the remaining fields keep the order of insertion.
     #[inline]
     pub fn remove(&mut self, key: &str) -> Option<Value> {
         self.map.remove(key)
     }
 
+    #[inline]
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.map.contains_key(key)
+    }
+
     #[inline]
     pub fn get(&self, key: &str) -> Option<&Value> {
         self.map.get(key)
     }
 
+    /// Get the value by a dotted path like "address.city",
+    /// the numeric segments index into the arrays, e.g. "tags.0".
+    ///
+    /// None returns if any segment is missing.
+    pub fn get_path(&self, dotted: &str) -> Option<&Value> {
+        let mut segments = dotted.split('.');
+        let mut current = self.get(segments.next()?)?;
+
+        for segment in segments {
+            current = match current {
+                Value::Document(doc) => doc.get(segment)?,
+                Value::Array(arr) => arr.get(segment.parse::<usize>().ok()?)?,
+                _ => return None,
+            };
+        }
+
+        Some(current)
+    }
+
     #[inline]
     pub fn len(&self) -> usize {
         self.map.len()
@@ -59,6 +97,14 @@ impl Document {
         self.map.get("_id").cloned()
     }
 
+    // the fields sorted by the keys,
+    // the documents are compared and hashed regardless of the order of the keys
+    pub(crate) fn sorted_entries(&self) -> Vec<(&String, &Value)> {
+        let mut entries: Vec<(&String, &Value)> = self.map.iter().collect();
+        entries.sort_by_key(|(key, _)| *key);
+        entries
+    }
+
     pub fn from_bytes(bytes: &[u8]) -> BsonResult<Document> {
         let mut doc = Document::new_without_id();
 
@@ -190,6 +236,16 @@ impl Document {
                     doc.map.insert(key, Value::UTCDateTime(Rc::new(UTCDateTime::new(integer))));
                 }
 
+                ty_int::TIMESTAMP => {
+                    let (key, to_ptr) = Document::parse_key(bytes, ptr)?;
+                    ptr = to_ptr;
+
+                    let (time, inc) = crate::value::timestamp_from_bytes(&bytes[ptr..])?;
+                    ptr += 8;
+
+                    doc.map.insert(key, Value::Timestamp { time, inc });
+                }
+
                 _ => return Err(BsonErr::ParseError(parse_error_reason::UNEXPECTED_DOCUMENT_FLAG.into())),
             }
         }
@@ -213,50 +269,50 @@ impl Document {
             Value::Null => {
                 buffer.push(ty_int::NULL);
 
-                Document::key_to_bytes(&key, buffer);
+                Document::key_to_bytes(key, buffer);
             }
 
             Value::Double(num) => {
                 buffer.push(ty_int::DOUBLE);
 
-                Document::key_to_bytes(&key, buffer);
+                Document::key_to_bytes(key, buffer);
 
                 buffer.extend_from_slice(&num.to_be_bytes());
             }
 
             Value::Boolean(bl) => {
                 buffer.push(ty_int::BOOLEAN);
-                Document::key_to_bytes(&key, buffer);
+                Document::key_to_bytes(key, buffer);
                 if *bl {
-                    buffer.push(0x00);
-                } else {
                     buffer.push(0x01);
+                } else {
+                    buffer.push(0x00);
                 }
             }
 
             Value::Int(int_num) => {
                 buffer.push(ty_int::INT);  // not standard, use vli
-                Document::key_to_bytes(&key, buffer);
+                Document::key_to_bytes(key, buffer);
                 vli::encode(buffer, *int_num).expect("encode vli error");
             }
 
             Value::String(str) => {
                 buffer.push(ty_int::STRING);
-                Document::key_to_bytes(&key, buffer);
+                Document::key_to_bytes(key, buffer);
 
-                Document::key_to_bytes(&str, buffer);
+                Document::key_to_bytes(str, buffer);
             }
 
             Value::ObjectId(oid) => {
                 buffer.push(ty_int::OBJECT_ID);
-                Document::key_to_bytes(&key, buffer);
+                Document::key_to_bytes(key, buffer);
 
                 oid.serialize(buffer)?;
             }
 
             Value::Array(arr) => {
                 buffer.push(ty_int::ARRAY);  // not standard
-                Document::key_to_bytes(&key, buffer);
+                Document::key_to_bytes(key, buffer);
 
                 let tmp = arr.to_bytes()?;
                 vli::encode( buffer, tmp.len() as i64)?;
@@ -266,7 +322,7 @@ impl Document {
 
             Value::Document(doc) => {
                 buffer.push(ty_int::DOCUMENT);
-                Document::key_to_bytes(&key, buffer);
+                Document::key_to_bytes(key, buffer);
 
                 let tmp = doc.to_bytes()?;
                 vli::encode(buffer, tmp.len() as i64)?;
@@ -277,7 +333,7 @@ impl Document {
             Value::Binary(bin) => {
                 buffer.push(ty_int::BINARY);
 
-                Document::key_to_bytes(&key, buffer);
+                Document::key_to_bytes(key, buffer);
 
                 vli::encode(buffer, bin.len() as i64)?;
 
@@ -286,11 +342,18 @@ impl Document {
 
             Value::UTCDateTime(datetime) => {
                 buffer.push(ty_int::UTC_DATETIME);  // not standard, use vli
-                Document::key_to_bytes(&key, buffer);
+                Document::key_to_bytes(key, buffer);
                 let ts = datetime.timestamp();
                 vli::encode(buffer, ts as i64)?;
             }
 
+            Value::Timestamp { time, inc } => {
+                buffer.push(ty_int::TIMESTAMP);  // not standard, big endian
+                Document::key_to_bytes(key, buffer);
+                buffer.extend_from_slice(&time.to_be_bytes());
+                buffer.extend_from_slice(&inc.to_be_bytes());
+            }
+
         }
 
         Ok(())
@@ -320,11 +383,18 @@ impl Document {
         Ok(result)
     }
 
+    /// Iterate over the fields in the order of insertion.
     #[inline]
-    pub fn iter(&self) -> Iter<String, Value> {
+    pub fn iter(&self) -> Iter<'_, String, Value> {
         self.map.iter()
     }
 
+    /// The keys of the fields in the order of insertion.
+    #[inline]
+    pub fn keys(&self) -> Keys<'_, String, Value> {
+        self.map.keys()
+    }
+
     fn key_to_bytes(key: &str, data: &mut Vec<u8>) {
         data.extend_from_slice(key.as_bytes());
         data.push(0); // cstring end
@@ -348,11 +418,81 @@ impl std::convert::AsMut<Document> for Document {
 
 }
 
+impl fmt::Display for Document {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{{ ")?;
+
+        for (index, (key, value)) in self.map.iter().enumerate() {
+            write!(f, "{}: {}", key, value)?;
+
+            if index < self.map.len() - 1 {
+                write!(f, ", ")?;
+            }
+        }
+
+        write!(f, " }}")
+    }
+
+}
+
+/// Two documents are equal if they have the same keys, and the values of the same key
+/// are equal, the order of the keys is ignored.
+///
+/// The values are compared as [Value](./enum.Value.html) does,
+/// e.g. `Int(1)` equals to `Double(1.0)`.
+impl PartialEq for Document {
+
+    fn eq(&self, other: &Document) -> bool {
+        if self.len() != other.len() {
+            return false;
+        }
+
+        self.map.iter().all(|(key, value)| other.get(key) == Some(value))
+    }
+
+}
+
+impl Eq for Document {}
+
+/// Consistent with the equality, the fields are hashed in the order of the keys.
+impl Hash for Document {
+
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len());
+        for (key, value) in self.sorted_entries() {
+            key.hash(state);
+            value.hash(state);
+        }
+    }
+
+}
+
+impl<'a> IntoIterator for &'a Document {
+    type Item = (&'a String, &'a Value);
+    type IntoIter = Iter<'a, String, Value>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+
+}
+
 #[cfg(test)]
 mod tests {
     use crate::document::Document;
+    use crate::Value;
     // use crate::object_id::ObjectIdMaker;
 
+    #[test]
+    fn test_with_id() {
+        let doc = Document::with_id(Value::from("external-key"));
+        assert_eq!(doc.len(), 1);
+        assert_eq!(doc.pkey_id(), Some(Value::from("external-key")));
+        assert!(Document::new_without_id().pkey_id().is_none());
+    }
+
     #[test]
     fn test_serialize() {
         // let mut id_maker = ObjectIdMaker::new();
@@ -374,24 +514,130 @@ mod tests {
         let parsed_doc = Document::from_bytes(&bytes).expect("deserialize error");
 
         assert_eq!(parsed_doc.len(), doc.len());
+        assert!(parsed_doc.get("can_do_a").unwrap().unwrap_boolean());
+        assert!(!parsed_doc.get("can_do_b").unwrap().unwrap_boolean());
     }
 
-}
+    #[test]
+    fn test_serialize_nested() {
+        let doc = mk_document! {
+            "matrix": mk_array![ mk_array![ 1, 2 ], mk_array![], mk_array![ mk_array![ "deep" ] ] ],
+            "items": mk_array![
+                mk_document! { "name": "a", "tags": mk_array![ "x", "y" ] },
+                mk_document! { "name": "b", "null": Value::Null },
+            ],
+        };
 
-impl fmt::Display for Document {
+        let bytes = doc.to_bytes().expect("serial error");
+        let parsed_doc = Document::from_bytes(&bytes).expect("deserialize error");
 
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{{ ")?;
+        let matrix = parsed_doc.get("matrix").unwrap().unwrap_array();
+        assert_eq!(matrix.len(), 3);
+        assert_eq!(matrix[0].unwrap_array()[1].unwrap_int(), 2);
+        assert_eq!(matrix[1].unwrap_array().len(), 0);
+        assert_eq!(matrix[2].unwrap_array()[0].unwrap_array()[0].unwrap_string(), "deep");
+
+        let items = parsed_doc.get("items").unwrap().unwrap_array();
+        let first = items[0].unwrap_document();
+        assert_eq!(first.get("tags").unwrap().unwrap_array()[1].unwrap_string(), "y");
+        let second = items[1].unwrap_document();
+        assert!(matches!(second.get("null"), Some(Value::Null)));
+    }
 
-        for (index, (key, value)) in (&self.map).iter().enumerate() {
-            write!(f, "{}: {}", key, value)?;
+    #[test]
+    fn test_serialize_timestamp() {
+        let doc = mk_document! {
+            "ts": Value::Timestamp { time: 1_600_000_000, inc: u32::MAX },
+            "arr": mk_array![ Value::Timestamp { time: 0, inc: 1 }, "after" ],
+        };
 
-            if index < self.map.len() - 1 {
-                write!(f, ", ")?;
-            }
+        let bytes = doc.to_bytes().expect("serial error");
+        let parsed_doc = Document::from_bytes(&bytes).expect("deserialize error");
+
+        assert!(matches!(parsed_doc.get("ts"), Some(Value::Timestamp { time: 1_600_000_000, inc: u32::MAX })));
+        let arr = parsed_doc.get("arr").unwrap().unwrap_array();
+        assert!(matches!(arr[0], Value::Timestamp { time: 0, inc: 1 }));
+        assert_eq!(arr[1].unwrap_string(), "after");
+    }
+
+    #[test]
+    fn test_remove_and_contains_key() {
+        let mut doc = mk_document! {
+            "a": 1,
+            "b": 2,
+            "c": 3,
+            "d": 4,
+        };
+
+        assert!(doc.contains_key("b"));
+        assert_eq!(doc.remove("b").unwrap().unwrap_int(), 2);
+        assert!(!doc.contains_key("b"));
+        assert!(doc.remove("b").is_none());
+        assert!(!doc.contains_key("not-exist"));
+
+        doc.insert("b".into(), Value::from(5));
+        let keys: Vec<&str> = doc.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(keys, vec!["a", "c", "d", "b"]);
+    }
+
+    #[test]
+    fn test_iterate() {
+        let doc = mk_document! {
+            "name": "Vincent",
+            "age": 32,
+            "tags": mk_array![ "a" ],
+        };
+
+        let mut keys = vec![];
+        for (key, value) in &doc {
+            keys.push(key.as_str());
+            assert_eq!(doc.get(key).unwrap().ty_int(), value.ty_int());
         }
+        assert_eq!(keys, vec!["name", "age", "tags"]);
 
-        write!(f, " }}")
+        let keys: Vec<&String> = doc.keys().collect();
+        assert_eq!(keys, vec!["name", "age", "tags"]);
+    }
+
+    #[test]
+    fn test_get_path() {
+        let doc = mk_document! {
+            "name": "Vincent",
+            "address": mk_document! {
+                "city": "NYC",
+                "zip": mk_array![ 10001, 10002 ],
+            },
+            "tags": mk_array![ mk_document! { "name": "a" } ],
+        };
+
+        assert_eq!(doc.get_path("name").unwrap().unwrap_string(), "Vincent");
+        assert_eq!(doc.get_path("address.city").unwrap().unwrap_string(), "NYC");
+        assert_eq!(doc.get_path("address.zip.1").unwrap().unwrap_int(), 10002);
+        assert_eq!(doc.get_path("tags.0.name").unwrap().unwrap_string(), "a");
+
+        assert!(doc.get_path("address.country").is_none());
+        assert!(doc.get_path("address.zip.2").is_none());
+        assert!(doc.get_path("address.zip.first").is_none());
+        assert!(doc.get_path("name.first").is_none());
+        assert!(doc.get_path("").is_none());
+        assert!(doc.get_path("address.").is_none());
+    }
+
+    #[test]
+    fn test_serialize_binary() {
+        let bin: Vec<u8> = (0..1000).map(|i| (i % 256) as u8).collect();
+        let doc = mk_document! {
+            "empty": Vec::<u8>::new(),
+            "bin": bin.clone(),
+            "after": 3,
+        };
+
+        let bytes = doc.to_bytes().expect("serial error");
+        let parsed_doc = Document::from_bytes(&bytes).expect("deserialize error");
+
+        assert!(parsed_doc.get("empty").unwrap().unwrap_binary().is_empty());
+        assert_eq!(parsed_doc.get("bin").unwrap().unwrap_binary(), bin.as_slice());
+        assert_eq!(parsed_doc.get("after").unwrap().unwrap_int(), 3);
     }
 
 }
@@ -9,12 +9,14 @@ pub enum BsonErr {
     DecodeIntUnknownByte,
     IOErr(Box<io::Error>),
     TypeNotComparable(String, String),
+    InvalidObjectId(String),
 }
 
 pub mod parse_error_reason {
 
     pub static OBJECT_ID_LEN: &str = "length of ObjectId should be 12";
     pub static OBJECT_ID_HEX_DECODE_ERROR: &str = "decode error failed for ObjectID";
+    pub static TIMESTAMP_LEN: &str = "length of Timestamp should be 8";
     pub static UNEXPECTED_DOCUMENT_FLAG: &str = "unexpected flag for document";
     pub static UNEXPECTED_PAGE_HEADER: &str = "unexpected page header";
     pub static UNEXPECTED_PAGE_TYPE: &str = "unexpected page type";
@@ -33,6 +35,19 @@ impl fmt::Display for BsonErr {
             BsonErr::IOErr(io_err) => std::fmt::Display::fmt(&io_err, f),
             BsonErr::TypeNotComparable(expected, actual) =>
                 write!(f, "TypeNotComparable(expected: {}, actual: {})", expected, actual),
+            BsonErr::InvalidObjectId(content) => write!(f, "invalid ObjectId: \"{}\"", content),
+        }
+    }
+
+}
+
+impl std::error::Error for BsonErr {
+
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BsonErr::ParseIntError(parse_int_err) => Some(parse_int_err.as_ref()),
+            BsonErr::IOErr(io_err) => Some(io_err.as_ref()),
+            _ => None,
         }
     }
 
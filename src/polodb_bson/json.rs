@@ -0,0 +1,317 @@
+//! Convert the documents from/to `serde_json::Value` in MongoDB extended-JSON style,
+//! with the feature `serde`.
+//!
+//! The types which can't be represented in JSON are wrapped:
+//! - `ObjectId`: `{"$oid": "<24-char hex>"}`
+//! - `UTCDateTime`: `{"$date": <milliseconds since the Unix epoch>}`
+//! - `Binary`: `{"$binary": "<base64>"}`
+//! - `Timestamp`: `{"$timestamp": {"t": <time>, "i": <inc>}}`
+//! - `Double` which is not finite or has no fractional part:
+//!   `{"$numberDouble": "NaN" | "Infinity" | "-Infinity" | "<number>"}`
+//!
+//! A JSON number is read as an `Int` if it has no fractional part and fits in an `i64`,
+//! otherwise it's a `Double`, so the wrapper keeps the `Double` like `3.0` a `Double`.
+use std::rc::Rc;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde_json::{Map, Number, Value as JsonValue};
+use crate::{Document, Array, Value, ObjectId, UTCDateTime, BsonResult};
+use crate::error::BsonErr;
+
+fn mk_wrapper(key: &str, value: JsonValue) -> JsonValue {
+    let mut map = Map::new();
+    map.insert(key.into(), value);
+    JsonValue::Object(map)
+}
+
+fn double_to_json(num: f64) -> JsonValue {
+    if num.is_nan() {
+        return mk_wrapper("$numberDouble", "NaN".into());
+    }
+    if num.is_infinite() {
+        let content = if num > 0.0 { "Infinity" } else { "-Infinity" };
+        return mk_wrapper("$numberDouble", content.into());
+    }
+    if num.fract() == 0.0 {
+        // the debug format parses back to the same number, including -0.0
+        return mk_wrapper("$numberDouble", format!("{:?}", num).into());
+    }
+    JsonValue::Number(Number::from_f64(num).unwrap())
+}
+
+fn number_from_json(num: &Number) -> Value {
+    if let Some(int) = num.as_i64() {
+        return Value::Int(int);
+    }
+    let double = num.as_f64().unwrap_or(f64::NAN);
+    if double.fract() == 0.0 && double >= i64::MIN as f64 && double < i64::MAX as f64 {
+        return Value::Int(double as i64);
+    }
+    Value::Double(double)
+}
+
+fn document_from_json(map: &Map<String, JsonValue>) -> BsonResult<Document> {
+    let mut doc = Document::new_without_id();
+    for (key, value) in map {
+        doc.insert(key.clone(), value_from_json(value)?);
+    }
+    Ok(doc)
+}
+
+// recognize the wrappers of the special types,
+// None if it's not a wrapper
+fn unwrap_extended_json(map: &Map<String, JsonValue>) -> BsonResult<Option<Value>> {
+    if map.len() != 1 {
+        return Ok(None);
+    }
+
+    let (key, value) = map.iter().next().unwrap();
+    let result: Value = match (key.as_str(), value) {
+        ("$oid", JsonValue::String(hex)) => ObjectId::from_hex(hex)?.into(),
+
+        ("$date", JsonValue::Number(timestamp)) => match timestamp.as_u64() {
+            Some(timestamp) => Value::UTCDateTime(Rc::new(UTCDateTime::new(timestamp))),
+            None => return Err(BsonErr::ParseError(format!("invalid $date {}", timestamp))),
+        },
+
+        ("$binary", JsonValue::String(content)) => match BASE64.decode(content) {
+            Ok(bin) => bin.into(),
+            Err(_) => return Err(BsonErr::ParseError(format!("invalid base64 \"{}\"", content))),
+        },
+
+        ("$timestamp", JsonValue::Object(content)) => {
+            let field = |key: &str| match content.get(key).and_then(JsonValue::as_u64) {
+                Some(num) if num <= u32::MAX as u64 => Ok(num as u32),
+                _ => Err(BsonErr::ParseError(format!("invalid \"{}\" of $timestamp", key))),
+            };
+            if content.len() != 2 {
+                return Err(BsonErr::ParseError("$timestamp should only have \"t\" and \"i\"".into()));
+            }
+            Value::Timestamp {
+                time: field("t")?,
+                inc: field("i")?,
+            }
+        }
+
+        ("$numberDouble", JsonValue::String(content)) => match content.as_str() {
+            "NaN" => Value::Double(f64::NAN),
+            "Infinity" => Value::Double(f64::INFINITY),
+            "-Infinity" => Value::Double(f64::NEG_INFINITY),
+            _ => match content.parse::<f64>() {
+                Ok(num) => Value::Double(num),
+                Err(_) => return Err(BsonErr::ParseError(format!("invalid double \"{}\"", content))),
+            },
+        },
+
+        _ => return Ok(None),
+    };
+
+    Ok(Some(result))
+}
+
+fn value_from_json(value: &JsonValue) -> BsonResult<Value> {
+    let result = match value {
+        JsonValue::Null => Value::Null,
+
+        JsonValue::Bool(bl) => Value::Boolean(*bl),
+
+        JsonValue::Number(num) => number_from_json(num),
+
+        JsonValue::String(str) => str.as_str().into(),
+
+        JsonValue::Array(items) => {
+            let mut arr = Array::new();
+            for item in items {
+                arr.push(value_from_json(item)?);
+            }
+            Value::Array(Rc::new(arr))
+        }
+
+        JsonValue::Object(map) => match unwrap_extended_json(map)? {
+            Some(value) => value,
+            None => Value::Document(Rc::new(document_from_json(map)?)),
+        },
+
+    };
+    Ok(result)
+}
+
+impl Document {
+
+    /// Read the document from a JSON object,
+    /// see the [module](./json/index.html) for the wrappers of the special types.
+    ///
+    /// `BsonErr::InvalidObjectId` returns if the hex of a `$oid` is malformed.
+    pub fn from_json(value: &JsonValue) -> BsonResult<Document> {
+        match value {
+            JsonValue::Object(map) => document_from_json(map),
+            _ => Err(BsonErr::ParseError("object expected".into())),
+        }
+    }
+
+    /// Convert the document to a JSON object,
+    /// see the [module](./json/index.html) for the wrappers of the special types.
+    pub fn to_json(&self) -> JsonValue {
+        let mut map = Map::new();
+        for (key, value) in self.iter() {
+            map.insert(key.clone(), value.to_json());
+        }
+        JsonValue::Object(map)
+    }
+
+}
+
+impl Value {
+
+    /// Convert the value to JSON, the special types are wrapped.
+    pub fn to_json(&self) -> JsonValue {
+        match self {
+            Value::Null => JsonValue::Null,
+
+            Value::Double(num) => double_to_json(*num),
+
+            Value::Boolean(bl) => JsonValue::Bool(*bl),
+
+            Value::Int(num) => JsonValue::Number((*num).into()),
+
+            Value::String(str) => JsonValue::String(str.to_string()),
+
+            Value::ObjectId(oid) => mk_wrapper("$oid", oid.to_hex().into()),
+
+            Value::Array(arr) => JsonValue::Array(arr.iter().map(Value::to_json).collect()),
+
+            Value::Document(doc) => doc.to_json(),
+
+            Value::Binary(bin) => mk_wrapper("$binary", BASE64.encode(bin).into()),
+
+            Value::UTCDateTime(datetime) => mk_wrapper("$date", datetime.timestamp().into()),
+
+            Value::Timestamp { time, inc } => {
+                let mut content = Map::new();
+                content.insert("t".into(), (*time).into());
+                content.insert("i".into(), (*inc).into());
+                mk_wrapper("$timestamp", JsonValue::Object(content))
+            }
+
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use crate::{Document, Value, UTCDateTime, ObjectId};
+    use crate::error::BsonErr;
+
+    #[test]
+    fn test_to_json() {
+        let oid = ObjectId::from_hex("0123456789abcdef01234567").unwrap();
+        let doc = mk_document! {
+            "_id": oid,
+            "name": "Vincent \"Chan\"\n",
+            "age": 32,
+            "score": 3.0,
+            "ratio": 0.5,
+            "big": 1e300,
+            "nan": f64::NAN,
+            "active": true,
+            "nothing": Value::Null,
+            "tags": mk_array![ "a", 1 ],
+            "child": mk_document! { "name": "b" },
+            "avatar": vec![0u8, 1, 2],
+            "created_at": UTCDateTime::new(1600000000000),
+            "ts": Value::Timestamp { time: 1600000000, inc: 1 },
+        };
+
+        assert_eq!(doc.to_json(), json!({
+            "_id": { "$oid": "0123456789abcdef01234567" },
+            "name": "Vincent \"Chan\"\n",
+            "age": 32,
+            "score": { "$numberDouble": "3.0" },
+            "ratio": 0.5,
+            "big": { "$numberDouble": "1e300" },
+            "nan": { "$numberDouble": "NaN" },
+            "active": true,
+            "nothing": null,
+            "tags": [ "a", 1 ],
+            "child": { "name": "b" },
+            "avatar": { "$binary": "AAEC" },
+            "created_at": { "$date": 1600000000000u64 },
+            "ts": { "$timestamp": { "t": 1600000000, "i": 1 } },
+        }));
+
+        // the fields are in the order of the document
+        let json = doc.to_json();
+        let keys: Vec<&String> = json.as_object().unwrap().keys().collect();
+        assert_eq!(keys[0], "_id");
+        assert_eq!(keys[13], "ts");
+    }
+
+    #[test]
+    fn test_from_json() {
+        let doc = Document::from_json(&json!({
+            "_id": { "$oid": "0123456789abcdef01234567" },
+            "name": "Vincent",
+            "age": 32,
+            "hundred": 1e2,
+            "score": 3.5,
+            "double": { "$numberDouble": "3.0" },
+            "active": false,
+            "nothing": null,
+            "tags": [ "a", 1, [], {} ],
+            "child": { "name": "b", "$oid": "not a wrapper with two keys" },
+            "avatar": { "$binary": "AAEC" },
+            "created_at": { "$date": 1600000000000u64 },
+            "ts": { "$timestamp": { "t": 1600000000, "i": 2 } },
+            "nan": { "$numberDouble": "NaN" },
+        })).unwrap();
+
+        assert_eq!(doc.get("_id").unwrap().to_json(), json!({ "$oid": "0123456789abcdef01234567" }));
+        assert_eq!(doc.get("name").unwrap().unwrap_string(), "Vincent");
+        assert!(matches!(doc.get("age"), Some(Value::Int(32))));
+        assert!(matches!(doc.get("hundred"), Some(Value::Int(100))));
+        assert!(matches!(doc.get("score"), Some(Value::Double(num)) if *num == 3.5));
+        assert!(matches!(doc.get("double"), Some(Value::Double(num)) if *num == 3.0));
+        assert!(!doc.get("active").unwrap().unwrap_boolean());
+        assert!(matches!(doc.get("nothing"), Some(Value::Null)));
+        assert_eq!(doc.get("tags").unwrap().unwrap_array().len(), 4);
+        assert_eq!(doc.get("child").unwrap().unwrap_document().len(), 2);
+        assert_eq!(doc.get("avatar").unwrap().unwrap_binary(), &[0, 1, 2]);
+        assert!(matches!(doc.get("created_at"), Some(Value::UTCDateTime(dt)) if dt.timestamp() == 1600000000000));
+        assert!(matches!(doc.get("ts"), Some(Value::Timestamp { time: 1600000000, inc: 2 })));
+        assert!(matches!(doc.get("nan"), Some(Value::Double(num)) if num.is_nan()));
+
+        // round trip
+        let json = doc.to_json();
+        assert_eq!(Document::from_json(&json).unwrap().to_json(), json);
+        let double = mk_document! { "a": 3.0, "b": -0.0, "c": 1e300 };
+        let double_json = double.to_json();
+        let double_back = Document::from_json(&double_json).unwrap();
+        assert!(matches!(double_back.get("a"), Some(Value::Double(num)) if *num == 3.0));
+        assert!(matches!(double_back.get("b"), Some(Value::Double(num)) if *num == 0.0 && num.is_sign_negative()));
+        assert_eq!(double_back.to_json(), double_json);
+    }
+
+    #[test]
+    fn test_from_json_invalid() {
+        assert!(matches!(
+            Document::from_json(&json!({ "_id": { "$oid": "0123" } })),
+            Err(BsonErr::InvalidObjectId(hex)) if hex == "0123"
+        ));
+
+        let invalid_cases = [
+            json!([]),
+            json!(1),
+            json!({ "a": { "$binary": "Zg=" } }),
+            json!({ "a": { "$date": -1 } }),
+            json!({ "a": { "$numberDouble": "x" } }),
+            json!({ "a": { "$timestamp": { "t": -1, "i": 0 } } }),
+            json!({ "a": { "$timestamp": { "t": 1, "i": 0, "x": 0 } } }),
+        ];
+        for case in invalid_cases.iter() {
+            assert!(Document::from_json(case).is_err(), "{} should be invalid", case);
+        }
+    }
+
+}
@@ -25,7 +25,10 @@ mod value;
 pub mod linked_hash_map;
 pub mod error;
 pub mod vli;
+#[cfg(feature = "serde")]
+pub mod json;
 mod datetime;
+mod standard;
 
 pub use object_id::{ObjectId, ObjectIdMaker};
 pub use document::Document;
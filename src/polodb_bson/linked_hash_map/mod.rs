@@ -1,7 +1,4 @@
 
-#![cfg_attr(feature = "clippy", feature(plugin))]
-#![cfg_attr(feature = "clippy", plugin(clippy))]
-#![cfg_attr(feature = "clippy", deny(clippy))]
 
 use std::borrow::Borrow;
 use std::cmp::Ordering;
@@ -112,7 +109,7 @@ impl<K, V, S> LinkedHashMap<K, V, S> {
         let mut cur = (*self.head).next;
         while cur != self.head {
             let next = (*cur).next;
-            Box::from_raw(cur);
+            drop(Box::from_raw(cur));
             cur = next;
         }
     }
@@ -196,7 +193,7 @@ impl<K: Hash + Eq, V, S: BuildHasher> LinkedHashMap<K, V, S> {
     /// assert_eq!(letters[&'u'], 1);
     /// assert_eq!(letters.get(&'y'), None);
     /// ```
-    pub fn entry(&mut self, k: K) -> Entry<K, V, S> {
+    pub fn entry(&mut self, k: K) -> Entry<'_, K, V, S> {
         let self_ptr: *mut Self = self;
 
         if let Some(entry) = self.map.get_mut(&KeyRef{k: &k}) {
@@ -235,7 +232,7 @@ impl<K: Hash + Eq, V, S: BuildHasher> LinkedHashMap<K, V, S> {
     ///
     /// assert_eq!(&17, map.get(&"a").unwrap());
     /// ```
-    pub fn entries(&mut self) -> Entries<K, V, S> {
+    pub fn entries(&mut self) -> Entries<'_, K, V, S> {
         let head = if ! self.head.is_null() {
             unsafe { (*self.head).prev }
         } else {
@@ -302,7 +299,7 @@ impl<K: Hash + Eq, V, S: BuildHasher> LinkedHashMap<K, V, S> {
     }
 
     /// Checks if the map contains the given key.
-    pub fn contains_key<Q: ?Sized>(&self, k: &Q) -> bool where K: Borrow<Q>, Q: Eq + Hash {
+    pub fn contains_key<Q>(&self, k: &Q) -> bool where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
         self.map.contains_key(Qey::from_ref(k))
     }
 
@@ -322,7 +319,7 @@ impl<K: Hash + Eq, V, S: BuildHasher> LinkedHashMap<K, V, S> {
     /// assert_eq!(map.get(&1), Some(&"a"));
     /// assert_eq!(map.get(&2), Some(&"c"));
     /// ```
-    pub fn get<Q: ?Sized>(&self, k: &Q) -> Option<&V> where K: Borrow<Q>, Q: Eq + Hash {
+    pub fn get<Q>(&self, k: &Q) -> Option<&V> where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
         self.map.get(Qey::from_ref(k)).map(|e| unsafe { &(**e).value })
     }
 
@@ -340,7 +337,7 @@ impl<K: Hash + Eq, V, S: BuildHasher> LinkedHashMap<K, V, S> {
     /// *map.get_mut(&1).unwrap() = "c";
     /// assert_eq!(map.get(&1), Some(&"c"));
     /// ```
-    pub fn get_mut<Q: ?Sized>(&mut self, k: &Q) -> Option<&mut V> where K: Borrow<Q>, Q: Eq + Hash {
+    pub fn get_mut<Q>(&mut self, k: &Q) -> Option<&mut V> where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
         self.map.get(Qey::from_ref(k)).map(|e| unsafe { &mut (**e).value })
     }
 
@@ -363,7 +360,7 @@ impl<K: Hash + Eq, V, S: BuildHasher> LinkedHashMap<K, V, S> {
     ///
     /// assert_eq!((&2, &"b"), map.iter().rev().next().unwrap());
     /// ```
-    pub fn get_refresh<Q: ?Sized>(&mut self, k: &Q) -> Option<&mut V> where K: Borrow<Q>, Q: Eq + Hash {
+    pub fn get_refresh<Q>(&mut self, k: &Q) -> Option<&mut V> where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
         let (value, node_ptr_opt) = match self.map.get(Qey::from_ref(k)) {
             None => (None, None),
             Some(node) => {
@@ -392,7 +389,7 @@ impl<K: Hash + Eq, V, S: BuildHasher> LinkedHashMap<K, V, S> {
     /// assert_eq!(map.remove(&2), None);
     /// assert_eq!(map.len(), 0);
     /// ```
-    pub fn remove<Q: ?Sized>(&mut self, k: &Q) -> Option<V> where K: Borrow<Q>, Q: Eq + Hash {
+    pub fn remove<Q>(&mut self, k: &Q) -> Option<V> where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
         let removed = self.map.remove(Qey::from_ref(k));
         removed.map(|node| {
             self.detach(node);
@@ -564,7 +561,7 @@ impl<K: Hash + Eq, V, S: BuildHasher> LinkedHashMap<K, V, S> {
     /// assert_eq!((&"b", &20), iter.next().unwrap());
     /// assert_eq!(None, iter.next());
     /// ```
-    pub fn iter(&self) -> Iter<K, V> {
+    pub fn iter(&self) -> Iter<'_, K, V> {
         let head = if self.head.is_null() {
             ptr::null_mut()
         } else {
@@ -598,7 +595,7 @@ impl<K: Hash + Eq, V, S: BuildHasher> LinkedHashMap<K, V, S> {
     ///
     /// assert_eq!(&17, map.get(&"a").unwrap());
     /// ```
-    pub fn iter_mut(&mut self) -> IterMut<K, V> {
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
         let head = if self.head.is_null() {
             ptr::null_mut()
         } else {
@@ -629,7 +626,7 @@ impl<K: Hash + Eq, V, S: BuildHasher> LinkedHashMap<K, V, S> {
     /// assert_eq!(&'b', keys.next().unwrap());
     /// assert_eq!(None, keys.next());
     /// ```
-    pub fn keys(&self) -> Keys<K, V> {
+    pub fn keys(&self) -> Keys<'_, K, V> {
         Keys { inner: self.iter() }
     }
 
@@ -650,7 +647,7 @@ impl<K: Hash + Eq, V, S: BuildHasher> LinkedHashMap<K, V, S> {
     /// assert_eq!(&20, values.next().unwrap());
     /// assert_eq!(None, values.next());
     /// ```
-    pub fn values(&self) -> Values<K, V> {
+    pub fn values(&self) -> Values<'_, K, V> {
         Values { inner: self.iter() }
     }
 }
@@ -1014,7 +1011,7 @@ impl<K, V> Drop for IntoIter<K, V> {
         for _ in 0..self.remaining {
             unsafe {
                 let next = (*self.tail).next;
-                Box::from_raw(self.tail);
+                drop(Box::from_raw(self.tail));
                 self.tail = next;
             }
         }
@@ -2,13 +2,14 @@ use std::fmt;
 use std::io::Write;
 use std::cmp::Ordering;
 use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering as AtomicOrdering};
 use std::ptr::null_mut;
 use std::os::raw::c_uint;
 use super::hex;
 use crate::BsonResult;
 use crate::error::{BsonErr, parse_error_reason};
 
-#[derive(Debug, Clone, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ObjectId {
     timestamp: u64,
     counter:   u32,
@@ -32,16 +33,34 @@ impl ObjectId {
         Ok(ObjectId { timestamp, counter })
     }
 
-    #[allow(dead_code)]
-    fn from_hex(data: &str) -> BsonResult<ObjectId> {
+    /// Parse the ObjectId from the 24-char hex string returned by [to_hex](#method.to_hex).
+    ///
+    /// `BsonErr::InvalidObjectId` returns if the length is not 24,
+    /// or it contains a non-hex char.
+    pub fn from_hex(data: &str) -> BsonResult<ObjectId> {
+        if data.len() != 24 || !data.bytes().all(|ch| ch.is_ascii_hexdigit()) {
+            return Err(BsonErr::InvalidObjectId(data.into()));
+        }
+
         let bytes = match hex::decode(data) {
             Ok(result) => result,
-            Err(_) => return Err(BsonErr::ParseError(parse_error_reason::OBJECT_ID_HEX_DECODE_ERROR.into()))
+            Err(_) => return Err(BsonErr::InvalidObjectId(data.into())),
         };
 
         ObjectId::deserialize(&bytes)
     }
 
+    /// The time the ObjectId was made, in seconds since the Unix epoch.
+    ///
+    /// The first 8 bytes of an ObjectId made by [ObjectIdMaker](./struct.ObjectIdMaker.html)
+    /// are the time in milliseconds, the last 4 bytes are a counter.
+    #[inline]
+    pub fn timestamp(&self) -> i64 {
+        (self.timestamp / 1000) as i64
+    }
+
+    /// Encode the ObjectId as a 24-char lowercase hex string,
+    /// `from_hex` on the result returns an equal ObjectId.
     pub fn to_hex(&self) -> String {
         let mut bytes = vec![];
 
@@ -90,17 +109,15 @@ impl PartialOrd for ObjectId {
 
 }
 
-impl PartialEq for ObjectId {
-
-    fn eq(&self, other: &Self) -> bool {
-        self.counter == other.counter && self.timestamp == other.timestamp
-    }
-
-}
-
+/// Make the ObjectIds increasing in a process.
+///
+/// The state is kept in atomics, so [mk_object_id](#method.mk_object_id)
+/// can be called from several threads at the same time without a `&mut`.
+/// An id made after another one returns is always greater than it.
 #[derive(Debug)]
 pub struct ObjectIdMaker {
-    pub counter:   u32,
+    counter:        AtomicU32,
+    last_timestamp: AtomicU64,
 }
 
 fn random_i32() -> i32 {
@@ -117,10 +134,32 @@ impl ObjectIdMaker {
             libc::srand(time as c_uint);
         }
         let counter: u32 = random_i32() as u32;
-        ObjectIdMaker { counter }
+        ObjectIdMaker::with_state(0, counter)
     }
 
-    pub fn mk_object_id(&mut self) -> ObjectId {
+    /// Restore the maker from the state saved before,
+    /// the ids made later are greater than the ones made before the state saved,
+    /// even if the clock goes backwards.
+    pub fn with_state(last_timestamp: u64, counter: u32) -> ObjectIdMaker {
+        ObjectIdMaker {
+            counter: AtomicU32::new(counter),
+            last_timestamp: AtomicU64::new(last_timestamp),
+        }
+    }
+
+    /// The timestamp of the last id made, in milliseconds.
+    #[inline]
+    pub fn last_timestamp(&self) -> u64 {
+        self.last_timestamp.load(AtomicOrdering::SeqCst)
+    }
+
+    /// The counter of the next id made.
+    #[inline]
+    pub fn counter(&self) -> u32 {
+        self.counter.load(AtomicOrdering::SeqCst)
+    }
+
+    pub fn mk_object_id(&self) -> ObjectId {
         let start = SystemTime::now();
         let since_the_epoch = start
             .duration_since(UNIX_EPOCH)
@@ -129,22 +168,27 @@ impl ObjectIdMaker {
         let in_ms = since_the_epoch.as_secs() * 1000 +
             since_the_epoch.subsec_nanos() as u64 / 1_000_000;
 
-        let id = self.counter;
-        self.plus_counter();
+        // take the counter before the timestamp, so the ids made after this one returns
+        // get a greater counter, or a greater timestamp if the counter starts over
+        let id = self.plus_counter();
+        let timestamp = std::cmp::max(in_ms, self.last_timestamp.fetch_max(in_ms, AtomicOrdering::SeqCst));
+
+        // the counter starts over, move to the next millisecond to keep the ids increasing
+        if id == u32::MAX {
+            self.last_timestamp.fetch_max(timestamp + 1, AtomicOrdering::SeqCst);
+        }
+
         ObjectId {
-            timestamp: in_ms,
+            timestamp,
             counter : id,
         }
     }
 
-    // avoid overflow
+    /// Increase the counter, and return the value before.
+    /// It starts over from 0 after `u32::MAX`.
     #[inline]
-    pub fn plus_counter(&mut self) {
-        if self.counter == u32::max_value() {
-            self.counter = 0;
-            return;
-        }
-        self.counter += 1;
+    pub fn plus_counter(&self) -> u32 {
+        self.counter.fetch_add(1, AtomicOrdering::SeqCst)
     }
 
     pub fn value_of(content: &str) -> BsonResult<ObjectId> {
@@ -180,15 +224,73 @@ mod tests {
 
     #[test]
     fn object_id_not_zero() {
-        let mut maker = ObjectIdMaker::new();
+        let maker = ObjectIdMaker::new();
         let oid = maker.mk_object_id();
 
         assert_ne!(oid.timestamp, 0);
     }
 
+    #[test]
+    fn object_id_timestamp() {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        let maker = ObjectIdMaker::new();
+        let oid = maker.mk_object_id();
+        assert!((oid.timestamp() - now).abs() <= 1);
+
+        let mut bytes = 1_600_000_000_999u64.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&[0, 0, 0, 1]);
+        let oid = ObjectId::deserialize(&bytes).unwrap();
+        assert_eq!(oid.timestamp(), 1_600_000_000);
+    }
+
+    #[test]
+    fn object_id_with_state() {
+        let last_timestamp = u64::MAX / 2;
+        let maker = ObjectIdMaker::with_state(last_timestamp, 100);
+        let oid = maker.mk_object_id();
+        assert_eq!(oid.timestamp, last_timestamp);
+        assert_eq!(oid.counter, 100);
+
+        let maker = ObjectIdMaker::with_state(last_timestamp, u32::MAX);
+        let oid1 = maker.mk_object_id();
+        let oid2 = maker.mk_object_id();
+        assert!(oid1 < oid2);
+        assert_eq!(maker.last_timestamp(), last_timestamp + 1);
+        assert_eq!(maker.counter(), 1);
+    }
+
+    #[test]
+    fn object_id_concurrent() {
+        let maker = std::sync::Arc::new(ObjectIdMaker::with_state(0, u32::MAX - 500));
+
+        let handles: Vec<_> = (0..4).map(|_| {
+            let maker = maker.clone();
+            std::thread::spawn(move || {
+                let ids: Vec<ObjectId> = (0..1000).map(|_| maker.mk_object_id()).collect();
+                ids
+            })
+        }).collect();
+
+        let mut all = vec![];
+        for handle in handles {
+            let ids = handle.join().unwrap();
+            // increasing in every thread
+            assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+            all.extend(ids);
+        }
+
+        all.sort();
+        all.dedup();
+        assert_eq!(all.len(), 4000);
+    }
+
     #[test]
     fn object_to_hex() {
-        let mut maker = ObjectIdMaker::new();
+        let maker = ObjectIdMaker::new();
         let oid = maker.mk_object_id();
 
         let hex_str = oid.to_hex();
@@ -197,9 +299,24 @@ mod tests {
         assert_eq!(from_hex, oid)
     }
 
+    #[test]
+    fn object_id_from_hex_invalid() {
+        assert!(ObjectId::from_hex("").is_err());
+        assert!(ObjectId::from_hex("0123456789abcdef0123456").is_err());
+        assert!(ObjectId::from_hex("0123456789abcdef012345678").is_err());
+        assert!(ObjectId::from_hex("0123456789abcdef0123456g").is_err());
+
+        let oid = ObjectId::from_hex("0123456789ABCDEF01234567").unwrap();
+        assert_eq!(oid.to_hex(), "0123456789abcdef01234567");
+
+        let mut set = std::collections::HashSet::new();
+        set.insert(oid.clone());
+        assert!(set.contains(&oid));
+    }
+
     #[test]
     fn serialize() {
-        let mut maker = ObjectIdMaker::new();
+        let maker = ObjectIdMaker::new();
         let oid = maker.mk_object_id();
 
         let mut buffer = vec![];
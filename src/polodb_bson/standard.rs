@@ -0,0 +1,404 @@
+//! The format of the BSON specification(bsonspec.org) used by MongoDB,
+//! the documents stored by PoloDB are encoded in another format.
+//!
+//! An integer is encoded as int32 if it fits, otherwise int64,
+//! both of them are decoded as `Value::Int`.
+use std::rc::Rc;
+use std::convert::TryFrom;
+use crate::{Document, Array, Value, ObjectId, UTCDateTime, BsonResult};
+use crate::error::BsonErr;
+
+const DOUBLE: u8       = 0x01;
+const STRING: u8       = 0x02;
+const DOCUMENT: u8     = 0x03;
+const ARRAY: u8        = 0x04;
+const BINARY: u8       = 0x05;
+const OBJECT_ID: u8    = 0x07;
+const BOOLEAN: u8      = 0x08;
+const UTC_DATETIME: u8 = 0x09;
+const NULL: u8         = 0x0A;
+const INT32: u8        = 0x10;
+const TIMESTAMP: u8    = 0x11;
+const INT64: u8        = 0x12;
+
+const BINARY_SUBTYPE_GENERIC: u8 = 0x00;
+
+impl Document {
+
+    /// Encode the document in the standard BSON format,
+    /// the first 4 bytes are the length of the whole document.
+    ///
+    /// The `_id` is written first.
+    pub fn to_standard_bytes(&self) -> BsonResult<Vec<u8>> {
+        let mut buffer = vec![];
+        write_document(self, &mut buffer)?;
+        Ok(buffer)
+    }
+
+    /// Decode a document encoded in the standard BSON format,
+    /// all the bytes must be consumed.
+    pub fn from_standard_bytes(bytes: &[u8]) -> BsonResult<Document> {
+        let mut reader = Reader { bytes, pos: 0 };
+        let doc = reader.read_document()?;
+        if reader.pos != bytes.len() {
+            return Err(BsonErr::ParseError(format!("{} bytes remain after the document", bytes.len() - reader.pos)));
+        }
+        Ok(doc)
+    }
+
+}
+
+// reserve 4 bytes for the length, the position of it returns
+fn begin_length(buffer: &mut Vec<u8>) -> usize {
+    let begin = buffer.len();
+    buffer.extend_from_slice(&[0; 4]);
+    begin
+}
+
+fn end_length(buffer: &mut Vec<u8>, begin: usize) -> BsonResult<()> {
+    buffer.push(0);
+    let len = buffer.len() - begin;
+    if len > i32::MAX as usize {
+        return Err(BsonErr::ParseError(format!("the document is too large: {} bytes", len)));
+    }
+    buffer[begin..(begin + 4)].copy_from_slice(&(len as i32).to_le_bytes());
+    Ok(())
+}
+
+fn write_cstring(str: &str, buffer: &mut Vec<u8>) -> BsonResult<()> {
+    if str.as_bytes().contains(&0) {
+        return Err(BsonErr::ParseError(format!("the key \"{}\" contains a zero byte", str.escape_debug())));
+    }
+    buffer.extend_from_slice(str.as_bytes());
+    buffer.push(0);
+    Ok(())
+}
+
+fn write_document(doc: &Document, buffer: &mut Vec<u8>) -> BsonResult<()> {
+    let begin = begin_length(buffer);
+
+    if let Some(id) = doc.get("_id") {
+        write_element("_id", id, buffer)?;
+    }
+
+    for (key, value) in doc.iter() {
+        if key == "_id" {
+            continue;
+        }
+        write_element(key, value, buffer)?;
+    }
+
+    end_length(buffer, begin)
+}
+
+// an array is a document with the keys "0", "1", "2"...
+fn write_array(arr: &Array, buffer: &mut Vec<u8>) -> BsonResult<()> {
+    let begin = begin_length(buffer);
+
+    for (index, value) in arr.iter().enumerate() {
+        write_element(&index.to_string(), value, buffer)?;
+    }
+
+    end_length(buffer, begin)
+}
+
+fn write_element(key: &str, value: &Value, buffer: &mut Vec<u8>) -> BsonResult<()> {
+    match value {
+        Value::Null => {
+            buffer.push(NULL);
+            write_cstring(key, buffer)?;
+        }
+
+        Value::Double(num) => {
+            buffer.push(DOUBLE);
+            write_cstring(key, buffer)?;
+            buffer.extend_from_slice(&num.to_le_bytes());
+        }
+
+        Value::Boolean(bl) => {
+            buffer.push(BOOLEAN);
+            write_cstring(key, buffer)?;
+            buffer.push(*bl as u8);
+        }
+
+        Value::Int(num) => {
+            if let Ok(num) = i32::try_from(*num) {
+                buffer.push(INT32);
+                write_cstring(key, buffer)?;
+                buffer.extend_from_slice(&num.to_le_bytes());
+            } else {
+                buffer.push(INT64);
+                write_cstring(key, buffer)?;
+                buffer.extend_from_slice(&num.to_le_bytes());
+            }
+        }
+
+        Value::String(str) => {
+            buffer.push(STRING);
+            write_cstring(key, buffer)?;
+            buffer.extend_from_slice(&((str.len() + 1) as i32).to_le_bytes());
+            buffer.extend_from_slice(str.as_bytes());
+            buffer.push(0);
+        }
+
+        Value::ObjectId(oid) => {
+            buffer.push(OBJECT_ID);
+            write_cstring(key, buffer)?;
+            oid.serialize(buffer)?;
+        }
+
+        Value::Array(arr) => {
+            buffer.push(ARRAY);
+            write_cstring(key, buffer)?;
+            write_array(arr, buffer)?;
+        }
+
+        Value::Document(doc) => {
+            buffer.push(DOCUMENT);
+            write_cstring(key, buffer)?;
+            write_document(doc, buffer)?;
+        }
+
+        Value::Binary(bin) => {
+            buffer.push(BINARY);
+            write_cstring(key, buffer)?;
+            buffer.extend_from_slice(&(bin.len() as i32).to_le_bytes());
+            buffer.push(BINARY_SUBTYPE_GENERIC);
+            buffer.extend_from_slice(bin);
+        }
+
+        Value::UTCDateTime(datetime) => {
+            buffer.push(UTC_DATETIME);
+            write_cstring(key, buffer)?;
+            buffer.extend_from_slice(&(datetime.timestamp() as i64).to_le_bytes());
+        }
+
+        // the increment is the low 32 bits
+        Value::Timestamp { time, inc } => {
+            buffer.push(TIMESTAMP);
+            write_cstring(key, buffer)?;
+            buffer.extend_from_slice(&((*time as u64) << 32 | (*inc as u64)).to_le_bytes());
+        }
+
+    }
+
+    Ok(())
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos:   usize,
+}
+
+impl<'a> Reader<'a> {
+
+    fn take(&mut self, len: usize) -> BsonResult<&'a [u8]> {
+        if self.pos + len > self.bytes.len() {
+            return Err(BsonErr::ParseError(format!("unexpected end of the bytes at {}", self.bytes.len())));
+        }
+        let result = &self.bytes[self.pos..(self.pos + len)];
+        self.pos += len;
+        Ok(result)
+    }
+
+    fn read_u8(&mut self) -> BsonResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn read_i32(&mut self) -> BsonResult<i32> {
+        let mut buffer: [u8; 4] = [0; 4];
+        buffer.copy_from_slice(self.take(4)?);
+        Ok(i32::from_le_bytes(buffer))
+    }
+
+    fn read_i64(&mut self) -> BsonResult<i64> {
+        let mut buffer: [u8; 8] = [0; 8];
+        buffer.copy_from_slice(self.take(8)?);
+        Ok(i64::from_le_bytes(buffer))
+    }
+
+    fn read_len(&mut self) -> BsonResult<usize> {
+        let len = self.read_i32()?;
+        if len < 0 {
+            return Err(BsonErr::ParseError(format!("negative length: {} at {}", len, self.pos - 4)));
+        }
+        Ok(len as usize)
+    }
+
+    fn read_cstring(&mut self) -> BsonResult<String> {
+        let remain = &self.bytes[self.pos..];
+        let len = match remain.iter().position(|byte| *byte == 0) {
+            Some(len) => len,
+            None => return Err(BsonErr::ParseError(format!("the string at {} is not terminated", self.pos))),
+        };
+        let bytes = self.take(len + 1)?;
+        match std::str::from_utf8(&bytes[0..len]) {
+            Ok(str) => Ok(str.into()),
+            Err(_) => Err(BsonErr::ParseError(format!("the string at {} is not utf8", self.pos - len - 1))),
+        }
+    }
+
+    // the elements with the keys are passed to `f`
+    fn read_elements<F: FnMut(String, Value)>(&mut self, mut f: F) -> BsonResult<()> {
+        let begin = self.pos;
+        let len = self.read_len()?;
+        if len < 5 {
+            return Err(BsonErr::ParseError(format!("invalid length of document: {} at {}", len, begin)));
+        }
+
+        loop {
+            let ty = self.read_u8()?;
+            if ty == 0 {
+                break;
+            }
+            let key = self.read_cstring()?;
+            let value = self.read_value(ty)?;
+            f(key, value);
+        }
+
+        if self.pos - begin != len {
+            return Err(BsonErr::ParseError(format!("the length of document at {} is {}, actual: {}", begin, len, self.pos - begin)));
+        }
+
+        Ok(())
+    }
+
+    fn read_document(&mut self) -> BsonResult<Document> {
+        let mut doc = Document::new_without_id();
+        self.read_elements(|key, value| {
+            doc.insert(key, value);
+        })?;
+        Ok(doc)
+    }
+
+    // the keys of the array are ignored, the order is kept
+    fn read_array(&mut self) -> BsonResult<Array> {
+        let mut arr = Array::new();
+        self.read_elements(|_, value| {
+            arr.push(value);
+        })?;
+        Ok(arr)
+    }
+
+    fn read_value(&mut self, ty: u8) -> BsonResult<Value> {
+        let value = match ty {
+            DOUBLE => {
+                let mut buffer: [u8; 8] = [0; 8];
+                buffer.copy_from_slice(self.take(8)?);
+                Value::Double(f64::from_le_bytes(buffer))
+            }
+
+            STRING => {
+                let begin = self.pos;
+                let len = self.read_len()?;
+                let bytes = self.take(len)?;
+                if len == 0 || bytes[len - 1] != 0 {
+                    return Err(BsonErr::ParseError(format!("the string at {} is not terminated", begin)));
+                }
+                match std::str::from_utf8(&bytes[0..(len - 1)]) {
+                    Ok(str) => Value::String(str.into()),
+                    Err(_) => return Err(BsonErr::ParseError(format!("the string at {} is not utf8", begin))),
+                }
+            }
+
+            DOCUMENT => Value::Document(Rc::new(self.read_document()?)),
+
+            ARRAY => Value::Array(Rc::new(self.read_array()?)),
+
+            BINARY => {
+                let len = self.read_len()?;
+                let _subtype = self.read_u8()?;
+                Value::Binary(self.take(len)?.into())
+            }
+
+            OBJECT_ID => Value::ObjectId(Rc::new(ObjectId::deserialize(self.take(12)?)?)),
+
+            BOOLEAN => Value::Boolean(self.read_u8()? != 0),
+
+            UTC_DATETIME => {
+                let timestamp = self.read_i64()?;
+                if timestamp < 0 {
+                    return Err(BsonErr::ParseError(format!("the datetime before 1970 is not supported: {}", timestamp)));
+                }
+                Value::UTCDateTime(Rc::new(UTCDateTime::new(timestamp as u64)))
+            }
+
+            NULL => Value::Null,
+
+            INT32 => Value::Int(self.read_i32()? as i64),
+
+            TIMESTAMP => {
+                let value = self.read_i64()? as u64;
+                Value::Timestamp {
+                    time: (value >> 32) as u32,
+                    inc: value as u32,
+                }
+            }
+
+            INT64 => Value::Int(self.read_i64()?),
+
+            _ => return Err(BsonErr::ParseError(format!("unsupported type of BSON: 0x{:02X}", ty))),
+
+        };
+
+        Ok(value)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Document, Value, UTCDateTime, ObjectIdMaker};
+
+    #[test]
+    fn test_spec_example() {
+        let doc = mk_document! {
+            "hello": "world",
+        };
+        let bytes = doc.to_standard_bytes().unwrap();
+        assert_eq!(bytes, b"\x16\x00\x00\x00\x02hello\x00\x06\x00\x00\x00world\x00\x00");
+
+        let doc = Document::from_standard_bytes(&bytes).unwrap();
+        assert_eq!(doc.get("hello").unwrap(), &Value::from("world"));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut id_maker = ObjectIdMaker::new();
+        let mut doc = Document::new(&mut id_maker);
+        doc.insert("null".into(), Value::Null);
+        doc.insert("double".into(), Value::Double(1.5));
+        doc.insert("bool".into(), Value::Boolean(true));
+        doc.insert("int32".into(), Value::Int(-3));
+        doc.insert("int64".into(), Value::Int(1 << 40));
+        doc.insert("string".into(), Value::from("hello"));
+        doc.insert("array".into(), Value::from(mk_array![1, "two", mk_document! { "three": 3 }]));
+        doc.insert("doc".into(), Value::from(mk_document! { "a": mk_array![] }));
+        doc.insert("binary".into(), Value::from(vec![0u8, 1, 2]));
+        doc.insert("datetime".into(), Value::from(UTCDateTime::new(1_600_000_000_000)));
+        doc.insert("timestamp".into(), Value::Timestamp { time: 1_600_000_000, inc: 7 });
+
+        let bytes = doc.to_standard_bytes().unwrap();
+        let back = Document::from_standard_bytes(&bytes).unwrap();
+        assert_eq!(back.to_bytes().unwrap(), doc.to_bytes().unwrap());
+    }
+
+    #[test]
+    fn test_broken_bytes() {
+        let bytes = mk_document! { "hello": "world" }.to_standard_bytes().unwrap();
+        assert!(Document::from_standard_bytes(&bytes[0..(bytes.len() - 1)]).is_err());
+
+        let mut wrong_len = bytes.clone();
+        wrong_len[0] += 1;
+        assert!(Document::from_standard_bytes(&wrong_len).is_err());
+
+        let mut unknown_type = bytes;
+        unknown_type[4] = 0x13;
+        assert!(Document::from_standard_bytes(&unknown_type).is_err());
+
+        let mut doc = Document::new_without_id();
+        doc.insert("a\0b".into(), Value::Null);
+        assert!(doc.to_standard_bytes().is_err());
+    }
+
+}
@@ -1,12 +1,13 @@
 use std::rc::Rc;
 use std::fmt;
 use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
 use super::ObjectId;
 use super::document::Document;
 use super::array::Array;
 use super::hex;
 use crate::BsonResult;
-use crate::error::BsonErr;
+use crate::error::{BsonErr, parse_error_reason};
 use crate::datetime::UTCDateTime;
 
 const BINARY_MAX_DISPLAY_LEN: usize = 64;
@@ -16,6 +17,21 @@ pub fn mk_object_id(content: &ObjectId) -> Value {
     Value::ObjectId(Rc::new(content.clone()))
 }
 
+// the time and the inc of a Timestamp, both in big endian
+pub(crate) fn timestamp_from_bytes(bytes: &[u8]) -> BsonResult<(u32, u32)> {
+    if bytes.len() < 8 {
+        return Err(BsonErr::ParseError(parse_error_reason::TIMESTAMP_LEN.into()));
+    }
+
+    let mut buffer: [u8; 4] = [0; 4];
+    buffer.copy_from_slice(&bytes[0..4]);
+    let time = u32::from_be_bytes(buffer);
+    buffer.copy_from_slice(&bytes[4..8]);
+    let inc = u32::from_be_bytes(buffer);
+
+    Ok((time, inc))
+}
+
 #[derive(Debug, Clone)]
 pub enum Value {
     Null,
@@ -35,17 +51,42 @@ pub enum Value {
 
     UTCDateTime(Rc<UTCDateTime>),
 
+    /// The internal timestamp ordered by `time` in seconds and then `inc`,
+    /// which isn't related to the wall clock like `UTCDateTime`,
+    /// e.g. to order the entries of a change log.
+    Timestamp { time: u32, inc: u32 },
+
 }
 
 impl Value {
 
+    /// Compare the values used as the keys of the B-tree,
+    /// it's the same as the total order of [Ord](#impl-Ord), and never fails.
+    ///
+    /// The arrays are compared element by element, `Null` is less than any other
+    /// value in an array, and an array is less than the longer one it's a prefix of.
+    /// They are the composite keys of the compound indexes.
+    #[inline]
     pub fn value_cmp(&self, other: &Value) -> BsonResult<Ordering> {
-        match (self, other) {
-            (Value::Null, Value::Null) => Ok(Ordering::Equal),
-            (Value::Int(i1), Value::Int(i2)) => Ok(i1.cmp(i2)),
-            (Value::String(str1), Value::String(str2)) => Ok(str1.cmp(str2)),
-            (Value::ObjectId(oid1), Value::ObjectId(oid2)) => Ok(oid1.cmp(oid2)),
-            _ => Err(BsonErr::TypeNotComparable(self.ty_name().into(), other.ty_name().into())),
+        Ok(self.cmp(other))
+    }
+
+    /// The rank of the type when the values of different types are compared,
+    /// the same as MongoDB:
+    /// Null < Numbers < String < Document < Array < Binary < ObjectId < Boolean < DateTime < Timestamp.
+    pub fn ty_rank(&self) -> u8 {
+        match self {
+            Value::Null           => 1,
+            Value::Int(_) |
+            Value::Double(_)      => 2,
+            Value::String(_)      => 3,
+            Value::Document(_)    => 4,
+            Value::Array(_)       => 5,
+            Value::Binary(_)      => 6,
+            Value::ObjectId(_)    => 7,
+            Value::Boolean(_)     => 8,
+            Value::UTCDateTime(_) => 9,
+            Value::Timestamp { .. } => 10,
         }
     }
 
@@ -61,6 +102,7 @@ impl Value {
             Value::Document(_)    => "Document",
             Value::Binary(_)      => "Binary",
             Value::UTCDateTime(_) => "UTCDateTime",
+            Value::Timestamp { .. } => "Timestamp",
         }
     }
 
@@ -76,6 +118,7 @@ impl Value {
             Value::Document(_)    => ty_int::DOCUMENT,
             Value::Binary(_)      => ty_int::BINARY,
             Value::UTCDateTime(_) => ty_int::UTC_DATETIME,
+            Value::Timestamp { .. } => ty_int::TIMESTAMP,
 
         }
     }
@@ -128,11 +171,281 @@ impl Value {
         }
     }
 
+    #[inline]
+    pub fn unwrap_binary(&self) -> &[u8] {
+        match self {
+            Value::Binary(bin) => bin,
+            _ => panic!("unwrap error: binary expected, but it's {}", self.ty_name()),
+        }
+    }
+
+    /// Return the string if the value is a `String`.
+    ///
+    /// ```
+    /// use polodb_bson::Value;
+    ///
+    /// assert_eq!(Value::from("polo").as_str(), Some("polo"));
+    /// assert_eq!(Value::from(1).as_str(), None);
+    /// ```
+    #[inline]
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(str) => Some(str),
+            _ => None,
+        }
+    }
+
+    /// Return the integer if the value is an `Int`, a `Double` is not converted.
+    ///
+    /// ```
+    /// use polodb_bson::Value;
+    ///
+    /// assert_eq!(Value::from(42).as_i64(), Some(42));
+    /// assert_eq!(Value::from(42.0).as_i64(), None);
+    /// ```
+    #[inline]
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    /// Return the number if the value is a `Double`, an `Int` is not converted.
+    ///
+    /// ```
+    /// use polodb_bson::Value;
+    ///
+    /// assert_eq!(Value::from(2.5).as_f64(), Some(2.5));
+    /// assert_eq!(Value::from(2).as_f64(), None);
+    /// ```
+    #[inline]
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Double(num) => Some(*num),
+            _ => None,
+        }
+    }
+
+    /// Return the `ObjectId` if the value is an `ObjectId`.
+    ///
+    /// ```
+    /// use polodb_bson::{Value, ObjectIdMaker};
+    ///
+    /// let oid = ObjectIdMaker::new().mk_object_id();
+    /// assert_eq!(Value::from(oid.clone()).as_object_id(), Some(&oid));
+    /// assert_eq!(Value::Null.as_object_id(), None);
+    /// ```
+    #[inline]
+    pub fn as_object_id(&self) -> Option<&ObjectId> {
+        match self {
+            Value::ObjectId(oid) => Some(oid),
+            _ => None,
+        }
+    }
+
+    /// Return the document if the value is a `Document`.
+    ///
+    /// ```
+    /// use polodb_bson::{Value, mk_document};
+    ///
+    /// let value = Value::from(mk_document! { "name": "polo" });
+    /// assert_eq!(value.as_document().unwrap().get("name").unwrap().as_str(), Some("polo"));
+    /// assert!(Value::from("polo").as_document().is_none());
+    /// ```
+    #[inline]
+    pub fn as_document(&self) -> Option<&Document> {
+        match self {
+            Value::Document(doc) => Some(doc),
+            _ => None,
+        }
+    }
+
+    /// Return the array if the value is an `Array`.
+    ///
+    /// ```
+    /// use polodb_bson::{Value, mk_array};
+    ///
+    /// let value = Value::from(mk_array![1, 2, 3]);
+    /// assert_eq!(value.as_array().unwrap().len(), 3);
+    /// assert!(Value::from(1).as_array().is_none());
+    /// ```
+    #[inline]
+    pub fn as_array(&self) -> Option<&Array> {
+        match self {
+            Value::Array(arr) => Some(arr),
+            _ => None,
+        }
+    }
+
+    /// Return the boolean if the value is a `Boolean`.
+    ///
+    /// ```
+    /// use polodb_bson::Value;
+    ///
+    /// assert_eq!(Value::from(true).as_bool(), Some(true));
+    /// assert_eq!(Value::from(1).as_bool(), None);
+    /// ```
+    #[inline]
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Boolean(bl) => Some(*bl),
+            _ => None,
+        }
+    }
+
+    /// The current time, for the audit fields like "created_at".
+    #[inline]
+    pub fn datetime_now() -> Value {
+        UTCDateTime::now().into()
+    }
+
     pub fn is_valid_key_type(&self) -> bool {
         matches!(self, Value::String(_) |
                        Value::Int(_) |
                        Value::ObjectId(_) |
-                       Value::Boolean(_))
+                       Value::Boolean(_) |
+                       Value::Timestamp { .. })
+    }
+
+}
+
+// 2^63, the doubles in [-2^63, 2^63) have their integral parts in the range of i64
+const I64_BOUND: f64 = 9223372036854775808.0;
+
+// the double with no fractional part in the range of i64, it's equal to the Int
+#[inline]
+fn double_as_int(d: f64) -> Option<i64> {
+    if d.fract() == 0.0 && (-I64_BOUND..I64_BOUND).contains(&d) {
+        Some(d as i64)
+    } else {
+        None
+    }
+}
+
+// compare exactly, an i64 may not be represented by a f64
+fn cmp_int_double(i: i64, d: f64) -> Ordering {
+    if d.is_nan() || d >= I64_BOUND {
+        return Ordering::Less;
+    }
+    if d < -I64_BOUND {
+        return Ordering::Greater;
+    }
+    let integral = d.trunc();
+    match i.cmp(&(integral as i64)) {
+        Ordering::Equal => 0.0.partial_cmp(&(d - integral)).unwrap(),
+        ord => ord,
+    }
+}
+
+/// The total order over all the values, shared by the sorts, the queries and the indexes.
+///
+/// Values of different types are ordered by the [rank](#method.ty_rank) of the types.
+///
+/// `Int` and `Double` are compared numerically, NaN is the greatest number and equal to itself,
+/// so it's always placed last in an ascending sort. An `Int` and a `Double` are compared exactly
+/// instead of converting the `Int` to `f64`, which loses the precision above 2^53.
+///
+/// The documents are compared field by field in the order of the keys, the key first and then the value,
+/// so the order of the fields in a document doesn't matter.
+/// The arrays are compared element by element. A prefix is less than the longer one.
+impl Ord for Value {
+
+    fn cmp(&self, other: &Value) -> Ordering {
+        let rank_ord = self.ty_rank().cmp(&other.ty_rank());
+        if rank_ord != Ordering::Equal {
+            return rank_ord;
+        }
+
+        match (self, other) {
+            (Value::Int(i1), Value::Int(i2)) => i1.cmp(i2),
+            (Value::Int(i), Value::Double(d)) => cmp_int_double(*i, *d),
+            (Value::Double(d), Value::Int(i)) => cmp_int_double(*i, *d).reverse(),
+            (Value::Double(d1), Value::Double(d2)) => {
+                match (d1.is_nan(), d2.is_nan()) {
+                    (true, true) => Ordering::Equal,
+                    (true, false) => Ordering::Greater,
+                    (false, true) => Ordering::Less,
+                    (false, false) => d1.partial_cmp(d2).unwrap(),
+                }
+            }
+            (Value::String(str1), Value::String(str2)) => str1.cmp(str2),
+            (Value::Document(doc1), Value::Document(doc2)) => {
+                doc1.sorted_entries().cmp(&doc2.sorted_entries())
+            }
+            (Value::Array(arr1), Value::Array(arr2)) => arr1.iter().cmp(arr2.iter()),
+            (Value::Binary(bin1), Value::Binary(bin2)) => bin1.cmp(bin2),
+            (Value::ObjectId(oid1), Value::ObjectId(oid2)) => oid1.cmp(oid2),
+            (Value::Boolean(bl1), Value::Boolean(bl2)) => bl1.cmp(bl2),
+            (Value::UTCDateTime(dt1), Value::UTCDateTime(dt2)) => dt1.cmp(dt2),
+            (Value::Timestamp { time: t1, inc: i1 }, Value::Timestamp { time: t2, inc: i2 }) => {
+                (t1, i1).cmp(&(t2, i2))
+            }
+            _ => Ordering::Equal,
+        }
+    }
+
+}
+
+impl PartialOrd for Value {
+
+    #[inline]
+    fn partial_cmp(&self, other: &Value) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+
+}
+
+impl PartialEq for Value {
+
+    #[inline]
+    fn eq(&self, other: &Value) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+
+}
+
+impl Eq for Value {}
+
+/// Consistent with the equality, a `Double` with no fractional part in the range of `i64`
+/// is hashed as the `Int`, so `Int(1)` and `Double(1.0)` have the same hash, and all the NaNs as well.
+impl Hash for Value {
+
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u8(self.ty_rank());
+
+        match self {
+            Value::Null => (),
+
+            Value::Int(num) => state.write_i64(*num),
+
+            // -0.0 is hashed as Int(0)
+            Value::Double(num) => match double_as_int(*num) {
+                Some(num) => state.write_i64(num),
+                None if num.is_nan() => state.write_u64(f64::NAN.to_bits()),
+                None => state.write_u64(num.to_bits()),
+            },
+
+            Value::Boolean(bl) => bl.hash(state),
+
+            Value::String(str) => str.hash(state),
+
+            Value::ObjectId(oid) => oid.hash(state),
+
+            Value::Array(arr) => arr.hash(state),
+
+            Value::Document(doc) => doc.hash(state),
+
+            Value::Binary(bin) => bin.hash(state),
+
+            Value::UTCDateTime(datetime) => datetime.hash(state),
+
+            Value::Timestamp { time, inc } => {
+                time.hash(state);
+                inc.hash(state);
+            }
+
+        }
     }
 
 }
@@ -174,6 +487,8 @@ impl fmt::Display for Value {
                 write!(f, "UTCDateTime({})", datetime.timestamp())
             }
 
+            Value::Timestamp { time, inc } => write!(f, "Timestamp({}, {})", time, inc),
+
         }
     }
 
@@ -190,6 +505,7 @@ pub mod ty_int {
     pub const DOCUMENT: u8     = 0x13;
     pub const BINARY: u8       = 0x05;
     pub const UTC_DATETIME: u8 = 0x09;
+    pub const TIMESTAMP: u8    = 0x11;
 
     pub fn to_str(i: u8) -> &'static str {
         match i {
@@ -202,6 +518,7 @@ pub mod ty_int {
             DOCUMENT => "Document",
             BINARY => "Binary",
             UTC_DATETIME => "UTCDateTime",
+            TIMESTAMP => "Timestamp",
 
             _ => "<unknown>"
         }
@@ -328,3 +645,156 @@ impl From<UTCDateTime> for Value {
     }
 
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+    use crate::{Value, Array, UTCDateTime};
+
+    #[test]
+    fn test_value_cmp() {
+        assert!(Value::from(1) < Value::from(2));
+        assert!(Value::from(2.5) > Value::from(2));
+        assert!(Value::from("a") < Value::from("b"));
+        assert_eq!(Value::from(3), Value::from(3.0));
+        assert_eq!(Value::Null.cmp(&Value::Null), Ordering::Equal);
+        assert_ne!(Value::from(1), Value::from("1"));
+        assert_eq!(Value::from(true), Value::from(true));
+        assert_ne!(Value::from(true), Value::from(false));
+        assert_ne!(Value::from(false), Value::Null);
+        assert_ne!(Value::from(0), Value::from(false));
+    }
+
+    #[test]
+    fn test_value_cmp_of_types() {
+        assert_eq!(Value::Null.cmp(&Value::from(0)), Ordering::Less);
+        assert_eq!(Value::from(100).cmp(&Value::from("1")), Ordering::Less);
+        assert_eq!(Value::from("z").cmp(&Value::from(false)), Ordering::Less);
+        assert_eq!(Value::from(1).cmp(&Value::from(1.5)), Ordering::Less);
+        assert_eq!(Value::from(f64::NAN).cmp(&Value::from(-100)), Ordering::Greater);
+        assert_eq!(Value::from(f64::INFINITY).cmp(&Value::from(f64::NAN)), Ordering::Less);
+        assert_eq!(Value::from(f64::NAN), Value::from(f64::NAN));
+        assert_eq!(Value::from(f64::NAN).cmp(&Value::from("")), Ordering::Less);
+        assert_eq!(Value::from(false).cmp(&Value::from(true)), Ordering::Less);
+        assert_eq!(Value::from(2).cmp(&Value::from(2.0)), Ordering::Equal);
+        assert!(Value::from(vec![1u8]) > Value::from(mk_array![1]));
+
+        let doc1 = Value::from(mk_document! { "a": 1, "b": 2 });
+        assert_eq!(doc1, Value::from(mk_document! { "a": 1, "b": 2.0 }));
+        assert!(doc1 < Value::from(mk_document! { "a": 1, "c": 0 }));
+        assert!(doc1 > Value::from(mk_document! { "a": 1 }));
+        assert!(doc1 < Value::from(mk_array![]));
+
+        let mut values = vec![Value::from(true), Value::from("s"), Value::from(1.5), Value::Null, Value::from(1)];
+        values.sort();
+        assert_eq!(values, vec![Value::Null, Value::from(1), Value::from(1.5), Value::from("s"), Value::from(true)]);
+    }
+
+    #[test]
+    fn test_datetime_cmp() {
+        let dt1 = Value::from(UTCDateTime::new(1000));
+        let dt2 = Value::from(UTCDateTime::new(2000));
+        assert!(dt1 < dt2);
+        assert_eq!(dt1, Value::from(UTCDateTime::new(1000)));
+        assert!(dt1 > Value::from(1000));
+        assert!(Value::datetime_now() > dt2);
+    }
+
+    fn hash_of<T: std::hash::Hash>(value: &T) -> u64 {
+        use std::hash::Hasher;
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_eq_and_hash() {
+        let pairs = vec![
+            (Value::from(1), Value::from(1.0)),
+            (Value::from(0), Value::from(-0.0)),
+            (Value::from(f64::NAN), Value::from(-f64::NAN)),
+            (Value::from(mk_array![1, "a"]), Value::from(mk_array![1.0, "a"])),
+            (
+                Value::from(mk_document! { "a": 1, "b": mk_document! { "c": 2, "d": 3 } }),
+                Value::from(mk_document! { "b": mk_document! { "d": 3.0, "c": 2 }, "a": 1 }),
+            ),
+        ];
+        for (value1, value2) in &pairs {
+            assert_eq!(value1, value2);
+            assert_eq!(hash_of(value1), hash_of(value2), "{} and {}", value1, value2);
+        }
+
+        assert_ne!(Value::from(mk_array![1, 2]), Value::from(mk_array![2, 1]));
+        assert_ne!(Value::from(mk_document! { "a": 1 }), Value::from(mk_document! { "a": 1, "b": 2 }));
+        assert_ne!(Value::from(1), Value::from("1"));
+        assert_ne!(hash_of(&Value::from(1)), hash_of(&Value::from("1")));
+
+        let doc1 = mk_document! { "x": 1, "y": mk_array![true, Value::Null] };
+        let doc2 = mk_document! { "y": mk_array![true, Value::Null], "x": 1.0 };
+        assert_eq!(doc1, doc2);
+        assert_eq!(hash_of(&doc1), hash_of(&doc2));
+        assert_eq!(Value::from(doc1.clone()).cmp(&Value::from(doc2.clone())), Ordering::Equal);
+
+        let mut set = std::collections::HashSet::new();
+        set.insert(doc1);
+        assert!(!set.insert(doc2));
+        assert!(set.insert(mk_document! { "x": 2 }));
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn test_cmp_int_double_exactly() {
+        let two_53: i64 = 1 << 53;
+        assert_eq!(Value::from(two_53), Value::from(two_53 as f64));
+        assert_ne!(Value::from(two_53 + 1), Value::from(two_53 as f64));
+        assert!(Value::from(two_53 + 1) > Value::from(two_53 as f64));
+        assert!(Value::from((two_53 + 2) as f64) > Value::from(two_53 + 1));
+        assert_ne!(hash_of(&Value::from(two_53 + 1)), hash_of(&Value::from(two_53 as f64)));
+        assert_eq!(hash_of(&Value::from(two_53)), hash_of(&Value::from(two_53 as f64)));
+
+        // sorted the same whatever the order of the input
+        let mut values = [
+            Value::from(two_53 + 1), Value::from(two_53 as f64), Value::from(two_53),
+            Value::from(two_53 as f64 + 2.0), Value::from(two_53 - 1),
+        ];
+        values.sort();
+        for pair in values.windows(2) {
+            assert!(pair[0] <= pair[1], "{} > {}", pair[0], pair[1]);
+        }
+        assert_eq!(values[0], Value::from(two_53 - 1));
+        assert_eq!(values[3], Value::from(two_53 + 1));
+
+        // the fractional parts and the doubles out of the range of i64
+        assert!(Value::from(2) < Value::from(2.5));
+        assert!(Value::from(-2) > Value::from(-2.5));
+        assert!(Value::from(-1) < Value::from(-0.5));
+        assert!(Value::from(i64::MAX) < Value::from(i64::MAX as f64));
+        assert_eq!(Value::from(i64::MIN), Value::from(i64::MIN as f64));
+        assert!(Value::from(i64::MIN) > Value::from(f64::NEG_INFINITY));
+        assert!(Value::from(i64::MAX) < Value::from(f64::NAN));
+        assert!(Value::from(f64::NAN) > Value::from(f64::INFINITY));
+    }
+
+    #[test]
+    fn test_timestamp_cmp() {
+        let ts = |time: u32, inc: u32| Value::Timestamp { time, inc };
+        assert!(ts(1, 2) < ts(1, 3));
+        assert!(ts(1, u32::MAX) < ts(2, 0));
+        assert_eq!(ts(5, 1), ts(5, 1));
+        assert!(ts(0, 0) > Value::from(UTCDateTime::new(u64::MAX)));
+        assert_eq!(ts(5, 1).to_string(), "Timestamp(5, 1)");
+    }
+
+    #[test]
+    fn test_array_value_cmp() {
+        let key = |arr: Array| Value::from(arr);
+        assert_eq!(key(mk_array![1, "b"]).value_cmp(&key(mk_array![1, "c"])).unwrap(), Ordering::Less);
+        assert_eq!(key(mk_array![2, "a"]).value_cmp(&key(mk_array![1, "c"])).unwrap(), Ordering::Greater);
+        assert_eq!(key(mk_array![1, "b"]).value_cmp(&key(mk_array![1, "b"])).unwrap(), Ordering::Equal);
+        assert_eq!(key(mk_array![1]).value_cmp(&key(mk_array![1, Value::Null])).unwrap(), Ordering::Less);
+        assert_eq!(key(mk_array![Value::Null, 5]).value_cmp(&key(mk_array![0, 1])).unwrap(), Ordering::Less);
+        assert_eq!(key(mk_array![1, 5]).value_cmp(&key(mk_array![1, Value::Null])).unwrap(), Ordering::Greater);
+        assert_eq!(key(mk_array![1]).value_cmp(&key(mk_array!["1"])).unwrap(), Ordering::Less);
+    }
+
+}
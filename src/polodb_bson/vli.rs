@@ -111,8 +111,8 @@ pub fn decode_u64(bytes: &[u8]) -> BsonResult<(u64, usize)> {
             let mut tmp: [u8; 8] = [0; 8];
 
             tmp[3] = first_byte & (!BYTE_MARK5);
-            for i in 4..8 {
-                tmp[i] = read_byte_plus!(bytes, ptr);
+            for item in tmp.iter_mut().skip(4) {
+                *item = read_byte_plus!(bytes, ptr);
             }
 
             return Ok((u64::from_be_bytes(tmp), ptr))
@@ -122,8 +122,8 @@ pub fn decode_u64(bytes: &[u8]) -> BsonResult<(u64, usize)> {
             let mut tmp: [u8; 8] = [0; 8];
 
             tmp[0] = first_byte & (!BYTE_MARK5);
-            for i in 1..8 {
-                tmp[i] = read_byte_plus!(bytes, ptr);
+            for item in tmp.iter_mut().skip(1) {
+                *item = read_byte_plus!(bytes, ptr);
             }
 
             return Ok((u64::from_be_bytes(tmp), ptr))
@@ -134,8 +134,8 @@ pub fn decode_u64(bytes: &[u8]) -> BsonResult<(u64, usize)> {
 
     if first_byte == 0b11111000 {  // 6 bytes
         let mut tmp: [u8; 8] = [0; 8];
-        for i in 3..8 {
-            tmp[i] = read_byte_plus!(bytes, ptr);
+        for item in tmp.iter_mut().skip(3) {
+            *item = read_byte_plus!(bytes, ptr);
         }
 
         return Ok((u64::from_be_bytes(tmp), ptr));
@@ -143,8 +143,8 @@ pub fn decode_u64(bytes: &[u8]) -> BsonResult<(u64, usize)> {
 
     if first_byte == 0b11111001 {  // 9 bytes
         let mut tmp: [u8; 8] = [0; 8];
-        for i in 0..8 {
-            tmp[i] = read_byte_plus!(bytes, ptr);
+        for item in tmp.iter_mut() {
+            *item = read_byte_plus!(bytes, ptr);
         }
 
         return Ok((u64::from_be_bytes(tmp), ptr));
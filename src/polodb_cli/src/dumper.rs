@@ -24,7 +24,7 @@ pub(crate) fn dump(src_path: &str, page_detail: bool) {
     println!("{}", FullDumpWrapper{ dump: &dump, print_page_detail: page_detail });
 }
 
-fn format_datetime(datetime: &DateTime<Local>) -> DelayedFormat<StrftimeItems> {
+fn format_datetime(datetime: &DateTime<Local>) -> DelayedFormat<StrftimeItems<'_>> {
     datetime.format("%Y/%m/%d %T")
 }
 
@@ -44,6 +44,7 @@ impl<'a> fmt::Display for FullDumpWrapper<'a> {
         write_kv!(f, "Meta Page Id", self.dump.meta_pid)?;
         write_kv!(f, "Free List Page Id" ,self.dump.free_list_pid)?;
         write_kv!(f, "Free List Size", self.dump.free_list_size)?;
+        write_kv!(f, "Null Page Bar", self.dump.null_page_bar)?;
 
         let created_datetime: DateTime<Local> = self.dump.file_meta.created().unwrap().into();
         write_kv!(f, "Created Time", format_datetime(&created_datetime))?;
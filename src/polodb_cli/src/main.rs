@@ -15,9 +15,9 @@ fn value_to_str(val: &JsValue) -> String {
     match val {
         JsValue::String(str) => {
             let mut result = String::new();
-            result.push_str("\"");
+            result.push('"');
             result.push_str(str);
-            result.push_str("\"");
+            result.push('"');
             result
         },
 
@@ -13,8 +13,8 @@ use std::borrow::Borrow;
 const DB_ERROR_MSG_SIZE: usize = 512;
 
 thread_local! {
-    static DB_GLOBAL_ERROR: RefCell<Option<DbErr>> = RefCell::new(None);
-    static DB_GLOBAL_ERROR_MSG: RefCell<[c_char; DB_ERROR_MSG_SIZE]> = RefCell::new([0; DB_ERROR_MSG_SIZE]);
+    static DB_GLOBAL_ERROR: RefCell<Option<DbErr>> = const { RefCell::new(None) };
+    static DB_GLOBAL_ERROR_MSG: RefCell<[c_char; DB_ERROR_MSG_SIZE]> = const { RefCell::new([0; DB_ERROR_MSG_SIZE]) };
 }
 
 macro_rules! try_read_utf8 {
@@ -336,7 +336,7 @@ pub unsafe extern "C" fn PLDB_free_handle(handle: *mut DbHandle) {
 pub extern "C" fn PLDB_error_code() -> c_int {
     DB_GLOBAL_ERROR.with(|f| {
         if let Some(err) = f.borrow().as_ref() {
-            let code = error_code_of_db_err(err) * -1;
+            let code = -error_code_of_db_err(err);
             return code
         }
         0
@@ -931,8 +931,27 @@ fn error_code_of_db_err(err: &DbErr) -> i32 {
         DbErr::MetaVersionMismatched(_, _) => 40,
         DbErr::Busy => 41,
         DbErr::InvalidField(_) => 42,
-        DbErr::CollectionAlreadyExits(_) => 43,
+        DbErr::CollectionAlreadyExists(_) => 43,
         DbErr::UnableToUpdatePrimaryKey => 44,
+        DbErr::DuplicateKey { .. } => 45,
+        DbErr::IllegalPageSize(_) => 46,
+        DbErr::PageSizeMismatch(_, _) => 47,
+        DbErr::InvalidObjectId(_) => 48,
+        DbErr::DataMalformed(_) => 49,
+        DbErr::JournalChecksumMismatch => 50,
+        DbErr::ReadOnly => 51,
+        DbErr::InvalidQuery(_) => 52,
+        DbErr::DocumentTooLarge { .. } => 53,
+        DbErr::IllegalBTreeOrder(_, _) => 54,
+        DbErr::BTreeOrderMismatch(_, _) => 55,
+        DbErr::UnsupportedValueType(_) => 56,
+        DbErr::PageChecksumMismatch { .. } => 57,
+        DbErr::SerdeError(_) => 58,
+        DbErr::UnsupportedFileVersion { .. } => 59,
+        DbErr::Cancelled => 60,
+
+        // the variants added after this version of the binding
+        _ => 255,
 
     }
 }
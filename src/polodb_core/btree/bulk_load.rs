@@ -0,0 +1,180 @@
+use std::cmp::Ordering;
+use polodb_bson::Document;
+use crate::DbResult;
+use crate::page::{RawPage, PageHandler};
+use super::{BTreeNode, BTreeNodeDataItem};
+
+// the items of a level, and the pids of the children,
+// there are `items.len() + 1` children if it's not the leaves
+struct Level {
+    items:    Vec<BTreeNodeDataItem>,
+    children: Option<Vec<u32>>,
+}
+
+/// Build the B-tree from the documents sorted by the primary keys ascending,
+/// the pages are constructed bottom-up.
+///
+/// The tree at `root_pid` must be empty, the root of the new tree is written to it,
+/// so the pid of the root never changes.
+///
+/// Every node except the root holds at least the min items required by the deletion,
+/// the nodes of a level are filled evenly.
+pub(crate) fn bulk_load(page_handler: &mut PageHandler, root_pid: u32, docs: &[Document]) -> DbResult<()> {
+    if docs.is_empty() {
+        return Ok(());
+    }
+
+    let mut items = Vec::with_capacity(docs.len());
+    for doc in docs {
+        let key = doc.pkey_id().unwrap();
+        if let Some(prev) = items.last() {
+            let prev: &BTreeNodeDataItem = prev;
+            debug_assert_eq!(prev.key.value_cmp(&key)?, Ordering::Less, "the documents are not sorted");
+        }
+        let data_ticket = page_handler.store_doc(doc)?;
+        items.push(BTreeNodeDataItem {
+            key,
+            data_ticket,
+        });
+    }
+
+    let item_size = page_handler.btree_order as usize;
+    let mut level = Level {
+        items,
+        children: None,
+    };
+
+    loop {
+        // every node takes one more item as the separator, except the last one
+        let node_count = (level.items.len() + 1).div_ceil(item_size + 1);
+        if node_count == 1 {
+            let leaf_indexes = vec![0; level.items.len() + 1];
+            let indexes = level.children.unwrap_or(leaf_indexes);
+            return write_node(page_handler, root_pid, level.items, indexes);
+        }
+
+        level = build_level(page_handler, level, node_count)?;
+    }
+}
+
+// split the level into `node_count` nodes,
+// the separators between them are the items of the upper level
+fn build_level(page_handler: &mut PageHandler, level: Level, node_count: usize) -> DbResult<Level> {
+    let content_count = level.items.len() - (node_count - 1);
+    let mut separators = Vec::with_capacity(node_count - 1);
+    let mut pids = Vec::with_capacity(node_count);
+
+    let mut items_iter = level.items.into_iter();
+    let mut children_iter = level.children.map(|children| children.into_iter());
+
+    for node_index in 0..node_count {
+        // the first ones take one more if it can't be divided evenly
+        let size = content_count / node_count + if node_index < content_count % node_count { 1 } else { 0 };
+
+        let content: Vec<BTreeNodeDataItem> = items_iter.by_ref().take(size).collect();
+        let indexes: Vec<u32> = match &mut children_iter {
+            Some(iter) => iter.by_ref().take(size + 1).collect(),
+            None => vec![0; size + 1],
+        };
+
+        let pid = page_handler.alloc_page_id()?;
+        write_node(page_handler, pid, content, indexes)?;
+        pids.push(pid);
+
+        if node_index < node_count - 1 {
+            separators.push(items_iter.next().unwrap());
+        }
+    }
+
+    Ok(Level {
+        items: separators,
+        children: Some(pids),
+    })
+}
+
+fn write_node(page_handler: &mut PageHandler, pid: u32, content: Vec<BTreeNodeDataItem>, indexes: Vec<u32>) -> DbResult<()> {
+    let node = BTreeNode {
+        parent_pid: 0,
+        pid,
+        content,
+        indexes,
+    };
+
+    let mut raw_page = RawPage::new(pid, page_handler.page_size);
+    node.to_raw(&mut raw_page)?;
+
+    page_handler.pipeline_write_page(&raw_page)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use polodb_bson::{Document, Value, mk_document};
+    use crate::TransactionType;
+    use crate::cursor::Cursor;
+    use crate::page::PageHandler;
+    use crate::btree::{BTreePageInsertWrapper, BTreePageSearchWrapper, BTreeSearchResult};
+    use crate::btree::bulk_load::bulk_load;
+
+    fn search_content(page_handler: &mut PageHandler, root_pid: u32, key: &Value) -> Option<String> {
+        let mut search_wrapper = BTreePageSearchWrapper::new(page_handler, root_pid);
+        match search_wrapper.search(key).unwrap() {
+            BTreeSearchResult::Found(item) => {
+                let doc = page_handler.get_doc_from_ticket(&item.data_ticket).unwrap().unwrap();
+                Some(doc.get("content").unwrap().unwrap_string().to_string())
+            }
+            BTreeSearchResult::NotFound(_) => None,
+        }
+    }
+
+    #[test]
+    fn test_bulk_load_same_as_insertion() {
+        let mut db_path = env::temp_dir();
+        db_path.push("test-bulk-load.db");
+        let _ = std::fs::remove_file(db_path.as_path());
+        let _ = std::fs::remove_file(PageHandler::mk_journal_path(db_path.as_path()));
+
+        let mut page_handler = PageHandler::new(db_path.as_path(), 4096).unwrap();
+        page_handler.start_transaction(TransactionType::Write).unwrap();
+        let item_size = page_handler.btree_order;
+
+        for size in &[1, 10, item_size as i64, item_size as i64 + 1, 2000, 10000] {
+            let docs: Vec<Document> = (0..*size).map(|i| mk_document! {
+                "_id": i * 2,
+                "content": (i * 2).to_string(),
+            }).collect();
+
+            let mut inserted_root_pid = page_handler.alloc_page_id().unwrap();
+            for doc in &docs {
+                let mut insert_wrapper = BTreePageInsertWrapper::new(&mut page_handler, inserted_root_pid);
+                let insert_result = insert_wrapper.insert_item(doc, false).unwrap();
+                if let Some(backward_item) = insert_result.backward_item {
+                    let new_root_pid = page_handler.alloc_page_id().unwrap();
+                    let raw_page = backward_item.write_to_page(&mut page_handler, new_root_pid, inserted_root_pid).unwrap();
+                    page_handler.pipeline_write_page(&raw_page).unwrap();
+                    inserted_root_pid = new_root_pid;
+                }
+            }
+
+            let loaded_root_pid = page_handler.alloc_page_id().unwrap();
+            bulk_load(&mut page_handler, loaded_root_pid, &docs).unwrap();
+
+            for i in -1..(size * 2 + 1) {
+                let key = Value::from(i);
+                let expected = search_content(&mut page_handler, inserted_root_pid, &key);
+                assert_eq!(search_content(&mut page_handler, loaded_root_pid, &key), expected);
+                assert_eq!(expected.is_some(), i >= 0 && i < size * 2 && i % 2 == 0, "size {} key {}", size, i);
+            }
+
+            let cursor = Cursor::new(item_size, loaded_root_pid);
+            let keys: Vec<i64> = cursor.iter_with(&mut page_handler)
+                .map(|doc| doc.unwrap().pkey_id().unwrap().unwrap_int())
+                .collect();
+            let expected_keys: Vec<i64> = (0..*size).map(|i| i * 2).collect();
+            assert_eq!(keys, expected_keys);
+        }
+
+        page_handler.commit().unwrap();
+    }
+
+}
@@ -1,14 +1,91 @@
+use std::collections::BTreeSet;
 use crate::page::PageHandler;
+use crate::data_ticket::DataTicket;
 use crate::meta_doc_helper::MetaDocEntry;
 use crate::DbResult;
 use super::BTreeNode;
-use super::wrapper_base::cal_item_size;
 
-pub(crate) fn count(page_handler: &mut PageHandler, collection_meta: MetaDocEntry) -> DbResult<u64> {
-    let item_size = cal_item_size(page_handler.page_size);
+pub(crate) fn count(page_handler: &mut PageHandler, collection_meta: &MetaDocEntry) -> DbResult<u64> {
+    let item_size = page_handler.btree_order;
     count_by_btree_pid(page_handler, item_size, 0, collection_meta.root_pid())
 }
 
+/// Count the documents and the overflow pages used by them.
+pub(crate) fn count_with_overflow_pages(page_handler: &mut PageHandler, collection_meta: MetaDocEntry) -> DbResult<(u64, u32)> {
+    let item_size = page_handler.btree_order;
+    let mut tickets: Vec<DataTicket> = vec![];
+    collect_tickets_by_btree_pid(page_handler, item_size, 0, collection_meta.root_pid(), &mut tickets)?;
+
+    let mut overflow_pages = 0;
+    for ticket in &tickets {
+        overflow_pages += page_handler.overflow_page_count_of_ticket(ticket)?;
+    }
+
+    Ok((tickets.len() as u64, overflow_pages))
+}
+
+/// Count the pages used by the collection: the nodes of the B-tree,
+/// the data pages storing the documents and the overflow pages.
+///
+/// A data page may be shared with the other collections, it's counted once here.
+pub(crate) fn count_pages(page_handler: &mut PageHandler, collection_meta: &MetaDocEntry) -> DbResult<u64> {
+    let item_size = page_handler.btree_order;
+    let mut node_pages: u64 = 0;
+    let mut tickets: Vec<DataTicket> = vec![];
+    collect_pages_by_btree_pid(page_handler, item_size, 0, collection_meta.root_pid(), &mut node_pages, &mut tickets)?;
+
+    let mut data_pages = BTreeSet::new();
+    let mut overflow_pages: u64 = 0;
+    for ticket in &tickets {
+        data_pages.insert(ticket.pid);
+        overflow_pages += page_handler.overflow_page_count_of_ticket(ticket)? as u64;
+    }
+
+    Ok(node_pages + data_pages.len() as u64 + overflow_pages)
+}
+
+fn collect_pages_by_btree_pid(page_handler: &mut PageHandler, item_size: u32, parent_pid: u32, pid: u32,
+                              node_pages: &mut u64, tickets: &mut Vec<DataTicket>) -> DbResult<()> {
+    let page = page_handler.pipeline_read_page(pid)?;
+    let btree_content = BTreeNode::from_raw(&page, parent_pid, item_size, page_handler)?;
+
+    // the root page is allocated even if the collection is empty
+    *node_pages += 1;
+
+    for item in &btree_content.content {
+        tickets.push(item.data_ticket.clone());
+    }
+
+    if btree_content.content.is_empty() || btree_content.is_leaf() {
+        return Ok(());
+    }
+
+    for child_pid in &btree_content.indexes {
+        collect_pages_by_btree_pid(page_handler, item_size, pid, *child_pid, node_pages, tickets)?;
+    }
+
+    Ok(())
+}
+
+fn collect_tickets_by_btree_pid(page_handler: &mut PageHandler, item_size: u32, parent_pid: u32, pid: u32, tickets: &mut Vec<DataTicket>) -> DbResult<()> {
+    let page = page_handler.pipeline_read_page(pid)?;
+    let btree_content = BTreeNode::from_raw(&page, parent_pid, item_size, page_handler)?;
+
+    for item in &btree_content.content {
+        tickets.push(item.data_ticket.clone());
+    }
+
+    if btree_content.content.is_empty() || btree_content.is_leaf() {
+        return Ok(());
+    }
+
+    for child_pid in &btree_content.indexes {
+        collect_tickets_by_btree_pid(page_handler, item_size, pid, *child_pid, tickets)?;
+    }
+
+    Ok(())
+}
+
 fn count_by_btree_pid(page_handler: &mut PageHandler, item_size: u32, parent_pid: u32, pid: u32) -> DbResult<u64> {
     let page = page_handler.pipeline_read_page(pid)?;
     let btree_content = BTreeNode::from_raw(&page, parent_pid, item_size, page_handler)?;
@@ -9,7 +9,6 @@ use super::wrapper_base::BTreePageWrapperBase;
 use crate::data_ticket::DataTicket;
 
 struct DeleteBackwardItem {
-    is_leaf:       bool,
     child_size:    usize,
     deleted_ticket:   Box<DataTicket>,
 }
@@ -22,7 +21,7 @@ pub struct BTreePageDeleteWrapper<'a> {
 
 impl<'a> BTreePageDeleteWrapper<'a> {
 
-    pub(crate) fn new(page_handler: &mut PageHandler, root_page_id: u32) -> BTreePageDeleteWrapper {
+    pub(crate) fn new(page_handler: &mut PageHandler, root_page_id: u32) -> BTreePageDeleteWrapper<'_> {
         let base = BTreePageWrapperBase::new(page_handler, root_page_id);
         BTreePageDeleteWrapper {
             base,
@@ -104,40 +103,17 @@ impl<'a> BTreePageDeleteWrapper<'a> {
                 let page_id = current_btree_node.indexes[idx];
                 let backward_item_opt = self.delete_item_on_subtree(pid, page_id, id)?;  // recursively delete
 
-                if let Some(backward_item) = backward_item_opt {
-                    let mut current_item_size = current_btree_node.content.len();
-
-                    if !self.is_content_size_satisfied(backward_item.child_size) {
-                        if backward_item.is_leaf {
-                            let borrow_ok = self.try_borrow_brothers(idx, current_btree_node.borrow_mut())?;
-                            if !borrow_ok {
-                                if current_btree_node.content.len() == 1 {
-                                    let _opt = self.try_merge_head(*current_btree_node)?;
-                                    debug_assert!(_opt);
-                                } else {
-                                    self.merge_leaves(idx, current_btree_node.borrow_mut())?;
-                                    current_item_size = current_btree_node.content.len();
-                                    self.write_btree(*current_btree_node);
-                                }
-                            } else {
-                                self.write_btree(*current_btree_node);
-                            }
-                        } else {
-                            // let current_btree_node = self.get_btree_by_pid(pid, parent_pid)?;
-                            if current_btree_node.content.len() == 1 {
-                                let _opt = self.try_merge_head(*current_btree_node)?;
-                                debug_assert!(_opt);
-                            }
-                        }
+                match backward_item_opt {
+                    Some(backward_item) => {
+                        let child_size = self.rebalance_subtree(idx, backward_item.child_size, current_btree_node)?;
+                        Ok(Some(DeleteBackwardItem {
+                            child_size,
+                            deleted_ticket: backward_item.deleted_ticket,
+                        }))
                     }
-                    return Ok(Some(DeleteBackwardItem {
-                        is_leaf: false,
-                        child_size: current_item_size,
-                        deleted_ticket: backward_item.deleted_ticket,
-                    }))
-                }
 
-                Ok(None)
+                    None => Ok(None),
+                }
             }
 
             // find the target node
@@ -154,84 +130,46 @@ impl<'a> BTreePageDeleteWrapper<'a> {
                     let subtree_pid = current_btree_node.indexes[idx + 1];
                     let next_item = self.find_min_element_in_subtree(subtree_pid, current_pid)?;
                     current_btree_node.content[idx] = next_item.clone();
-                    let mut current_item_size = current_btree_node.content.len();
-                    self.write_btree(*current_btree_node);
-
-                    let backward_opt = self.delete_item_on_subtree(current_pid, subtree_pid, &next_item.key)?;
-                    match backward_opt {
-                        Some(backward_item) => {
-                            if !self.is_content_size_satisfied(backward_item.child_size) {
-                                if backward_item.is_leaf {  // borrow or merge leaves
-                                    let mut current_btree_node = self.get_btree_by_pid(pid, parent_pid)?;
-                                    let borrow_ok = self.try_borrow_brothers(idx + 1, current_btree_node.borrow_mut())?;
-                                    if !borrow_ok {
-                                        // self.merge_leaves(idx + 1, current_btree_node.borrow_mut())?;
-                                        // current_item_size = current_btree_node.content.len();
-
-                                        if current_btree_node.content.len() == 1 {
-                                            let _opt = self.try_merge_head(*current_btree_node)?;
-                                            debug_assert!(_opt);
-                                        } else {
-                                            self.merge_leaves(idx + 1, current_btree_node.borrow_mut())?;
-                                            current_item_size = current_btree_node.content.len();
-                                            self.write_btree(*current_btree_node);
-                                        }
-                                    } else {
-                                        self.write_btree(*current_btree_node);
-                                    }
-                                } else {
-                                    let current_btree_node = self.get_btree_by_pid(pid, parent_pid)?;
-                                    if current_btree_node.content.len() == 1 {
-                                        let _opt = self.try_merge_head(*current_btree_node)?;
-                                        debug_assert!(_opt);
-                                    }
-                                }
-                            }
-
-                            Ok(Some(DeleteBackwardItem {
-                                is_leaf: false,
-                                child_size: current_item_size,
-                                deleted_ticket,
-                            }))
-                        }
-
-                        None => Ok(None),
-                    }
+
+                    // the min element is always on the leaf, it must be found
+                    let backward_item = self.delete_item_on_subtree(current_pid, subtree_pid, &next_item.key)?.unwrap();
+                    let child_size = self.rebalance_subtree(idx + 1, backward_item.child_size, current_btree_node)?;
+
+                    Ok(Some(DeleteBackwardItem {
+                        child_size,
+                        deleted_ticket,
+                    }))
                 }
             }
         }
     }
 
-    fn try_merge_head(&mut self, parent_btree_node: BTreeNode) -> DbResult<bool> {
-        let left_pid = parent_btree_node.indexes[0];
-        let right_pid = parent_btree_node.indexes[1];
-
-        let left_node = self.get_btree_by_pid(left_pid, parent_btree_node.pid)?;
-        let right_node = self.get_btree_by_pid(right_pid, parent_btree_node.pid)?;
-
-        if left_node.content.len() + right_node.content.len() + 1 > self.base.item_size as usize {
-            return Ok(false);
+    // the subtree at node_idx lost an item,
+    // borrow an item from the brothers or merge with one of them if it's too small,
+    // the size of the current node returns
+    fn rebalance_subtree(&mut self, node_idx: usize, subtree_size: usize, mut current_btree_node: Box<BTreeNode>) -> DbResult<usize> {
+        if !self.is_content_size_satisfied(subtree_size) {
+            let borrow_ok = self.try_borrow_brothers(node_idx, current_btree_node.borrow_mut())?;
+            if !borrow_ok {
+                self.merge_subtrees(node_idx, current_btree_node.borrow_mut())?;
+            }
         }
 
-        let mut new_content = Vec::with_capacity(self.base.item_size as usize);
-        let mut new_indexes = Vec::with_capacity((self.base.item_size + 1) as usize);
+        // the root is empty after merging its last two children,
+        // the merged child is moved to the root, so the root pid is unchanged
+        if current_btree_node.parent_pid == 0 && current_btree_node.is_empty() {
+            let child_pid = current_btree_node.indexes[0];
+            let child_node = self.get_btree_by_pid(child_pid, current_btree_node.pid)?;
 
-        new_content.extend_from_slice(&left_node.content);
-        new_indexes.extend_from_slice(&left_node.indexes);
+            self.base.page_handler.free_page(child_pid)?;
 
-        new_content.push(parent_btree_node.content[0].clone());
-
-        new_content.extend_from_slice(&right_node.content);
-        new_indexes.extend_from_slice(&right_node.indexes);
-
-        self.base.page_handler.free_pages(&[left_pid, right_pid])?;
-
-        // move
-        let new_node: Box<BTreeNode> = Box::new(parent_btree_node.clone_with_contents(new_content, new_indexes));
+            *current_btree_node = current_btree_node.clone_with_contents(child_node.content, child_node.indexes);
+        }
 
-        self.write_btree(*new_node);
+        let current_item_size = current_btree_node.content.len();
+        self.write_btree(*current_btree_node);
 
-        Ok(true)
+        Ok(current_item_size)
     }
 
     fn try_borrow_brothers(&mut self, node_idx: usize, current_btree_node: &mut BTreeNode) -> DbResult<bool> {
@@ -241,7 +179,7 @@ impl<'a> BTreePageDeleteWrapper<'a> {
         // node on [node_idx] is borrowed
         let subtree_pid = current_btree_node.indexes[node_idx];  // subtree need to shift
 
-        let (left_opt, right_opt) = self.get_brothers_id(&current_btree_node, node_idx);
+        let (left_opt, right_opt) = self.get_brothers_id(current_btree_node, node_idx);
 
         let left_node_opt = match left_opt {
             Some(pid) => Some(self.get_btree_by_pid(pid, current_pid)?),
@@ -272,12 +210,15 @@ impl<'a> BTreePageDeleteWrapper<'a> {
 
         // if max_brother_size satisfies the number, shift one item the middle child
         // if NOT, merge the brother the the middle child
-        if self.is_content_size_satisfied(max_brother_size) {
+        if self.is_content_size_satisfied(max_brother_size - 1) {
+            // the item between the subtree and the brother
+            let separator_idx = if is_brother_right { node_idx } else { node_idx - 1 };
+
             let replace_item = if is_brother_right { // middle <-(item)- right
                 let mut shift_node = right_node_opt.unwrap();
-                let (_, right_head_content) = shift_node.shift_head();
+                let (right_head_index, right_head_content) = shift_node.shift_head();
 
-                subtree_node.insert_back(current_btree_node.content[node_idx].clone(), 0);
+                subtree_node.insert_back(current_btree_node.content[separator_idx].clone(), right_head_index);
 
                 self.write_btree(*shift_node);
                 self.write_btree(*subtree_node);
@@ -285,9 +226,9 @@ impl<'a> BTreePageDeleteWrapper<'a> {
                 right_head_content
             } else {  // left -(item)-> middle
                 let mut shift_node = left_node_opt.unwrap();
-                let (left_last_content, _) = shift_node.shift_last();
+                let (left_last_content, left_last_index) = shift_node.shift_last();
 
-                subtree_node.insert_head(0, current_btree_node.content[node_idx].clone());
+                subtree_node.insert_head(left_last_index, current_btree_node.content[separator_idx].clone());
 
                 self.write_btree(*shift_node);
                 self.write_btree(*subtree_node);
@@ -296,7 +237,7 @@ impl<'a> BTreePageDeleteWrapper<'a> {
             };
 
             // shift complete
-            current_btree_node.content[node_idx] = replace_item;
+            current_btree_node.content[separator_idx] = replace_item;
 
             return Ok(true);
         }
@@ -304,14 +245,13 @@ impl<'a> BTreePageDeleteWrapper<'a> {
         Ok(false)
     }
 
-    // merge the nth elements of the current_btree_node
-    fn merge_leaves(&mut self, node_idx: usize, current_btree_node: &mut BTreeNode) -> DbResult<()> {
-        debug_assert!(current_btree_node.content.len() > 1);
-
+    // merge the nth subtree of the current_btree_node with one of its brothers,
+    // the item between them moves down
+    fn merge_subtrees(&mut self, node_idx: usize, current_btree_node: &mut BTreeNode) -> DbResult<()> {
         let current_pid = current_btree_node.pid;
         let subtree_pid = current_btree_node.indexes[node_idx];  // subtree need to shift
 
-        let (left_opt, right_opt) = self.get_brothers_id(&current_btree_node, node_idx);
+        let (left_opt, right_opt) = self.get_brothers_id(current_btree_node, node_idx);
 
         let left_node_opt = match left_opt {
             Some(pid) => Some(self.get_btree_by_pid(pid, current_pid)?),
@@ -345,11 +285,11 @@ impl<'a> BTreePageDeleteWrapper<'a> {
             left_node.content.extend_from_slice(&subtree_node.content);
             left_node.indexes.extend_from_slice(&subtree_node.indexes);
 
+            debug_assert_eq!(current_btree_node.indexes[node_idx], subtree_node.pid);
+
             current_btree_node.content.remove(node_idx - 1);
             current_btree_node.indexes.remove(node_idx);
 
-            debug_assert_eq!(current_btree_node.indexes[node_idx], subtree_node.pid);
-
             self.base.page_handler.free_page(subtree_node.pid)?;
 
             self.write_btree(*left_node);
@@ -375,7 +315,7 @@ impl<'a> BTreePageDeleteWrapper<'a> {
     }
 
     fn erase_item(&mut self, item: &DataTicket) -> DbResult<Rc<Document>> {
-        let bytes = self.base.page_handler.free_data_ticket(&item)?;
+        let bytes = self.base.page_handler.free_data_ticket(item)?;
         debug_assert!(!bytes.is_empty(), "bytes is empty");
         let doc = Document::from_bytes(bytes.as_ref())?;
         Ok(Rc::new(doc))
@@ -384,7 +324,7 @@ impl<'a> BTreePageDeleteWrapper<'a> {
     #[inline]
     fn is_content_size_satisfied(&self, size: usize) -> bool {
         let item_size = self.base.item_size as usize;
-        size >= (item_size + 1) / 2 - 1
+        size >= item_size.div_ceil(2) - 1
     }
 
     fn get_brothers_id(&self, btree_node: &BTreeNode, node_idx: usize) -> (Option<u32>, Option<u32>) {
@@ -412,7 +352,6 @@ impl<'a> BTreePageDeleteWrapper<'a> {
         self.base.write_btree_node(&btree_node)?;
 
         Ok(DeleteBackwardItem {
-            is_leaf: true,
             child_size: remain_content_len,
             deleted_ticket,
         })
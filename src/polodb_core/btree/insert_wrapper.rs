@@ -13,6 +13,7 @@ pub(crate) struct InsertBackwardItem {
 
 pub(crate) struct InsertResult {
     pub backward_item: Option<InsertBackwardItem>,
+    #[allow(dead_code)]
     pub primary_key: Value,
 }
 
@@ -82,7 +83,7 @@ pub struct BTreePageInsertWrapper<'a>(BTreePageWrapperBase<'a>);
 
 impl<'a> BTreePageInsertWrapper<'a> {
 
-    pub(crate) fn new(page_handler: &mut PageHandler, root_page_id: u32) -> BTreePageInsertWrapper {
+    pub(crate) fn new(page_handler: &mut PageHandler, root_page_id: u32) -> BTreePageInsertWrapper<'_> {
         let base = BTreePageWrapperBase::new(page_handler, root_page_id);
         BTreePageInsertWrapper(base)
     }
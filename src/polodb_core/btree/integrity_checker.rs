@@ -0,0 +1,215 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use polodb_bson::Value;
+use crate::DbResult;
+use crate::page::PageHandler;
+use super::BTreeNode;
+
+/// Walk the B-trees and collect the problems found,
+/// the pages are shared by all the trees checked by the same checker,
+/// so a page referenced by two trees is reported.
+pub(crate) struct IntegrityChecker {
+    page_count: u32,
+    visited:    HashSet<u32>,
+    problems:   Vec<String>,
+}
+
+// the keys of a subtree must be in (lower, upper)
+#[derive(Clone, Copy)]
+struct KeyBounds<'a> {
+    lower: Option<&'a Value>,
+    upper: Option<&'a Value>,
+}
+
+impl IntegrityChecker {
+
+    /// `page_count` is the count of the pages allocated,
+    /// a child pid out of it is invalid.
+    pub(crate) fn new(page_count: u32) -> IntegrityChecker {
+        IntegrityChecker {
+            page_count,
+            visited: HashSet::new(),
+            problems: vec![],
+        }
+    }
+
+    /// Check the tree at `root_pid`, the count of the items returns.
+    ///
+    /// Checked:
+    /// - the keys are ascending, and in the range of the parent
+    /// - the child pids are valid, and no page is referenced twice
+    /// - all the leaves are at the same depth
+    /// - every node except the root holds at least the min items
+    pub(crate) fn check_tree(&mut self, page_handler: &mut PageHandler, name: &str, root_pid: u32) -> DbResult<u64> {
+        let bounds = KeyBounds {
+            lower: None,
+            upper: None,
+        };
+        let mut leaf_depth = None;
+        self.check_node(page_handler, name, 0, root_pid, bounds, 0, &mut leaf_depth)
+    }
+
+    #[inline]
+    pub(crate) fn add_problem(&mut self, problem: String) {
+        self.problems.push(problem);
+    }
+
+    #[inline]
+    pub(crate) fn into_problems(self) -> Vec<String> {
+        self.problems
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn check_node(&mut self, page_handler: &mut PageHandler, name: &str, parent_pid: u32, pid: u32,
+                  bounds: KeyBounds, depth: usize, leaf_depth: &mut Option<usize>) -> DbResult<u64> {
+        if pid == 0 || pid >= self.page_count {
+            self.add_problem(format!("{}: page {} referenced by page {} is out of range", name, pid, parent_pid));
+            return Ok(0);
+        }
+
+        if !self.visited.insert(pid) {
+            self.add_problem(format!("{}: page {} referenced by page {} is referenced twice", name, pid, parent_pid));
+            return Ok(0);
+        }
+
+        let page = page_handler.pipeline_read_page(pid)?;
+        let node = match BTreeNode::from_raw(&page, parent_pid, page_handler.btree_order, page_handler) {
+            Ok(node) => node,
+            Err(err) => {
+                self.add_problem(format!("{}: page {} can't be read: {}", name, pid, err));
+                return Ok(0);
+            }
+        };
+
+        if node.is_empty() {
+            if depth > 0 {
+                self.add_problem(format!("{}: page {} is empty", name, pid));
+            }
+            return Ok(0);
+        }
+
+        let min_size = (page_handler.btree_order as usize).div_ceil(2) - 1;
+        if depth > 0 && node.content.len() < min_size {
+            self.add_problem(format!("{}: page {} holds {} items, less than {}", name, pid, node.content.len(), min_size));
+        }
+
+        let mut prev = bounds.lower;
+        for item in &node.content {
+            if let Some(prev) = prev {
+                self.check_key_order(name, pid, prev, &item.key);
+            }
+            prev = Some(&item.key);
+        }
+        if let (Some(last), Some(upper)) = (prev, bounds.upper) {
+            self.check_key_order(name, pid, last, upper);
+        }
+
+        let mut count = node.content.len() as u64;
+
+        if node.is_leaf() {
+            if node.indexes.iter().any(|child_pid| *child_pid != 0) {
+                self.add_problem(format!("{}: leaf page {} has children", name, pid));
+            }
+
+            match leaf_depth {
+                Some(expected) if *expected != depth => {
+                    self.add_problem(format!("{}: leaf page {} is at depth {}, expected {}", name, pid, depth, expected));
+                }
+                Some(_) => (),
+                None => *leaf_depth = Some(depth),
+            }
+
+            return Ok(count);
+        }
+
+        for (index, child_pid) in node.indexes.iter().enumerate() {
+            let child_bounds = KeyBounds {
+                lower: if index == 0 { bounds.lower } else { Some(&node.content[index - 1].key) },
+                upper: node.content.get(index).map(|item| &item.key).or(bounds.upper),
+            };
+            count += self.check_node(page_handler, name, pid, *child_pid, child_bounds, depth + 1, leaf_depth)?;
+        }
+
+        Ok(count)
+    }
+
+    fn check_key_order(&mut self, name: &str, pid: u32, prev: &Value, next: &Value) {
+        match prev.value_cmp(next) {
+            Ok(Ordering::Less) => (),
+            Ok(_) => {
+                self.add_problem(format!("{}: page {} has key {} not less than {}", name, pid, prev, next));
+            }
+            Err(err) => {
+                self.add_problem(format!("{}: page {} has keys not comparable: {}", name, pid, err));
+            }
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use polodb_bson::{Document, mk_document};
+    use crate::TransactionType;
+    use crate::page::{PageHandler, RawPage};
+    use crate::btree::{BTreeNode, bulk_load};
+    use crate::btree::integrity_checker::IntegrityChecker;
+
+    fn read_node(page_handler: &mut PageHandler, pid: u32) -> BTreeNode {
+        let page = page_handler.pipeline_read_page(pid).unwrap();
+        BTreeNode::from_raw(&page, 0, page_handler.btree_order, page_handler).unwrap()
+    }
+
+    fn write_node(page_handler: &mut PageHandler, node: &BTreeNode) {
+        let mut page = RawPage::new(node.pid, page_handler.page_size);
+        node.to_raw(&mut page).unwrap();
+        page_handler.pipeline_write_page(&page).unwrap();
+    }
+
+    #[test]
+    fn test_check_corrupt_tree() {
+        let mut db_path = env::temp_dir();
+        db_path.push("test-integrity-checker.db");
+        let _ = std::fs::remove_file(db_path.as_path());
+        let _ = std::fs::remove_file(PageHandler::mk_journal_path(db_path.as_path()));
+
+        let mut page_handler = PageHandler::new(db_path.as_path(), 4096).unwrap();
+        page_handler.start_transaction(TransactionType::Write).unwrap();
+
+        let root_pid = page_handler.alloc_page_id().unwrap();
+        let docs: Vec<Document> = (0..2000).map(|i| mk_document! { "_id": i }).collect();
+        bulk_load(&mut page_handler, root_pid, &docs).unwrap();
+        let page_count = page_handler.alloc_page_id().unwrap();
+
+        let mut checker = IntegrityChecker::new(page_count);
+        assert_eq!(checker.check_tree(&mut page_handler, "test", root_pid).unwrap(), 2000);
+        assert!(checker.into_problems().is_empty());
+
+        // swap the first two keys of the first leaf
+        let mut root = read_node(&mut page_handler, root_pid);
+        let mut leaf = read_node(&mut page_handler, root.indexes[0]);
+        leaf.content.swap(0, 1);
+        write_node(&mut page_handler, &leaf);
+
+        let mut checker = IntegrityChecker::new(page_count);
+        checker.check_tree(&mut page_handler, "test", root_pid).unwrap();
+        let problems = checker.into_problems();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("has key 1 not less than 0"), "{}", problems[0]);
+
+        // the second child is replaced by the first one, and an invalid pid
+        root.indexes[1] = root.indexes[0];
+        root.indexes[2] = page_count + 10;
+        write_node(&mut page_handler, &root);
+
+        let mut checker = IntegrityChecker::new(page_count);
+        checker.check_tree(&mut page_handler, "test", root_pid).unwrap();
+        let problems = checker.into_problems();
+        assert!(problems.iter().any(|problem| problem.contains("is referenced twice")));
+        assert!(problems.iter().any(|problem| problem.contains("is out of range")));
+
+        page_handler.rollback().unwrap();
+    }
+
+}
@@ -2,17 +2,23 @@
 mod wrapper_base;
 mod insert_wrapper;
 mod delete_wrapper;
+mod search_wrapper;
 pub mod counter_helper;
 pub(crate) mod delete_all_helper;
+mod bulk_load;
+pub(crate) mod integrity_checker;
 
 pub(crate) use delete_wrapper::BTreePageDeleteWrapper;
 pub(crate) use insert_wrapper::{BTreePageInsertWrapper, InsertBackwardItem, InsertResult};
+pub(crate) use search_wrapper::{BTreePageSearchWrapper, BTreeSearchResult};
+pub(crate) use bulk_load::bulk_load;
+pub(crate) use wrapper_base::cal_item_size;
 
 use std::cmp::Ordering;
 use polodb_bson::{vli, Value, ObjectId, ty_int};
 use crate::db::DbResult;
 use crate::page::{RawPage, PageType, PageHandler};
-use crate::error::{DbErr, mk_unexpected_header_for_btree_page};
+use crate::error::{DbErr, mk_unexpected_header_for_btree_page, mk_data_malformed};
 use crate::data_ticket::DataTicket;
 
 pub const HEADER_SIZE: u32      = 64;
@@ -117,7 +123,8 @@ impl BTreeNode {
         let len = page.get_u16(2);
 
         if (len as u32) > item_size {  // data error
-            return Err(DbErr::ItemSizeGreaterThanExpected);
+            return Err(mk_data_malformed(page.page_id, 2, format!(
+                "the length of items {} is greater than expected {}", len, item_size)));
         }
 
         for i in 0..len {
@@ -125,7 +132,7 @@ impl BTreeNode {
 
             let right_pid = page.get_u32(offset);
 
-            let node_data_item = BTreeNode::parse_node_data_item(&page, offset, page_handler)?;
+            let node_data_item = BTreeNode::parse_node_data_item(page, offset, page_handler)?;
 
             content.push(node_data_item);
             indexes.push(right_pid);
@@ -170,19 +177,19 @@ impl BTreeNode {
 
         let key_ty_int = page.get_u8(begin_offset + 4 + 1);  // use to parse data
 
+        let value_begin_offset = (begin_offset + 6) as usize;
         let key: Value = match key_ty_int {
-            0 => return Err(DbErr::KeyTypeOfBtreeShouldNotBeZero),
+            0 => return Err(mk_data_malformed(page.page_id, value_begin_offset - 1, "key type of btree should not be zero")),
 
             ty_int::OBJECT_ID => {
-                let oid_bytes_begin = (begin_offset + 6) as usize;
-                let oid_bytes = &page.data[oid_bytes_begin..(oid_bytes_begin + 12)];
-                let oid = ObjectId::deserialize(oid_bytes)?;
+                let oid_bytes = &page.data[value_begin_offset..(value_begin_offset + 12)];
+                let oid = ObjectId::deserialize(oid_bytes)
+                    .map_err(|err| mk_data_malformed(page.page_id, value_begin_offset, err.to_string()))?;
 
                 oid.into()
             }
 
             ty_int::BOOLEAN => {
-                let value_begin_offset = (begin_offset + 6) as usize;
                 let value = page.data[value_begin_offset];
 
                 let bl_value = value != 0;
@@ -191,16 +198,14 @@ impl BTreeNode {
             }
 
             ty_int::INT => {
-                let value_begin_offset = (begin_offset + 6) as usize;
-
-                let (int_value, _) = vli::decode_u64(&page.data[value_begin_offset..])?;
+                let value_end_offset = value_begin_offset + BTREE_ENTRY_KEY_CONTENT_SIZE;
+                let (int_value, _) = vli::decode_u64(&page.data[value_begin_offset..value_end_offset])
+                    .map_err(|err| mk_data_malformed(page.page_id, value_begin_offset, err.to_string()))?;
 
                 Value::Int(int_value as i64)
             }
 
             ty_int::STRING => {
-                let value_begin_offset = (begin_offset + 6) as usize;
-
                 let mut buffer = Vec::new();
 
                 let mut offset = 0;
@@ -220,16 +225,25 @@ impl BTreeNode {
                     offset += 1;
                 }
 
-                let str = unsafe {
-                    String::from_utf8_unchecked(buffer)
-                };
+                let str = String::from_utf8(buffer)
+                    .map_err(|err| mk_data_malformed(page.page_id, value_begin_offset, err.to_string()))?;
 
                 str.into()
             }
 
+            ty_int::TIMESTAMP => {
+                let mut buffer: [u8; 4] = [0; 4];
+                buffer.copy_from_slice(&page.data[value_begin_offset..(value_begin_offset + 4)]);
+                let time = u32::from_be_bytes(buffer);
+                buffer.copy_from_slice(&page.data[(value_begin_offset + 4)..(value_begin_offset + 8)]);
+                let inc = u32::from_be_bytes(buffer);
+
+                Value::Timestamp { time, inc }
+            }
+
             _ => {
                 let error_msg = format!("type {} is not suitable for _id", key_ty_int);
-                return Err(DbErr::ParseError(error_msg));
+                return Err(mk_data_malformed(page.page_id, value_begin_offset - 1, error_msg));
             },
 
         };
@@ -244,8 +258,15 @@ impl BTreeNode {
 
     fn parse_complex_data_item(page: &RawPage, begin_offset: u32, page_handler: &mut PageHandler) -> DbResult<BTreeNodeDataItem> {
         let data_ticket = BTreeNode::parse_data_item_ticket(page, begin_offset);
-        let doc = page_handler.get_doc_from_ticket(&data_ticket)?.unwrap();
-        let pkey = doc.pkey_id().unwrap();
+        let ticket_offset = (begin_offset + 6 + 12) as usize;
+        let doc = match page_handler.get_doc_from_ticket(&data_ticket)? {
+            Some(doc) => doc,
+            None => return Err(mk_data_malformed(page.page_id, ticket_offset, format!("the item of ticket {} is removed", data_ticket))),
+        };
+        let pkey = match doc.pkey_id() {
+            Some(pkey) => pkey,
+            None => return Err(mk_data_malformed(page.page_id, ticket_offset, "the document has no primary key")),
+        };
         Ok(BTreeNodeDataItem {
             key: pkey,
             data_ticket,
@@ -270,12 +291,12 @@ impl BTreeNode {
         page.seek(2);
         page.put_u16(items_len);
 
-        if let Some(_first) = self.content.first() {
-            page.seek(4);
+        // the left pid is kept even if the node is empty,
+        // an internal node is empty for a moment while deleting
+        page.seek(4);
 
-            let left_id = self.indexes.first().expect("get first left id failed");
-            page.put_u32(*left_id);
-        };
+        let left_id = self.indexes.first().expect("get first left id failed");
+        page.put_u32(*left_id);
 
         let mut index = 0;
         while index < self.content.len() {
@@ -353,7 +374,7 @@ impl BTreeNode {
                 let str_len = str.len();
 
                 if str_len > BTREE_ENTRY_KEY_CONTENT_SIZE {
-                    return BTreeNode::put_string_complex_key(page, key);
+                    return BTreeNode::put_complex_key(page, key);
                 }
 
                 BTreeNode::put_standard_content_key(page, key);
@@ -366,13 +387,29 @@ impl BTreeNode {
                 Ok(())
             }
 
+            Value::Timestamp { time, inc } => {
+                BTreeNode::put_standard_content_key(page, key);
+
+                let mut buffer: [u8; BTREE_ENTRY_KEY_CONTENT_SIZE] = [0; BTREE_ENTRY_KEY_CONTENT_SIZE];
+                buffer[0..4].copy_from_slice(&time.to_be_bytes());
+                buffer[4..8].copy_from_slice(&inc.to_be_bytes());
+
+                page.put(&buffer);
+
+                Ok(())
+            }
+
+            // the composite key of the compound index, and the Double of the index,
+            // they are always read from the document
+            Value::Array(_) | Value::Double(_) => BTreeNode::put_complex_key(page, key),
+
             _ => Err(DbErr::NotAValidKeyType(key.ty_name().into()))
         }
     }
 
     // | 1      | ty_int |
     // | 1 byte | 1 byte |
-    fn put_string_complex_key(page: &mut RawPage, key: &Value) -> DbResult<()> {
+    fn put_complex_key(page: &mut RawPage, key: &Value) -> DbResult<()> {
         let key_ty_int = key.ty_int();
 
         page.put_u8(1);
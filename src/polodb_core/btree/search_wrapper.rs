@@ -0,0 +1,58 @@
+use polodb_bson::Value;
+use crate::DbResult;
+use crate::page::PageHandler;
+use super::{BTreeNodeDataItem, SearchKeyResult};
+use super::wrapper_base::BTreePageWrapperBase;
+
+#[allow(dead_code)]
+pub(crate) enum BTreeSearchResult {
+    // the item with the key
+    Found(BTreeNodeDataItem),
+
+    // the key is not found,
+    // the pid of the leaf the key would belong to
+    NotFound(u32),
+}
+
+pub(crate) struct BTreePageSearchWrapper<'a>(BTreePageWrapperBase<'a>);
+
+impl<'a> BTreePageSearchWrapper<'a> {
+
+    pub(crate) fn new(page_handler: &mut PageHandler, root_page_id: u32) -> BTreePageSearchWrapper<'_> {
+        let base = BTreePageWrapperBase::new(page_handler, root_page_id);
+        BTreePageSearchWrapper(base)
+    }
+
+    // descend from the root by the order of the keys,
+    // only the pages on the path are read
+    pub(crate) fn search(&mut self, key: &Value) -> DbResult<BTreeSearchResult> {
+        let mut current_pid = self.0.root_page_id;
+        let mut parent_pid = 0;
+
+        loop {
+            let btree_node = self.0.get_node(current_pid, parent_pid)?;
+            if btree_node.is_empty() {
+                return Ok(BTreeSearchResult::NotFound(current_pid));
+            }
+
+            match btree_node.search(key)? {
+                SearchKeyResult::Node(index) => {
+                    let item = btree_node.content[index].clone();
+                    return Ok(BTreeSearchResult::Found(item));
+                }
+
+                SearchKeyResult::Index(index) => {
+                    let next_pid = btree_node.indexes[index];
+                    if next_pid == 0 {
+                        return Ok(BTreeSearchResult::NotFound(current_pid));
+                    }
+
+                    parent_pid = current_pid;
+                    current_pid = next_pid;
+                }
+
+            }
+        }
+    }
+
+}
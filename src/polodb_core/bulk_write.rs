@@ -0,0 +1,25 @@
+use polodb_bson::{Document, Value};
+use crate::DbErr;
+
+/// An operation of [Database::bulk_write](./db/struct.Database.html#method.bulk_write).
+#[derive(Debug, Clone)]
+pub enum WriteOp {
+    /// An `ObjectId` is generated for the document without `_id`.
+    Insert(Document),
+    /// Update all the documents matching the `filter`,
+    /// the operators are the same as [Database::update](./db/struct.Database.html#method.update).
+    Update { filter: Document, update: Document },
+    /// Delete all the documents matching the `filter`.
+    Delete { filter: Document },
+}
+
+/// The tally returned by [Database::bulk_write](./db/struct.Database.html#method.bulk_write).
+#[derive(Debug, Default)]
+pub struct BulkResult {
+    /// The `_id`s of the documents inserted, in the order of the operations.
+    pub inserted_ids: Vec<Value>,
+    pub modified:     usize,
+    pub deleted:      usize,
+    /// The operations skipped in the unordered mode, with their indexes in the batch.
+    pub errors:       Vec<(usize, DbErr)>,
+}
@@ -1,7 +1,112 @@
+use std::time::Duration;
 
+// the same as the limit of MongoDB
+const DEFAULT_MAX_DOCUMENT_SIZE: usize = 16 * 1024 * 1024;
+
+/// When the data is synced to the disk (`fsync`).
+///
+/// The policies below are for `JournalMode::Wal`. In `JournalMode::Shadow`, the database file
+/// is written at every commit, the journal and the database file are synced at every commit
+/// unless it's `Off`, and `Full` also syncs the journal truncated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SyncPolicy {
+    /// Sync the journal at every commit, and the database file at every checkpoint.
+    /// Nothing committed is lost even if the OS crashes.
+    Full,
+    /// Sync the database file at every checkpoint only, the default.
+    /// The database is not corrupted by a crash, but the transactions committed
+    /// after the last checkpoint may be lost if the OS crashes.
+    #[default]
+    Normal,
+    /// Never sync, leave it to the OS.
+    ///
+    /// **Unsafe for durability**, a power failure can corrupt the database,
+    /// only for the data that can be thrown away.
+    Off,
+}
+
+/// How the changes of a transaction are journaled, see [Config::journal_mode](./struct.Config.html#structfield.journal_mode).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JournalMode {
+    /// The changed pages are kept in the memory until the commit,
+    /// the original pages are copied to the journal, and the changed ones are written
+    /// to the database file in place, the journal is truncated when it's finished.
+    ///
+    /// If the process dies during the commit, the original pages are written back
+    /// when the database is opened. The database file is always up to date,
+    /// but every commit writes the database file.
+    Shadow,
+    /// The write-ahead log, the default. The changed pages are appended to the journal,
+    /// and copied to the database file at the checkpoint.
+    ///
+    /// If the process dies before the checkpoint, the committed pages are copied
+    /// when the database is opened. The writes are sequential, it's faster for
+    /// the small transactions.
+    #[default]
+    Wal,
+}
+
+#[derive(Clone)]
 pub struct Config {
     pub init_block_count:  u64,
+    /// The count of the frames in the journal to trigger a checkpoint.
+    ///
+    /// In `JournalMode::Wal`, the pages are appended to the journal and copied to
+    /// the database file at the checkpoint, a larger size makes fewer checkpoints.
     pub journal_full_size: u64,
+    /// How the changes are journaled, `JournalMode::Wal` by default.
+    ///
+    /// A database can be opened with another mode than the one it was written with,
+    /// the journal left is recovered by the mode it was written with.
+    pub journal_mode:      JournalMode,
+    /// The max time between two checkpoints, the journal is checkpointed at the commit
+    /// after it's elapsed, even if it's not full. None by default, only the size is checked.
+    ///
+    /// A crash before the checkpoint loses nothing committed, the journal is replayed
+    /// when the database is opened. The journal is also checkpointed when the database is dropped.
+    pub checkpoint_interval: Option<Duration>,
+    /// The size of a page, a power of two in [4096, 32768].
+    ///
+    /// It's stored in the header of the file when the database is created,
+    /// opening an existing file with another page size fails with `DbErr::PageSizeMismatch`.
+    pub page_size:         u32,
+    /// Open the database without writing anything to the disk,
+    /// the writing operations fail with `DbErr::ReadOnly`.
+    pub read_only:         bool,
+    /// The max size of an encoded document, 16 MiB by default.
+    ///
+    /// Inserting or updating a larger document fails with `DbErr::DocumentTooLarge`,
+    /// before any page is allocated.
+    pub max_document_size: usize,
+    /// The max count of the items in a B-tree node, derived from the page size by default.
+    ///
+    /// It's stored in the header of the file when the database is created,
+    /// opening an existing file with another order fails with `DbErr::BTreeOrderMismatch`.
+    /// A value that can't fit in a page fails with `DbErr::IllegalBTreeOrder`.
+    pub btree_order:       Option<u32>,
+    /// Store a checksum in every page, a page read from the file with a mismatched checksum
+    /// fails with `DbErr::PageChecksumMismatch`.
+    ///
+    /// It's stored in the header of the file when the database is created,
+    /// an existing file keeps the setting it was created with.
+    pub page_checksum:     bool,
+    /// When the journal and the database file are synced to the disk, `SyncPolicy::Normal` by default.
+    pub sync_policy:       SyncPolicy,
+    /// Record the insertions, the updates and the deletions in the collection
+    /// [OPLOG_COLLECTION_NAME](./constant.OPLOG_COLLECTION_NAME.html), false by default.
+    ///
+    /// An entry is written in the same transaction as the change,
+    /// read the new entries by [Database::tail_oplog](./struct.Database.html#method.tail_oplog).
+    pub oplog:             bool,
+    /// Read the pages of the database file through a memory mapping instead of `read`,
+    /// false by default. It saves the system calls for a large database which is mostly read.
+    ///
+    /// Only with the feature `mmap` on unix, opening a database file with it fails with
+    /// `DbErr::MmapNotSupported` otherwise. The database in the memory always uses the buffered IO.
+    ///
+    /// **The file must not be truncated by another process while it's opened**,
+    /// reading a page of the mapping beyond the end of the file crashes the process by `SIGBUS`.
+    pub mmap:              bool,
 }
 
 impl Default for Config {
@@ -10,6 +115,16 @@ impl Default for Config {
         Config {
             init_block_count:  16,
             journal_full_size: 1000,
+            journal_mode:      JournalMode::default(),
+            checkpoint_interval: None,
+            page_size:         4096,
+            read_only:         false,
+            max_document_size: DEFAULT_MAX_DOCUMENT_SIZE,
+            btree_order:       None,
+            page_checksum:     false,
+            sync_policy:       SyncPolicy::default(),
+            oplog:             false,
+            mmap:              false,
         }
     }
 
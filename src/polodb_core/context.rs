@@ -1,16 +1,22 @@
 use std::rc::Rc;
+use std::sync::Arc;
 use std::borrow::Borrow;
 use std::path::{Path, PathBuf};
-use polodb_bson::{Document, Value, ObjectIdMaker, mk_document};
+use polodb_bson::{Document, Array, Value, ObjectIdMaker, mk_document};
 use super::page::{header_page_wrapper, PageHandler};
-use super::error::DbErr;
-use crate::Config;
-use crate::vm::{SubProgram, VM, VmState};
+use super::error::{DbErr, mk_unsupported_value_type};
+use crate::{Config, SyncPolicy, DbStats, CollectionStats, OperationOptions};
+use crate::vm::{SubProgram, VM, VmState, DbOp};
 use crate::db::DbResult;
+use crate::migration;
+use crate::oplog::{self, OPLOG_COLLECTION_NAME, op_name};
 use crate::meta_doc_helper::{meta_doc_key, MetaDocEntry};
-use crate::index_ctx::{IndexCtx, merge_options_into_default};
+use crate::index_ctx::{IndexCtx, merge_options_into_default, mk_fields_array};
 use crate::btree::*;
-use crate::page::{RawPage, TransactionState};
+use crate::btree::integrity_checker::IntegrityChecker;
+use crate::page::{RawPage, TransactionState, PageCacheStats, PageMetrics, ReadView, MEMORY_DB_PATH};
+use crate::cursor::Cursor;
+use crate::query::{self, QueryPlan};
 use crate::db_handle::DbHandle;
 use crate::journal::TransactionType;
 use crate::dump::{FullDump, PageDump, OverflowDataPageDump, DataPageDump, FreeListPageDump, BTreePageDump};
@@ -33,9 +39,68 @@ macro_rules! try_db_op {
     }
 }
 
+// the documents are copied in batches when compacting
+const COMPACT_BATCH_SIZE: usize = 1024;
+
 #[inline]
 fn index_already_exists(index_doc: &Document, key: &str) -> bool {
-    index_doc.get(key).is_some()
+    index_doc.contains_key(key)
+}
+
+// the keys and the strings are encoded as C strings,
+// a NUL byte in them would corrupt the document
+fn check_doc_encodable(doc: &Document, prefix: &str) -> DbResult<()> {
+    for (key, value) in doc.iter() {
+        let field = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+
+        if key.contains('\0') {
+            return Err(mk_unsupported_value_type(&field, "the key contains a NUL byte"));
+        }
+
+        check_value_encodable(value, &field)?;
+    }
+
+    Ok(())
+}
+
+fn check_array_encodable(arr: &Array, prefix: &str) -> DbResult<()> {
+    for (index, value) in arr.iter().enumerate() {
+        check_value_encodable(value, &format!("{}.{}", prefix, index))?;
+    }
+
+    Ok(())
+}
+
+fn check_value_encodable(value: &Value, field: &str) -> DbResult<()> {
+    match value {
+        Value::String(str) if str.contains('\0') => {
+            Err(mk_unsupported_value_type(field, format!("{} contains a NUL byte", value.ty_name())))
+        }
+        Value::Document(doc) => check_doc_encodable(doc, field),
+        Value::Array(arr) => check_array_encodable(arr, field),
+        _ => Ok(()),
+    }
+}
+
+// the primary keys of all the rows of the handle
+fn collect_primary_keys(handle: &mut DbHandle) -> DbResult<Vec<Value>> {
+    let mut buffer = vec![];
+
+    handle.step()?;
+
+    while handle.has_row() {
+        let doc = handle.get().unwrap_document();
+        let pkey = doc.pkey_id().unwrap();
+        buffer.push(pkey);
+
+        handle.step()?;
+    }
+
+    Ok(buffer)
 }
 
 /**
@@ -45,6 +110,8 @@ pub struct DbContext {
     path:                PathBuf,
     page_handler:        Box<PageHandler>,
     obj_id_maker:        ObjectIdMaker,
+    last_timestamp:      (u32, u32),
+    oplog:               bool,
     meta_version:        u32,
 
 }
@@ -65,23 +132,47 @@ pub struct CollectionMeta {
 impl DbContext {
 
     pub fn new(path: &Path, config: Config) -> DbResult<DbContext> {
-        let page_size = 4096;
+        let page_size = config.page_size;
+
+        let mut page_handler = PageHandler::with_config(path, page_size, Arc::new(config))?;
+
+        migration::migrate(&mut page_handler)?;
+
+        DbContext::from_page_handler(path, page_handler)
+    }
+
+    /// Open a database in the memory, nothing is written to the disk.
+    pub fn open_memory(config: Config) -> DbResult<DbContext> {
+        let page_size = config.page_size;
+
+        let page_handler = PageHandler::open_memory(page_size, Arc::new(config))?;
 
-        let page_handler = PageHandler::with_config(path, page_size, Rc::new(config))?;
+        DbContext::from_page_handler(Path::new(MEMORY_DB_PATH), page_handler)
+    }
 
-        let obj_id_maker = ObjectIdMaker::new();
+    fn from_page_handler(path: &Path, mut page_handler: PageHandler) -> DbResult<DbContext> {
+        let obj_id_maker = DbContext::restore_object_id_maker(&mut page_handler)?;
+        let oplog = page_handler.config().oplog;
+        let read_only = page_handler.config().read_only;
 
         let mut ctx = DbContext {
             path: path.to_path_buf(),
             page_handler: Box::new(page_handler),
             // first_page,
             obj_id_maker,
+            last_timestamp: (0, 0),
+            oplog,
             meta_version: 0,
         };
 
         let meta_source = ctx.get_meta_source()?;
         ctx.meta_version = meta_source.meta_version;
 
+        // created before any handle is made, so the meta version isn't changed later
+        if oplog && !read_only && !ctx.exists_collection(OPLOG_COLLECTION_NAME)? {
+            ctx.create_collection(OPLOG_COLLECTION_NAME)?;
+        }
+
         Ok(ctx)
     }
 
@@ -102,6 +193,22 @@ impl DbContext {
         Ok(())
     }
 
+    /// Resolve the meta of the collection, the [Collection](./db/struct.Collection.html) caches it,
+    /// so its operations don't look it up again.
+    pub(crate) fn get_collection_meta_entry(&mut self, col_id: u32, meta_version: u32) -> DbResult<MetaDocEntry> {
+        self.check_meta_version(meta_version)?;
+        self.page_handler.auto_start_transaction(TransactionType::Read)?;
+
+        let result = try_db_op!(self, self.internal_get_collection_meta_entry(col_id));
+
+        Ok(result)
+    }
+
+    fn internal_get_collection_meta_entry(&mut self, col_id: u32) -> DbResult<MetaDocEntry> {
+        let meta_source = self.get_meta_source()?;
+        self.find_collection_root_pid_by_id(0, meta_source.meta_pid, col_id)
+    }
+
     pub fn get_collection_meta_by_name(&mut self, name: &str) -> DbResult<CollectionMeta> {
         self.page_handler.auto_start_transaction(TransactionType::Read)?;
 
@@ -144,6 +251,26 @@ impl DbContext {
         Err(DbErr::CollectionNotFound(name.into()))
     }
 
+    // restore the state of the ObjectIdMaker saved in the header page,
+    // so the ids made after reopening are greater than the ones saved before
+    fn restore_object_id_maker(page_handler: &mut PageHandler) -> DbResult<ObjectIdMaker> {
+        let head_page = page_handler.pipeline_read_page(0)?;
+        let head_page_wrapper = HeaderPageWrapper::from_raw_page(head_page);
+        let last_timestamp = head_page_wrapper.get_oid_timestamp();
+        if last_timestamp == 0 {  // no id is made
+            return Ok(ObjectIdMaker::new());
+        }
+        Ok(ObjectIdMaker::with_state(last_timestamp, head_page_wrapper.get_oid_counter()))
+    }
+
+    fn save_object_id_maker(&mut self) -> DbResult<()> {
+        let head_page = self.page_handler.pipeline_read_page(0)?;
+        let mut head_page_wrapper = HeaderPageWrapper::from_raw_page(head_page);
+        head_page_wrapper.set_oid_timestamp(self.obj_id_maker.last_timestamp());
+        head_page_wrapper.set_oid_counter(self.obj_id_maker.counter());
+        self.page_handler.pipeline_write_page(&head_page_wrapper.0)
+    }
+
     pub(crate) fn get_meta_source(&mut self) -> DbResult<MetaSource> {
         let head_page = self.page_handler.pipeline_read_page(0)?;
         let head_page_wrapper = header_page_wrapper::HeaderPageWrapper::from_raw_page(head_page);
@@ -165,6 +292,29 @@ impl DbContext {
         Ok(meta)
     }
 
+    #[inline]
+    pub fn max_document_size(&self) -> usize {
+        self.page_handler.config().max_document_size
+    }
+
+    #[inline]
+    pub fn sync_policy(&self) -> SyncPolicy {
+        self.page_handler.config().sync_policy
+    }
+
+    pub fn exists_collection(&mut self, name: &str) -> DbResult<bool> {
+        self.page_handler.auto_start_transaction(TransactionType::Read)?;
+
+        let result = try_db_op!(self, self.internal_exists_collection(name));
+
+        Ok(result)
+    }
+
+    fn internal_exists_collection(&mut self, name: &str) -> DbResult<bool> {
+        let meta_src = self.get_meta_source()?;
+        self.check_collection_exist(name, &meta_src)
+    }
+
     fn check_collection_exist(&mut self, name: &str, meta_src: &MetaSource) -> DbResult<bool> {
         let collection_meta = MetaDocEntry::new(0, "<meta>".into(), meta_src.meta_pid);
 
@@ -192,6 +342,48 @@ impl DbContext {
         Ok(false)
     }
 
+    /// Change the name in the meta of the collection,
+    /// the meta is keyed by the id, so it's updated in place.
+    pub fn rename_collection(&mut self, old_name: &str, new_name: &str) -> DbResult<()> {
+        self.page_handler.auto_start_transaction(TransactionType::Write)?;
+
+        try_db_op!(self, self.internal_rename_collection(old_name, new_name));
+
+        Ok(())
+    }
+
+    fn internal_rename_collection(&mut self, old_name: &str, new_name: &str) -> DbResult<()> {
+        if new_name.is_empty() {
+            return Err(DbErr::IllegalCollectionName(new_name.into()));
+        }
+
+        let mut meta_source = self.get_meta_source()?;
+
+        let mut collection_meta = None;
+        for doc in self.internal_query_all_meta()? {
+            let entry = MetaDocEntry::from_doc(doc);
+            if entry.name() == new_name {
+                return Err(DbErr::CollectionAlreadyExists(new_name.into()));
+            }
+            if entry.name() == old_name {
+                collection_meta = Some(entry);
+            }
+        }
+
+        let mut collection_meta = match collection_meta {
+            Some(entry) => entry,
+            None => return Err(DbErr::CollectionNotFound(old_name.into())),
+        };
+        let col_id = collection_meta.doc_ref().pkey_id().unwrap().unwrap_int() as u32;
+
+        collection_meta.set_name(new_name);
+        self.update_collection_meta(&meta_source, col_id, &collection_meta)?;
+
+        // the handles of the collection are outdated
+        meta_source.meta_version += 1;
+        self.update_meta_source(&meta_source)
+    }
+
     fn internal_create_collection(&mut self, name: &str) -> DbResult<CollectionMeta> {
         if name.is_empty() {
             return Err(DbErr::IllegalCollectionName(name.into()));
@@ -200,7 +392,7 @@ impl DbContext {
 
         let exist = self.check_collection_exist(name, &meta_source)?;
         if exist {
-            return Err(DbErr::CollectionAlreadyExits(name.into()));
+            return Err(DbErr::CollectionAlreadyExists(name.into()));
         }
 
         let mut doc = Document::new_without_id();
@@ -215,6 +407,8 @@ impl DbContext {
 
         doc.insert(meta_doc_key::FLAGS.into(), Value::Int(0));
 
+        doc.insert(meta_doc_key::COUNT.into(), Value::Int(0));
+
         let mut btree_wrapper = BTreePageInsertWrapper::new(
             &mut self.page_handler, meta_source.meta_pid);
 
@@ -253,10 +447,10 @@ impl DbContext {
 
     #[inline]
     fn item_size(&self) -> u32 {
-        (self.page_handler.page_size - HEADER_SIZE) / ITEM_SIZE
+        self.page_handler.btree_order
     }
 
-    pub(crate) fn make_handle(&mut self, program: SubProgram) -> DbHandle {
+    pub(crate) fn make_handle(&mut self, program: SubProgram) -> DbHandle<'_> {
         let vm = VM::new(&mut self.page_handler, Box::new(program));
         DbHandle::new(vm)
     }
@@ -300,9 +494,13 @@ impl DbContext {
 
     fn internal_create_index(&mut self, col_id: u32, keys: &Document, options: Option<&Document>) -> DbResult<()> {
         let meta_source = self.get_meta_source()?;
-        let mut meta_doc = self.find_collection_root_pid_by_id(
+        let meta_doc = self.find_collection_root_pid_by_id(
             0, meta_source.meta_pid, col_id)?;
 
+        let indexes_doc = DbContext::get_indexes_doc(&meta_doc);
+
+        let mut new_indexes_doc = Document::new_without_id();
+
         for (key_name, value_of_key) in keys.iter() {
             if let Value::Int(1) = value_of_key {
                 // nothing
@@ -310,51 +508,161 @@ impl DbContext {
                 return Err(DbErr::InvalidOrderOfIndex(key_name.into()));
             }
 
-            match meta_doc.doc_ref().get(meta_doc_key::INDEXES) {
-                Some(indexes_obj) => match indexes_obj {
-                    Value::Document(index_doc) => {
-                        if index_already_exists(index_doc.borrow(), key_name) {
-                            return Err(DbErr::IndexAlreadyExists(key_name.into()));
-                        }
+            if index_already_exists(&indexes_doc, key_name) || index_already_exists(&new_indexes_doc, key_name) {
+                return Err(DbErr::IndexAlreadyExists(key_name.into()));
+            }
+
+            let root_pid = self.page_handler.alloc_page_id()?;
+            let options_doc = merge_options_into_default(root_pid, options)?;
+            new_indexes_doc.insert(key_name.into(), Value::Document(Rc::new(options_doc)));
+        }
+
+        self.add_new_indexes(&meta_source, col_id, meta_doc, indexes_doc, new_indexes_doc)
+    }
+
+    /// Create an index on several fields, the key of the index is
+    /// the fields joined by commas, e.g. "a,b".
+    pub fn create_index_compound(&mut self, col_id: u32, fields: &[&str], options: Option<&Document>) -> DbResult<()> {
+        self.page_handler.auto_start_transaction(TransactionType::Write)?;
+
+        try_db_op!(self, self.internal_create_index_compound(col_id, fields, options));
+
+        Ok(())
+    }
+
+    fn internal_create_index_compound(&mut self, col_id: u32, fields: &[&str], options: Option<&Document>) -> DbResult<()> {
+        if fields.len() < 2 {
+            return Err(DbErr::ValidationError("a compound index needs at least two fields".into()));
+        }
+        for (index, field) in fields.iter().enumerate() {
+            if fields[..index].contains(field) {
+                return Err(DbErr::ValidationError(format!("the field {} is duplicated in the compound index", field)));
+            }
+        }
+
+        let meta_source = self.get_meta_source()?;
+        let meta_doc = self.find_collection_root_pid_by_id(
+            0, meta_source.meta_pid, col_id)?;
+
+        let indexes_doc = DbContext::get_indexes_doc(&meta_doc);
+
+        let key_name = fields.join(",");
+        if index_already_exists(&indexes_doc, &key_name) {
+            return Err(DbErr::IndexAlreadyExists(key_name));
+        }
 
-                        unimplemented!()
-                    }
+        let fields: Vec<String> = fields.iter().map(|field| field.to_string()).collect();
 
-                    _ => {
-                        panic!("unexpected: indexes object is not a Document");
-                    }
+        let root_pid = self.page_handler.alloc_page_id()?;
+        let mut options_doc = merge_options_into_default(root_pid, options)?;
+        options_doc.insert(meta_doc_key::index::FIELDS.into(), mk_fields_array(&fields).into());
 
-                },
+        let mut new_indexes_doc = Document::new_without_id();
+        new_indexes_doc.insert(key_name, Value::Document(Rc::new(options_doc)));
 
-                None => {
-                    // create indexes
-                    let mut doc = Document::new_without_id();
+        self.add_new_indexes(&meta_source, col_id, meta_doc, indexes_doc, new_indexes_doc)
+    }
 
-                    let root_pid = self.page_handler.alloc_page_id()?;
-                    let options_doc = merge_options_into_default(root_pid, options)?;
-                    doc.insert(key_name.into(), Value::Document(Rc::new(options_doc)));
+    fn add_new_indexes(&mut self, meta_source: &MetaSource, col_id: u32, mut meta_doc: MetaDocEntry,
+                       mut indexes_doc: Document, new_indexes_doc: Document) -> DbResult<()> {
+        // build the new indexes for the existing documents
+        // the pages are built bottom-up, the root pids never change
+        if let Some(index_ctx) = IndexCtx::from_indexes_doc(&new_indexes_doc) {
+            let cursor = Cursor::new(self.item_size(), meta_doc.root_pid());
+            let docs: Vec<Rc<Document>> = cursor.iter_with(&mut self.page_handler).collect::<DbResult<_>>()?;
 
-                    meta_doc.set_indexes(doc);
-                }
+            index_ctx.bulk_load_by_contents(&docs, &mut self.page_handler)?;
+        }
+
+        for (key_name, value) in new_indexes_doc.iter() {
+            indexes_doc.insert(key_name.clone(), value.clone());
+        }
+
+        meta_doc.set_indexes(indexes_doc);
 
+        self.update_collection_meta(meta_source, col_id, &meta_doc)
+    }
+
+    fn get_indexes_doc(meta_doc: &MetaDocEntry) -> Document {
+        match meta_doc.doc_ref().get(meta_doc_key::INDEXES) {
+            Some(Value::Document(index_doc)) => index_doc.as_ref().clone(),
+
+            Some(_) => {
+                panic!("unexpected: indexes object is not a Document");
             }
+
+            None => Document::new_without_id(),
+
         }
+    }
+
+    /// Drop the index of the key, the pages of the index are freed.
+    ///
+    /// false returns if the index doesn't exist.
+    pub fn drop_index(&mut self, col_id: u32, meta_version: u32, key: &str) -> DbResult<bool> {
+        self.check_meta_version(meta_version)?;
 
-        let key_col = Value::from(col_id);
+        self.page_handler.auto_start_transaction(TransactionType::Write)?;
+
+        let result = try_db_op!(self, self.internal_drop_index(col_id, key));
+
+        Ok(result)
+    }
 
+    fn internal_drop_index(&mut self, col_id: u32, key: &str) -> DbResult<bool> {
         let meta_source = self.get_meta_source()?;
-        let inserted = self.update_by_root_pid(
-            0, meta_source.meta_pid, &key_col, meta_doc.doc_ref())?;
-        if !inserted {
-            panic!("update failed");
-        }
+        let mut meta_doc = self.find_collection_root_pid_by_id(
+            0, meta_source.meta_pid, col_id)?;
 
-        Ok(())
+        let mut indexes_doc = DbContext::get_indexes_doc(&meta_doc);
+        let root_pid = match indexes_doc.get(key) {
+            Some(Value::Document(options)) => {
+                options.get(meta_doc_key::index::ROOT_PID).unwrap().unwrap_int() as u32
+            }
+
+            Some(_) => {
+                panic!("unexpected: index options is not a Document");
+            }
+
+            None => return Ok(false),
+
+        };
+
+        delete_all_helper::delete_all_by_root_pid(&mut self.page_handler, root_pid)?;
+
+        indexes_doc.remove(key);
+        meta_doc.set_indexes(indexes_doc);
+
+        self.update_collection_meta(&meta_source, col_id, &meta_doc)?;
+
+        Ok(true)
+    }
+
+    pub fn list_indexes(&mut self, col_id: u32, meta_version: u32) -> DbResult<Vec<String>> {
+        self.check_meta_version(meta_version)?;
+
+        self.page_handler.auto_start_transaction(TransactionType::Read)?;
+
+        let result = try_db_op!(self, self.internal_list_indexes(col_id));
+
+        Ok(result)
+    }
+
+    fn internal_list_indexes(&mut self, col_id: u32) -> DbResult<Vec<String>> {
+        let meta_source = self.get_meta_source()?;
+        let meta_doc = self.find_collection_root_pid_by_id(
+            0, meta_source.meta_pid, col_id)?;
+
+        let indexes_doc = DbContext::get_indexes_doc(&meta_doc);
+        let mut result: Vec<String> = indexes_doc.iter().map(|(key, _)| key.clone()).collect();
+        result.sort();
+
+        Ok(result)
     }
 
     #[inline]
     fn fix_doc(&mut self, doc: &mut Document) -> bool {
-        if doc.get(meta_doc_key::ID).is_some() {
+        if doc.contains_key(meta_doc_key::ID) {
             return false;
         }
 
@@ -374,13 +682,110 @@ impl DbContext {
     }
 
     fn internal_insert(&mut self, col_id: u32, doc: &mut Document) -> DbResult<bool> {
+        let meta_source = self.get_meta_source()?;
+        let mut collection_meta = self.find_collection_root_pid_by_id(
+            0, meta_source.meta_pid, col_id)?;
+
+        self.internal_insert_with_meta(col_id, &mut collection_meta, doc)
+    }
+
+    /// The same as [insert](#method.insert), but the meta of the collection resolved before is used
+    /// instead of looking it up, it's updated if the insertion changes it.
+    pub(crate) fn insert_with_meta(&mut self, col_id: u32, meta_version: u32, collection_meta: &mut MetaDocEntry, doc: &mut Document) -> DbResult<bool> {
+        self.check_meta_version(meta_version)?;
+
+        self.page_handler.auto_start_transaction(TransactionType::Write)?;
+
+        let changed = try_db_op!(self, self.internal_insert_with_meta(col_id, collection_meta, doc));
+
+        Ok(changed)
+    }
+
+    fn internal_insert_with_meta(&mut self, col_id: u32, collection_meta: &mut MetaDocEntry, doc: &mut Document) -> DbResult<bool> {
         let meta_source = self.get_meta_source()?;
         let changed  = self.fix_doc(doc);
 
+        let is_meta_changed = self.insert_to_collection(collection_meta, doc)?;
+
+        // update meta begin
+        if is_meta_changed {
+            self.update_collection_meta(&meta_source, col_id, collection_meta)?;
+        }
+        // update meta end
+
+        if changed {
+            self.save_object_id_maker()?;
+        }
+
+        self.append_oplog(col_id, op_name::INSERT, &[doc.pkey_id().unwrap()])?;
+
+        Ok(changed)
+    }
+
+    /// Insert all the documents in one transaction,
+    /// the `_id`s of the documents return in order.
+    ///
+    /// The collection meta is resolved only once for the whole batch.
+    /// If one of the insertions fails, the error returns and:
+    /// - if the transaction is started automatically, all the documents of the batch are rolled back
+    /// - if the transaction is started by the user, the documents inserted before are kept
+    ///   until the user commits or rolls back the transaction
+    pub fn insert_many(&mut self, col_id: u32, meta_version: u32, docs: Vec<Rc<Document>>) -> DbResult<Vec<Value>> {
+        self.check_meta_version(meta_version)?;
+
+        self.page_handler.auto_start_transaction(TransactionType::Write)?;
+
+        let ids = try_db_op!(self, self.internal_insert_many(col_id, docs));
+
+        Ok(ids)
+    }
+
+    fn internal_insert_many(&mut self, col_id: u32, docs: Vec<Rc<Document>>) -> DbResult<Vec<Value>> {
+        let meta_source = self.get_meta_source()?;
+
         let mut collection_meta = self.find_collection_root_pid_by_id(
             0, meta_source.meta_pid, col_id)?;
 
+        let mut is_meta_changed = false;
+        let mut is_id_made = false;
+        let mut ids = Vec::with_capacity(docs.len());
+
+        for mut doc in docs {
+            let doc_mut = Rc::make_mut(&mut doc);
+            if self.fix_doc(doc_mut) {
+                is_id_made = true;
+            }
+
+            if self.insert_to_collection(&mut collection_meta, doc_mut)? {
+                is_meta_changed = true;
+            }
+
+            ids.push(doc_mut.pkey_id().unwrap());
+        }
+
+        if is_meta_changed {
+            self.update_collection_meta(&meta_source, col_id, &collection_meta)?;
+        }
+
+        if is_id_made {
+            self.save_object_id_maker()?;
+        }
+
+        self.append_oplog(col_id, op_name::INSERT, &ids)?;
+
+        Ok(ids)
+    }
+
+    // insert the doc into the collection, the doc should have a primary key,
+    // return if the collection meta is changed
+    fn insert_to_collection(&mut self, collection_meta: &mut MetaDocEntry, doc: &Document) -> DbResult<bool> {
+        check_doc_encodable(doc, "")?;
+
         let pkey = doc.pkey_id().unwrap();
+        // a Double can be stored in the B-tree of an index, but it's not a primary key
+        if !pkey.is_valid_key_type() {
+            return Err(DbErr::NotAValidKeyType(pkey.ty_name().into()));
+        }
 
         let mut is_pkey_check_skipped = false;
         collection_meta.check_pkey_ty(&pkey, &mut is_pkey_check_skipped)?;
@@ -400,7 +805,7 @@ impl DbContext {
             )?;
 
             if is_ctx_changed {
-                index_ctx.merge_to_meta_doc(&mut collection_meta);
+                index_ctx.merge_to_meta_doc(collection_meta);
                 is_meta_changed = true;
             }
         }
@@ -412,7 +817,7 @@ impl DbContext {
 
         if let Some(backward_item) = &insert_result.backward_item {
             let root_pid = collection_meta.root_pid();
-            self.handle_insert_backward_item(&mut collection_meta, root_pid, backward_item)?;
+            self.handle_insert_backward_item(collection_meta, root_pid, backward_item)?;
             is_meta_changed = true;
         }
 
@@ -422,246 +827,1288 @@ impl DbContext {
             is_meta_changed = true;
         }
 
-        // update meta begin
-        if is_meta_changed {
-            let key = Value::from(col_id);
-            let updated= self.update_by_root_pid(
-                0, meta_source.meta_pid, &key, collection_meta.doc_ref())?;
-            if !updated {
-                panic!("unexpected: update meta page failed")
-            }
+        if let Some(count) = collection_meta.count() {
+            collection_meta.set_count(count + 1);
+            is_meta_changed = true;
         }
-        // update meta end
 
-        Ok(changed)
+        Ok(is_meta_changed)
+    }
+
+    fn update_collection_meta(&mut self, meta_source: &MetaSource, col_id: u32, collection_meta: &MetaDocEntry) -> DbResult<()> {
+        let key = Value::from(col_id);
+        let updated= self.update_by_root_pid(
+            0, meta_source.meta_pid, &key, collection_meta.doc_ref())?;
+        if !updated {
+            panic!("unexpected: update meta page failed")
+        }
+        Ok(())
     }
 
     /// query: None for findAll
-    pub fn find(&mut self, col_id: u32, meta_version: u32, query: Option<&Document>) -> DbResult<DbHandle> {
+    pub fn find(&mut self, col_id: u32, meta_version: u32, query: Option<&Document>) -> DbResult<DbHandle<'_>> {
         self.check_meta_version(meta_version)?;
 
         let meta_source = self.get_meta_source()?;
         let collection_meta = self.find_collection_root_pid_by_id(
             0, meta_source.meta_pid, col_id)?;
 
-        let subprogram = match query {
+        self.make_query_handle(&collection_meta, query)
+    }
+
+    /// The same as [find](#method.find), but the meta of the collection resolved before is used.
+    pub(crate) fn find_with_meta(&mut self, meta_version: u32, collection_meta: &MetaDocEntry, query: Option<&Document>) -> DbResult<DbHandle<'_>> {
+        self.check_meta_version(meta_version)?;
+
+        self.make_query_handle(collection_meta, query)
+    }
+
+    fn make_query_handle(&mut self, collection_meta: &MetaDocEntry, query: Option<&Document>) -> DbResult<DbHandle<'_>> {
+        let subprogram = DbContext::compile_query_program(collection_meta, query)?;
+
+        Ok(self.make_handle(subprogram))
+    }
+
+    fn compile_query_program(collection_meta: &MetaDocEntry, query: Option<&Document>) -> DbResult<SubProgram> {
+        match query {
             Some(query) => SubProgram::compile_query(
-                &collection_meta,
+                collection_meta,
                 collection_meta.doc_ref(),
                 query,
                 true
             ),
-            None => SubProgram::compile_query_all(&collection_meta, true),
-        }?;
+            None => SubProgram::compile_query_all(collection_meta, true),
+        }
+    }
 
-        let handle = self.make_handle(subprogram);
+    /// Pin the committed state of the database for a snapshot.
+    #[inline]
+    pub(crate) fn pin_read_view(&mut self) -> Arc<ReadView> {
+        self.page_handler.pin_read_view()
+    }
 
-        Ok(handle)
+    /// The reads see the state of the view until [finish_read_view](#method.finish_read_view).
+    #[inline]
+    pub(crate) fn start_read_view(&mut self, view: &Arc<ReadView>) -> DbResult<()> {
+        self.page_handler.start_read_view(view)
     }
 
-    pub fn update(&mut self, col_id: u32, meta_version: u32, query: Option<&Document>, update: &Document) -> DbResult<usize> {
-        self.check_meta_version(meta_version)?;
+    pub(crate) fn finish_read_view(&mut self) -> DbResult<()> {
+        self.page_handler.finish_read_view()?;
+        // a failed operation resets it from the meta of the view
+        self.reset_meta_version()
+    }
 
-        self.page_handler.auto_start_transaction(TransactionType::Write)?;
+    /// The same as [find](#method.find) in the view,
+    /// the read transaction is finished when the handle is dropped.
+    pub(crate) fn find_in_view(&mut self, view: &Arc<ReadView>, col_name: &str, query: &Document) -> DbResult<DbHandle<'_>> {
+        self.start_read_view(view)?;
 
-        let result = try_db_op!(self, self.internal_update(col_id, query, update));
+        let subprogram = match self.compile_query_by_name(col_name, query) {
+            Ok(subprogram) => subprogram,
+            Err(err) => {
+                self.finish_read_view()?;
+                return Err(err);
+            }
+        };
 
-        Ok(result)
+        let mut handle = self.make_handle(subprogram);
+        handle.set_finish_read_view_on_drop(true);
+        Ok(handle)
     }
 
-    fn internal_update(&mut self, col_id: u32, query: Option<&Document>, update: &Document) -> DbResult<usize> {
+    fn compile_query_by_name(&mut self, col_name: &str, query: &Document) -> DbResult<SubProgram> {
+        let meta = self.get_collection_meta_by_name(col_name)?;
+        let meta_source = self.get_meta_source()?;
+        let collection_meta = self.find_collection_root_pid_by_id(
+            0, meta_source.meta_pid, meta.id)?;
+
+        DbContext::compile_query_program(&collection_meta, Some(query))
+    }
+
+    pub fn update(&mut self, col_id: u32, meta_version: u32, query: Option<&Document>, update: &Document) -> DbResult<usize> {
+        self.check_meta_version(meta_version)?;
+
+        self.page_handler.auto_start_transaction(TransactionType::Write)?;
+
+        let result = try_db_op!(self, self.internal_update(col_id, query, update));
+
+        Ok(result)
+    }
+
+    fn internal_update(&mut self, col_id: u32, query: Option<&Document>, update: &Document) -> DbResult<usize> {
+        let meta_source = self.get_meta_source()?;
+        let mut collection_meta = self.find_collection_root_pid_by_id(
+            0, meta_source.meta_pid, col_id)?;
+
+        self.internal_update_with_meta(col_id, &mut collection_meta, query, update)
+    }
+
+    /// The same as [update](#method.update), but the meta of the collection resolved before is used
+    /// instead of looking it up, it's updated if the indexes are changed.
+    pub(crate) fn update_with_meta(&mut self, col_id: u32, meta_version: u32, collection_meta: &mut MetaDocEntry, query: Option<&Document>, update: &Document) -> DbResult<usize> {
+        self.check_meta_version(meta_version)?;
+
+        self.page_handler.auto_start_transaction(TransactionType::Write)?;
+
+        let result = try_db_op!(self, self.internal_update_with_meta(col_id, collection_meta, query, update));
+
+        Ok(result)
+    }
+
+    fn internal_update_with_meta(&mut self, col_id: u32, collection_meta: &mut MetaDocEntry, query: Option<&Document>, update: &Document) -> DbResult<usize> {
+        let meta_source = self.get_meta_source()?;
+
+        // the documents before updating are needed to update the indexes,
+        // and their ids are written to the oplog
+        let mut index_ctx_opt = IndexCtx::from_meta_doc(collection_meta.doc_ref());
+        let old_docs = if index_ctx_opt.is_some() || self.oplog {
+            self.internal_find_docs(collection_meta, query)?
+        } else {
+            vec![]
+        };
+
+        let subprogram = SubProgram::compile_update(collection_meta, query, update, true)?;
+
+        let updated = {
+            let mut vm = VM::new(&mut self.page_handler, Box::new(subprogram));
+            vm.execute()?;
+            vm.r2 as usize
+        };
+
+        if let Some(index_ctx) = &mut index_ctx_opt {
+            let mut is_ctx_changed = false;
+
+            for old_doc in &old_docs {
+                let pkey = old_doc.pkey_id().unwrap();
+                index_ctx.delete_index_by_content(old_doc.borrow(), &mut self.page_handler)?;
+
+                let new_doc = self.find_doc_by_root_pid(collection_meta.root_pid(), &pkey)?.unwrap();
+                index_ctx.insert_index_by_content(
+                    new_doc.borrow(),
+                    &pkey,
+                    &mut is_ctx_changed,
+                    &mut self.page_handler
+                )?;
+            }
+
+            if is_ctx_changed {
+                index_ctx.merge_to_meta_doc(collection_meta);
+                self.update_collection_meta(&meta_source, col_id, collection_meta)?;
+            }
+        }
+
+        if self.oplog {
+            let ids: Vec<Value> = old_docs.iter().map(|doc| doc.pkey_id().unwrap()).collect();
+            self.append_oplog(col_id, op_name::UPDATE, &ids)?;
+        }
+
+        Ok(updated)
+    }
+
+    fn internal_find_docs(&mut self, collection_meta: &MetaDocEntry, query: Option<&Document>) -> DbResult<Vec<Rc<Document>>> {
+        let mut handle = self.make_query_handle(collection_meta, query)?;
+        let mut result = vec![];
+
+        handle.step()?;
+
+        while handle.has_row() {
+            let doc = handle.get().unwrap_document();
+            result.push(doc.clone());
+
+            handle.step()?;
+        }
+
+        Ok(result)
+    }
+
+    /// Update the documents matching the `query`,
+    /// insert a new document if nothing matches.
+    ///
+    /// The size of the documents updated and the `_id` of the document inserted return.
+    pub fn update_upsert(&mut self, col_id: u32, meta_version: u32, query: &Document, update: &Document) -> DbResult<(usize, Option<Value>)> {
+        self.check_meta_version(meta_version)?;
+
+        self.page_handler.auto_start_transaction(TransactionType::Write)?;
+
+        let result = try_db_op!(self, self.internal_update_upsert(col_id, query, update));
+
+        Ok(result)
+    }
+
+    fn internal_update_upsert(&mut self, col_id: u32, query: &Document, update: &Document) -> DbResult<(usize, Option<Value>)> {
+        let updated = self.internal_update(col_id, Some(query), update)?;
+        if updated > 0 {
+            return Ok((updated, None));
+        }
+
+        let mut doc = query::mk_upsert_document(query, update);
+        self.internal_insert(col_id, &mut doc)?;
+
+        Ok((0, doc.pkey_id()))
+    }
+
+    /// Update the first document matching the `query`, the matching and the updating
+    /// are in one write transaction, so nothing can be written between them.
+    ///
+    /// The document after updating returns if `return_new` is true, otherwise the one before.
+    /// None returns if nothing matches.
+    pub fn find_and_modify(&mut self, col_id: u32, meta_version: u32, query: &Document,
+                           update: &Document, return_new: bool) -> DbResult<Option<Rc<Document>>> {
+        self.check_meta_version(meta_version)?;
+
+        self.page_handler.auto_start_transaction(TransactionType::Write)?;
+
+        let result = try_db_op!(self, self.internal_find_and_modify(col_id, query, update, return_new));
+
+        Ok(result)
+    }
+
+    fn internal_find_and_modify(&mut self, col_id: u32, query: &Document,
+                                update: &Document, return_new: bool) -> DbResult<Option<Rc<Document>>> {
+        let meta_source = self.get_meta_source()?;
+        let collection_meta = self.find_collection_root_pid_by_id(
+            0, meta_source.meta_pid, col_id)?;
+
+        let old_doc = {
+            let subprogram = SubProgram::compile_query(
+                &collection_meta,
+                collection_meta.doc_ref(),
+                query,
+                true
+            )?;
+            let mut handle = self.make_handle(subprogram);
+            handle.step()?;
+
+            if !handle.has_row() {
+                return Ok(None);
+            }
+
+            handle.get().unwrap_document().clone()
+        };
+        let pkey = old_doc.pkey_id().unwrap();
+
+        // only the matched one is updated, even if the others match the query as well
+        let pkey_query = mk_document! {
+            "_id": pkey.clone(),
+        };
+        self.internal_update(col_id, Some(&pkey_query), update)?;
+
+        if !return_new {
+            return Ok(Some(old_doc));
+        }
+
+        self.internal_find_by_pkey(col_id, &pkey)
+    }
+
+    /// Replace the first document matching the `query` with `replacement`,
+    /// the `_id` of the matched document is kept.
+    ///
+    /// Return if a document is replaced.
+    pub fn replace_one(&mut self, col_id: u32, meta_version: u32, query: &Document, replacement: &Document) -> DbResult<bool> {
+        self.check_meta_version(meta_version)?;
+
+        self.page_handler.auto_start_transaction(TransactionType::Write)?;
+
+        let result = try_db_op!(self, self.internal_replace_one(col_id, query, replacement));
+
+        Ok(result)
+    }
+
+    fn internal_replace_one(&mut self, col_id: u32, query: &Document, replacement: &Document) -> DbResult<bool> {
+        let meta_source = self.get_meta_source()?;
+        let mut collection_meta = self.find_collection_root_pid_by_id(
+            0, meta_source.meta_pid, col_id)?;
+
+        let old_doc = {
+            let subprogram = SubProgram::compile_query(
+                &collection_meta,
+                collection_meta.doc_ref(),
+                query,
+                true
+            )?;
+            let mut handle = self.make_handle(subprogram);
+            handle.step()?;
+
+            if !handle.has_row() {
+                return Ok(false);
+            }
+
+            handle.get().unwrap_document().clone()
+        };
+        let pkey = old_doc.pkey_id().unwrap();
+
+        let mut new_doc = Document::new_without_id();
+        new_doc.insert(meta_doc_key::ID.into(), pkey.clone());
+        for (key, value) in replacement.iter() {
+            if key != meta_doc_key::ID {
+                new_doc.insert(key.clone(), value.clone());
+            }
+        }
+        check_doc_encodable(&new_doc, "")?;
+
+        let mut index_ctx_opt = IndexCtx::from_meta_doc(collection_meta.doc_ref());
+        if let Some(index_ctx) = &mut index_ctx_opt {
+            let mut is_ctx_changed = false;
+
+            index_ctx.delete_index_by_content(old_doc.borrow(), &mut self.page_handler)?;
+            index_ctx.insert_index_by_content(
+                &new_doc,
+                &pkey,
+                &mut is_ctx_changed,
+                &mut self.page_handler
+            )?;
+
+            if is_ctx_changed {
+                index_ctx.merge_to_meta_doc(&mut collection_meta);
+                self.update_collection_meta(&meta_source, col_id, &collection_meta)?;
+            }
+        }
+
+        // the data ticket of the old document is freed, with its overflow pages
+        let replaced = self.update_by_root_pid(0, collection_meta.root_pid(), &pkey, &new_doc)?;
+        debug_assert!(replaced);
+
+        self.append_oplog(col_id, op_name::UPDATE, &[pkey])?;
+
+        Ok(true)
+    }
+
+    pub fn drop(&mut self, col_id: u32, meta_version: u32) -> DbResult<()> {
+        self.check_meta_version(meta_version)?;
+
+        self.page_handler.auto_start_transaction(TransactionType::Write)?;
+
+        try_db_op!(self, self.internal_drop(col_id));
+
+        Ok(())
+    }
+
+    fn internal_drop(&mut self, col_id: u32) -> DbResult<()> {
+        let mut meta_source = self.get_meta_source()?;
+        let collection_meta = self.find_collection_root_pid_by_id(
+            0, meta_source.meta_pid, col_id)?;
+
+        if let Some(index_ctx) = IndexCtx::from_meta_doc(collection_meta.doc_ref()) {
+            index_ctx.delete_all(&mut self.page_handler)?;
+        }
+
+        delete_all_helper::delete_all(&mut self.page_handler, collection_meta)?;
+
+        let mut btree_wrapper = BTreePageDeleteWrapper::new(
+            &mut self.page_handler, meta_source.meta_pid);
+
+        let pkey = Value::from(col_id);
+        btree_wrapper.delete_item(&pkey)?;
+        btree_wrapper.flush_pages()?;
+
+        meta_source.meta_version += 1;
+        self.update_meta_source(&meta_source)
+    }
+
+    pub fn delete(&mut self, col_id: u32, meta_version: u32, query: &Document) -> DbResult<usize> {
+        let primary_keys = self.get_primary_keys_by_query(col_id, meta_version, Some(query))?;
+
+        self.page_handler.auto_start_transaction(TransactionType::Write)?;
+
+        let result = try_db_op!(self, self.internal_delete(col_id, &primary_keys));
+
+        Ok(result)
+    }
+
+    fn internal_delete(&mut self, col_id: u32, primary_keys: &[Value]) -> DbResult<usize> {
+        let meta_source = self.get_meta_source()?;
+        let mut collection_meta = self.find_collection_root_pid_by_id(
+            0, meta_source.meta_pid, col_id)?;
+
+        self.internal_delete_with_meta(col_id, &mut collection_meta, primary_keys)
+    }
+
+    /// The same as [delete](#method.delete), or [delete_all](#method.delete_all) if the `query` is None,
+    /// but the meta of the collection resolved before is used instead of looking it up,
+    /// the count cached in it is updated.
+    pub(crate) fn delete_with_meta(&mut self, col_id: u32, meta_version: u32, collection_meta: &mut MetaDocEntry, query: Option<&Document>) -> DbResult<usize> {
+        self.check_meta_version(meta_version)?;
+
+        let primary_keys = {
+            let mut handle = self.make_query_handle(collection_meta, query)?;
+            collect_primary_keys(&mut handle)?
+        };
+
+        self.page_handler.auto_start_transaction(TransactionType::Write)?;
+
+        let result = try_db_op!(self, self.internal_delete_with_meta(col_id, collection_meta, &primary_keys));
+
+        Ok(result)
+    }
+
+    fn internal_delete_with_meta(&mut self, col_id: u32, collection_meta: &mut MetaDocEntry, primary_keys: &[Value]) -> DbResult<usize> {
+        let mut deleted = vec![];
+        for pkey in primary_keys {
+            let result = self.internal_delete_by_pkey_with_meta(col_id, collection_meta, pkey)?;
+            if self.oplog && result.is_some() {
+                deleted.push(pkey.clone());
+            }
+        }
+
+        self.append_oplog(col_id, op_name::DELETE, &deleted)?;
+
+        Ok(primary_keys.len())
+    }
+
+    // append the entries of the changed documents to the oplog if it's enabled,
+    // it's called in the transaction of the change
+    fn append_oplog(&mut self, col_id: u32, op: &str, ids: &[Value]) -> DbResult<()> {
+        if !self.oplog || ids.is_empty() {
+            return Ok(());
+        }
+
+        let meta_source = self.get_meta_source()?;
+        let collection_meta = self.find_collection_root_pid_by_id(
+            0, meta_source.meta_pid, col_id)?;
+        if collection_meta.name() == OPLOG_COLLECTION_NAME {
+            return Ok(());
+        }
+
+        let oplog_meta = match self.find_collection_meta_by_name(OPLOG_COLLECTION_NAME)? {
+            Some(meta) => meta,
+            None => return Err(DbErr::CollectionNotFound(OPLOG_COLLECTION_NAME.into())),
+        };
+        let oplog_id = oplog_meta.doc_ref().pkey_id().unwrap().unwrap_int() as u32;
+
+        // the timestamps are made in memory, continue from the last entry,
+        // in case the clock goes backwards after reopening
+        if let Some(last) = self.internal_find_first_or_last(oplog_id, true)? {
+            if let Some(Value::Timestamp { time, inc }) = last.pkey_id() {
+                self.last_timestamp = std::cmp::max(self.last_timestamp, (time, inc));
+            }
+        }
+
+        let col_name = collection_meta.name().to_string();
+        let mut oplog_meta = oplog_meta;
+        let mut is_meta_changed = false;
+        for id in ids {
+            let timestamp = self.mk_timestamp();
+            let entry = oplog::mk_oplog_entry(timestamp, op, &col_name, id);
+            if self.insert_to_collection(&mut oplog_meta, &entry)? {
+                is_meta_changed = true;
+            }
+        }
+
+        if is_meta_changed {
+            self.update_collection_meta(&meta_source, oplog_id, &oplog_meta)?;
+        }
+
+        Ok(())
+    }
+
+    fn find_collection_meta_by_name(&mut self, name: &str) -> DbResult<Option<MetaDocEntry>> {
+        for doc in self.internal_query_all_meta()? {
+            let entry = MetaDocEntry::from_doc(doc);
+            if entry.name() == name {
+                return Ok(Some(entry));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// The entries of the oplog whose timestamps are greater than `since`, in order.
+    ///
+    /// Pass `Value::Null` to read from the beginning, and the `_id` of the last entry read
+    /// to read the new ones later. Nothing returns if the oplog collection doesn't exist.
+    pub fn tail_oplog(&mut self, since: &Value) -> DbResult<Vec<Rc<Document>>> {
+        self.page_handler.auto_start_transaction(TransactionType::Read)?;
+
+        let result = try_db_op!(self, self.internal_tail_oplog(since));
+
+        Ok(result)
+    }
+
+    fn internal_tail_oplog(&mut self, since: &Value) -> DbResult<Vec<Rc<Document>>> {
+        let oplog_meta = match self.find_collection_meta_by_name(OPLOG_COLLECTION_NAME)? {
+            Some(meta) => meta,
+            None => return Ok(vec![]),
+        };
+
+        let mut cursor = Cursor::new(self.item_size(), oplog_meta.root_pid());
+        cursor.reset_by_lower_bound(&mut self.page_handler, since)?;
+
+        let mut result = vec![];
+        while let Some(doc) = cursor.next(&mut self.page_handler)? {
+            if doc.pkey_id().as_ref() == Some(since) {
+                continue;
+            }
+            result.push(doc);
+        }
+
+        Ok(result)
+    }
+
+    pub fn delete_all(&mut self, col_id: u32, meta_version: u32) -> DbResult<usize> {
+        let primary_keys = self.get_primary_keys_by_query(col_id, meta_version, None)?;
+
+        self.page_handler.auto_start_transaction(TransactionType::Write)?;
+
+        let result = try_db_op!(self, self.internal_delete(col_id, &primary_keys));
+
+        Ok(result)
+    }
+
+    fn get_primary_keys_by_query(&mut self, col_id: u32, meta_version: u32, query: Option<&Document>) -> DbResult<Vec<Value>> {
+        let mut handle = self.find(col_id, meta_version, query)?;
+        collect_primary_keys(&mut handle)
+    }
+
+    fn update_by_root_pid(&mut self, parent_pid: u32, root_pid: u32, key: &Value, doc: &Document) -> DbResult<bool> {
+        let page = self.page_handler.pipeline_read_page(root_pid)?;
+        let btree_node = BTreeNode::from_raw(&page, parent_pid, self.item_size(), &mut self.page_handler)?;
+
+        let search_result = btree_node.search(key)?;
+        match search_result {
+            SearchKeyResult::Node(idx) => {
+                self.page_handler.free_data_ticket(&btree_node.content[idx].data_ticket)?;
+
+                let new_ticket = self.page_handler.store_doc(doc)?;
+                let new_btree_node = btree_node.clone_with_content(idx, BTreeNodeDataItem {
+                    key: key.clone(),
+                    data_ticket: new_ticket,
+                });
+
+                let mut page = RawPage::new(btree_node.pid, self.page_handler.page_size);
+                new_btree_node.to_raw(&mut page)?;
+
+                self.page_handler.pipeline_write_page(&page)?;
+
+                Ok(true)
+            }
+
+            SearchKeyResult::Index(idx) => {
+                let next_pid = btree_node.indexes[idx];
+                if next_pid == 0 {
+                    return Ok(false);
+                }
+
+                self.update_by_root_pid(root_pid, next_pid, key, doc)
+            }
+
+        }
+    }
+
+    fn handle_insert_backward_item(&mut self,
+                                   collection_meta: &mut MetaDocEntry,
+                                   left_pid: u32,
+                                   backward_item: &InsertBackwardItem) -> DbResult<()> {
+
+        let new_root_id = self.page_handler.alloc_page_id()?;
+
+        crate::polo_log!("handle backward item, left_pid: {}, new_root_id: {}, right_pid: {}", left_pid, new_root_id, backward_item.right_pid);
+
+        let new_root_page = backward_item.write_to_page(&mut self.page_handler, new_root_id, left_pid)?;
+        self.page_handler.pipeline_write_page(&new_root_page)?;
+
+        collection_meta.set_root_pid(new_root_id);
+
+        Ok(())
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn delete_by_pkey(&mut self, col_id: u32, key: &Value) -> DbResult<Option<Rc<Document>>> {
+        self.page_handler.auto_start_transaction(TransactionType::Write)?;
+
+        let result = try_db_op!(self, self.internal_delete_by_pkey(col_id, key));
+
+        Ok(result)
+    }
+
+    fn internal_delete_by_pkey(&mut self, col_id: u32, key: &Value) -> DbResult<Option<Rc<Document>>> {
+        let meta_source = self.get_meta_source()?;
+        let mut collection_meta = self.find_collection_root_pid_by_id(
+            0, meta_source.meta_pid, col_id)?;
+
+        self.internal_delete_by_pkey_with_meta(col_id, &mut collection_meta, key)
+    }
+
+    fn internal_delete_by_pkey_with_meta(&mut self, col_id: u32, collection_meta: &mut MetaDocEntry, key: &Value) -> DbResult<Option<Rc<Document>>> {
+        let meta_source = self.get_meta_source()?;
+
+        let mut delete_wrapper = BTreePageDeleteWrapper::new(
+            &mut self.page_handler,
+            collection_meta.root_pid()
+        );
+        let result = delete_wrapper.delete_item(key)?;
+        delete_wrapper.flush_pages()?;
+
+        if let Some(deleted_item) = &result {
+            let index_ctx_opt = IndexCtx::from_meta_doc(collection_meta.doc_ref());
+            if let Some(index_ctx) = &index_ctx_opt {
+                index_ctx.delete_index_by_content(deleted_item.borrow(), &mut self.page_handler)?;
+            }
+
+            if let Some(count) = collection_meta.count() {
+                collection_meta.set_count(count.saturating_sub(1));
+                self.update_collection_meta(&meta_source, col_id, collection_meta)?;
+            }
+
+            return Ok(result)
+        }
+
+        Ok(None)
+    }
+
+    pub fn find_by_pkey(&mut self, col_id: u32, meta_version: u32, key: &Value) -> DbResult<Option<Rc<Document>>> {
+        self.check_meta_version(meta_version)?;
+        self.page_handler.auto_start_transaction(TransactionType::Read)?;
+
+        let result = try_db_op!(self, self.internal_find_by_pkey(col_id, key));
+
+        Ok(result)
+    }
+
+    fn internal_find_by_pkey(&mut self, col_id: u32, key: &Value) -> DbResult<Option<Rc<Document>>> {
+        let meta_source = self.get_meta_source()?;
+        let collection_meta = self.find_collection_root_pid_by_id(
+            0, meta_source.meta_pid, col_id)?;
+
+        self.find_doc_by_root_pid(collection_meta.root_pid(), key)
+    }
+
+    /// The document with the smallest primary key, or the greatest one if `last` is true,
+    /// only the pages on the path to the left most (or right most) leaf are read.
+    pub fn find_first_or_last(&mut self, col_id: u32, meta_version: u32, last: bool) -> DbResult<Option<Rc<Document>>> {
+        self.check_meta_version(meta_version)?;
+        self.page_handler.auto_start_transaction(TransactionType::Read)?;
+
+        let result = try_db_op!(self, self.internal_find_first_or_last(col_id, last));
+
+        Ok(result)
+    }
+
+    fn internal_find_first_or_last(&mut self, col_id: u32, last: bool) -> DbResult<Option<Rc<Document>>> {
+        let meta_source = self.get_meta_source()?;
+        let collection_meta = self.find_collection_root_pid_by_id(
+            0, meta_source.meta_pid, col_id)?;
+
+        let mut cursor = if last {
+            Cursor::new_reverse(self.item_size(), collection_meta.root_pid())
+        } else {
+            Cursor::new(self.item_size(), collection_meta.root_pid())
+        };
+        cursor.reset(&mut self.page_handler)?;
+
+        match cursor.peek() {
+            Some(ticket) => self.page_handler.get_doc_from_ticket(&ticket),
+            None => Ok(None),
+        }
+    }
+
+    fn find_doc_by_root_pid(&mut self, root_pid: u32, key: &Value) -> DbResult<Option<Rc<Document>>> {
+        let mut search_wrapper = BTreePageSearchWrapper::new(
+            &mut self.page_handler,
+            root_pid,
+        );
+        let item = match search_wrapper.search(key)? {
+            BTreeSearchResult::Found(item) => item,
+            BTreeSearchResult::NotFound(_) => return Ok(None),
+        };
+
+        self.page_handler.get_doc_from_ticket(&item.data_ticket)
+    }
+
+    /// Find the documents by the index if the query can be satisfied by an index.
+    ///
+    /// The index is used only when all the fields of the query are equality comparisons,
+    /// and one of the fields is indexed, or the leading fields of a compound index are
+    /// in the query. Otherwise `Ok(None)` returns,
+    /// the caller should scan the collection instead.
+    pub fn find_by_index(&mut self, col_id: u32, meta_version: u32, query: &Document) -> DbResult<Option<Vec<Rc<Document>>>> {
+        self.find_by_index_cancellable(col_id, meta_version, query, &OperationOptions::default())
+    }
+
+    /// The same as [find_by_index](#method.find_by_index), but fails with `DbErr::Cancelled`
+    /// when the `options` are triggered, they are checked once per document fetched by the index.
+    pub fn find_by_index_cancellable(&mut self, col_id: u32, meta_version: u32, query: &Document, options: &OperationOptions) -> DbResult<Option<Vec<Rc<Document>>>> {
+        self.check_meta_version(meta_version)?;
+        self.page_handler.auto_start_transaction(TransactionType::Read)?;
+
+        let result = try_db_op!(self, self.internal_find_by_index(col_id, query, options));
+
+        Ok(result)
+    }
+
+    /// Return the strategy [find_by_index](#method.find_by_index) and
+    /// [find](#method.find) use to answer the query.
+    pub fn explain(&mut self, col_id: u32, meta_version: u32, query: &Document) -> DbResult<QueryPlan> {
+        self.check_meta_version(meta_version)?;
+        self.page_handler.auto_start_transaction(TransactionType::Read)?;
+
+        let result = try_db_op!(self, self.internal_explain(col_id, query));
+
+        Ok(result)
+    }
+
+    fn internal_explain(&mut self, col_id: u32, query: &Document) -> DbResult<QueryPlan> {
+        let meta_source = self.get_meta_source()?;
+        let collection_meta = self.find_collection_root_pid_by_id(
+            0, meta_source.meta_pid, col_id)?;
+
+        let index_ctx = IndexCtx::from_meta_doc(collection_meta.doc_ref());
+
+        Ok(query::plan_query(query, index_ctx.as_ref()))
+    }
+
+    fn internal_find_by_index(&mut self, col_id: u32, query: &Document, options: &OperationOptions) -> DbResult<Option<Vec<Rc<Document>>>> {
         let meta_source = self.get_meta_source()?;
         let collection_meta = self.find_collection_root_pid_by_id(
             0, meta_source.meta_pid, col_id)?;
 
-        let subprogram = SubProgram::compile_update(&collection_meta, query, update, true)?;
+        self.internal_find_by_index_with_meta(&collection_meta, query, options)
+    }
+
+    /// The same as [find_by_index](#method.find_by_index), but the meta of the collection resolved before is used.
+    pub(crate) fn find_by_index_with_meta(&mut self, meta_version: u32, collection_meta: &MetaDocEntry, query: &Document) -> DbResult<Option<Vec<Rc<Document>>>> {
+        self.check_meta_version(meta_version)?;
+        self.page_handler.auto_start_transaction(TransactionType::Read)?;
+
+        let result = try_db_op!(self, self.internal_find_by_index_with_meta(collection_meta, query, &OperationOptions::default()));
+
+        Ok(result)
+    }
+
+    fn internal_find_by_index_with_meta(&mut self, collection_meta: &MetaDocEntry, query: &Document, options: &OperationOptions) -> DbResult<Option<Vec<Rc<Document>>>> {
+        let index_ctx = match IndexCtx::from_meta_doc(collection_meta.doc_ref()) {
+            Some(index_ctx) => index_ctx,
+            None => return Ok(None),
+        };
+
+        let pkeys = match query::plan_query(query, Some(&index_ctx)) {
+            QueryPlan::Index(key) => {
+                let value = query.get(&key).unwrap();
+                index_ctx.find_pkeys_by_index(&key, value, &mut self.page_handler)?.unwrap()
+            }
+
+            QueryPlan::CompoundIndex { index, fields } => {
+                let prefix: Vec<Value> = fields.iter().map(|field| query.get(field).unwrap().clone()).collect();
+                index_ctx.find_pkeys_by_compound_index(&index, &prefix, &mut self.page_handler)?.unwrap()
+            }
+
+            // the primary key is searched by the VM
+            QueryPlan::PrimaryKey | QueryPlan::FullScan => return Ok(None),
+        };
+
+        let mut result = Vec::with_capacity(pkeys.len());
+        for pkey in &pkeys {
+            options.check_cancelled()?;
+
+            let doc = match self.find_doc_by_root_pid(collection_meta.root_pid(), pkey)? {
+                Some(doc) => doc,
+                None => continue,
+            };
+
+            let is_matched = query.iter().all(|(key, value)| {
+                match doc.get_path(key) {
+                    Some(field) => query::value_matches_cmp(DbOp::Equal, field, value),
+                    None => false,
+                }
+            });
+            if is_matched {
+                result.push(doc);
+            }
+        }
+
+        // the same order as scanning the collection
+        result.sort_by(|doc1, doc2| query::doc_field_cmp(doc1, doc2, meta_doc_key::ID));
 
-        let mut vm = VM::new(&mut self.page_handler, Box::new(subprogram));
-        vm.execute()?;
+        Ok(Some(result))
+    }
 
-        Ok(vm.r2 as usize)
+    /// The count cached in the meta returns without scanning,
+    /// `None` if the collection is created before the count is cached.
+    pub fn estimated_count(&mut self, col_id: u32, meta_version: u32) -> DbResult<Option<u64>> {
+        self.check_meta_version(meta_version)?;
+        let meta_source = self.get_meta_source()?;
+        let collection_meta = self.find_collection_root_pid_by_id(
+            0, meta_source.meta_pid, col_id)?;
+        Ok(collection_meta.count())
     }
 
-    pub fn drop(&mut self, col_id: u32, meta_version: u32) -> DbResult<()> {
+    /// The count cached in the meta returns,
+    /// the collection is scanned only if it's created before the count is cached.
+    pub fn count(&mut self, col_id: u32, meta_version: u32) -> DbResult<u64> {
+        self.check_meta_version(meta_version)?;
+        let meta_source = self.get_meta_source()?;
+        let collection_meta = self.find_collection_root_pid_by_id(
+            0, meta_source.meta_pid, col_id)?;
+        self.count_with_meta(meta_version, &collection_meta)
+    }
+
+    /// The same as [count](#method.count), but the meta of the collection resolved before is used,
+    /// no page is read if the count is cached in it.
+    pub(crate) fn count_with_meta(&mut self, meta_version: u32, collection_meta: &MetaDocEntry) -> DbResult<u64> {
+        self.check_meta_version(meta_version)?;
+        match collection_meta.count() {
+            Some(count) => Ok(count),
+            None => counter_helper::count(&mut self.page_handler, collection_meta),
+        }
+    }
+
+    /// Count the documents by a full scan, and write the count back to the meta.
+    pub fn recount(&mut self, col_id: u32, meta_version: u32) -> DbResult<u64> {
         self.check_meta_version(meta_version)?;
 
         self.page_handler.auto_start_transaction(TransactionType::Write)?;
 
-        try_db_op!(self, self.internal_drop(col_id));
+        let result = try_db_op!(self, self.internal_recount(col_id));
 
-        Ok(())
+        Ok(result)
     }
 
-    fn internal_drop(&mut self, col_id: u32) -> DbResult<()> {
-        let mut meta_source = self.get_meta_source()?;
-        let collection_meta = self.find_collection_root_pid_by_id(
+    fn internal_recount(&mut self, col_id: u32) -> DbResult<u64> {
+        let meta_source = self.get_meta_source()?;
+        let mut collection_meta = self.find_collection_root_pid_by_id(
             0, meta_source.meta_pid, col_id)?;
-        delete_all_helper::delete_all(&mut self.page_handler, collection_meta)?;
 
-        let mut btree_wrapper = BTreePageDeleteWrapper::new(
-            &mut self.page_handler, meta_source.meta_pid);
+        let count = counter_helper::count(&mut self.page_handler, &collection_meta)?;
 
-        let pkey = Value::from(col_id);
-        btree_wrapper.delete_item(&pkey)?;
+        collection_meta.set_count(count);
+        self.update_collection_meta(&meta_source, col_id, &collection_meta)?;
 
-        meta_source.meta_version += 1;
-        self.update_meta_source(&meta_source)
+        Ok(count)
     }
 
-    pub fn delete(&mut self, col_id: u32, meta_version: u32, query: &Document) -> DbResult<usize> {
-        let primary_keys = self.get_primary_keys_by_query(col_id, meta_version, Some(query))?;
+    /// Walk the B-tree of the collection, and return the size of the pages used by it,
+    /// see [Database::collection_size_bytes](./db/struct.Database.html#method.collection_size_bytes).
+    pub fn collection_size_bytes(&mut self, col_id: u32, meta_version: u32) -> DbResult<u64> {
+        self.check_meta_version(meta_version)?;
 
-        self.page_handler.auto_start_transaction(TransactionType::Write)?;
+        self.page_handler.auto_start_transaction(TransactionType::Read)?;
 
-        let result = try_db_op!(self, self.internal_delete(col_id, &primary_keys));
+        let result = try_db_op!(self, self.internal_collection_size_bytes(col_id));
 
         Ok(result)
     }
 
-    fn internal_delete(&mut self, col_id: u32, primary_keys: &[Value]) -> DbResult<usize> {
-        for pkey in primary_keys {
-            let _ = self.internal_delete_by_pkey(col_id, pkey)?;
-        }
+    fn internal_collection_size_bytes(&mut self, col_id: u32) -> DbResult<u64> {
+        let meta_source = self.get_meta_source()?;
+        let collection_meta = self.find_collection_root_pid_by_id(
+            0, meta_source.meta_pid, col_id)?;
 
-        Ok(primary_keys.len())
+        let pages = counter_helper::count_pages(&mut self.page_handler, &collection_meta)?;
+
+        Ok(pages * (self.page_handler.page_size as u64))
     }
 
-    pub fn delete_all(&mut self, col_id: u32, meta_version: u32) -> DbResult<usize> {
-        let primary_keys = self.get_primary_keys_by_query(col_id, meta_version, None)?;
+    pub fn stats(&mut self) -> DbResult<DbStats> {
+        self.page_handler.auto_start_transaction(TransactionType::Read)?;
 
-        self.page_handler.auto_start_transaction(TransactionType::Write)?;
+        let result = try_db_op!(self, self.internal_stats());
 
-        let result = try_db_op!(self, self.internal_delete(col_id, &primary_keys));
+        Ok(result)
+    }
+
+    fn internal_stats(&mut self) -> DbResult<DbStats> {
+        let first_page = self.page_handler.pipeline_read_page(0)?;
+        let total_pages = HeaderPageWrapper::from_raw_page(first_page).get_null_page_bar();
+        let free_pages = self.page_handler.free_page_count()?;
+
+        let mut overflow_pages = 0;
+        let mut collections = vec![];
+        for meta_doc in self.internal_query_all_meta()? {
+            let meta_entry = MetaDocEntry::from_doc(meta_doc);
+            let name = meta_entry.name().to_string();
+            let (count, collection_overflow_pages) = counter_helper::count_with_overflow_pages(&mut self.page_handler, meta_entry)?;
+            overflow_pages += collection_overflow_pages;
+            collections.push(CollectionStats {
+                name,
+                count,
+            });
+        }
+        collections.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(DbStats {
+            page_size: self.page_handler.page_size,
+            total_pages,
+            free_pages,
+            overflow_pages,
+            collections,
+        })
+    }
+
+    /// Walk all the B-trees: the meta, the collections and the indexes,
+    /// the problems found return, empty if the database is consistent.
+    pub fn verify_integrity(&mut self) -> DbResult<Vec<String>> {
+        self.page_handler.auto_start_transaction(TransactionType::Read)?;
+
+        let result = try_db_op!(self, self.internal_verify_integrity());
 
         Ok(result)
     }
 
-    fn get_primary_keys_by_query(&mut self, col_id: u32, meta_version: u32, query: Option<&Document>) -> DbResult<Vec<Value>> {
-        let mut handle = self.find(col_id, meta_version, query)?;
-        let mut buffer = vec![];
+    fn internal_verify_integrity(&mut self) -> DbResult<Vec<String>> {
+        let first_page = self.page_handler.pipeline_read_page(0)?;
+        let page_count = HeaderPageWrapper::from_raw_page(first_page).get_null_page_bar();
+        let meta_src = self.get_meta_source()?;
 
-        handle.step()?;
+        let mut checker = IntegrityChecker::new(page_count);
+        checker.check_tree(&mut self.page_handler, "<meta>", meta_src.meta_pid)?;
 
-        while handle.has_row() {
-            let doc = handle.get().unwrap_document();
-            let pkey = doc.pkey_id().unwrap();
-            buffer.push(pkey);
+        for meta_doc in self.internal_query_all_meta()? {
+            let meta_entry = MetaDocEntry::from_doc(meta_doc);
+            let name = meta_entry.name().to_string();
 
-            handle.step()?;
+            let count = checker.check_tree(&mut self.page_handler, &name, meta_entry.root_pid())?;
+            if let Some(cached_count) = meta_entry.count() {
+                if cached_count != count {
+                    checker.add_problem(format!("{}: count is {}, but {} documents found", name, cached_count, count));
+                }
+            }
+
+            let indexes_doc = DbContext::get_indexes_doc(&meta_entry);
+            for (key, options) in indexes_doc.iter() {
+                let root_pid = options.unwrap_document()
+                    .get(meta_doc_key::index::ROOT_PID).unwrap().unwrap_int() as u32;
+                let index_name = format!("{}.<index {}>", name, key);
+                checker.check_tree(&mut self.page_handler, &index_name, root_pid)?;
+            }
         }
 
-        Ok(buffer)
+        Ok(checker.into_problems())
     }
 
-    fn update_by_root_pid(&mut self, parent_pid: u32, root_pid: u32, key: &Value, doc: &Document) -> DbResult<bool> {
-        let page = self.page_handler.pipeline_read_page(root_pid)?;
-        let btree_node = BTreeNode::from_raw(&page, parent_pid, self.item_size(), &mut self.page_handler)?;
+    pub fn query_all_meta(&mut self) -> DbResult<Vec<Rc<Document>>> {
+        self.page_handler.auto_start_transaction(TransactionType::Read)?;
 
-        let search_result = btree_node.search(key)?;
-        match search_result {
-            SearchKeyResult::Node(idx) => {
-                self.page_handler.free_data_ticket(&btree_node.content[idx].data_ticket)?;
+        let result = try_db_op!(self, self.internal_query_all_meta());
 
-                let new_ticket = self.page_handler.store_doc(doc)?;
-                let new_btree_node = btree_node.clone_with_content(idx, BTreeNodeDataItem {
-                    key: key.clone(),
-                    data_ticket: new_ticket,
-                });
+        Ok(result)
+    }
 
-                let mut page = RawPage::new(btree_node.pid, self.page_handler.page_size);
-                new_btree_node.to_raw(&mut page)?;
+    fn internal_query_all_meta(&mut self) -> DbResult<Vec<Rc<Document>>> {
+        let meta_src = self.get_meta_source()?;
 
-                self.page_handler.pipeline_write_page(&page)?;
+        let cursor = Cursor::new(self.item_size(), meta_src.meta_pid);
 
-                Ok(true)
-            }
+        cursor.iter_with(&mut self.page_handler).collect()
+    }
 
-            SearchKeyResult::Index(idx) => {
-                let next_pid = btree_node.indexes[idx];
-                if next_pid == 0 {
-                    return Ok(false);
-                }
+    pub fn checkpoint(&mut self) -> DbResult<()> {
+        if self.page_handler.transaction_state() != TransactionState::NoTrans {
+            return Err(DbErr::StartTransactionInAnotherTransaction);
+        }
+        self.page_handler.checkpoint_journal()
+    }
 
-                self.update_by_root_pid(root_pid, next_pid, key, doc)
+    /// Copy all the pages into a new file at `path`, which can be opened as a database.
+    ///
+    /// The pages are read in a read transaction, the committed pages in the journal are included.
+    /// Other processes can't write during the backup, they get `DbErr::Busy`.
+    pub fn backup_to(&mut self, path: &Path) -> DbResult<()> {
+        if self.page_handler.transaction_state() != TransactionState::NoTrans {
+            return Err(DbErr::StartTransactionInAnotherTransaction);
+        }
+        if let (Ok(target), Ok(origin)) = (std::fs::canonicalize(path), std::fs::canonicalize(&self.path)) {
+            if target == origin {
+                return Err(DbErr::ValidationError("can't backup the database to itself".into()));
             }
-
         }
-    }
 
-    fn handle_insert_backward_item(&mut self,
-                                   collection_meta: &mut MetaDocEntry,
-                                   left_pid: u32,
-                                   backward_item: &InsertBackwardItem) -> DbResult<()> {
+        self.page_handler.auto_start_transaction(TransactionType::Read)?;
 
-        let new_root_id = self.page_handler.alloc_page_id()?;
+        try_db_op!(self, self.internal_backup_to(path));
 
-        crate::polo_log!("handle backward item, left_pid: {}, new_root_id: {}, right_pid: {}", left_pid, new_root_id, backward_item.right_pid);
+        Ok(())
+    }
 
-        let new_root_page = backward_item.write_to_page(&mut self.page_handler, new_root_id, left_pid)?;
-        self.page_handler.pipeline_write_page(&new_root_page)?;
+    fn internal_backup_to(&mut self, path: &Path) -> DbResult<()> {
+        // the journal left by the database at the same path would be replayed on the backup
+        match std::fs::remove_file(PageHandler::mk_journal_path(path)) {
+            Ok(()) => (),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => (),
+            Err(err) => return Err(err.into()),
+        }
 
-        collection_meta.set_root_pid(new_root_id);
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .open(path)?;
+
+        let first_page = self.page_handler.pipeline_read_page(0)?;
+        let page_count = HeaderPageWrapper::from_raw_page(first_page).get_null_page_bar();
+        let page_size = self.page_handler.page_size as u64;
+        for pid in 0..page_count {
+            let page = self.page_handler.pipeline_read_page(pid)?;
+            page.sync_to_file(&mut file, (pid as u64) * page_size)?;
+        }
+
+        file.sync_all()?;
 
         Ok(())
     }
 
-    #[allow(dead_code)]
-    pub(crate) fn delete_by_pkey(&mut self, col_id: u32, key: &Value) -> DbResult<Option<Rc<Document>>> {
+    /// Rebuild the B-tree and the indexes of one collection into new balanced pages,
+    /// the old pages are freed.
+    ///
+    /// Everything is done in one transaction, including the new root pid in the meta,
+    /// so the collection is either the old one or the rebuilt one.
+    pub fn compact_collection(&mut self, col_id: u32, meta_version: u32) -> DbResult<()> {
+        self.check_meta_version(meta_version)?;
+
         self.page_handler.auto_start_transaction(TransactionType::Write)?;
 
-        let result = try_db_op!(self, self.internal_delete_by_pkey(col_id, key));
+        try_db_op!(self, self.internal_compact_collection(col_id));
 
-        Ok(result)
+        Ok(())
     }
 
-    fn internal_delete_by_pkey(&mut self, col_id: u32, key: &Value) -> DbResult<Option<Rc<Document>>> {
+    fn internal_compact_collection(&mut self, col_id: u32) -> DbResult<()> {
         let meta_source = self.get_meta_source()?;
-        let collection_meta = self.find_collection_root_pid_by_id(
+        let mut collection_meta = self.find_collection_root_pid_by_id(
             0, meta_source.meta_pid, col_id)?;
 
-        let mut delete_wrapper = BTreePageDeleteWrapper::new(
-            &mut self.page_handler,
-            collection_meta.root_pid() as u32
-        );
-        let result = delete_wrapper.delete_item(key)?;
-        delete_wrapper.flush_pages()?;
+        // the cursor returns the documents in the order of the primary keys
+        let cursor = Cursor::new(self.item_size(), collection_meta.root_pid());
+        let docs: Vec<Rc<Document>> = cursor.iter_with(&mut self.page_handler).collect::<DbResult<_>>()?;
 
-        if let Some(deleted_item) = &result {
-            let index_ctx_opt = IndexCtx::from_meta_doc(collection_meta.doc_ref());
-            if let Some(index_ctx) = &index_ctx_opt {
-                index_ctx.delete_index_by_content(deleted_item.borrow(), &mut self.page_handler)?;
-            }
+        let indexes_doc = DbContext::get_indexes_doc(&collection_meta);
+        if let Some(index_ctx) = IndexCtx::from_indexes_doc(&indexes_doc) {
+            index_ctx.delete_all(&mut self.page_handler)?;
+        }
+        delete_all_helper::delete_all_by_root_pid(&mut self.page_handler, collection_meta.root_pid())?;
 
-            return Ok(result)
+        let root_pid = self.page_handler.alloc_page_id()?;
+        let contents: Vec<Document> = docs.iter().map(|doc| doc.as_ref().clone()).collect();
+        bulk_load(&mut self.page_handler, root_pid, &contents)?;
+        collection_meta.set_root_pid(root_pid);
+
+        if indexes_doc.is_empty() {
+            return self.update_collection_meta(&meta_source, col_id, &collection_meta);
         }
 
-        Ok(None)
+        let new_indexes_doc = self.alloc_index_roots(&indexes_doc)?;
+
+        let index_ctx = IndexCtx::from_indexes_doc(&new_indexes_doc).unwrap();
+        index_ctx.bulk_load_by_contents(&docs, &mut self.page_handler)?;
+
+        collection_meta.set_indexes(new_indexes_doc);
+
+        self.update_collection_meta(&meta_source, col_id, &collection_meta)
     }
 
-    pub fn count(&mut self, col_id: u32, meta_version: u32) -> DbResult<u64> {
+    // the same options of the indexes with the new empty root pages
+    fn alloc_index_roots(&mut self, indexes_doc: &Document) -> DbResult<Document> {
+        let mut new_indexes_doc = Document::new_without_id();
+        for (key, options) in indexes_doc.iter() {
+            let index_root_pid = self.page_handler.alloc_page_id()?;
+            let mut options_doc = options.unwrap_document().as_ref().clone();
+            options_doc.insert(meta_doc_key::index::ROOT_PID.into(), Value::Int(index_root_pid as i64));
+            new_indexes_doc.insert(key.clone(), Value::Document(Rc::new(options_doc)));
+        }
+
+        Ok(new_indexes_doc)
+    }
+
+    /// Remove all the documents of the collection, the meta and the definitions of the indexes are kept.
+    ///
+    /// The pages of the documents and the indexes are freed,
+    /// and the collection starts over with empty root pages. The count of the documents removed returns.
+    pub fn truncate_collection(&mut self, col_id: u32, meta_version: u32) -> DbResult<u64> {
         self.check_meta_version(meta_version)?;
+
+        self.page_handler.auto_start_transaction(TransactionType::Write)?;
+
+        let result = try_db_op!(self, self.internal_truncate_collection(col_id));
+
+        Ok(result)
+    }
+
+    fn internal_truncate_collection(&mut self, col_id: u32) -> DbResult<u64> {
         let meta_source = self.get_meta_source()?;
-        let collection_meta = self.find_collection_root_pid_by_id(
+        let mut collection_meta = self.find_collection_root_pid_by_id(
             0, meta_source.meta_pid, col_id)?;
-        counter_helper::count(&mut self.page_handler, collection_meta)
+
+        // the ids are only read for the oplog
+        let (count, deleted_ids) = if self.oplog {
+            let cursor = Cursor::new(self.item_size(), collection_meta.root_pid());
+            let ids: Vec<Value> = cursor.iter_with(&mut self.page_handler)
+                .map(|doc| doc.map(|doc| doc.pkey_id().unwrap()))
+                .collect::<DbResult<_>>()?;
+            (ids.len() as u64, ids)
+        } else {
+            let count = match collection_meta.count() {
+                Some(count) => count,
+                None => counter_helper::count(&mut self.page_handler, &collection_meta)?,
+            };
+            (count, vec![])
+        };
+
+        let indexes_doc = DbContext::get_indexes_doc(&collection_meta);
+        if let Some(index_ctx) = IndexCtx::from_indexes_doc(&indexes_doc) {
+            index_ctx.delete_all(&mut self.page_handler)?;
+        }
+        delete_all_helper::delete_all_by_root_pid(&mut self.page_handler, collection_meta.root_pid())?;
+
+        let root_pid = self.page_handler.alloc_page_id()?;
+        collection_meta.set_root_pid(root_pid);
+        collection_meta.set_count(0);
+
+        if !indexes_doc.is_empty() {
+            let new_indexes_doc = self.alloc_index_roots(&indexes_doc)?;
+            collection_meta.set_indexes(new_indexes_doc);
+        }
+
+        self.update_collection_meta(&meta_source, col_id, &collection_meta)?;
+
+        self.append_oplog(col_id, op_name::DELETE, &deleted_ids)?;
+
+        Ok(count)
     }
 
-    pub fn query_all_meta(&mut self) -> DbResult<Vec<Rc<Document>>> {
-        // let meta_page_id = self.get_meta_page_id()?;
-        //
-        // let mut result = vec![];
-        // let mut cursor = Cursor::new(&mut self.page_handler, meta_page_id)?;
-        //
-        // while cursor.has_next() {
-        //     let ticket = cursor.peek().unwrap();
-        //     let doc = cursor.get_doc_from_ticket(&ticket)?;
-        //     result.push(doc);
-        //
-        //     let _ = cursor.next()?;
-        // }
-        //
-        // Ok(result)
-        unimplemented!()
+    /// Rewrite all the collections and the indexes into a new file,
+    /// and replace the database file with it, the free pages are reclaimed.
+    ///
+    /// The new file is written to `<path>.compact` and renamed to the database file at last,
+    /// if the process dies before the renaming, the original file is intact.
+    #[inline]
+    pub fn compact(&mut self) -> DbResult<()> {
+        self.compact_cancellable(&OperationOptions::default())
+    }
+
+    /// The same as [compact](#method.compact), but the copying stops with `DbErr::Cancelled`
+    /// when the `options` are triggered, the new file is removed and the database is unchanged.
+    pub fn compact_cancellable(&mut self, options: &OperationOptions) -> DbResult<()> {
+        if self.page_handler.transaction_state() != TransactionState::NoTrans {
+            return Err(DbErr::StartTransactionInAnotherTransaction);
+        }
+
+        self.page_handler.checkpoint_journal()?;
+
+        if self.page_handler.is_memory() {
+            return self.compact_memory(options);
+        }
+
+        let compacted_path = DbContext::mk_compacted_path(&self.path);
+
+        self.page_handler.auto_start_transaction(TransactionType::Read)?;
+
+        let result = self.copy_to_compacted_file(&compacted_path, options);
+        if result.is_err() {
+            let _ = std::fs::remove_file(&compacted_path);
+            let _ = std::fs::remove_file(PageHandler::mk_journal_path(&compacted_path));
+        }
+        try_db_op!(self, result);
+
+        // make sure the new file is on the disk before replacing
+        std::fs::OpenOptions::new().write(true).open(&compacted_path)?.sync_all()?;
+
+        // the journal is empty after the checkpoint
+        match std::fs::remove_file(self.page_handler.journal_file_path()) {
+            Ok(()) => (),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => (),
+            Err(err) => return Err(err.into()),
+        }
+        std::fs::rename(&compacted_path, &self.path)?;
+
+        let config = self.page_handler.config().clone();
+        let page_size = config.page_size;
+        *self.page_handler = PageHandler::with_config(&self.path, page_size, Arc::new(config))?;
+        self.reset_meta_version()?;
+
+        Ok(())
+    }
+
+    // the data is copied into a new database in the memory,
+    // and the pages are swapped
+    fn compact_memory(&mut self, options: &OperationOptions) -> DbResult<()> {
+        let mut config = self.page_handler.config().clone();
+        config.oplog = false;  // the oplog is copied as a collection
+        let mut compacted = DbContext::open_memory(config)?;
+
+        self.page_handler.auto_start_transaction(TransactionType::Read)?;
+
+        try_db_op!(self, self.copy_to_compacted_ctx(&mut compacted, options));
+
+        std::mem::swap(&mut self.page_handler, &mut compacted.page_handler);
+        self.reset_meta_version()?;
+
+        Ok(())
+    }
+
+    fn mk_compacted_path(db_path: &Path) -> PathBuf {
+        let mut buf = db_path.to_path_buf();
+        let filename = buf.file_name().unwrap().to_str().unwrap();
+        let new_filename = String::from(filename) + ".compact";
+        buf.set_file_name(new_filename);
+        buf
+    }
+
+    fn copy_to_compacted_file(&mut self, compacted_path: &Path, options: &OperationOptions) -> DbResult<()> {
+        // the file left by an interrupted compaction
+        let _ = std::fs::remove_file(compacted_path);
+        let _ = std::fs::remove_file(PageHandler::mk_journal_path(compacted_path));
+
+        let mut config = self.page_handler.config().clone();
+        config.oplog = false;  // the oplog is copied as a collection
+        let mut compacted = DbContext::new(compacted_path, config)?;
+
+        self.copy_to_compacted_ctx(&mut compacted, options)
+    }
+
+    fn copy_to_compacted_ctx(&mut self, compacted: &mut DbContext, options: &OperationOptions) -> DbResult<()> {
+        let meta_docs = self.internal_query_all_meta()?;
+        for meta_doc in meta_docs {
+            let meta_entry = MetaDocEntry::from_doc(meta_doc);
+            let collection_meta = compacted.create_collection(meta_entry.name())?;
+
+            compacted.start_transaction(Some(TransactionType::Write))?;
+
+            options.check_cancelled()?;
+
+            let mut batch = Vec::with_capacity(COMPACT_BATCH_SIZE);
+            let mut cursor = Cursor::new(self.item_size(), meta_entry.root_pid());
+            cursor.reset(&mut self.page_handler)?;
+            let mut pid = cursor.current_pid();
+            while let Some(doc) = cursor.next(&mut self.page_handler)? {
+                // checked when moving to another page instead of every document
+                if cursor.current_pid() != pid {
+                    pid = cursor.current_pid();
+                    options.check_cancelled()?;
+                }
+                batch.push(doc);
+                if batch.len() >= COMPACT_BATCH_SIZE {
+                    let docs = std::mem::replace(&mut batch, Vec::with_capacity(COMPACT_BATCH_SIZE));
+                    compacted.insert_many(collection_meta.id, collection_meta.meta_version, docs)?;
+                }
+            }
+            if !batch.is_empty() {
+                compacted.insert_many(collection_meta.id, collection_meta.meta_version, batch)?;
+            }
+
+            compacted.commit()?;
+
+            // the indexes are built from the documents inserted
+            let indexes_doc = DbContext::get_indexes_doc(&meta_entry);
+            for (key, options) in indexes_doc.iter() {
+                let options = options.unwrap_document();
+                if let Some(fields) = options.get(meta_doc_key::index::FIELDS) {
+                    let fields: Vec<&str> = fields.unwrap_array().iter().map(|field| field.unwrap_string()).collect();
+                    compacted.create_index_compound(collection_meta.id, &fields, Some(options))?;
+                    continue;
+                }
+
+                let mut keys = Document::new_without_id();
+                keys.insert(key.clone(), Value::Int(1));
+                compacted.create_index(collection_meta.id, &keys, Some(options))?;
+            }
+        }
+
+        compacted.obj_id_maker = ObjectIdMaker::with_state(
+            self.obj_id_maker.last_timestamp(), self.obj_id_maker.counter());
+        compacted.start_transaction(Some(TransactionType::Write))?;
+        compacted.save_object_id_maker()?;
+        compacted.commit()?;
+
+        Ok(())
     }
 
     pub fn start_transaction(&mut self, ty: Option<TransactionType>) -> DbResult<()> {
@@ -692,11 +2139,55 @@ impl DbContext {
         Ok(())
     }
 
+    #[inline]
+    pub fn set_page_cache_capacity(&mut self, pages: usize) {
+        self.page_handler.set_cache_capacity(pages)
+    }
+
+    #[inline]
+    pub fn page_cache_stats(&self) -> PageCacheStats {
+        self.page_handler.cache_stats()
+    }
+
+    #[inline]
+    pub fn page_metrics(&self) -> PageMetrics {
+        self.page_handler.metrics()
+    }
+
+    #[inline]
+    pub fn reset_page_metrics(&mut self) {
+        self.page_handler.reset_metrics()
+    }
+
     #[inline]
     pub fn object_id_maker(&mut self) -> &mut ObjectIdMaker {
         &mut self.obj_id_maker
     }
 
+    /// Make a `Value::Timestamp` greater than all the ones made before by this context.
+    ///
+    /// The time is the current time in seconds, the inc starts from 1 in every second.
+    /// If the clock goes backwards, or the inc runs out, the time of the last one is kept
+    /// or moved forward, so they are always increasing.
+    pub fn mk_timestamp(&mut self) -> Value {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("Time went backwards")
+            .as_secs() as u32;
+
+        let (last_time, last_inc) = self.last_timestamp;
+        self.last_timestamp = if now > last_time {
+            (now, 1)
+        } else if last_inc < u32::MAX {
+            (last_time, last_inc + 1)
+        } else {
+            (last_time + 1, 1)
+        };
+
+        let (time, inc) = self.last_timestamp;
+        Value::Timestamp { time, inc }
+    }
+
     pub fn dump(&mut self) -> DbResult<FullDump> {
         let file_meta = self.page_handler.file_meta()?;
         let first_page = self.page_handler.pipeline_read_page(0)?;
@@ -705,6 +2196,7 @@ impl DbContext {
         let meta_pid = first_page_wrapper.get_meta_page_id();
         let free_list_pid = first_page_wrapper.get_free_list_page_id();
         let free_list_size = first_page_wrapper.get_free_list_size();
+        let null_page_bar = first_page_wrapper.get_null_page_bar();
         let page_size = self.page_handler.page_size;
 
         let pages = self.dump_all_pages(file_meta.len())?;
@@ -718,6 +2210,7 @@ impl DbContext {
             meta_pid,
             free_list_pid,
             free_list_size,
+            null_page_bar,
             page_size,
             pages,
         };
@@ -781,9 +2274,15 @@ fn dump_version(version: &[u8]) -> String {
 impl Drop for DbContext {
 
     fn drop(&mut self) {
+        // nothing to write back
+        if self.page_handler.is_read_only() || self.page_handler.is_memory() {
+            return;
+        }
         if self.page_handler.transaction_state() != TransactionState::NoTrans {
             let _ = self.page_handler.only_rollback_journal();
         }
+        // the snapshots can't be read without the database
+        self.page_handler.release_read_views();
         let checkpoint_result = self.page_handler.checkpoint_journal();  // ignored
         if checkpoint_result.is_ok() {
             let path = self.page_handler.journal_file_path().to_path_buf();
@@ -24,11 +24,13 @@ impl CursorItem {
 
 }
 
+#[derive(Clone)]
 pub(crate) struct Cursor {
     root_pid:           u32,
     item_size:          u32,
     btree_stack:        LinkedList<CursorItem>,
     current:            Option<Rc<Document>>,
+    reverse:            bool,
 }
 
 impl Cursor {
@@ -39,10 +41,26 @@ impl Cursor {
             item_size,
             btree_stack: LinkedList::new(),
             current: None,
+            reverse: false,
+        }
+    }
+
+    /// The cursor iterating the B-tree in the descending order of the keys,
+    /// it starts from the right most leaf.
+    pub fn new_reverse(item_size: u32, root_pid: u32) -> Cursor {
+        Cursor {
+            reverse: true,
+            ..Cursor::new(item_size, root_pid)
         }
     }
 
+    /// Rewind to the beginning of the B-tree,
+    /// the stack is re-descended to the left most leaf.
     pub fn reset(&mut self, page_handler: &mut PageHandler) -> DbResult<()> {
+        if self.reverse {
+            return self.reset_reverse(page_handler);
+        }
+
         self.mk_initial_btree(page_handler, self.root_pid, self.item_size)?;
 
         if self.btree_stack.is_empty() {
@@ -54,6 +72,23 @@ impl Cursor {
         Ok(())
     }
 
+    fn reset_reverse(&mut self, page_handler: &mut PageHandler) -> DbResult<()> {
+        self.btree_stack.clear();
+
+        let btree_page = page_handler.pipeline_read_page(self.root_pid)?;
+        let btree_node = BTreeNode::from_raw(
+            &btree_page, 0,
+            self.item_size,
+            page_handler
+        )?;
+
+        if btree_node.content.is_empty() {
+            return Ok(());
+        }
+
+        self.push_all_right_nodes(page_handler, 0, self.root_pid)
+    }
+
     pub fn reset_by_pkey(&mut self, page_handler: &mut PageHandler, pkey: &Value) -> DbResult<bool> {
         self.btree_stack.clear();
 
@@ -103,6 +138,46 @@ impl Cursor {
         Ok(false)
     }
 
+    /// Move to the first item whose key is not less than `key`,
+    /// the cursor is at the end if there is no such item.
+    pub fn reset_by_lower_bound(&mut self, page_handler: &mut PageHandler, key: &Value) -> DbResult<()> {
+        self.btree_stack.clear();
+
+        let mut current_pid = self.root_pid;
+        let mut parent_pid = 0;
+
+        while current_pid > 0 {
+            let btree_page = page_handler.pipeline_read_page(current_pid)?;
+            let btree_node = BTreeNode::from_raw(
+                &btree_page, parent_pid,
+                self.item_size,
+                page_handler
+            )?;
+
+            if btree_node.is_empty() {
+                break;
+            }
+
+            let (index, next_pid) = match btree_node.search(key)? {
+                SearchKeyResult::Node(index) => (index, 0),
+                SearchKeyResult::Index(index) => (index, btree_node.indexes[index]),
+            };
+
+            // the items before the index are less than the key
+            if index < btree_node.content.len() {
+                self.btree_stack.push_back(CursorItem {
+                    node: Rc::new(btree_node),
+                    index,
+                });
+            }
+
+            parent_pid = current_pid;
+            current_pid = next_pid;
+        }
+
+        Ok(())
+    }
+
     fn mk_initial_btree(&mut self, page_handler: &mut PageHandler, root_page_id: u32, item_size: u32) -> DbResult<()> {
         self.btree_stack.clear();
 
@@ -151,6 +226,34 @@ impl Cursor {
         Ok(())
     }
 
+    // descend from the node of `pid` to the right most leaf,
+    // the index of every node points to its last item
+    fn push_all_right_nodes(&mut self, page_handler: &mut PageHandler, parent_pid: u32, pid: u32) -> DbResult<()> {
+        let mut parent_pid = parent_pid;
+        let mut right_pid = pid;
+
+        while right_pid != 0 {
+            let btree_page = page_handler.pipeline_read_page(right_pid)?;
+            let btree_node = BTreeNode::from_raw(
+                &btree_page,
+                parent_pid,
+                self.item_size,
+                page_handler
+            )?;
+
+            let last_index = btree_node.content.len() - 1;
+            parent_pid = right_pid;
+            right_pid = btree_node.indexes[last_index + 1];
+
+            self.btree_stack.push_back(CursorItem {
+                node: Rc::new(btree_node),
+                index: last_index,
+            });
+        }
+
+        Ok(())
+    }
+
     pub fn peek(&mut self) -> Option<DataTicket> {
         if self.btree_stack.is_empty() {
             return None;
@@ -192,6 +295,12 @@ impl Cursor {
         page_handler.pipeline_write_page(&page)
     }
 
+    /// The page of the node the cursor points to, None at the end.
+    #[inline]
+    pub fn current_pid(&self) -> Option<u32> {
+        self.btree_stack.back().map(|item| item.node.pid)
+    }
+
     #[inline]
     pub fn has_next(&self) -> bool {
         !self.btree_stack.is_empty()
@@ -202,30 +311,48 @@ impl Cursor {
             return Ok(None);
         }
 
-        let top = self.btree_stack.pop_back().unwrap();
+        let top = self.btree_stack.back().unwrap();
         let result_ticket = &top.node.content[top.index].data_ticket;
-        let result = page_handler.get_doc_from_ticket(&result_ticket)?.unwrap();
+        let result = page_handler.get_doc_from_ticket(result_ticket)?.unwrap();
 
-        let next_index = top.index + 1;
+        self.step(page_handler)?;
 
-        if next_index >= top.node.content.len() {  // right most index
-            let right_most_index = top.node.indexes[next_index];
+        self.current = Some(result.clone());
+        Ok(Some(result))
+    }
 
-            if right_most_index != 0 {
-                self.btree_stack.push_back(CursorItem {
-                    node: top.node.clone(),
-                    index: next_index,
-                });
+    /// Count the items from the current position to the end,
+    /// the documents are not read, and the position of the cursor is kept.
+    pub fn count_remaining(&mut self, page_handler: &mut PageHandler) -> DbResult<usize> {
+        let saved_stack = self.btree_stack.clone();
+
+        let mut count: usize = 0;
+        let mut result = Ok(());
+        while !self.btree_stack.is_empty() {
+            result = self.step(page_handler);
+            if result.is_err() {
+                break;
+            }
+            count += 1;
+        }
 
-                self.push_all_left_nodes(page_handler)?;
+        self.btree_stack = saved_stack;
+        result.map(|_| count)
+    }
 
-                return Ok(Some(result));
-            }
+    // move to the next item without reading the document
+    fn step(&mut self, page_handler: &mut PageHandler) -> DbResult<()> {
+        if self.reverse {
+            return self.step_reverse(page_handler);
+        }
 
-            // pop
-            self.pop_all_right_most_item();
+        let top = self.btree_stack.pop_back().unwrap();
+        let next_index = top.index + 1;
 
-            return Ok(Some(result));
+        if next_index >= top.node.content.len() && top.node.indexes[next_index] == 0 {
+            // the right most item of the leaf
+            self.pop_all_right_most_item();
+            return Ok(());
         }
 
         self.btree_stack.push_back(CursorItem {
@@ -233,10 +360,31 @@ impl Cursor {
             index: next_index,
         });
 
-        self.push_all_left_nodes(page_handler)?;
+        self.push_all_left_nodes(page_handler)
+    }
 
-        self.current = Some(result.clone());
-        Ok(Some(result))
+    // the item of the node is popped after it's visited,
+    // so the top of the stack is always the next item
+    fn step_reverse(&mut self, page_handler: &mut PageHandler) -> DbResult<()> {
+        let top = self.btree_stack.pop_back().unwrap();
+        let left_pid = top.node.indexes[top.index];
+
+        if top.index > 0 {
+            self.btree_stack.push_back(CursorItem {
+                node: top.node.clone(),
+                index: top.index - 1,
+            });
+        }
+
+        self.push_all_right_nodes(page_handler, top.node.pid, left_pid)
+    }
+
+    /// Consume the cursor and return an iterator over the documents.
+    ///
+    /// The cursor will be reset when the iterator is polled first time.
+    #[inline]
+    pub fn iter_with(self, page_handler: &mut PageHandler) -> CursorIter<'_> {
+        CursorIter::new(self, page_handler)
     }
 
     pub fn pop_all_right_most_item(&mut self) {
@@ -257,3 +405,240 @@ impl Cursor {
     }
 
 }
+
+/// An owned iterator wrapper of the [Cursor].
+///
+/// The [Cursor] itself doesn't hold the [PageHandler], but the iterator
+/// has to read pages in `next()`, so the wrapper borrows the `PageHandler`
+/// mutably until it's dropped. No other operation can be done on the
+/// `PageHandler` during the iteration, and the underlying B-tree must not be
+/// changed by others, so the iteration must be finished in a transaction.
+///
+/// The iterator stops after first error is returned.
+pub(crate) struct CursorIter<'a> {
+    cursor:       Cursor,
+    page_handler: &'a mut PageHandler,
+    is_reset:     bool,
+    is_finished:  bool,
+}
+
+impl<'a> CursorIter<'a> {
+
+    fn new(cursor: Cursor, page_handler: &'a mut PageHandler) -> CursorIter<'a> {
+        CursorIter {
+            cursor,
+            page_handler,
+            is_reset: false,
+            is_finished: false,
+        }
+    }
+
+    fn next_doc(&mut self) -> DbResult<Option<Rc<Document>>> {
+        if !self.is_reset {
+            self.cursor.reset(self.page_handler)?;
+            self.is_reset = true;
+        }
+
+        self.cursor.next(self.page_handler)
+    }
+
+}
+
+impl<'a> Iterator for CursorIter<'a> {
+    type Item = DbResult<Rc<Document>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_finished {
+            return None;
+        }
+
+        match self.next_doc() {
+            Ok(Some(doc)) => Some(Ok(doc)),
+            Ok(None) => {
+                self.is_finished = true;
+                None
+            }
+            Err(err) => {
+                self.is_finished = true;
+                Some(Err(err))
+            }
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use polodb_bson::{Document, Value, mk_document};
+    use crate::TransactionType;
+    use crate::page::PageHandler;
+    use crate::btree::{BTreePageInsertWrapper, bulk_load};
+    use crate::cursor::Cursor;
+
+    fn prepare_page_handler(db_name: &str) -> PageHandler {
+        let mut db_path = env::temp_dir();
+        let mut journal_path = env::temp_dir();
+
+        let db_filename = String::from(db_name) + ".db";
+        let journal_filename = String::from(db_name) + ".db.journal";
+
+        db_path.push(db_filename);
+        journal_path.push(journal_filename);
+
+        let _ = std::fs::remove_file(db_path.as_path());
+        let _ = std::fs::remove_file(journal_path);
+
+        PageHandler::new(db_path.as_ref(), 4096).unwrap()
+    }
+
+    #[test]
+    fn test_cursor_iter() {
+        let mut page_handler = prepare_page_handler("test-cursor-iter");
+        page_handler.start_transaction(TransactionType::Write).unwrap();
+
+        let root_pid = page_handler.alloc_page_id().unwrap();
+        let item_size = page_handler.btree_order;
+
+        {
+            let mut insert_wrapper = BTreePageInsertWrapper::new(&mut page_handler, root_pid);
+            for i in 0..50 {
+                let doc = mk_document! {
+                    "_id": i,
+                    "content": i.to_string(),
+                };
+                let result = insert_wrapper.insert_item(&doc, false).unwrap();
+                assert!(result.backward_item.is_none());
+            }
+        }
+
+        let cursor = Cursor::new(item_size, root_pid);
+        let ids: Vec<i64> = cursor.iter_with(&mut page_handler)
+            .map(|doc| doc.unwrap().pkey_id().unwrap().unwrap_int())
+            .collect();
+        assert_eq!(ids, (0..50).collect::<Vec<i64>>());
+
+        let cursor = Cursor::new(item_size, root_pid);
+        let even_count = cursor.iter_with(&mut page_handler)
+            .filter(|doc| doc.as_ref().unwrap().pkey_id().unwrap().unwrap_int() % 2 == 0)
+            .take(10)
+            .count();
+        assert_eq!(even_count, 10);
+
+        let empty_pid = page_handler.alloc_page_id().unwrap();
+        let cursor = Cursor::new(item_size, empty_pid);
+        assert_eq!(cursor.iter_with(&mut page_handler).count(), 0);
+    }
+
+    #[test]
+    fn test_cursor_reset_and_count_remaining() {
+        let mut page_handler = prepare_page_handler("test-cursor-count-remaining");
+        page_handler.start_transaction(TransactionType::Write).unwrap();
+
+        // the tree has several levels
+        let root_pid = page_handler.alloc_page_id().unwrap();
+        let docs: Vec<Document> = (0..2000).map(|i| mk_document! { "_id": i }).collect();
+        bulk_load(&mut page_handler, root_pid, &docs).unwrap();
+
+        let mut cursor = Cursor::new(page_handler.btree_order, root_pid);
+        for _ in 0..2 {
+            cursor.reset(&mut page_handler).unwrap();
+            assert_eq!(cursor.count_remaining(&mut page_handler).unwrap(), 2000);
+
+            for i in 0..2000 {
+                if i % 500 == 0 {
+                    assert_eq!(cursor.count_remaining(&mut page_handler).unwrap(), 2000 - i as usize);
+                }
+                let doc = cursor.next(&mut page_handler).unwrap().unwrap();
+                assert_eq!(doc.pkey_id().unwrap().unwrap_int(), i);
+            }
+
+            assert!(cursor.next(&mut page_handler).unwrap().is_none());
+            assert_eq!(cursor.count_remaining(&mut page_handler).unwrap(), 0);
+        }
+
+        let empty_pid = page_handler.alloc_page_id().unwrap();
+        let mut cursor = Cursor::new(page_handler.btree_order, empty_pid);
+        cursor.reset(&mut page_handler).unwrap();
+        assert_eq!(cursor.count_remaining(&mut page_handler).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_cursor_reverse() {
+        let mut page_handler = prepare_page_handler("test-cursor-reverse");
+        page_handler.start_transaction(TransactionType::Write).unwrap();
+        let item_size = page_handler.btree_order;
+
+        for size in &[1, 10, item_size as i64 + 1, 2000] {
+            let root_pid = page_handler.alloc_page_id().unwrap();
+            let docs: Vec<Document> = (0..*size).map(|i| mk_document! { "_id": i }).collect();
+            bulk_load(&mut page_handler, root_pid, &docs).unwrap();
+
+            let cursor = Cursor::new(item_size, root_pid);
+            let mut forward_ids: Vec<i64> = cursor.iter_with(&mut page_handler)
+                .map(|doc| doc.unwrap().pkey_id().unwrap().unwrap_int())
+                .collect();
+
+            let cursor = Cursor::new_reverse(item_size, root_pid);
+            let reverse_ids: Vec<i64> = cursor.iter_with(&mut page_handler)
+                .map(|doc| doc.unwrap().pkey_id().unwrap().unwrap_int())
+                .collect();
+
+            forward_ids.reverse();
+            assert_eq!(reverse_ids, forward_ids);
+            assert_eq!(reverse_ids.len(), *size as usize);
+
+            let mut cursor = Cursor::new_reverse(item_size, root_pid);
+            cursor.reset(&mut page_handler).unwrap();
+            assert_eq!(cursor.count_remaining(&mut page_handler).unwrap(), *size as usize);
+        }
+
+        // the tree built by the insertions
+        let root_pid = page_handler.alloc_page_id().unwrap();
+        {
+            let mut insert_wrapper = BTreePageInsertWrapper::new(&mut page_handler, root_pid);
+            for i in [5, 3, 9, 1, 7] {
+                insert_wrapper.insert_item(&mk_document! { "_id": i }, false).unwrap();
+            }
+        }
+        let cursor = Cursor::new_reverse(item_size, root_pid);
+        let ids: Vec<i64> = cursor.iter_with(&mut page_handler)
+            .map(|doc| doc.unwrap().pkey_id().unwrap().unwrap_int())
+            .collect();
+        assert_eq!(ids, vec![9, 7, 5, 3, 1]);
+
+        let empty_pid = page_handler.alloc_page_id().unwrap();
+        let cursor = Cursor::new_reverse(item_size, empty_pid);
+        assert_eq!(cursor.iter_with(&mut page_handler).count(), 0);
+    }
+
+    #[test]
+    fn test_cursor_reset_by_lower_bound() {
+        let mut page_handler = prepare_page_handler("test-cursor-lower-bound");
+        page_handler.start_transaction(TransactionType::Write).unwrap();
+
+        // the even numbers
+        let root_pid = page_handler.alloc_page_id().unwrap();
+        let docs: Vec<Document> = (0..2000).map(|i| mk_document! { "_id": i * 2 }).collect();
+        bulk_load(&mut page_handler, root_pid, &docs).unwrap();
+
+        let mut cursor = Cursor::new(page_handler.btree_order, root_pid);
+        for key in [-1, 0, 1, 777, 1000, 3997, 3998] {
+            cursor.reset_by_lower_bound(&mut page_handler, &Value::from(key)).unwrap();
+            let expected_first = if key <= 0 { 0 } else { (key + 1) / 2 * 2 };
+            assert_eq!(cursor.count_remaining(&mut page_handler).unwrap(), (2000 - expected_first / 2) as usize);
+
+            let doc = cursor.next(&mut page_handler).unwrap().unwrap();
+            assert_eq!(doc.pkey_id().unwrap().unwrap_int(), expected_first);
+        }
+
+        cursor.reset_by_lower_bound(&mut page_handler, &Value::from(3999)).unwrap();
+        assert!(cursor.next(&mut page_handler).unwrap().is_none());
+
+        let empty_pid = page_handler.alloc_page_id().unwrap();
+        let mut cursor = Cursor::new(page_handler.btree_order, empty_pid);
+        cursor.reset_by_lower_bound(&mut page_handler, &Value::from(1)).unwrap();
+        assert!(cursor.next(&mut page_handler).unwrap().is_none());
+    }
+
+}
@@ -1,11 +1,18 @@
 use std::rc::Rc;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Read, Write};
 use std::path::Path;
-use polodb_bson::{Document, ObjectId};
+use std::sync::Arc;
+use polodb_bson::{Document, ObjectId, Value, mk_document};
 use super::error::DbErr;
-use crate::Config;
+use crate::{Config, SyncPolicy, OperationOptions};
 use crate::context::DbContext;
-use crate::{DbHandle, TransactionType};
+use crate::{DbHandle, TransactionType, PageCacheStats, PageMetrics, Transaction, Snapshot, DbStats, WriteOp, BulkResult, QueryPlan};
 use crate::dump::FullDump;
+use crate::meta_doc_helper::MetaDocEntry;
+use crate::page::ReadView;
+use crate::query;
+use crate::shared_db::SharedDatabase;
 
 fn consume_handle_to_vec(handle: &mut DbHandle, result: &mut Vec<Rc<Document>>) -> DbResult<()> {
     handle.step()?;
@@ -20,6 +27,80 @@ fn consume_handle_to_vec(handle: &mut DbHandle, result: &mut Vec<Rc<Document>>)
     Ok(())
 }
 
+// read the length of the next BSON record, false returns at the end of the reader
+fn read_bson_len<R: Read>(reader: &mut R, buffer: &mut [u8; 4]) -> DbResult<bool> {
+    let mut read = 0;
+    while read < buffer.len() {
+        match reader.read(&mut buffer[read..]) {
+            Ok(0) => break,
+            Ok(size) => read += size,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    match read {
+        0 => Ok(false),
+        4 => Ok(true),
+        _ => Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+    }
+}
+
+/// The documents matching the filter of [Database::query](./struct.Database.html#method.query),
+/// they are read one by one while walking the B-tree of the collection.
+///
+/// It borrows the database, the transaction started automatically is finished
+/// when all the documents are read or the cursor is dropped.
+pub struct QueryCursor<'a> {
+    handle:       DbHandle<'a>,
+    finished:     bool,
+    is_full_scan: bool,
+}
+
+impl<'a> QueryCursor<'a> {
+
+    /// Rewind to the beginning of the collection, the documents are read again
+    /// from the leftmost leaf of the B-tree, even if the cursor is exhausted.
+    pub fn reset(&mut self) {
+        self.handle.rewind();
+        self.finished = false;
+    }
+
+    /// Count the documents matching the filter which are not read yet,
+    /// the position of the cursor is kept.
+    ///
+    /// The documents are not read if the filter is empty.
+    pub fn count_remaining(&mut self) -> DbResult<usize> {
+        if self.finished {
+            return Ok(0);
+        }
+        self.handle.count_remaining(self.is_full_scan)
+    }
+
+}
+
+impl<'a> Iterator for QueryCursor<'a> {
+    type Item = DbResult<Rc<Document>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        if let Err(err) = self.handle.step() {
+            self.finished = true;
+            return Some(Err(err));
+        }
+
+        if !self.handle.has_row() {
+            self.finished = true;
+            return None;
+        }
+
+        Some(Ok(self.handle.get().unwrap_document().clone()))
+    }
+
+}
+
 /// A wrapper of collection in struct.
 ///
 /// All CURD methods can be done through this structure.
@@ -38,6 +119,8 @@ fn consume_handle_to_vec(handle: &mut DbHandle, result: &mut Vec<Rc<Document>>)
 /// | $lte | Matches values that are less than or equal to a specified value. |
 /// | $ne | Matches all values that are not equal to a specified value. |
 /// | $nin | Matches none of the values specified in an array. |
+/// | $regex | Matches the strings by the regular expression, needs the feature `regex`. |
+/// | $exists | Matches the documents having the field if it's true, a field storing null exists. |
 ///
 /// ## Logical operation:
 ///
@@ -45,6 +128,7 @@ fn consume_handle_to_vec(handle: &mut DbHandle, result: &mut Vec<Rc<Document>>)
 /// | ---- | ----------- |
 /// | $and | Joins query clauses with a logical AND returns all documents that match the conditions of both clauses. |
 /// | $or | Joins query clauses with a logical OR returns all documents that match the conditions of either clause. |
+/// | $not | Inverts the effect of a query expression, returns the documents that do not match it. |
 ///
 /// ## Example:
 ///
@@ -66,6 +150,8 @@ pub struct Collection<'a> {
     id: u32,
     meta_version: u32,
     name: String,
+    // the meta resolved by the first operation, None after an operation fails
+    meta: Option<MetaDocEntry>,
 }
 
 impl<'a>  Collection<'a> {
@@ -76,47 +162,74 @@ impl<'a>  Collection<'a> {
             id,
             meta_version,
             name: name.into(),
+            meta: None,
         }
     }
 
+    // take the cached meta out, it's put back by the operation succeeded,
+    // so the meta maybe changed by a failed one is resolved again
+    fn take_meta(&mut self) -> DbResult<MetaDocEntry> {
+        match self.meta.take() {
+            Some(meta) => Ok(meta),
+            None => self.db.ctx.get_collection_meta_entry(self.id, self.meta_version),
+        }
+    }
+
+    fn with_meta<T, F>(&mut self, f: F) -> DbResult<T> where
+        F: FnOnce(&mut DbContext, &mut MetaDocEntry) -> DbResult<T> {
+        let mut meta = self.take_meta()?;
+        let result = f(&mut self.db.ctx, &mut meta)?;
+        self.meta = Some(meta);
+        Ok(result)
+    }
+
     /// all the data in the collection return.
     pub fn find_all(&mut self) -> DbResult<Vec<Rc<Document>>> {
-        let mut handle = self.db.ctx.find(self.id, self.meta_version, None)?;
+        let meta_version = self.meta_version;
+        self.with_meta(|ctx, meta| {
+            let mut handle = ctx.find_with_meta(meta_version, meta, None)?;
 
-        let mut result = Vec::new();
+            let mut result = Vec::new();
 
-        consume_handle_to_vec(&mut handle, &mut result)?;
+            consume_handle_to_vec(&mut handle, &mut result)?;
 
-        Ok(result)
+            Ok(result)
+        })
     }
 
     /// When query document is passed to the function. The result satisfies
     /// the query document.
     pub fn find(&mut self, query: &Document) -> DbResult<Vec<Rc<Document>>> {
-        let mut handle = self.db.ctx.find(
-            self.id, self.meta_version, Some(query)
-        )?;
+        let meta_version = self.meta_version;
+        self.with_meta(|ctx, meta| {
+            if let Some(result) = ctx.find_by_index_with_meta(meta_version, meta, query)? {
+                return Ok(result);
+            }
 
-        let mut result = Vec::new();
+            let mut handle = ctx.find_with_meta(meta_version, meta, Some(query))?;
 
-        consume_handle_to_vec(&mut handle, &mut result)?;
+            let mut result = Vec::new();
 
-        Ok(result)
+            consume_handle_to_vec(&mut handle, &mut result)?;
+
+            Ok(result)
+        })
     }
 
     /// Return the first element in the collection satisfies the query.
     pub fn find_one(&mut self, query: &Document) -> DbResult<Option<Rc<Document>>> {
-        let mut handle = self.db.ctx.find(
-            self.id, self.meta_version, Some(query)
-        )?;
-        handle.step()?;
+        let meta_version = self.meta_version;
+        self.with_meta(|ctx, meta| {
+            let mut handle = ctx.find_with_meta(meta_version, meta, Some(query))?;
+            handle.step()?;
 
-        if !handle.has_row() {
-            return Ok(None);
-        }
+            if !handle.has_row() {
+                return Ok(None);
+            }
 
-        let result = handle.get().unwrap_document();
-        Ok(Some(result.clone()))
+            let result = handle.get().unwrap_document();
+            Ok(Some(result.clone()))
+        })
     }
 
     pub fn name(&self) -> &str {
@@ -126,7 +239,8 @@ impl<'a>  Collection<'a> {
     /// Return the size of all data in the collection.
     #[inline]
     pub fn count(&mut self) -> DbResult<u64> {
-        self.db.ctx.count(self.id, self.meta_version)
+        let meta_version = self.meta_version;
+        self.with_meta(|ctx, meta| ctx.count_with_meta(meta_version, meta))
     }
 
     /// When query is `None`, all the data in the collection will be updated.
@@ -140,17 +254,21 @@ impl<'a>  Collection<'a> {
     /// | $min | Only updates the field if the specified value is less than the existing field value. |
     /// | $max | Only updates the field if the specified value is greater than the existing field value. |
     /// | $mul | Multiplies the value of the field by the specified amount. |
+    /// | $push | Appends the value to the array field. |
+    /// | $pull | Removes all the elements equal to the value from the array field. |
     /// | $rename | Renames a field. |
     /// | $set | Sets the value of a field in a document. |
     /// | $unset | Removes the specified field from a document. |
     #[inline]
     pub fn update(&mut self, query: Option<&Document>, update: &Document) -> DbResult<usize> {
-        self.db.ctx.update(self.id, self.meta_version, query, update)
+        let (id, meta_version) = (self.id, self.meta_version);
+        self.with_meta(|ctx, meta| ctx.update_with_meta(id, meta_version, meta, query, update))
     }
 
     #[inline]
     pub fn insert(&mut self, doc: &mut Document) -> DbResult<bool> {
-        self.db.ctx.insert(self.id, self.meta_version, doc)
+        let (id, meta_version) = (self.id, self.meta_version);
+        self.with_meta(|ctx, meta| ctx.insert_with_meta(id, meta_version, meta, doc))
     }
 
     /// When query is `None`, all the data in the collection will be deleted.
@@ -158,12 +276,8 @@ impl<'a>  Collection<'a> {
     /// The size of data deleted returns.
     #[inline]
     pub fn delete(&mut self, query: Option<&Document>) -> DbResult<usize> {
-        match query {
-            Some(query) =>
-                self.db.ctx.delete(self.id, self.meta_version, query),
-            None =>
-                self.db.ctx.delete_all(self.id, self.meta_version),
-        }
+        let (id, meta_version) = (self.id, self.meta_version);
+        self.with_meta(|ctx, meta| ctx.delete_with_meta(id, meta_version, meta, query))
     }
 
     // // release in 0.2
@@ -215,6 +329,19 @@ pub struct Database {
 
 pub type DbResult<T> = Result<T, DbErr>;
 
+/// The result of [Database::update_upsert](./struct.Database.html#method.update_upsert).
+#[derive(Debug, Clone)]
+pub struct UpdateResult {
+    /// The size of the documents matching the filter.
+    pub matched: usize,
+
+    /// The size of the documents updated.
+    pub modified: usize,
+
+    /// The `_id` of the document inserted if nothing matches the filter.
+    pub upserted_id: Option<Value>,
+}
+
 impl Database {
 
     #[inline]
@@ -222,10 +349,54 @@ impl Database {
         self.ctx.object_id_maker().mk_object_id()
     }
 
+    /// Make a `Value::Timestamp` greater than all the ones made before since the database is opened,
+    /// see [DbContext::mk_timestamp](../struct.DbContext.html#method.mk_timestamp).
+    ///
+    /// It's useful as the `_id` of a change log.
+    #[inline]
+    pub fn mk_timestamp(&mut self) -> Value {
+        self.ctx.mk_timestamp()
+    }
+
     pub fn open<P: AsRef<Path>>(path: P) -> DbResult<Database>  {
         Database::open_with_config(path, Config::default())
     }
 
+    /// Open an existing database without writing anything to the disk,
+    /// no journal file is created and nothing is checkpointed when it's dropped.
+    ///
+    /// The writing operations fail with `DbErr::ReadOnly`,
+    /// so several processes can read the same file at the same time.
+    ///
+    /// The journal is read once when the database is opened,
+    /// the changes committed by other processes after that are not visible.
+    pub fn open_read_only<P: AsRef<Path>>(path: P) -> DbResult<Database>  {
+        let config = Config {
+            read_only: true,
+            ..Default::default()
+        };
+        Database::open_with_config(path, config)
+    }
+
+    /// Open a database in the memory, no file is created on the disk.
+    ///
+    /// The database behaves like the one opened from a file,
+    /// but all the data is lost when it's dropped.
+    pub fn open_memory() -> DbResult<Database> {
+        let ctx = DbContext::open_memory(Config::default())?;
+
+        Ok(Database {
+            ctx: Box::new(ctx),
+        })
+    }
+
+    /// Open the database which can be shared between threads,
+    /// see [SharedDatabase](../struct.SharedDatabase.html) for the guarantees.
+    pub fn open_shared<P: AsRef<Path>>(path: P) -> DbResult<Arc<SharedDatabase>> {
+        let db = Database::open(path)?;
+        Ok(Arc::new(SharedDatabase::new(db)))
+    }
+
     pub fn open_with_config<P: AsRef<Path>>(path: P, config: Config) -> DbResult<Database>  {
         let ctx = DbContext::new(path.as_ref(), config)?;
         let rc_ctx = Box::new(ctx);
@@ -235,7 +406,26 @@ impl Database {
         })
     }
 
-    pub fn create_collection(&mut self, name: &str) -> DbResult<Collection> {
+    /// The max size of an encoded document, see [Config](../struct.Config.html).
+    #[inline]
+    pub fn max_document_size(&self) -> usize {
+        self.ctx.max_document_size()
+    }
+
+    /// When the data is synced to the disk, see [Config](../struct.Config.html).
+    #[inline]
+    pub fn sync_policy(&self) -> SyncPolicy {
+        self.ctx.sync_policy()
+    }
+
+    /// Check if the collection named `name` exists.
+    pub fn exists_collection(&mut self, name: &str) -> DbResult<bool> {
+        self.ctx.exists_collection(name)
+    }
+
+    /// Create a collection named `name`,
+    /// `DbErr::CollectionAlreadyExists` returns if the name is used.
+    pub fn create_collection(&mut self, name: &str) -> DbResult<Collection<'_>> {
         let collection_meta = self.ctx.create_collection(name)?;
         Ok(Collection::new(self,
                            collection_meta.id,
@@ -243,6 +433,105 @@ impl Database {
                            name))
     }
 
+    /// Rename the collection named `old_name` to `new_name`,
+    /// the documents and the indexes are kept.
+    ///
+    /// `DbErr::CollectionNotFound` returns if `old_name` doesn't exist,
+    /// and `DbErr::CollectionAlreadyExists` returns if `new_name` is used.
+    pub fn rename_collection(&mut self, old_name: &str, new_name: &str) -> DbResult<()> {
+        self.ctx.rename_collection(old_name, new_name)
+    }
+
+    /// Delete the collection named `name`, all the pages belonging to
+    /// the collection are freed.
+    ///
+    /// Return `false` if the collection doesn't exist.
+    pub fn delete_collection(&mut self, name: &str) -> DbResult<bool> {
+        let meta = match self.ctx.get_collection_meta_by_name(name) {
+            Ok(meta) => meta,
+            Err(DbErr::CollectionNotFound(_)) => return Ok(false),
+            Err(err) => return Err(err),
+        };
+        self.ctx.as_mut().drop(meta.id, meta.meta_version)?;
+        Ok(true)
+    }
+
+    /// Create an index on the field `field` of the collection named `col_name`.
+    ///
+    /// The index is a B-tree mapping the value of the field to the `_id`s of the documents,
+    /// several documents can share the same value.
+    /// The existing documents are indexed immediately.
+    ///
+    /// The field can be a dotted path into the embedded documents, e.g. `"address.city"`.
+    ///
+    /// The values of the field must be one of the index types: String, Int, Double,
+    /// ObjectId, Boolean, Timestamp, otherwise `DbErr::NotAValidKeyType` returns.
+    /// The values of different types can be mixed, they are ordered by the types first.
+    /// The numbers are indexed by the numeric value, so `1` and `1.0` are the same entry.
+    pub fn create_index(&mut self, col_name: &str, field: &str) -> DbResult<()> {
+        let meta = self.ctx.get_collection_meta_by_name(col_name)?;
+
+        let mut keys = Document::new_without_id();
+        keys.insert(field.into(), Value::Int(1));
+
+        self.ctx.create_index(meta.id, &keys, None)
+    }
+
+    /// The same as [create_index](#method.create_index), but the values of the field
+    /// must be unique in the collection.
+    ///
+    /// Inserting or updating a document which would duplicate an existing value
+    /// fails with `DbErr::DuplicateKey`, and nothing is written.
+    /// If the collection already contains duplicated values, the index is not created
+    /// and `DbErr::DuplicateKey` returns with the duplicated value.
+    ///
+    /// The documents without the field are not indexed, so they are not checked.
+    pub fn create_unique_index(&mut self, col_name: &str, field: &str) -> DbResult<()> {
+        let meta = self.ctx.get_collection_meta_by_name(col_name)?;
+
+        let mut keys = Document::new_without_id();
+        keys.insert(field.into(), Value::Int(1));
+
+        let options = mk_document! {
+            "unique": true,
+        };
+
+        self.ctx.create_index(meta.id, &keys, Some(&options))
+    }
+
+    /// Create an index on several fields of the collection named `col_name`.
+    ///
+    /// The key of the index is the array of the values of the fields in order,
+    /// compared field by field. A missing field is stored as `Null`, which is less
+    /// than any other value, and the documents missing all the fields are not indexed.
+    ///
+    /// The index is used by the queries comparing the leading fields for equality,
+    /// e.g. the index on `["a", "b"]` is used by `{ a: 1 }` and `{ a: 1, b: 2 }`,
+    /// but not by `{ b: 2 }`.
+    ///
+    /// The name of the index is the fields joined by commas, e.g. "a,b",
+    /// use it to [drop](#method.drop_index) the index.
+    pub fn create_index_compound(&mut self, col_name: &str, fields: &[&str]) -> DbResult<()> {
+        let meta = self.ctx.get_collection_meta_by_name(col_name)?;
+        self.ctx.create_index_compound(meta.id, fields, None)
+    }
+
+    /// Drop the index on the field `field` of the collection named `col_name`,
+    /// the pages of the index are freed.
+    ///
+    /// `Ok(false)` returns if the index doesn't exist.
+    pub fn drop_index(&mut self, col_name: &str, field: &str) -> DbResult<bool> {
+        let meta = self.ctx.get_collection_meta_by_name(col_name)?;
+        self.ctx.drop_index(meta.id, meta.meta_version, field)
+    }
+
+    /// Return the names of the fields indexed in the collection named `col_name`,
+    /// sorted by the names.
+    pub fn list_indexes(&mut self, col_name: &str) -> DbResult<Vec<String>> {
+        let meta = self.ctx.get_collection_meta_by_name(col_name)?;
+        self.ctx.list_indexes(meta.id, meta.meta_version)
+    }
+
     /// Return the version of package version in string.
     /// Defined in `Cargo.toml`.
     #[inline]
@@ -256,7 +545,13 @@ impl Database {
     /// Return an exist collection. If the collection is not exists,
     /// a new collection will be created.
     ///
-    pub fn collection(&mut self, col_name: &str) -> DbResult<Collection> {
+    /// The name is resolved once, the handle keeps the id of the collection.
+    /// The meta of the collection, e.g. the root page and the indexes, is looked up by the first operation
+    /// and cached by the handle, it's kept up to date by the operations of the handle,
+    /// and looked up again after one of them fails.
+    /// It borrows the database, so the collection can't be dropped or recreated while it's alive,
+    /// the meta version kept is checked by every operation, the cache is never used after it's changed.
+    pub fn collection(&mut self, col_name: &str) -> DbResult<Collection<'_>> {
         let info = match self.ctx.get_collection_meta_by_name(col_name) {
             Ok(meta) => meta,
             Err(DbErr::CollectionNotFound(_)) => self.ctx.create_collection(col_name)?,
@@ -265,222 +560,3637 @@ impl Database {
         Ok(Collection::new(self, info.id, info.meta_version, col_name))
     }
 
-    #[inline]
-    pub fn dump(&mut self) -> DbResult<FullDump> {
-        self.ctx.dump()
+    /// Insert the document into the collection named `col_name`, the `_id` of it returns.
+    ///
+    /// An `ObjectId` is generated and written into `doc` if it has no `_id`,
+    /// a supplied `_id` is kept.
+    pub fn insert(&mut self, col_name: &str, doc: &mut Document) -> DbResult<Value> {
+        let meta = self.ctx.get_collection_meta_by_name(col_name)?;
+        self.ctx.insert(meta.id, meta.meta_version, doc)?;
+        Ok(doc.pkey_id().unwrap())
     }
 
-    /// Manually start a transaction. There are three types of transaction.
+    /// Insert the documents into the collection named `col_name`,
+    /// the collection is resolved only once for the whole batch.
     ///
-    /// - `None`: Auto transaction
-    /// - `Some(Transaction::Write)`: Write transaction
-    /// - `Some(Transaction::Read)`: Read transaction
+    /// An `ObjectId` is generated for the document without `_id`.
+    /// The `_id`s of the documents return in the same order as `docs`.
     ///
-    /// When you pass `None` to type parameter. The PoloDB will go into
-    /// auto mode. The PoloDB will go into read mode firstly, once the users
-    /// execute write operations(insert/update/delete), the DB will turn into
-    /// write mode.
-    #[inline]
-    pub fn start_transaction(&mut self, ty: Option<TransactionType>) -> DbResult<()> {
-        self.ctx.start_transaction(ty)
+    /// All the documents are inserted in one transaction.
+    /// If one of the insertions fails, the error returns and no document of the batch is kept,
+    /// unless the batch is in a transaction started by the user,
+    /// in which case the documents inserted before are kept until the transaction is rolled back.
+    pub fn insert_many(&mut self, col_name: &str, docs: Vec<Rc<Document>>) -> DbResult<Vec<Value>> {
+        let meta = self.ctx.get_collection_meta_by_name(col_name)?;
+        self.ctx.insert_many(meta.id, meta.meta_version, docs)
     }
 
+    /// Find the documents in the collection named `col_name` which
+    /// match every field of the `filter`. An empty filter returns all the documents.
+    ///
+    /// If all the fields of the `filter` are equality comparisons and one of them
+    /// is indexed by [create_index](#method.create_index), the index is used
+    /// instead of scanning the whole collection.
+    /// Use [explain](#method.explain) to check the strategy chosen.
+    ///
+    /// Unlike [collection], this method would not create the collection,
+    /// `DbErr::CollectionNotFound` returns if the collection doesn't exist.
     #[inline]
-    pub fn commit(&mut self) -> DbResult<()> {
-        self.ctx.commit()
+    pub fn find(&mut self, col_name: &str, filter: &Document) -> DbResult<Vec<Rc<Document>>> {
+        self.find_cancellable(col_name, filter, &OperationOptions::default())
     }
 
-    #[inline]
-    pub fn rollback(&mut self) -> DbResult<()> {
-        self.ctx.rollback()
+    /// Like [find](#method.find), but fails with `DbErr::Cancelled` when the `options`
+    /// are triggered while scanning the collection or fetching the documents found by the index, see [OperationOptions](../struct.OperationOptions.html).
+    pub fn find_cancellable(&mut self, col_name: &str, filter: &Document, options: &OperationOptions) -> DbResult<Vec<Rc<Document>>> {
+        let meta = self.ctx.get_collection_meta_by_name(col_name)?;
+        if let Some(result) = self.ctx.find_by_index_cancellable(meta.id, meta.meta_version, filter, options)? {
+            return Ok(result);
+        }
+
+        let mut handle = self.ctx.find(meta.id, meta.meta_version, Some(filter))?;
+        handle.set_options(*options);
+
+        let mut result = Vec::new();
+
+        consume_handle_to_vec(&mut handle, &mut result)?;
+
+        Ok(result)
     }
 
-    #[allow(dead_code)]
-    pub(crate) fn query_all_meta(&mut self) -> DbResult<Vec<Rc<Document>>> {
-        self.ctx.query_all_meta()
+    /// Like [find](#method.find), but the documents are returned lazily by a cursor,
+    /// the filter is applied while walking the collection, the indexes are not used.
+    ///
+    /// ```rust
+    /// use polodb_core::Database;
+    /// use polodb_bson::mk_document;
+    ///
+    /// let mut db = Database::open_memory().unwrap();
+    /// db.create_collection("test").unwrap();
+    /// let first_two: Vec<_> = db.query("test", &mk_document! {}).unwrap()
+    ///     .take(2)
+    ///     .collect::<Result<_, _>>()
+    ///     .unwrap();
+    /// assert!(first_two.is_empty());
+    /// ```
+    pub fn query(&mut self, col_name: &str, filter: &Document) -> DbResult<QueryCursor<'_>> {
+        let meta = self.ctx.get_collection_meta_by_name(col_name)?;
+        let handle = self.ctx.find(meta.id, meta.meta_version, Some(filter))?;
+        Ok(QueryCursor {
+            handle,
+            finished: false,
+            is_full_scan: filter.is_empty(),
+        })
     }
 
-}
+    /// Return the strategy [find](#method.find) uses for the `filter`,
+    /// the primary key, an index, or scanning the whole collection.
+    /// The documents are not read.
+    pub fn explain(&mut self, col_name: &str, filter: &Document) -> DbResult<QueryPlan> {
+        let meta = self.ctx.get_collection_meta_by_name(col_name)?;
+        self.ctx.explain(meta.id, meta.meta_version, filter)
+    }
 
-#[cfg(test)]
-mod tests {
-    use std::rc::Rc;
-    use std::env;
-    use polodb_bson::{Document, Value, mk_document};
-    use crate::{Database, Config};
+    /// The same as [find](#method.find), but the first `skip` matched
+    /// documents are skipped, and at most `limit` documents return.
+    ///
+    /// The skipped documents are not collected. `Some(0)` as `limit` returns
+    /// an empty result, `None` means no limit.
+    pub fn find_with_options(&mut self, col_name: &str, filter: &Document, skip: usize, limit: Option<usize>) -> DbResult<Vec<Rc<Document>>> {
+        let meta = self.ctx.get_collection_meta_by_name(col_name)?;
 
-    static TEST_SIZE: usize = 1000;
+        let mut result = Vec::new();
+        if limit == Some(0) {
+            return Ok(result);
+        }
 
-    fn prepare_db_with_config(db_name: &str, config: Config) -> Database {
-        let mut db_path = env::temp_dir();
-        let mut journal_path = env::temp_dir();
+        let mut handle = self.ctx.find(meta.id, meta.meta_version, Some(filter))?;
+        let mut skipped: usize = 0;
 
-        let db_filename = String::from(db_name) + ".db";
-        let journal_filename = String::from(db_name) + ".db.journal";
+        handle.step()?;
 
-        db_path.push(db_filename);
-        journal_path.push(journal_filename);
+        while handle.has_row() {
+            if skipped < skip {
+                skipped += 1;
+            } else {
+                let doc = handle.get().unwrap_document();
+                result.push(doc.clone());
 
-        let _ = std::fs::remove_file(db_path.as_path());
-        let _ = std::fs::remove_file(journal_path);
+                if Some(result.len()) == limit {
+                    break;
+                }
+            }
 
-        Database::open_with_config(db_path.as_path().to_str().unwrap(), config).unwrap()
-    }
+            handle.step()?;
+        }
 
-    fn prepare_db(db_name: &str) -> Database {
-        prepare_db_with_config(db_name, Config::default())
+        Ok(result)
     }
 
-    fn create_and_return_db_with_items(db_name: &str, size: usize) -> Database {
-        let mut db = prepare_db(db_name);
-        let mut collection = db.create_collection("test").unwrap();
+    /// Find the documents in the collection named `col_name` which match the `filter`,
+    /// only the fields listed in `project` are returned.
+    ///
+    /// The `_id` is always returned even if it's not listed, the same as MongoDB.
+    /// If `project` is `None`, the whole documents are returned as [find](#method.find).
+    pub fn find_with_projection(&mut self, col_name: &str, filter: &Document, project: Option<&[&str]>) -> DbResult<Vec<Rc<Document>>> {
+        let fields = match project {
+            Some(fields) => fields,
+            None => return self.find(col_name, filter),
+        };
 
-        // let meta = db.query_all_meta().unwrap();
+        let meta = self.ctx.get_collection_meta_by_name(col_name)?;
+        let mut handle = self.ctx.find(meta.id, meta.meta_version, Some(filter))?;
 
-        for i in 0..size {
-            let content = i.to_string();
-            let mut new_doc = mk_document! {
-                "content": content,
-            };
-            collection.insert(&mut new_doc).unwrap();
+        let mut result = Vec::new();
+
+        handle.step()?;
+
+        while handle.has_row() {
+            let doc = handle.get().unwrap_document();
+            result.push(Rc::new(query::project_document(doc, fields)));
+
+            handle.step()?;
         }
 
-        db
+        Ok(result)
     }
 
-    #[test]
-    fn test_create_collection_and_find_all() {
-        let mut db = create_and_return_db_with_items("test-collection", TEST_SIZE);
+    /// Find the documents in the collection named `col_name` which match the `filter`,
+    /// sorted by the field `sort_field`.
+    ///
+    /// The documents missing the field are treated as the lowest.
+    /// The values of different types are ordered by the type:
+    /// Null < Numbers < String < Document < Array < Binary < ObjectId < Boolean < DateTime,
+    /// the same as MongoDB.
+    ///
+    /// All the matched documents are collected into the memory before sorting.
+    pub fn find_sorted(&mut self, col_name: &str, filter: &Document, sort_field: &str, ascending: bool) -> DbResult<Vec<Rc<Document>>> {
+        let mut result = self.find(col_name, filter)?;
 
-        let mut test_collection = db.collection("test").unwrap();
-        let all = test_collection.find_all( ).unwrap();
+        // stable sort, the documents with the equal keys keep the order of the primary keys
+        result.sort_by(|doc1, doc2| {
+            let ord = query::doc_field_cmp(doc1, doc2, sort_field);
+            if ascending {
+                ord
+            } else {
+                ord.reverse()
+            }
+        });
 
-        let second = test_collection.find_one(&mk_document! {
-            "content": "1",
-        }).unwrap().unwrap();
-        assert_eq!(second.get("content").unwrap().unwrap_string(), "1");
-        assert!(second.get("content").is_some());
+        Ok(result)
+    }
 
-        assert_eq!(TEST_SIZE, all.len())
+    /// Find the document whose `_id` is `id` in the collection named `col_name`.
+    ///
+    /// The B-tree of the collection is descended by the order of the primary keys,
+    /// so only the pages on the path are read, instead of scanning the whole collection.
+    ///
+    /// `Ok(None)` returns if the document is not found.
+    pub fn find_by_id(&mut self, col_name: &str, id: &Value) -> DbResult<Option<Rc<Document>>> {
+        let meta = self.ctx.get_collection_meta_by_name(col_name)?;
+        self.ctx.find_by_pkey(meta.id, meta.meta_version, id)
     }
 
-    #[test]
-    fn test_transaction_commit() {
-        let mut db = prepare_db("test-transaction");
-        db.start_transaction(None).unwrap();
-        let mut collection = db.create_collection("test").unwrap();
+    /// Return the entries of the oplog after the timestamp `since`, in the order of the changes,
+    /// the oplog is enabled by [Config::oplog](../struct.Config.html#structfield.oplog).
+    ///
+    /// Pass `Value::Null` to read all the entries, then the `_id` of the last entry returned
+    /// to read the new ones. See [OPLOG_COLLECTION_NAME](../constant.OPLOG_COLLECTION_NAME.html)
+    /// for the fields of an entry.
+    pub fn tail_oplog(&mut self, since: Value) -> DbResult<Vec<Document>> {
+        let entries = self.ctx.tail_oplog(&since)?;
+        Ok(entries.into_iter().map(|doc| Rc::try_unwrap(doc).unwrap_or_else(|doc| doc.as_ref().clone())).collect())
+    }
 
-        for i in 0..10{
-            let content = i.to_string();
-            let mut new_doc = mk_document! {
-                    "_id": i,
-                    "content": content,
-                };
-            collection.insert(&mut new_doc).unwrap();
-        }
-        db.commit().unwrap()
+    /// Return the document with the smallest `_id` in the collection named `col_name`,
+    /// e.g. the earliest one if the ids are increasing.
+    ///
+    /// Only the pages on the path to the left most leaf of the B-tree are read.
+    /// `Ok(None)` returns if the collection is empty.
+    pub fn first(&mut self, col_name: &str) -> DbResult<Option<Rc<Document>>> {
+        let meta = self.ctx.get_collection_meta_by_name(col_name)?;
+        self.ctx.find_first_or_last(meta.id, meta.meta_version, false)
     }
 
-    #[test]
-    fn test_commit_after_commit() {
-        let mut config = Config::default();
-        config.journal_full_size = 1;
-        let mut db = prepare_db_with_config("test-commit-2", config);
-        db.start_transaction(None).unwrap();
-        let mut collection = db.create_collection("test").unwrap();
+    /// Return the document with the greatest `_id` in the collection named `col_name`,
+    /// see [first](#method.first).
+    pub fn last(&mut self, col_name: &str) -> DbResult<Option<Rc<Document>>> {
+        let meta = self.ctx.get_collection_meta_by_name(col_name)?;
+        self.ctx.find_first_or_last(meta.id, meta.meta_version, true)
+    }
 
-        for i in 0..1000 {
-            let content = i.to_string();
-            let mut new_doc = mk_document! {
-                "_id": i,
-                "content": content,
-            };
-            collection.insert(&mut new_doc).unwrap();
+    /// Return the first document in the collection named `col_name` which
+    /// matches the `filter`, the same rules as [find](#method.find) are used.
+    ///
+    /// `Ok(None)` returns if nothing matches,
+    /// `DbErr::CollectionNotFound` returns if the collection doesn't exist.
+    pub fn find_one(&mut self, col_name: &str, filter: &Document) -> DbResult<Option<Rc<Document>>> {
+        let meta = self.ctx.get_collection_meta_by_name(col_name)?;
+        let mut handle = self.ctx.find(meta.id, meta.meta_version, Some(filter))?;
+        handle.step()?;
+
+        if !handle.has_row() {
+            return Ok(None);
         }
-        db.commit().unwrap();
 
-        db.start_transaction(None).unwrap();
-        let mut collection2 = db.create_collection("test-2").unwrap();
-        for i in 0..10{
-            let content = i.to_string();
+        let result = handle.get().unwrap_document();
+        Ok(Some(result.clone()))
+    }
+
+    /// Return the count of the documents in the collection named `col_name`
+    /// which match the `filter`, the documents are not collected.
+    #[inline]
+    pub fn count_documents(&mut self, col_name: &str, filter: &Document) -> DbResult<u64> {
+        self.count_documents_cancellable(col_name, filter, &OperationOptions::default())
+    }
+
+    /// Like [count_documents](#method.count_documents), but fails with `DbErr::Cancelled` when the `options`
+    /// are triggered while scanning the collection or fetching the documents found by the index, see [OperationOptions](../struct.OperationOptions.html).
+    pub fn count_documents_cancellable(&mut self, col_name: &str, filter: &Document, options: &OperationOptions) -> DbResult<u64> {
+        let meta = self.ctx.get_collection_meta_by_name(col_name)?;
+
+        // the count of all the documents is cached in the meta
+        if filter.is_empty() {
+            return self.ctx.count(meta.id, meta.meta_version);
+        }
+
+        let mut handle = self.ctx.find(meta.id, meta.meta_version, Some(filter))?;
+        handle.set_options(*options);
+        let mut count: u64 = 0;
+
+        handle.step()?;
+
+        while handle.has_row() {
+            count += 1;
+
+            handle.step()?;
+        }
+
+        Ok(count)
+    }
+
+    /// Return the distinct values of the field `field` in the documents of the
+    /// collection named `col_name` which match the `filter`, in ascending order.
+    ///
+    /// The field can be a dotted path. The documents missing the field are ignored,
+    /// the elements of an array are counted as separate values, the same as MongoDB.
+    /// The values are compared by the order of [Value](../polodb_bson/enum.Value.html),
+    /// so `1` and `1.0` are the same value.
+    pub fn distinct(&mut self, col_name: &str, field: &str, filter: &Document) -> DbResult<Vec<Value>> {
+        let docs = self.find(col_name, filter)?;
+
+        let mut values = BTreeSet::new();
+        for doc in &docs {
+            match doc.get_path(field) {
+                Some(Value::Array(arr)) => {
+                    values.extend(arr.iter().cloned());
+                }
+                Some(value) => {
+                    values.insert(value.clone());
+                }
+                None => (),
+            }
+        }
+
+        Ok(values.into_iter().collect())
+    }
+
+    /// Group the documents in the collection named `col_name` by the value of the field
+    /// `group_field`, and return the count of the documents of each group,
+    /// ordered by the group value.
+    ///
+    /// The documents missing the field are counted in the group of `Null`.
+    /// The collection is walked once, the documents are not collected.
+    pub fn group_count(&mut self, col_name: &str, group_field: &str) -> DbResult<Vec<(Value, u64)>> {
+        let mut groups: BTreeMap<Value, u64> = BTreeMap::new();
+
+        self.for_each_group(col_name, group_field, |key, _doc| {
+            *groups.entry(key).or_insert(0) += 1;
+        })?;
+
+        Ok(groups.into_iter().collect())
+    }
+
+    /// Group the documents as [group_count](#method.group_count),
+    /// and return the sum of the field `sum_field` of each group.
+    ///
+    /// The values which are not numbers are ignored, the same as `$sum` of MongoDB.
+    /// The sum is an `Int` if all the values are `Int`, otherwise a `Double`.
+    pub fn group_sum(&mut self, col_name: &str, group_field: &str, sum_field: &str) -> DbResult<Vec<(Value, Value)>> {
+        let mut groups: BTreeMap<Value, Value> = BTreeMap::new();
+
+        self.for_each_group(col_name, group_field, |key, doc| {
+            let sum = groups.entry(key).or_insert(Value::Int(0));
+            let new_sum = match (&*sum, doc.get_path(sum_field)) {
+                (Value::Int(a), Some(Value::Int(b))) => match a.checked_add(*b) {
+                    Some(result) => Value::Int(result),
+                    None => Value::Double(*a as f64 + *b as f64),
+                },
+                (Value::Int(a), Some(Value::Double(b))) => Value::Double(*a as f64 + b),
+                (Value::Double(a), Some(Value::Int(b))) => Value::Double(a + *b as f64),
+                (Value::Double(a), Some(Value::Double(b))) => Value::Double(a + b),
+                _ => return,
+            };
+            *sum = new_sum;
+        })?;
+
+        Ok(groups.into_iter().collect())
+    }
+
+    fn for_each_group<F>(&mut self, col_name: &str, group_field: &str, mut f: F) -> DbResult<()>
+        where F: FnMut(Value, &Document)
+    {
+        let meta = self.ctx.get_collection_meta_by_name(col_name)?;
+        let mut handle = self.ctx.find(meta.id, meta.meta_version, None)?;
+
+        handle.step()?;
+
+        while handle.has_row() {
+            let doc = handle.get().unwrap_document();
+            let key = doc.get_path(group_field).cloned().unwrap_or(Value::Null);
+            f(key, doc);
+
+            handle.step()?;
+        }
+
+        Ok(())
+    }
+
+    /// Return the count of all the documents in the collection named `col_name`
+    /// cached in the meta of the collection, only the meta record is read.
+    ///
+    /// The count is updated in the same transaction as the documents, but it may be stale
+    /// if the bookkeeping was broken before, use [recount](#method.recount) to fix it.
+    /// `None` returns if the collection is created by an old version without the count,
+    /// use [count_documents](#method.count_documents) instead.
+    pub fn estimated_document_count(&mut self, col_name: &str) -> DbResult<Option<u64>> {
+        let meta = self.ctx.get_collection_meta_by_name(col_name)?;
+        self.ctx.estimated_count(meta.id, meta.meta_version)
+    }
+
+    /// Recount the documents of the collection named `col_name` by a full scan,
+    /// and replace the count cached in the meta.
+    ///
+    /// The count is updated with the documents in the same transaction,
+    /// it's only needed if the cached count is suspected wrong.
+    pub fn recount(&mut self, col_name: &str) -> DbResult<u64> {
+        let meta = self.ctx.get_collection_meta_by_name(col_name)?;
+        self.ctx.recount(meta.id, meta.meta_version)
+    }
+
+    /// Delete all the documents in the collection named `col_name` which
+    /// match the `filter`. The pages freed by the B-tree are returned to the free list.
+    ///
+    /// The size of data deleted returns.
+    pub fn delete(&mut self, col_name: &str, filter: &Document) -> DbResult<usize> {
+        let meta = self.ctx.get_collection_meta_by_name(col_name)?;
+        self.ctx.delete(meta.id, meta.meta_version, filter)
+    }
+
+    /// Update all the documents in the collection named `col_name` which
+    /// match the `filter`. The update operators are the same as
+    /// [Collection::update](./struct.Collection.html#method.update), e.g. `$set`.
+    ///
+    /// The updated document is re-written to the data pages, it will be
+    /// moved to another page if it doesn't fit in the original one.
+    ///
+    /// The size of data modified returns.
+    pub fn update(&mut self, col_name: &str, filter: &Document, update: &Document) -> DbResult<usize> {
+        let meta = self.ctx.get_collection_meta_by_name(col_name)?;
+        self.ctx.update(meta.id, meta.meta_version, Some(filter), update)
+    }
+
+    /// The same as [update](#method.update), but a new document is inserted
+    /// if nothing matches the `filter`.
+    ///
+    /// The new document is made of the equality fields of the `filter` and
+    /// the fields of `$set`. An `ObjectId` is generated if no `_id` is supplied.
+    ///
+    /// Every matched document is written back, so `matched` equals to `modified`.
+    pub fn update_upsert(&mut self, col_name: &str, filter: &Document, update: &Document) -> DbResult<UpdateResult> {
+        let meta = self.ctx.get_collection_meta_by_name(col_name)?;
+        let (updated, upserted_id) = self.ctx.update_upsert(meta.id, meta.meta_version, filter, update)?;
+        Ok(UpdateResult {
+            matched: updated,
+            modified: updated,
+            upserted_id,
+        })
+    }
+
+    /// Update the first document matching the `filter` in the collection named `col_name`
+    /// with the operators of `update`, and return it in one write transaction,
+    /// no other writer can change the document in between. e.g. to claim a task in a queue.
+    ///
+    /// The document after updating returns if `return_new` is true, otherwise the one before.
+    /// `Ok(None)` returns if nothing matches.
+    pub fn find_and_modify(&mut self, col_name: &str, filter: &Document, update: &Document,
+                           return_new: bool) -> DbResult<Option<Rc<Document>>> {
+        let meta = self.ctx.get_collection_meta_by_name(col_name)?;
+        self.ctx.find_and_modify(meta.id, meta.meta_version, filter, update, return_new)
+    }
+
+    /// Replace the first document matching the `filter` in the collection named `col_name`
+    /// with `replacement` as a whole, instead of updating the fields.
+    ///
+    /// The `_id` of the matched document is kept, the `_id` in `replacement` is ignored.
+    /// Return `false` if nothing matches.
+    pub fn replace_one(&mut self, col_name: &str, filter: &Document, replacement: Rc<Document>) -> DbResult<bool> {
+        let meta = self.ctx.get_collection_meta_by_name(col_name)?;
+        self.ctx.replace_one(meta.id, meta.meta_version, filter, &replacement)
+    }
+
+    /// Apply the operations to the collection named `col_name` in one write transaction.
+    ///
+    /// If `ordered` is true, the batch stops at the first error, and all the operations are rolled back.
+    /// Otherwise the failed operations are skipped and reported in `errors`, the others are committed,
+    /// but a failed operation may leave the changes it made before the error,
+    /// the same as the operations in a transaction started by the user.
+    ///
+    /// It's an error if another transaction has been started.
+    pub fn bulk_write(&mut self, col_name: &str, ops: Vec<WriteOp>, ordered: bool) -> DbResult<BulkResult> {
+        let meta = self.ctx.get_collection_meta_by_name(col_name)?;
+
+        // rolled back if it's dropped without committing
+        let mut txn = self.transaction()?;
+        let mut result = BulkResult::default();
+
+        for (index, op) in ops.into_iter().enumerate() {
+            if let Err(err) = txn.apply_write_op(meta.id, meta.meta_version, op, &mut result) {
+                if ordered {
+                    return Err(err);
+                }
+                result.errors.push((index, err));
+            }
+        }
+
+        txn.commit()?;
+
+        Ok(result)
+    }
+
+    fn apply_write_op(&mut self, col_id: u32, meta_version: u32, op: WriteOp, result: &mut BulkResult) -> DbResult<()> {
+        match op {
+            WriteOp::Insert(mut doc) => {
+                self.ctx.insert(col_id, meta_version, &mut doc)?;
+                result.inserted_ids.push(doc.pkey_id().unwrap());
+            }
+
+            WriteOp::Update { filter, update } => {
+                result.modified += self.ctx.update(col_id, meta_version, Some(&filter), &update)?;
+            }
+
+            WriteOp::Delete { filter } => {
+                result.deleted += self.ctx.delete(col_id, meta_version, &filter)?;
+            }
+
+        }
+
+        Ok(())
+    }
+
+    /// Write the documents of the collection named `col_name` in the standard BSON format,
+    /// one after another. Every document starts with its length, the same as the files of `mongodump`.
+    pub fn dump_collection<W: Write>(&mut self, col_name: &str, mut writer: W) -> DbResult<()> {
+        let meta = self.ctx.get_collection_meta_by_name(col_name)?;
+        let mut handle = self.ctx.find(meta.id, meta.meta_version, None)?;
+
+        handle.step()?;
+        while handle.has_row() {
+            let doc = handle.get().unwrap_document();
+            writer.write_all(&doc.to_standard_bytes()?)?;
+
+            handle.step()?;
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Insert the documents written by [dump_collection](#method.dump_collection)
+    /// into the collection named `col_name`, the collection is created if it doesn't exist.
+    ///
+    /// All the documents are inserted in one transaction, nothing is inserted if one of them fails.
+    /// The count of the documents inserted returns.
+    pub fn import_collection<R: Read>(&mut self, col_name: &str, mut reader: R) -> DbResult<usize> {
+        let mut docs = Vec::new();
+        let mut len_buffer: [u8; 4] = [0; 4];
+        while read_bson_len(&mut reader, &mut len_buffer)? {
+            let len = i32::from_le_bytes(len_buffer);
+            if len < 5 {
+                return Err(DbErr::ParseError(format!("invalid length of BSON document: {}", len)));
+            }
+            let len = len as usize;
+            if len > self.max_document_size() {
+                return Err(DbErr::DocumentTooLarge { size: len, max: self.max_document_size() });
+            }
+
+            let mut bytes = vec![0; len];
+            bytes[0..4].copy_from_slice(&len_buffer);
+            reader.read_exact(&mut bytes[4..])?;
+            docs.push(Rc::new(Document::from_standard_bytes(&bytes)?));
+        }
+
+        let meta = match self.ctx.get_collection_meta_by_name(col_name) {
+            Ok(meta) => meta,
+            Err(DbErr::CollectionNotFound(_)) => self.ctx.create_collection(col_name)?,
+            Err(err) => return Err(err),
+        };
+        let count = docs.len();
+        self.ctx.insert_many(meta.id, meta.meta_version, docs)?;
+
+        Ok(count)
+    }
+
+    #[inline]
+    pub fn dump(&mut self) -> DbResult<FullDump> {
+        self.ctx.dump()
+    }
+
+    /// Write the committed pages of the journal into the database file, and truncate the journal.
+    ///
+    /// The database file is synced to the disk (`fsync`) before truncating the journal,
+    /// so the data is durable even if the system crashes after it returns.
+    /// The journal is also checkpointed when it's full and when the database is dropped.
+    ///
+    /// It's an error to checkpoint in a transaction, commit or rollback it before.
+    /// `DbErr::Busy` returns while a [Snapshot](../struct.Snapshot.html) is alive.
+    #[inline]
+    pub fn checkpoint(&mut self) -> DbResult<()> {
+        self.ctx.checkpoint()
+    }
+
+    /// Copy the whole database into a new file at `path`, which can be opened directly,
+    /// see [DbContext::backup_to](../struct.DbContext.html#method.backup_to).
+    ///
+    /// The backup is consistent with the last commit, the writes are blocked until it's finished.
+    /// It's an error to backup in a transaction.
+    #[inline]
+    pub fn backup_to<P: AsRef<Path>>(&mut self, path: P) -> DbResult<()> {
+        self.ctx.backup_to(path.as_ref())
+    }
+
+    /// Rewrite the live data into a new file to reclaim the space of the deleted data,
+    /// see [DbContext::compact](../struct.DbContext.html#method.compact).
+    ///
+    /// It's an error to compact in a transaction.
+    /// `DbErr::Busy` returns while a [Snapshot](../struct.Snapshot.html) is alive.
+    #[inline]
+    pub fn compact(&mut self) -> DbResult<()> {
+        self.ctx.compact()
+    }
+
+    /// Like [compact](#method.compact), but fails with `DbErr::Cancelled` when the `options`
+    /// are triggered while copying the documents, the database is left unchanged.
+    #[inline]
+    pub fn compact_cancellable(&mut self, options: &OperationOptions) -> DbResult<()> {
+        self.ctx.compact_cancellable(options)
+    }
+
+    /// Remove all the documents in the collection named `col_name`, and return the count of them.
+    ///
+    /// Unlike [drop](./struct.Collection.html#method.drop), the collection and the definitions
+    /// of its indexes are kept. The pages are freed at once instead of deleting the documents
+    /// one by one, see [DbContext::truncate_collection](../struct.DbContext.html#method.truncate_collection).
+    pub fn truncate_collection(&mut self, col_name: &str) -> DbResult<u64> {
+        let meta = self.ctx.get_collection_meta_by_name(col_name)?;
+        self.ctx.truncate_collection(meta.id, meta.meta_version)
+    }
+
+    /// Rebuild the pages of the collection named `col_name` and its indexes,
+    /// see [DbContext::compact_collection](../struct.DbContext.html#method.compact_collection).
+    ///
+    /// It's lighter than [compact](#method.compact), the file isn't shrunk,
+    /// but the old pages are freed and reused by the later writes.
+    pub fn compact_collection(&mut self, col_name: &str) -> DbResult<()> {
+        let meta = self.ctx.get_collection_meta_by_name(col_name)?;
+        self.ctx.compact_collection(meta.id, meta.meta_version)
+    }
+
+    /// Manually start a transaction. There are three types of transaction.
+    ///
+    /// - `None`: Auto transaction
+    /// - `Some(Transaction::Write)`: Write transaction
+    /// - `Some(Transaction::Read)`: Read transaction
+    ///
+    /// When you pass `None` to type parameter. The PoloDB will go into
+    /// auto mode. The PoloDB will go into read mode firstly, once the users
+    /// execute write operations(insert/update/delete), the DB will turn into
+    /// write mode.
+    #[inline]
+    pub fn start_transaction(&mut self, ty: Option<TransactionType>) -> DbResult<()> {
+        self.ctx.start_transaction(ty)
+    }
+
+    /// Start a write transaction, the returned guard is used to execute the operations.
+    /// See [Transaction](../struct.Transaction.html) for the guarantees.
+    ///
+    /// It's an error if another transaction has been started.
+    #[inline]
+    pub fn transaction(&mut self) -> DbResult<Transaction<'_>> {
+        Transaction::new(self)
+    }
+
+    /// Pin the committed state of the database, all the reads through the returned snapshot
+    /// see it, the writes after it are not seen. See [Snapshot](../struct.Snapshot.html) for the cost.
+    ///
+    /// The transaction in progress is not included.
+    #[inline]
+    pub fn snapshot(&mut self) -> DbResult<Snapshot> {
+        Ok(Snapshot::new(self.ctx.pin_read_view()))
+    }
+
+    // the reads of `f` see the state of the view
+    pub(crate) fn with_read_view<T, F>(&mut self, view: &Arc<ReadView>, f: F) -> DbResult<T> where
+        F: FnOnce(&mut Database) -> DbResult<T> {
+        self.ctx.start_read_view(view)?;
+        let result = f(self);
+        let finish_result = self.ctx.finish_read_view();
+        let value = result?;
+        finish_result?;
+        Ok(value)
+    }
+
+    /// The same as [query](#method.query) in the view.
+    pub(crate) fn query_in_view(&mut self, view: &Arc<ReadView>, col_name: &str, filter: &Document) -> DbResult<QueryCursor<'_>> {
+        let handle = self.ctx.find_in_view(view, col_name, filter)?;
+        Ok(QueryCursor {
+            handle,
+            finished: false,
+            is_full_scan: filter.is_empty(),
+        })
+    }
+
+    #[inline]
+    pub fn commit(&mut self) -> DbResult<()> {
+        self.ctx.commit()
+    }
+
+    #[inline]
+    pub fn rollback(&mut self) -> DbResult<()> {
+        self.ctx.rollback()
+    }
+
+    /// Set the max count of pages kept in memory, the cached pages are dropped.
+    /// The default capacity is 1024 pages, passing 0 disables the cache.
+    #[inline]
+    pub fn set_page_cache_capacity(&mut self, pages: usize) {
+        self.ctx.set_page_cache_capacity(pages)
+    }
+
+    /// Return the hits and misses of the page cache,
+    /// use it to tune the [capacity](#method.set_page_cache_capacity).
+    #[inline]
+    pub fn page_cache_stats(&self) -> PageCacheStats {
+        self.ctx.page_cache_stats()
+    }
+
+    /// Return the counters of the pages read, written and allocated,
+    /// use it to check the read amplification of the queries.
+    #[inline]
+    pub fn page_metrics(&self) -> PageMetrics {
+        self.ctx.page_metrics()
+    }
+
+    /// Reset all the counters of [page_metrics](#method.page_metrics) to zero,
+    /// including the hits and misses of [page_cache_stats](#method.page_cache_stats).
+    #[inline]
+    pub fn reset_page_metrics(&mut self) {
+        self.ctx.reset_page_metrics()
+    }
+
+    /// Return the size in bytes of the pages used by the collection named `col_name`:
+    /// the nodes of its B-tree, the data pages of the documents and the overflow pages
+    /// of the large documents. The pages of the indexes are not counted.
+    ///
+    /// A data page can be shared by several collections, so the sizes of
+    /// the collections may add up to more than the size of the file.
+    /// The B-tree is walked every time, the size is not cached.
+    pub fn collection_size_bytes(&mut self, col_name: &str) -> DbResult<u64> {
+        let meta = self.ctx.get_collection_meta_by_name(col_name)?;
+        self.ctx.collection_size_bytes(meta.id, meta.meta_version)
+    }
+
+    /// Return the count of the pages and the documents,
+    /// the free list and all the collections are walked.
+    #[inline]
+    pub fn stats(&mut self) -> DbResult<DbStats> {
+        self.ctx.stats()
+    }
+
+    /// Walk all the B-trees of the database, and check the order of the keys,
+    /// the page ids, the depth of the leaves and the counts of the collections.
+    ///
+    /// The problems found return, an empty list means the database is consistent.
+    #[inline]
+    pub fn verify_integrity(&mut self) -> DbResult<Vec<String>> {
+        self.ctx.verify_integrity()
+    }
+
+    /// Return the names of all the collections in the database, sorted.
+    pub fn list_collections(&mut self) -> DbResult<Vec<String>> {
+        let meta_docs = self.ctx.query_all_meta()?;
+
+        let mut result: Vec<String> = meta_docs
+            .iter()
+            .map(|doc| MetaDocEntry::from_doc(doc.clone()).name().into())
+            .collect();
+        result.sort();
+
+        Ok(result)
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn query_all_meta(&mut self) -> DbResult<Vec<Rc<Document>>> {
+        self.ctx.query_all_meta()
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use std::env;
+    use std::time::{Duration, Instant};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use polodb_bson::{Document, Value, Array, UTCDateTime, mk_document, mk_array};
+    use crate::{Database, Config, SyncPolicy, JournalMode, DbErr, TransactionType, WriteOp, QueryPlan, OperationOptions, OPLOG_COLLECTION_NAME};
+    use crate::journal::JournalManager;
+    use crate::storage::Storage;
+
+    static TEST_SIZE: usize = 1000;
+
+    fn prepare_db_with_config(db_name: &str, config: Config) -> Database {
+        let mut db_path = env::temp_dir();
+        let mut journal_path = env::temp_dir();
+
+        let db_filename = String::from(db_name) + ".db";
+        let journal_filename = String::from(db_name) + ".db.journal";
+
+        db_path.push(db_filename);
+        journal_path.push(journal_filename);
+
+        let _ = std::fs::remove_file(db_path.as_path());
+        let _ = std::fs::remove_file(journal_path);
+
+        Database::open_with_config(db_path.as_path().to_str().unwrap(), config).unwrap()
+    }
+
+    fn prepare_db(db_name: &str) -> Database {
+        prepare_db_with_config(db_name, Config::default())
+    }
+
+    fn create_and_return_db_with_items(db_name: &str, size: usize) -> Database {
+        let mut db = prepare_db(db_name);
+        let mut collection = db.create_collection("test").unwrap();
+
+        // let meta = db.query_all_meta().unwrap();
+
+        for i in 0..size {
+            let content = i.to_string();
             let mut new_doc = mk_document! {
-                "_id": i,
                 "content": content,
             };
-            collection2.insert(&mut new_doc).expect(&*format!("insert failed: {}", i));
+            collection.insert(&mut new_doc).unwrap();
+        }
+
+        db
+    }
+
+    #[test]
+    fn test_create_collection_and_find_all() {
+        let mut db = create_and_return_db_with_items("test-collection", TEST_SIZE);
+
+        let mut test_collection = db.collection("test").unwrap();
+        let all = test_collection.find_all( ).unwrap();
+
+        let second = test_collection.find_one(&mk_document! {
+            "content": "1",
+        }).unwrap().unwrap();
+        assert_eq!(second.get("content").unwrap().unwrap_string(), "1");
+        assert!(second.get("content").is_some());
+
+        assert_eq!(TEST_SIZE, all.len())
+    }
+
+    #[test]
+    fn test_transaction_commit() {
+        let mut db = prepare_db("test-transaction");
+        db.start_transaction(None).unwrap();
+        let mut collection = db.create_collection("test").unwrap();
+
+        for i in 0..10{
+            let content = i.to_string();
+            let mut new_doc = mk_document! {
+                    "_id": i,
+                    "content": content,
+                };
+            collection.insert(&mut new_doc).unwrap();
+        }
+        db.commit().unwrap()
+    }
+
+    #[test]
+    fn test_transaction_guard() {
+        let mut db = prepare_db("test-transaction-guard");
+        db.create_collection("test").unwrap();
+
+        let mut txn = db.transaction().unwrap();
+        assert!(txn.transaction().is_err());
+        for i in 0..10 {
+            txn.collection("test").unwrap().insert(&mut mk_document! { "_id": i }).unwrap();
+        }
+        txn.rollback().unwrap();
+        assert_eq!(db.collection("test").unwrap().count().unwrap(), 0);
+
+        {
+            let mut txn = db.transaction().unwrap();
+            txn.collection("test").unwrap().insert(&mut mk_document! { "_id": 100 }).unwrap();
+            // dropped without committing
+        }
+        assert_eq!(db.collection("test").unwrap().count().unwrap(), 0);
+
+        let mut txn = db.transaction().unwrap();
+        for i in 0..10 {
+            txn.collection("test").unwrap().insert(&mut mk_document! { "_id": i }).unwrap();
+        }
+        txn.commit().unwrap();
+        drop(db);
+
+        let mut db_path = env::temp_dir();
+        db_path.push("test-transaction-guard.db");
+        let mut db = Database::open(db_path.as_path()).unwrap();
+        assert_eq!(db.collection("test").unwrap().count().unwrap(), 10);
+    }
+
+    // copy the files while the database is still open,
+    // the copies look like the files left by a crashed process
+    fn copy_db_files_as_crashed(db_name: &str, crashed_name: &str) -> std::path::PathBuf {
+        let mut crashed_path = env::temp_dir();
+        for suffix in &[".db", ".db.journal"] {
+            let mut src = env::temp_dir();
+            src.push(String::from(db_name) + suffix);
+            crashed_path = env::temp_dir();
+            crashed_path.push(String::from(crashed_name) + suffix);
+            std::fs::copy(src.as_path(), crashed_path.as_path()).unwrap();
+        }
+        crashed_path.set_file_name(String::from(crashed_name) + ".db");
+        crashed_path
+    }
+
+    #[test]
+    fn test_recover_from_journal() {
+        let mut db = prepare_db("test-recover-journal");
+        let mut collection = db.create_collection("test").unwrap();
+        for i in 0..100 {
+            collection.insert(&mut mk_document! { "_id": i, "content": i.to_string() }).unwrap();
+        }
+
+        // committed but not checkpointed
+        let crashed_path = copy_db_files_as_crashed("test-recover-journal", "test-recover-journal-committed");
+
+        // not committed
+        let mut txn = db.transaction().unwrap();
+        for i in 100..200 {
+            txn.collection("test").unwrap().insert(&mut mk_document! { "_id": i }).unwrap();
+        }
+        let uncommitted_path = copy_db_files_as_crashed("test-recover-journal", "test-recover-journal-uncommitted");
+        txn.rollback().unwrap();
+
+        let mut db = Database::open(crashed_path.as_path()).unwrap();
+        let mut collection = db.collection("test").unwrap();
+        assert_eq!(collection.count().unwrap(), 100);
+        let doc = collection.find_one(&mk_document! { "_id": 99 }).unwrap().unwrap();
+        assert_eq!(doc.get("content").unwrap().unwrap_string(), "99");
+
+        // the journal has been replayed into the database file
+        let mut journal_path = crashed_path.clone();
+        journal_path.set_file_name("test-recover-journal-committed.db.journal");
+        assert_eq!(std::fs::metadata(journal_path.as_path()).unwrap().len(), 64);
+
+        let mut db = Database::open(uncommitted_path.as_path()).unwrap();
+        assert_eq!(db.collection("test").unwrap().count().unwrap(), 100);
+    }
+
+    #[test]
+    fn test_shadow_journal() {
+        let config = Config {
+            journal_mode: JournalMode::Shadow,
+            ..Default::default()
+        };
+        let mut db = prepare_db_with_config("test-shadow-journal", config);
+        let mut collection = db.create_collection("test").unwrap();
+        for i in 0..100 {
+            collection.insert(&mut mk_document! { "_id": i, "content": i.to_string() }).unwrap();
+        }
+
+        // the pages are in the database file after the commit
+        let mut journal_path = env::temp_dir();
+        journal_path.push("test-shadow-journal.db.journal");
+        assert_eq!(std::fs::metadata(journal_path.as_path()).unwrap().len(), 64);
+        let committed_path = copy_db_files_as_crashed("test-shadow-journal", "test-shadow-journal-committed");
+
+        // the rolled back pages are never written to the file
+        let db_len = std::fs::metadata(committed_path.as_path()).unwrap().len();
+        let mut txn = db.transaction().unwrap();
+        for i in 100..200 {
+            txn.collection("test").unwrap().insert(&mut mk_document! { "_id": i }).unwrap();
+        }
+        assert_eq!(txn.collection("test").unwrap().count().unwrap(), 200);
+        let uncommitted_path = copy_db_files_as_crashed("test-shadow-journal", "test-shadow-journal-uncommitted");
+        txn.rollback().unwrap();
+        assert_eq!(db.collection("test").unwrap().count().unwrap(), 100);
+        assert_eq!(std::fs::metadata(uncommitted_path.as_path()).unwrap().len(), db_len);
+
+        let mut db = Database::open(uncommitted_path.as_path()).unwrap();
+        assert_eq!(db.collection("test").unwrap().count().unwrap(), 100);
+
+        // opened in the write-ahead mode
+        let mut db = Database::open(committed_path.as_path()).unwrap();
+        let mut collection = db.collection("test").unwrap();
+        assert_eq!(collection.count().unwrap(), 100);
+        let doc = collection.find_one(&mk_document! { "_id": 99 }).unwrap().unwrap();
+        assert_eq!(doc.get("content").unwrap().unwrap_string(), "99");
+    }
+
+    #[test]
+    fn test_recover_from_shadow_journal() {
+        let config = Config {
+            journal_mode: JournalMode::Shadow,
+            ..Default::default()
+        };
+        let mut db = prepare_db_with_config("test-recover-shadow", config);
+        let mut collection = db.create_collection("test").unwrap();
+        for i in 0..100 {
+            collection.insert(&mut mk_document! { "_id": i }).unwrap();
+        }
+        let crashed_path = copy_db_files_as_crashed("test-recover-shadow", "test-recover-shadow-crashed");
+        let original_len = std::fs::metadata(crashed_path.as_path()).unwrap().len();
+
+        let mut txn = db.transaction().unwrap();
+        for i in 100..1000 {
+            txn.collection("test").unwrap().insert(&mut mk_document! { "_id": i }).unwrap();
+        }
+        txn.commit().unwrap();
+        drop(db);
+
+        // the process dies after the original pages are appended to the journal,
+        // and the changed pages are written to the database file
+        let mut journal_path = crashed_path.clone();
+        journal_path.set_file_name("test-recover-shadow-crashed.db.journal");
+        let crashed_file = std::fs::OpenOptions::new().read(true).write(true).open(crashed_path.as_path()).unwrap();
+        let mut crashed_file = Storage::File(crashed_file);
+        let mut journal_manager = JournalManager::open(journal_path.as_path(), 4096, original_len, JournalMode::Shadow).unwrap();
+        journal_manager.start_transaction(TransactionType::Write).unwrap();
+        let page_count = (original_len / 4096) as u32;
+        assert_eq!(journal_manager.append_original_pages(&mut crashed_file, 0..page_count).unwrap(), page_count);
+        drop(journal_manager);
+        drop(crashed_file);
+
+        let mut src_path = env::temp_dir();
+        src_path.push("test-recover-shadow.db");
+        std::fs::copy(src_path.as_path(), crashed_path.as_path()).unwrap();
+        assert!(std::fs::metadata(crashed_path.as_path()).unwrap().len() > original_len);
+
+        // the original pages are written back
+        let mut db = Database::open(crashed_path.as_path()).unwrap();
+        assert_eq!(db.collection("test").unwrap().count().unwrap(), 100);
+        assert_eq!(std::fs::metadata(crashed_path.as_path()).unwrap().len(), original_len);
+        assert_eq!(std::fs::metadata(journal_path.as_path()).unwrap().len(), 64);
+
+        let mut db = Database::open(src_path.as_path()).unwrap();
+        assert_eq!(db.collection("test").unwrap().count().unwrap(), 1000);
+    }
+
+    #[test]
+    fn test_commit_after_commit() {
+        let config = Config {
+            journal_full_size: 1,
+            ..Default::default()
+        };
+        let mut db = prepare_db_with_config("test-commit-2", config);
+        db.start_transaction(None).unwrap();
+        let mut collection = db.create_collection("test").unwrap();
+
+        for i in 0..1000 {
+            let content = i.to_string();
+            let mut new_doc = mk_document! {
+                "_id": i,
+                "content": content,
+            };
+            collection.insert(&mut new_doc).unwrap();
+        }
+        db.commit().unwrap();
+
+        db.start_transaction(None).unwrap();
+        let mut collection2 = db.create_collection("test-2").unwrap();
+        for i in 0..10{
+            let content = i.to_string();
+            let mut new_doc = mk_document! {
+                "_id": i,
+                "content": content,
+            };
+            collection2.insert(&mut new_doc).unwrap_or_else(|_| panic!("insert failed: {}", i));
+        }
+        db.commit().unwrap();
+    }
+
+    #[test]
+    fn test_rollback() {
+        let mut db = prepare_db("test-rollback");
+        let mut collection = db.create_collection("test").unwrap();
+
+        assert_eq!(collection.count().unwrap(), 0);
+
+        db.start_transaction(None).unwrap();
+
+        let mut collection = db.collection("test").unwrap();
+        for i in 0..10{
+            let content = i.to_string();
+            let mut new_doc = mk_document! {
+                "_id": i,
+                "content": content,
+            };
+            collection.insert(new_doc.as_mut()).unwrap();
+        }
+        assert_eq!(collection.count().unwrap(), 10);
+
+        db.rollback().unwrap();
+
+        let mut collection = db.collection("test").unwrap();
+        assert_eq!(collection.count().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_create_collection_with_number_pkey() {
+        let mut db = {
+            let mut db = prepare_db("test-number-pkey");
+            let mut collection = db.create_collection("test").unwrap();
+
+            for i in 0..TEST_SIZE {
+                let content = i.to_string();
+                let mut new_doc = mk_document! {
+                    "_id": i,
+                    "content": content,
+                };
+                collection.insert(new_doc.as_mut()).unwrap();
+            }
+
+            db
+        };
+
+        let mut collection = db.collection("test").unwrap();
+
+        let count = collection.count().unwrap();
+        assert_eq!(TEST_SIZE, count as usize);
+
+        let all = collection.find_all( ).unwrap();
+
+        assert_eq!(TEST_SIZE, all.len())
+    }
+
+    #[test]
+    fn test_find() {
+        let mut db = create_and_return_db_with_items("test-find", TEST_SIZE);
+        let mut collection = db.collection("test").unwrap();
+
+        let result = collection.find(
+            &mk_document! {
+                "content": "3",
+            }
+        ).unwrap();
+
+        assert_eq!(result.len(), 1);
+
+        let one = result[0].clone();
+        assert_eq!(one.get("content").unwrap().unwrap_string(), "3");
+    }
+
+    #[test]
+    fn test_db_find_with_filter() {
+        let mut db = create_and_return_db_with_items("test-db-find", 100);
+
+        let result = db.find("test", &mk_document! {
+            "content": "42",
+        }).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].get("content").unwrap().unwrap_string(), "42");
+
+        let all = db.find("test", &mk_document! {}).unwrap();
+        assert_eq!(all.len(), 100);
+
+        let not_found = db.find("test", &mk_document! {
+            "content": "not-exist",
+        }).unwrap();
+        assert!(not_found.is_empty());
+
+        match db.find("other", &mk_document! {}) {
+            Err(DbErr::CollectionNotFound(_)) => (),
+            _ => panic!("collection should not be found"),
+        }
+    }
+
+    #[test]
+    fn test_db_find_one() {
+        let mut db = create_and_return_db_with_items("test-db-find-one", 100);
+
+        let result = db.find_one("test", &mk_document! {
+            "content": "42",
+        }).unwrap().unwrap();
+        assert_eq!(result.get("content").unwrap().unwrap_string(), "42");
+
+        let result = db.find_one("test", &mk_document! {
+            "content": "not-exist",
+        }).unwrap();
+        assert!(result.is_none());
+
+        match db.find_one("other", &mk_document! {}) {
+            Err(DbErr::CollectionNotFound(_)) => (),
+            _ => panic!("collection should not be found"),
+        }
+    }
+
+    #[test]
+    fn test_db_delete() {
+        let mut db = create_and_return_db_with_items("test-db-delete", TEST_SIZE);
+
+        let deleted = db.delete("test", &mk_document! {
+            "content": "3",
+        }).unwrap();
+        assert_eq!(deleted, 1);
+
+        let deleted = db.delete("test", &mk_document! {
+            "content": "3",
+        }).unwrap();
+        assert_eq!(deleted, 0);
+
+        let deleted = db.delete("test", &mk_document! {}).unwrap();
+        assert_eq!(deleted, TEST_SIZE - 1);
+
+        let mut collection = db.collection("test").unwrap();
+        assert_eq!(collection.count().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_db_update_set() {
+        let mut db = create_and_return_db_with_items("test-db-update", 100);
+
+        let long_str: String = "x".repeat(2000);
+
+        let updated = db.update("test", &mk_document! {
+            "content": "3",
+        }, &mk_document! {
+            "$set": mk_document! {
+                "content": "three",
+                "extra": long_str.as_str(),
+            },
+        }).unwrap();
+        assert_eq!(updated, 1);
+
+        let doc = db.find_one("test", &mk_document! {
+            "content": "three",
+        }).unwrap().unwrap();
+        assert_eq!(doc.get("extra").unwrap().unwrap_string(), long_str.as_str());
+
+        assert!(db.find_one("test", &mk_document! {
+            "content": "3",
+        }).unwrap().is_none());
+
+        let all = db.find("test", &mk_document! {}).unwrap();
+        assert_eq!(all.len(), 100);
+
+        let mut collection = db.collection("test").unwrap();
+        let updated = collection.update(None, &mk_document! {
+            "$set": mk_document! {
+                "flag": true,
+            },
+        }).unwrap();
+        assert_eq!(updated, 100);
+    }
+
+    #[test]
+    fn test_delete_collection() {
+        let mut db = create_and_return_db_with_items("test-delete-collection", TEST_SIZE);
+        {
+            let mut collection = db.create_collection("test-2").unwrap();
+            let mut doc = mk_document! {
+                "content": "hello",
+            };
+            collection.insert(doc.as_mut()).unwrap();
+        }
+
+        assert!(db.delete_collection("test").unwrap());
+        assert!(!db.delete_collection("test").unwrap());
+
+        match db.find("test", &mk_document! {}) {
+            Err(DbErr::CollectionNotFound(_)) => (),
+            _ => panic!("collection should be deleted"),
+        }
+
+        let rest = db.find("test-2", &mk_document! {}).unwrap();
+        assert_eq!(rest.len(), 1);
+
+        let mut collection = db.create_collection("test").unwrap();
+        assert_eq!(collection.count().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_list_collections() {
+        let mut db = prepare_db("test-list-collections");
+        assert!(db.list_collections().unwrap().is_empty());
+
+        for name in &["c", "a", "b"] {
+            let _ = db.create_collection(name).unwrap();
+        }
+        assert_eq!(db.list_collections().unwrap(), vec!["a", "b", "c"]);
+
+        assert!(db.delete_collection("b").unwrap());
+        assert_eq!(db.list_collections().unwrap(), vec!["a", "c"]);
+    }
+
+    #[test]
+    fn test_count_documents() {
+        let mut db = create_and_return_db_with_items("test-count-documents", TEST_SIZE);
+
+        assert_eq!(db.count_documents("test", &mk_document! {}).unwrap(), TEST_SIZE as u64);
+        assert_eq!(db.count_documents("test", &mk_document! {
+            "content": "10",
+        }).unwrap(), 1);
+        assert_eq!(db.count_documents("test", &mk_document! {
+            "content": "not-exist",
+        }).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_insert_return_id() {
+        let mut db = prepare_db("test-insert-return-id");
+        db.create_collection("test").unwrap();
+
+        let mut doc = mk_document! { "content": "generated" };
+        let id = db.insert("test", &mut doc).unwrap();
+        assert!(matches!(id, Value::ObjectId(_)));
+        let found = db.find_by_id("test", &id).unwrap().unwrap();
+        assert_eq!(found.get("content").unwrap().unwrap_string(), "generated");
+
+        db.create_collection("supplied").unwrap();
+        let mut doc = mk_document! { "_id": "supplied" };
+        assert_eq!(db.insert("supplied", &mut doc).unwrap(), Value::from("supplied"));
+        let mut doc = Document::with_id(Value::from("external-key"));
+        assert_eq!(db.insert("supplied", &mut doc).unwrap(), Value::from("external-key"));
+        assert!(db.find_by_id("supplied", &Value::from("external-key")).unwrap().is_some());
+
+        assert!(matches!(db.insert("not-exist", &mut mk_document! {}), Err(DbErr::CollectionNotFound(_))));
+    }
+
+    #[test]
+    fn test_insert_unsupported_value() {
+        let mut db = prepare_db("test-insert-unsupported-value");
+        db.create_collection("test").unwrap();
+        db.insert("test", &mut mk_document! { "_id": 0 }).unwrap();
+
+        let mut doc = mk_document! {
+            "_id": 1,
+            "nested": mk_document! {
+                "list": mk_array![ "ok", "bad\0string" ],
+            },
+        };
+        match db.insert("test", &mut doc) {
+            Err(DbErr::UnsupportedValueType(st)) => {
+                assert_eq!(st.field, "nested.list.1");
+                assert_eq!(st.ty, "String contains a NUL byte");
+            }
+            _ => panic!("the value should be unsupported"),
+        }
+
+        let mut doc = mk_document! { "_id": 2 };
+        doc.insert("bad\0key".into(), Value::from(2));
+        assert!(matches!(db.insert("test", &mut doc), Err(DbErr::UnsupportedValueType(st)) if st.field == "bad\0key"));
+
+        let docs = vec![
+            Rc::new(mk_document! { "_id": 3 }),
+            Rc::new(mk_document! { "_id": 4, "content": "\0" }),
+        ];
+        assert!(db.insert_many("test", docs).is_err());
+
+        assert_eq!(db.count_documents("test", &mk_document! {}).unwrap(), 1);
+        assert!(db.find_by_id("test", &Value::from(3)).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_bulk_write() {
+        let mut db = prepare_db("test-bulk-write");
+        db.create_collection("test").unwrap();
+        db.insert("test", &mut mk_document! { "_id": 0, "content": "old" }).unwrap();
+
+        let ops = vec![
+            WriteOp::Insert(mk_document! { "_id": 1, "content": "old" }),
+            WriteOp::Insert(mk_document! { "_id": 2, "content": "old" }),
+            WriteOp::Update {
+                filter: mk_document! { "content": "old" },
+                update: mk_document! { "$set": mk_document! { "content": "new" } },
+            },
+            WriteOp::Delete { filter: mk_document! { "_id": 0 } },
+        ];
+        let result = db.bulk_write("test", ops, true).unwrap();
+        assert_eq!(result.inserted_ids, vec![Value::from(1), Value::from(2)]);
+        assert_eq!(result.modified, 3);
+        assert_eq!(result.deleted, 1);
+        assert!(result.errors.is_empty());
+        assert_eq!(db.count_documents("test", &mk_document! { "content": "new" }).unwrap(), 2);
+
+        // all or nothing in the ordered mode
+        let ops = vec![
+            WriteOp::Insert(mk_document! { "_id": 3 }),
+            WriteOp::Insert(mk_document! { "_id": "not-an-int" }),
+            WriteOp::Delete { filter: mk_document! {} },
+        ];
+        assert!(matches!(db.bulk_write("test", ops.clone(), true), Err(DbErr::UnexpectedIdType(_, _))));
+        assert_eq!(db.count_documents("test", &mk_document! {}).unwrap(), 2);
+        assert!(db.find_by_id("test", &Value::from(3)).unwrap().is_none());
+
+        // the failed operation is skipped in the unordered mode
+        let result = db.bulk_write("test", ops, false).unwrap();
+        assert_eq!(result.inserted_ids, vec![Value::from(3)]);
+        assert_eq!(result.deleted, 3);
+        assert_eq!(result.errors.len(), 1);
+        assert!(matches!(result.errors[0], (1, DbErr::UnexpectedIdType(_, _))));
+        assert_eq!(db.count_documents("test", &mk_document! {}).unwrap(), 0);
+
+        db.start_transaction(None).unwrap();
+        assert!(db.bulk_write("test", vec![], true).is_err());
+        db.rollback().unwrap();
+        assert!(matches!(db.bulk_write("not-exist", vec![], true), Err(DbErr::CollectionNotFound(_))));
+    }
+
+    #[test]
+    fn test_cached_count() {
+        let mut db = prepare_db("test-cached-count");
+        db.create_collection("test").unwrap();
+        let docs: Vec<Rc<Document>> = (0..100).map(|i| Rc::new(mk_document! { "_id": i })).collect();
+        db.insert_many("test", docs).unwrap();
+
+        // the count is read from the meta, the pages read don't grow with the collection
+        let count_reads = |db: &mut Database| {
+            db.set_page_cache_capacity(0);
+            db.reset_page_metrics();
+            db.count_documents("test", &mk_document! {}).unwrap();
+            let metrics = db.page_metrics();
+            db.set_page_cache_capacity(1024);
+            metrics.cache_hits + metrics.cache_misses
+        };
+        let small_reads = count_reads(&mut db);
+        let docs: Vec<Rc<Document>> = (100..5000).map(|i| Rc::new(mk_document! { "_id": i })).collect();
+        db.insert_many("test", docs).unwrap();
+        assert_eq!(count_reads(&mut db), small_reads);
+        assert_eq!(db.count_documents("test", &mk_document! {}).unwrap(), 5000);
+        db.delete("test", &mk_document! { "_id": mk_document! { "$gte": 100 } }).unwrap();
+        assert_eq!(db.count_documents("test", &mk_document! {}).unwrap(), 100);
+
+        assert_eq!(db.delete("test", &mk_document! { "_id": 10 }).unwrap(), 1);
+        assert_eq!(db.delete("test", &mk_document! { "_id": 1000 }).unwrap(), 0);
+        assert_eq!(db.count_documents("test", &mk_document! {}).unwrap(), 99);
+
+        // the count is rolled back with the documents
+        db.start_transaction(None).unwrap();
+        db.collection("test").unwrap().insert(&mut mk_document! { "_id": 1000 }).unwrap();
+        db.delete("test", &mk_document! { "_id": 20 }).unwrap();
+        db.delete("test", &mk_document! { "_id": 30 }).unwrap();
+        db.rollback().unwrap();
+        assert_eq!(db.count_documents("test", &mk_document! {}).unwrap(), 99);
+
+        assert_eq!(db.recount("test").unwrap(), 99);
+        db.collection("test").unwrap().delete(None).unwrap();
+        assert_eq!(db.count_documents("test", &mk_document! {}).unwrap(), 0);
+        assert_eq!(db.recount("test").unwrap(), 0);
+    }
+
+    #[test]
+    fn test_collection_cached_meta() {
+        let mut db = prepare_db("test-collection-cached-meta");
+        db.create_collection("test").unwrap();
+        db.create_index("test", "group").unwrap();
+
+        // the root of the collection and the index are split, the cached meta is kept up to date
+        let mut collection = db.collection("test").unwrap();
+        for i in 0..1000 {
+            collection.insert(&mut mk_document! { "_id": i, "group": i % 10 }).unwrap();
+        }
+        assert_eq!(collection.find(&mk_document! { "group": 3 }).unwrap().len(), 100);
+        assert_eq!(collection.update(Some(&mk_document! { "_id": 3 }), &mk_document! {
+            "$set": mk_document! { "group": 11 },
+        }).unwrap(), 1);
+        assert_eq!(collection.delete(Some(&mk_document! { "group": 3 })).unwrap(), 99);
+        assert_eq!(collection.count().unwrap(), 901);
+
+        // the meta isn't looked up again, the cached count is read without any page
+        collection.db.set_page_cache_capacity(0);
+        collection.db.reset_page_metrics();
+        assert_eq!(collection.count().unwrap(), 901);
+        let metrics = collection.db.page_metrics();
+        assert_eq!(metrics.cache_hits + metrics.cache_misses, 0);
+        collection.db.set_page_cache_capacity(1024);
+
+        // the meta is looked up again after an operation fails
+        let mut doc = mk_document! { "_id": 0 };
+        assert!(matches!(collection.insert(&mut doc), Err(DbErr::DataExist(_))));
+        collection.insert(&mut mk_document! { "_id": 1000, "group": 11 }).unwrap();
+        assert_eq!(collection.find(&mk_document! { "group": 11 }).unwrap().len(), 2);
+        assert_eq!(collection.count().unwrap(), 902);
+
+        assert_eq!(db.count_documents("test", &mk_document! {}).unwrap(), 902);
+        assert_eq!(db.find("test", &mk_document! { "group": 11 }).unwrap().len(), 2);
+        assert_eq!(db.verify_integrity().unwrap(), Vec::<String>::new());
+
+        // the meta of the collection recreated is looked up by the new handle
+        db.delete_collection("test").unwrap();
+        let mut collection = db.collection("test").unwrap();
+        assert_eq!(collection.count().unwrap(), 0);
+        collection.insert(&mut mk_document! { "_id": 0 }).unwrap();
+        assert_eq!(collection.find_all().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_find_with_options() {
+        let mut db = prepare_db("test-find-with-options");
+        let mut collection = db.create_collection("test").unwrap();
+
+        for i in 0..100 {
+            let mut doc = mk_document! {
+                "_id": i,
+            };
+            collection.insert(doc.as_mut()).unwrap();
+        }
+
+        let empty = mk_document! {};
+
+        let page = db.find_with_options("test", &empty, 40, Some(20)).unwrap();
+        assert_eq!(page.len(), 20);
+        assert_eq!(page[0].pkey_id().unwrap().unwrap_int(), 40);
+        assert_eq!(page[19].pkey_id().unwrap().unwrap_int(), 59);
+
+        let rest = db.find_with_options("test", &empty, 90, None).unwrap();
+        assert_eq!(rest.len(), 10);
+
+        assert!(db.find_with_options("test", &empty, 0, Some(0)).unwrap().is_empty());
+        assert!(db.find_with_options("test", &empty, 100, Some(10)).unwrap().is_empty());
+        assert_eq!(db.find_with_options("test", &empty, 0, None).unwrap().len(), 100);
+
+        // the transaction of the unfinished handle is closed
+        assert_eq!(db.count_documents("test", &empty).unwrap(), 100);
+    }
+
+    #[test]
+    fn test_update_upsert() {
+        let mut db = create_and_return_db_with_items("test-update-upsert", 10);
+
+        let filter = mk_document! { "content": "3" };
+        let update = mk_document! {
+            "$set": mk_document! { "value": 3 },
+        };
+        let result = db.update_upsert("test", &filter, &update).unwrap();
+        assert_eq!(result.matched, 1);
+        assert_eq!(result.modified, 1);
+        assert!(result.upserted_id.is_none());
+
+        let filter = mk_document! { "content": "new" };
+        let result = db.update_upsert("test", &filter, &update).unwrap();
+        assert_eq!(result.matched, 0);
+        let upserted_id = result.upserted_id.unwrap();
+
+        let doc = db.find_by_id("test", &upserted_id).unwrap().unwrap();
+        assert_eq!(doc.get("content").unwrap().unwrap_string(), "new");
+        assert_eq!(doc.get("value").unwrap().unwrap_int(), 3);
+
+        let empty = mk_document! {};
+        assert_eq!(db.count_documents("test", &empty).unwrap(), 11);
+
+        // the document inserted would be matched next time
+        let result = db.update_upsert("test", &filter, &update).unwrap();
+        assert_eq!(result.matched, 1);
+        assert_eq!(db.count_documents("test", &empty).unwrap(), 11);
+    }
+
+    #[test]
+    fn test_insert_many() {
+        let mut db = prepare_db("test-insert-many");
+        db.create_collection("test").unwrap();
+
+        let mut docs = Vec::new();
+        for i in 0..TEST_SIZE {
+            docs.push(Rc::new(mk_document! {
+                "content": i.to_string(),
+            }));
+        }
+
+        let ids = db.insert_many("test", docs).unwrap();
+        assert_eq!(ids.len(), TEST_SIZE);
+
+        let empty = mk_document! {};
+        assert_eq!(db.count_documents("test", &empty).unwrap(), TEST_SIZE as u64);
+
+        let doc = db.find_by_id("test", &ids[42]).unwrap().unwrap();
+        assert_eq!(doc.get("content").unwrap().unwrap_string(), "42");
+
+        // the type of _id is different from the ObjectIds inserted before,
+        // the whole batch is rolled back
+        let docs = vec![
+            Rc::new(mk_document! { "content": "new" }),
+            Rc::new(mk_document! { "_id": 1, "content": "bad" }),
+        ];
+        assert!(db.insert_many("test", docs).is_err());
+        assert_eq!(db.count_documents("test", &empty).unwrap(), TEST_SIZE as u64);
+
+        assert!(db.insert_many("not-exist", vec![]).is_err());
+    }
+
+    #[test]
+    fn test_find_by_id() {
+        let mut db = prepare_db("test-find-by-id");
+        let mut collection = db.create_collection("test").unwrap();
+
+        for i in 0..TEST_SIZE {
+            let mut doc = mk_document! {
+                "_id": (i * 2) as i64,
+                "content": i.to_string(),
+            };
+            collection.insert(&mut doc).unwrap();
+        }
+
+        for i in &[0, 2, 500, 1998] {
+            let doc = db.find_by_id("test", &Value::from(*i as i64)).unwrap().unwrap();
+            assert_eq!(doc.get("content").unwrap().unwrap_string(), (i / 2).to_string());
+        }
+
+        assert!(db.find_by_id("test", &Value::from(1)).unwrap().is_none());
+        assert!(db.find_by_id("test", &Value::from(-1)).unwrap().is_none());
+        assert!(db.find_by_id("test", &Value::from(2000)).unwrap().is_none());
+
+        db.create_collection("empty").unwrap();
+        assert!(db.find_by_id("empty", &Value::from(0)).unwrap().is_none());
+        assert!(db.find_by_id("not-exist", &Value::from(0)).is_err());
+    }
+
+    #[test]
+    fn test_find_with_projection() {
+        let mut db = create_and_return_db_with_items("test-find-with-projection", 10);
+
+        let filter = mk_document! { "content": "3" };
+        let result = db.find_with_projection("test", &filter, Some(&["content"])).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].len(), 2);
+        assert!(result[0].pkey_id().is_some());
+        assert_eq!(result[0].get("content").unwrap().unwrap_string(), "3");
+
+        let empty = mk_document! {};
+        let result = db.find_with_projection("test", &empty, Some(&[])).unwrap();
+        assert_eq!(result.len(), 10);
+        for doc in &result {
+            assert_eq!(doc.len(), 1);
+            assert!(doc.get("content").is_none());
+        }
+
+        let result = db.find_with_projection("test", &empty, None).unwrap();
+        assert_eq!(result[0].len(), 2);
+    }
+
+    #[test]
+    fn test_find_sorted() {
+        let mut db = prepare_db("test-find-sorted");
+        let mut collection = db.create_collection("test").unwrap();
+
+        let mut docs = vec![
+            mk_document! { "_id": 1, "age": 30 },
+            mk_document! { "_id": 2, "age": "unknown" },
+            mk_document! { "_id": 3 },
+            mk_document! { "_id": 4, "age": 18.5 },
+            mk_document! { "_id": 5, "age": Value::Null },
+            mk_document! { "_id": 6, "age": 18 },
+        ];
+        for doc in &mut docs {
+            collection.insert(doc).unwrap();
+        }
+
+        let empty = mk_document! {};
+        let ids = |docs: Vec<Rc<Document>>| -> Vec<i64> {
+            docs.iter().map(|doc| doc.pkey_id().unwrap().unwrap_int()).collect()
+        };
+
+        let result = db.find_sorted("test", &empty, "age", true).unwrap();
+        assert_eq!(ids(result), vec![3, 5, 6, 4, 1, 2]);
+
+        let result = db.find_sorted("test", &empty, "age", false).unwrap();
+        assert_eq!(ids(result), vec![2, 1, 4, 6, 5, 3]);
+
+        assert!(db.find_sorted("not-exist", &empty, "age", true).is_err());
+    }
+
+    #[test]
+    fn test_page_cache_capacity() {
+        let mut db = create_and_return_db_with_items("test-page-cache-capacity", 100);
+        let empty = mk_document! {};
+
+        db.set_page_cache_capacity(16);
+        let before = db.page_cache_stats();
+        assert_eq!(before.capacity, 16);
+
+        db.find("test", &empty).unwrap();
+        let first = db.page_cache_stats();
+        assert!(first.misses > before.misses);
+
+        db.find("test", &empty).unwrap();
+        let second = db.page_cache_stats();
+        assert!(second.hits > first.hits);
+
+        db.set_page_cache_capacity(0);
+        let result = db.find("test", &empty).unwrap();
+        assert_eq!(result.len(), 100);
+        assert_eq!(db.page_cache_stats().hits, second.hits);
+    }
+
+    #[test]
+    fn test_page_metrics() {
+        let mut db = prepare_db("test-page-metrics");
+        db.create_collection("test").unwrap();
+        let docs: Vec<Rc<Document>> = (0..200).map(|i| Rc::new(mk_document! { "_id": i })).collect();
+        db.insert_many("test", docs).unwrap();
+
+        let metrics = db.page_metrics();
+        assert!(metrics.pages_written > 0);
+        assert!(metrics.pages_allocated > 0);
+        assert_eq!(metrics.journal_bytes_written, metrics.pages_written * (4096 + 40));
+
+        db.reset_page_metrics();
+        assert_eq!(db.page_metrics(), Default::default());
+        assert_eq!(db.page_cache_stats().hits, 0);
+
+        // all the pages are cached
+        db.find("test", &mk_document! {}).unwrap();
+        let metrics = db.page_metrics();
+        assert!(metrics.cache_hits > 0);
+        assert_eq!(metrics.cache_misses, 0);
+        assert_eq!(metrics.pages_written, 0);
+
+        db.set_page_cache_capacity(0);
+        db.reset_page_metrics();
+        db.find("test", &mk_document! {}).unwrap();
+        let metrics = db.page_metrics();
+        assert_eq!(metrics.cache_hits, 0);
+        assert_eq!(metrics.cache_misses, metrics.pages_read + metrics.journal_pages_read);
+        assert!(metrics.cache_misses > 0);
+    }
+
+    #[test]
+    fn test_page_size_config() {
+        let config = Config {
+            page_size: 16384,
+            ..Default::default()
+        };
+        let mut db = prepare_db_with_config("test-page-size-config", config);
+        let mut collection = db.create_collection("test").unwrap();
+        for i in 0..500 {
+            let content = i.to_string().repeat(100);
+            let mut new_doc = mk_document! {
+                "_id": i,
+                "content": content,
+            };
+            collection.insert(&mut new_doc).unwrap();
+        }
+        drop(db);
+
+        let mut db_path = env::temp_dir();
+        db_path.push("test-page-size-config.db");
+
+        let config = Config {
+            page_size: 16384,
+            ..Default::default()
+        };
+        let mut db = Database::open_with_config(db_path.as_path(), config).unwrap();
+        let found = db.find_by_id("test", &Value::from(321)).unwrap().unwrap();
+        assert_eq!(found.get("content").unwrap().unwrap_string(), "321".repeat(100));
+        assert_eq!(db.count_documents("test", &mk_document! {}).unwrap(), 500);
+        drop(db);
+
+        match Database::open(db_path.as_path()) {
+            Err(DbErr::PageSizeMismatch(expect, actual)) => {
+                assert_eq!(expect, 4096);
+                assert_eq!(actual, 16384);
+            }
+            _ => panic!("page size should be mismatched"),
+        }
+
+        let config = Config {
+            page_size: 5000,
+            ..Default::default()
+        };
+        assert!(matches!(Database::open_with_config(db_path.as_path(), config), Err(DbErr::IllegalPageSize(5000))));
+    }
+
+    #[cfg(all(unix, feature = "mmap"))]
+    #[test]
+    fn test_mmap() {
+        let config = Config {
+            journal_full_size: 10,
+            mmap: true,
+            ..Default::default()
+        };
+        let mut db = prepare_db_with_config("test-mmap", config);
+        // every page is read from the file, which grows between the checkpoints
+        db.set_page_cache_capacity(0);
+        let mut collection = db.create_collection("test").unwrap();
+        for i in 0..500 {
+            let content = i.to_string().repeat(100);
+            collection.insert(&mut mk_document! {
+                "_id": i,
+                "content": content,
+            }).unwrap();
+        }
+        let found = db.find_by_id("test", &Value::from(321)).unwrap().unwrap();
+        assert_eq!(found.get("content").unwrap().unwrap_string(), "321".repeat(100));
+        drop(db);
+
+        let mut db_path = env::temp_dir();
+        db_path.push("test-mmap.db");
+
+        let config = Config {
+            mmap: true,
+            ..Default::default()
+        };
+        let mut db = Database::open_with_config(db_path.as_path(), config).unwrap();
+        db.set_page_cache_capacity(0);
+        assert_eq!(db.count_documents("test", &mk_document! {}).unwrap(), 500);
+        let found = db.find_by_id("test", &Value::from(499)).unwrap().unwrap();
+        assert_eq!(found.get("content").unwrap().unwrap_string(), "499".repeat(100));
+        assert_eq!(db.verify_integrity().unwrap(), Vec::<String>::new());
+    }
+
+    #[cfg(not(all(unix, feature = "mmap")))]
+    #[test]
+    fn test_mmap_not_supported() {
+        let mut db_path = env::temp_dir();
+        db_path.push("test-mmap-not-supported.db");
+        let _ = std::fs::remove_file(db_path.as_path());
+
+        let config = Config {
+            mmap: true,
+            ..Default::default()
+        };
+        let result = Database::open_with_config(db_path.as_path(), config);
+        assert!(matches!(result, Err(DbErr::MmapNotSupported)));
+    }
+
+    #[test]
+    fn test_btree_order_config() {
+        let config = Config {
+            btree_order: Some(4),
+            ..Default::default()
+        };
+        let mut db = prepare_db_with_config("test-btree-order-config", config);
+        db.create_collection("test").unwrap();
+        db.create_index("test", "num").unwrap();
+        let docs: Vec<Rc<Document>> = (0..500).map(|i| Rc::new(mk_document! {
+            "_id": i,
+            "num": i % 50,
+        })).collect();
+        db.insert_many("test", docs).unwrap();
+        assert_eq!(db.delete("test", &mk_document! { "num": 7 }).unwrap(), 10);
+        drop(db);
+
+        let mut db_path = env::temp_dir();
+        db_path.push("test-btree-order-config.db");
+
+        // the order stored in the file is used
+        let mut db = Database::open(db_path.as_path()).unwrap();
+        assert_eq!(db.count_documents("test", &mk_document! {}).unwrap(), 490);
+        assert!(db.find_by_id("test", &Value::from(7)).unwrap().is_none());
+        assert_eq!(db.find_by_id("test", &Value::from(321)).unwrap().unwrap().get("num").unwrap(), &Value::from(21));
+        drop(db);
+
+        let config = Config {
+            btree_order: Some(5),
+            ..Default::default()
+        };
+        assert!(matches!(Database::open_with_config(db_path.as_path(), config), Err(DbErr::BTreeOrderMismatch(5, 4))));
+
+        let config = Config {
+            btree_order: Some(1000),
+            ..Default::default()
+        };
+        assert!(matches!(Database::open_with_config(db_path.as_path(), config), Err(DbErr::IllegalBTreeOrder(1000, 168))));
+
+        let config = Config {
+            btree_order: Some(2),
+            ..Default::default()
+        };
+        assert!(matches!(Database::open_with_config(db_path.as_path(), config), Err(DbErr::IllegalBTreeOrder(2, 168))));
+    }
+
+    #[test]
+    fn test_page_checksum() {
+        let config = Config {
+            page_checksum: true,
+            ..Default::default()
+        };
+        let mut db = prepare_db_with_config("test-page-checksum", config);
+        db.create_collection("test").unwrap();
+        let docs: Vec<Rc<Document>> = (0..500).map(|i| Rc::new(mk_document! {
+            "_id": i,
+            "content": format!("content-{}", i),
+        })).collect();
+        db.insert_many("test", docs).unwrap();
+        drop(db);
+
+        let mut db_path = env::temp_dir();
+        db_path.push("test-page-checksum.db");
+
+        // the flag stored in the file is used
+        let mut db = Database::open(db_path.as_path()).unwrap();
+        assert_eq!(db.count_documents("test", &mk_document! {}).unwrap(), 500);
+        assert_eq!(db.verify_integrity().unwrap(), Vec::<String>::new());
+        drop(db);
+
+        // flip a byte at the end of every page except the header
+        let mut bytes = std::fs::read(db_path.as_path()).unwrap();
+        let page_count = bytes.len() / 4096;
+        for pid in 1..page_count {
+            bytes[(pid + 1) * 4096 - 1] ^= 0xFF;
+        }
+        std::fs::write(db_path.as_path(), &bytes).unwrap();
+
+        let mut db = Database::open(db_path.as_path()).unwrap();
+        let result = db.count_documents("test", &mk_document! {});
+        assert!(matches!(result, Err(DbErr::PageChecksumMismatch { page_id: _ })), "{:?}", result);
+    }
+
+    #[test]
+    fn test_file_format_version_and_migration() {
+        let mut db = prepare_db("test-file-format-version");
+        db.create_collection("test").unwrap();
+        db.insert("test", &mut mk_document! { "_id": 1 }).unwrap();
+        drop(db);
+
+        let mut db_path = env::temp_dir();
+        db_path.push("test-file-format-version.db");
+
+        let mut bytes = std::fs::read(db_path.as_path()).unwrap();
+        assert_eq!(&bytes[32..36], &2u32.to_be_bytes());
+
+        // a file from a newer version
+        bytes[32..36].copy_from_slice(&3u32.to_be_bytes());
+        std::fs::write(db_path.as_path(), &bytes).unwrap();
+        let result = Database::open(db_path.as_path());
+        assert!(matches!(result, Err(DbErr::UnsupportedFileVersion { found: 3, supported: 2 })));
+
+        // an older one is migrated, but not when it's opened as read-only
+        bytes[32..36].copy_from_slice(&1u32.to_be_bytes());
+        std::fs::write(db_path.as_path(), &bytes).unwrap();
+        assert!(matches!(Database::open_read_only(db_path.as_path()), Err(DbErr::ReadOnly)));
+
+        let mut db = Database::open(db_path.as_path()).unwrap();
+        assert_eq!(db.count_documents("test", &mk_document! {}).unwrap(), 1);
+        drop(db);
+
+        let bytes = std::fs::read(db_path.as_path()).unwrap();
+        assert_eq!(&bytes[32..36], &2u32.to_be_bytes());
+
+        // too old to migrate
+        let mut bytes = bytes;
+        bytes[32..36].copy_from_slice(&0u32.to_be_bytes());
+        std::fs::write(db_path.as_path(), &bytes).unwrap();
+        let result = Database::open(db_path.as_path());
+        assert!(matches!(result, Err(DbErr::UnsupportedFileVersion { found: 0, supported: 2 })));
+    }
+
+    #[test]
+    fn test_page_checksum_disabled() {
+        let mut db = prepare_db("test-page-checksum-disabled");
+        db.create_collection("test").unwrap();
+        db.insert("test", &mut mk_document! { "_id": 1, "content": "hello" }).unwrap();
+        drop(db);
+
+        let mut db_path = env::temp_dir();
+        db_path.push("test-page-checksum-disabled.db");
+
+        // the checksums are not stored, enabling it for an existing file takes no effect
+        let config = Config {
+            page_checksum: true,
+            ..Default::default()
+        };
+        let mut db = Database::open_with_config(db_path.as_path(), config).unwrap();
+        assert_eq!(db.count_documents("test", &mk_document! {}).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_verify_integrity_random_ops() {
+        let config = Config {
+            btree_order: Some(4),
+            ..Default::default()
+        };
+        let mut db = prepare_db_with_config("test-verify-integrity-random-ops", config);
+        db.create_collection("test").unwrap();
+        db.create_index("test", "num").unwrap();
+        assert!(db.verify_integrity().unwrap().is_empty());
+
+        // xorshift, the sequence is the same in every run
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next_rand = move |bound: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % bound
+        };
+
+        for round in 0..3000 {
+            let id = next_rand(300) as i64;
+            match next_rand(3) {
+                0 => {
+                    let mut doc = mk_document! {
+                        "_id": id,
+                        "num": next_rand(20) as i64,
+                    };
+                    match db.insert("test", &mut doc) {
+                        Ok(_) | Err(DbErr::DataExist(_)) => (),
+                        Err(err) => panic!("insert {} failed: {}", id, err),
+                    }
+                }
+                1 => {
+                    db.delete("test", &mk_document! { "_id": id }).unwrap();
+                }
+                _ => {
+                    db.update("test", &mk_document! { "_id": id }, &mk_document! {
+                        "$set": mk_document! {
+                            "num": next_rand(20) as i64,
+                        },
+                    }).unwrap();
+                }
+            }
+
+            if round % 100 == 0 {
+                let problems = db.verify_integrity().unwrap();
+                assert!(problems.is_empty(), "round {}: {:?}", round, problems);
+            }
+        }
+
+        let problems = db.verify_integrity().unwrap();
+        assert!(problems.is_empty(), "{:?}", problems);
+    }
+
+    #[test]
+    fn test_object_id_monotonic_after_reopen() {
+        let mut db = prepare_db("test-object-id-reopen");
+        db.create_collection("test").unwrap();
+        let docs: Vec<Rc<Document>> = (0..10).map(|i| Rc::new(mk_document! { "content": i })).collect();
+        let old_ids = db.insert_many("test", docs).unwrap();
+        drop(db);
+
+        let mut db_path = env::temp_dir();
+        db_path.push("test-object-id-reopen.db");
+        let mut db = Database::open(db_path.as_path()).unwrap();
+        let docs: Vec<Rc<Document>> = (10..20).map(|i| Rc::new(mk_document! { "content": i })).collect();
+        let new_ids = db.insert_many("test", docs).unwrap();
+
+        for new_id in new_ids.iter() {
+            assert!(matches!(new_id, Value::ObjectId(_)));
+            for old_id in old_ids.iter() {
+                assert!(new_id > old_id);
+            }
+        }
+    }
+
+    #[test]
+    fn test_insert_large_binary() {
+        let mut db = prepare_db("test-insert-large-binary");
+        let mut collection = db.create_collection("test").unwrap();
+
+        let blob: Vec<u8> = (0..50000).map(|i| (i * 7 % 256) as u8).collect();
+        for i in 0..10 {
+            let mut doc = mk_document! {
+                "_id": i,
+                "blob": blob.clone(),
+            };
+            collection.insert(&mut doc).unwrap();
+        }
+
+        let found = db.find_by_id("test", &Value::from(5)).unwrap().unwrap();
+        assert_eq!(found.get("blob").unwrap().unwrap_binary(), blob.as_slice());
+
+        let filter = mk_document! { "_id": 5 };
+        assert_eq!(db.delete("test", &filter).unwrap(), 1);
+        assert!(db.find_by_id("test", &Value::from(5)).unwrap().is_none());
+
+        let update = mk_document! {
+            "$set": mk_document! { "blob": Vec::<u8>::from(&b"small"[..]) },
+        };
+        let filter = mk_document! { "_id": 6 };
+        assert_eq!(db.update("test", &filter, &update).unwrap(), 1);
+        let found = db.find_by_id("test", &Value::from(6)).unwrap().unwrap();
+        assert_eq!(found.get("blob").unwrap().unwrap_binary(), b"small");
+
+        let all = db.find("test", &mk_document! {}).unwrap();
+        assert_eq!(all.len(), 9);
+        for doc in all.iter() {
+            if doc.pkey_id().unwrap().unwrap_int() != 6 {
+                assert_eq!(doc.get("blob").unwrap().unwrap_binary(), blob.as_slice());
+            }
+        }
+    }
+
+    #[test]
+    fn test_find_datetime_range() {
+        let mut db = prepare_db("test-find-datetime-range");
+        let mut collection = db.create_collection("test").unwrap();
+
+        for i in 0..10 {
+            let mut doc = mk_document! {
+                "_id": i,
+                "created_at": UTCDateTime::new(1_600_000_000_000 + i * 1000),
+            };
+            collection.insert(&mut doc).unwrap();
+        }
+
+        let result = db.find("test", &mk_document! {
+            "created_at": mk_document! {
+                "$gte": UTCDateTime::new(1_600_000_003_000),
+                "$lt": UTCDateTime::new(1_600_000_006_000),
+            },
+        }).unwrap();
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0].pkey_id().unwrap().unwrap_int(), 3);
+
+        let result = db.find("test", &mk_document! {
+            "created_at": mk_document! {
+                "$lt": Value::datetime_now(),
+            },
+        }).unwrap();
+        assert_eq!(result.len(), 10);
+    }
+
+    #[test]
+    fn test_double_values() {
+        let mut db = prepare_db("test-double-values");
+        let mut collection = db.create_collection("test").unwrap();
+
+        let values = [Value::from(2.5), Value::from(f64::NAN), Value::from(3), Value::from(-1.0), Value::from(3.0)];
+        for (i, value) in values.iter().enumerate() {
+            let mut doc = mk_document! {
+                "_id": i,
+                "num": value.clone(),
+            };
+            collection.insert(&mut doc).unwrap();
+        }
+
+        let found = db.find_by_id("test", &Value::from(0)).unwrap().unwrap();
+        assert_eq!(found.get("num").unwrap(), &Value::from(2.5));
+
+        // Int and Double are compared numerically
+        let result = db.find("test", &mk_document! { "num": 3 }).unwrap();
+        assert_eq!(result.len(), 2);
+        let result = db.find("test", &mk_document! { "num": 3.0 }).unwrap();
+        assert_eq!(result.len(), 2);
+        let result = db.find("test", &mk_document! {
+            "num": mk_document! { "$gt": 2 },
+        }).unwrap();
+        assert_eq!(result.len(), 3);
+
+        let empty = mk_document! {};
+        let ids: Vec<i64> = db.find_sorted("test", &empty, "num", true).unwrap()
+            .iter()
+            .map(|doc| doc.pkey_id().unwrap().unwrap_int())
+            .collect();
+        assert_eq!(ids, vec![3, 0, 2, 4, 1]);
+    }
+
+    #[test]
+    fn test_find_arrays() {
+        let mut db = prepare_db("test-find-arrays");
+        let mut collection = db.create_collection("test").unwrap();
+
+        let mut doc = mk_document! {
+            "_id": 1,
+            "tags": mk_array![ "rust", "db" ],
+            "score": 3,
+        };
+        collection.insert(&mut doc).unwrap();
+        let mut doc = mk_document! {
+            "_id": 2,
+            "tags": mk_array![ "db", "rust" ],
+            "score": 4.5,
+        };
+        collection.insert(&mut doc).unwrap();
+
+        let mut many = Array::new();
+        for i in 0..3000 {
+            many.push(mk_document! { "index": i }.into());
+        }
+        let mut doc = mk_document! {
+            "_id": 3,
+            "tags": many,
+            "score": 3.0,
+        };
+        collection.insert(&mut doc).unwrap();
+
+        let result = db.find("test", &mk_document! {
+            "tags": mk_array![ "rust", "db" ],
+        }).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].pkey_id().unwrap().unwrap_int(), 1);
+
+        let result = db.find("test", &mk_document! {
+            "score": mk_document! { "$in": mk_array![ 3, 10 ] },
+        }).unwrap();
+        assert_eq!(result.len(), 2);
+
+        let found = db.find_by_id("test", &Value::from(3)).unwrap().unwrap();
+        let tags = found.get("tags").unwrap().unwrap_array();
+        assert_eq!(tags.len(), 3000);
+        assert_eq!(tags[2999].unwrap_document().get("index").unwrap().unwrap_int(), 2999);
+    }
+
+    #[test]
+    fn test_find_boolean_and_null() {
+        let mut db = prepare_db("test-find-boolean-and-null");
+        let mut collection = db.create_collection("test").unwrap();
+
+        let values = [Value::from(true), Value::Null, Value::from(false), Value::from(0)];
+        for (i, value) in values.iter().enumerate() {
+            let mut doc = mk_document! {
+                "_id": i,
+                "flag": value.clone(),
+            };
+            collection.insert(&mut doc).unwrap();
+        }
+        let mut doc = mk_document! {
+            "_id": 4,
+        };
+        collection.insert(&mut doc).unwrap();
+
+        let result = db.find("test", &mk_document! { "flag": true }).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].pkey_id().unwrap().unwrap_int(), 0);
+
+        let result = db.find("test", &mk_document! { "flag": false }).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].pkey_id().unwrap().unwrap_int(), 2);
+
+        // a missing field doesn't match the Null
+        let result = db.find("test", &mk_document! { "flag": Value::Null }).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].pkey_id().unwrap().unwrap_int(), 1);
+
+        let ids: Vec<i64> = db.find_sorted("test", &mk_document! {}, "flag", true).unwrap()
+            .iter()
+            .map(|doc| doc.pkey_id().unwrap().unwrap_int())
+            .collect();
+        assert_eq!(ids, vec![4, 1, 3, 2, 0]);
+    }
+
+    #[test]
+    fn test_find_by_dotted_path() {
+        let mut db = prepare_db("test-find-by-dotted-path");
+        let mut collection = db.create_collection("test").unwrap();
+
+        let cities = ["NYC", "LA", "NYC"];
+        for (i, city) in cities.iter().enumerate() {
+            let mut doc = mk_document! {
+                "_id": i,
+                "address": mk_document! {
+                    "city": *city,
+                    "zip": mk_array![ i * 10, i * 10 + 1 ],
+                },
+            };
+            collection.insert(&mut doc).unwrap();
+        }
+        let mut doc = mk_document! {
+            "_id": 3,
+            "address": "unknown",
+        };
+        collection.insert(&mut doc).unwrap();
+
+        let result = db.find("test", &mk_document! { "address.city": "NYC" }).unwrap();
+        assert_eq!(result.len(), 2);
+
+        let result = db.find("test", &mk_document! { "address.zip.1": 11 }).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].pkey_id().unwrap().unwrap_int(), 1);
+
+        let result = db.find("test", &mk_document! {
+            "address.zip.0": mk_document! { "$gte": 10 },
+        }).unwrap();
+        assert_eq!(result.len(), 2);
+
+        let result = db.find("test", &mk_document! { "address.country": "US" }).unwrap();
+        assert_eq!(result.len(), 0);
+    }
+
+    #[test]
+    fn test_reuse_freed_pages() {
+        let mut db = create_and_return_db_with_items("test-reuse-freed-pages", TEST_SIZE);
+        let before_delete = db.dump().unwrap();
+        assert_eq!(before_delete.free_list_size, 0);
+
+        assert!(db.delete_collection("test").unwrap());
+        let after_delete = db.dump().unwrap();
+        assert!(after_delete.free_list_size > 0);
+        assert_eq!(after_delete.null_page_bar, before_delete.null_page_bar);
+
+        let mut collection = db.create_collection("test2").unwrap();
+        for i in 0..10 {
+            collection.insert(&mut mk_document! { "_id": i }).unwrap();
+        }
+
+        // the pages are taken from the free list, the file is not extended
+        let after_insert = db.dump().unwrap();
+        assert!(after_insert.free_list_size < after_delete.free_list_size);
+        assert_eq!(after_insert.null_page_bar, before_delete.null_page_bar);
+    }
+
+    #[test]
+    fn test_compact() {
+        let mut db = prepare_db("test-compact");
+        let mut collection = db.create_collection("test").unwrap();
+        for i in 0..1000 {
+            collection.insert(&mut mk_document! {
+                "name": i.to_string(),
+                "content": "a long content which makes the file larger",
+            }).unwrap();
+        }
+        db.create_index("test", "name").unwrap();
+        let filter = mk_document! { "content": "a long content which makes the file larger" };
+        db.delete("test", &filter).unwrap();
+        for i in 0..10 {
+            db.collection("test").unwrap().insert(&mut mk_document! { "name": i.to_string() }).unwrap();
+        }
+        let last_id = db.find_one("test", &mk_document! { "name": "9" }).unwrap().unwrap().pkey_id().unwrap();
+        db.create_collection("empty").unwrap();
+
+        let mut db_path = env::temp_dir();
+        db_path.push("test-compact.db");
+
+        // the file left by an interrupted compaction is ignored
+        let mut compacted_path = env::temp_dir();
+        compacted_path.push("test-compact.db.compact");
+        std::fs::write(compacted_path.as_path(), b"broken").unwrap();
+
+        let size_before = std::fs::metadata(db_path.as_path()).unwrap().len();
+        db.compact().unwrap();
+        let size_after = std::fs::metadata(db_path.as_path()).unwrap().len();
+        assert!(size_after < size_before, "{} should be less than {}", size_after, size_before);
+        assert!(!compacted_path.exists());
+
+        assert_eq!(db.list_collections().unwrap(), vec!["empty".to_string(), "test".to_string()]);
+        assert_eq!(db.list_indexes("test").unwrap(), vec!["name".to_string()]);
+        assert_eq!(db.collection("test").unwrap().count().unwrap(), 10);
+        let doc = db.find_one("test", &mk_document! { "name": "9" }).unwrap().unwrap();
+        assert_eq!(doc.pkey_id().unwrap(), last_id);
+
+        let mut doc = mk_document! { "name": "new" };
+        db.collection("test").unwrap().insert(&mut doc).unwrap();
+        assert!(doc.pkey_id().unwrap() > last_id);
+        drop(db);
+
+        let mut db = Database::open(db_path.as_path()).unwrap();
+        assert_eq!(db.collection("test").unwrap().count().unwrap(), 11);
+
+        db.start_transaction(None).unwrap();
+        assert!(db.compact().is_err());
+        db.rollback().unwrap();
+    }
+
+    #[test]
+    fn test_find_and_modify() {
+        let mut db = prepare_db("test-find-and-modify");
+        let mut collection = db.create_collection("tasks").unwrap();
+        for i in 0..3 {
+            collection.insert(&mut mk_document! { "_id": i, "state": "pending" }).unwrap();
+        }
+        db.create_index("tasks", "state").unwrap();
+
+        let claim = mk_document! {
+            "$set": mk_document! { "state": "running" },
+        };
+        let old = db.find_and_modify("tasks", &mk_document! { "state": "pending" }, &claim, false).unwrap().unwrap();
+        assert_eq!(old.get("_id").unwrap(), &Value::Int(0));
+        assert_eq!(old.get("state").unwrap(), &Value::from("pending"));
+
+        let new = db.find_and_modify("tasks", &mk_document! { "state": "pending" }, &claim, true).unwrap().unwrap();
+        assert_eq!(new.get("_id").unwrap(), &Value::Int(1));
+        assert_eq!(new.get("state").unwrap(), &Value::from("running"));
+
+        // only one document is updated each time
+        assert_eq!(db.find("tasks", &mk_document! { "state": "running" }).unwrap().len(), 2);
+        assert_eq!(db.find("tasks", &mk_document! { "state": "pending" }).unwrap().len(), 1);
+
+        db.find_and_modify("tasks", &mk_document! { "state": "pending" }, &claim, true).unwrap().unwrap();
+        assert!(db.find_and_modify("tasks", &mk_document! { "state": "pending" }, &claim, true).unwrap().is_none());
+
+        // the transaction is rolled back if the update fails
+        let invalid = mk_document! {
+            "$set": mk_document! { "_id": 100 },
+        };
+        assert!(db.find_and_modify("tasks", &mk_document! { "_id": 0 }, &invalid, true).is_err());
+        assert_eq!(db.find_by_id("tasks", &Value::Int(0)).unwrap().unwrap().get("state").unwrap(), &Value::from("running"));
+        assert!(db.find_and_modify("not-exist", &mk_document! {}, &claim, true).is_err());
+    }
+
+    #[test]
+    fn test_oplog() {
+        let config = Config {
+            oplog: true,
+            ..Config::default()
+        };
+        let mut db = prepare_db_with_config("test-oplog", config.clone());
+        assert!(db.exists_collection(OPLOG_COLLECTION_NAME).unwrap());
+        assert!(db.tail_oplog(Value::Null).unwrap().is_empty());
+
+        let mut collection = db.create_collection("test").unwrap();
+        collection.insert(&mut mk_document! { "_id": 1, "name": "a" }).unwrap();
+        collection.insert(&mut mk_document! { "_id": 2, "name": "b" }).unwrap();
+        db.insert_many("test", vec![Rc::new(mk_document! { "_id": 3 })]).unwrap();
+        db.update("test", &mk_document! { "_id": 1 }, &mk_document! {
+            "$set": mk_document! { "name": "c" },
+        }).unwrap();
+        db.replace_one("test", &mk_document! { "_id": 2 }, Rc::new(mk_document! { "name": "d" })).unwrap();
+        db.delete("test", &mk_document! { "_id": 3 }).unwrap();
+        // nothing matched, nothing recorded
+        db.update("test", &mk_document! { "_id": 100 }, &mk_document! {
+            "$set": mk_document! { "name": "e" },
+        }).unwrap();
+
+        let entries = db.tail_oplog(Value::Null).unwrap();
+        let ops: Vec<(String, i64)> = entries.iter().map(|entry| (
+            entry.get("op").unwrap().unwrap_string().to_string(),
+            entry.get("doc_id").unwrap().unwrap_int(),
+        )).collect();
+        assert_eq!(ops, vec![
+            ("insert".to_string(), 1), ("insert".to_string(), 2), ("insert".to_string(), 3),
+            ("update".to_string(), 1), ("update".to_string(), 2), ("delete".to_string(), 3),
+        ]);
+        assert!(entries.iter().all(|entry| entry.get("collection").unwrap().unwrap_string() == "test"));
+        assert!(entries.windows(2).all(|pair| pair[0].pkey_id() < pair[1].pkey_id()));
+
+        let last_id = entries.last().unwrap().pkey_id().unwrap();
+        assert!(db.tail_oplog(last_id.clone()).unwrap().is_empty());
+
+        // the entries are rolled back with the changes
+        db.start_transaction(Some(TransactionType::Write)).unwrap();
+        db.collection("test").unwrap().insert(&mut mk_document! { "_id": 4 }).unwrap();
+        db.rollback().unwrap();
+        assert!(db.tail_oplog(last_id.clone()).unwrap().is_empty());
+
+        // the changes of the oplog itself are not recorded
+        db.delete(OPLOG_COLLECTION_NAME, &mk_document! { "_id": entries[0].pkey_id().unwrap() }).unwrap();
+        assert_eq!(db.tail_oplog(Value::Null).unwrap().len(), 5);
+        drop(db);
+
+        let mut db_path = env::temp_dir();
+        db_path.push("test-oplog.db");
+        let mut db = Database::open_with_config(db_path.as_path(), config).unwrap();
+        db.collection("test").unwrap().insert(&mut mk_document! { "_id": 5 }).unwrap();
+        db.compact().unwrap();
+        let entries = db.tail_oplog(last_id).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].get("doc_id").unwrap(), &Value::Int(5));
+        assert_eq!(db.tail_oplog(Value::Null).unwrap().len(), 6);
+        drop(db);
+
+        // disabled by default
+        let mut db = Database::open(db_path.as_path()).unwrap();
+        db.collection("test").unwrap().insert(&mut mk_document! { "_id": 6 }).unwrap();
+        assert_eq!(db.tail_oplog(Value::Null).unwrap().len(), 6);
+    }
+
+    #[test]
+    fn test_timestamp() {
+        let mut db = prepare_db("test-timestamp");
+        let timestamps: Vec<Value> = (0..1000).map(|_| db.mk_timestamp()).collect();
+        assert!(timestamps.windows(2).all(|pair| pair[0] < pair[1]));
+
+        // the change log keyed by the timestamps
+        let mut collection = db.create_collection("oplog").unwrap();
+        for (index, ts) in timestamps.iter().enumerate() {
+            collection.insert(&mut mk_document! { "_id": ts.clone(), "op": index as i64 }).unwrap();
+        }
+        let doc = db.find_by_id("oplog", &timestamps[500]).unwrap().unwrap();
+        assert_eq!(doc.get("op").unwrap(), &Value::Int(500));
+        let last = db.last("oplog").unwrap().unwrap();
+        assert_eq!(last.pkey_id().unwrap(), timestamps[999]);
+
+        let mut collection = db.create_collection("events").unwrap();
+        collection.insert(&mut mk_document! { "at": Value::Timestamp { time: 10, inc: 2 } }).unwrap();
+        collection.insert(&mut mk_document! { "at": Value::Timestamp { time: 10, inc: 1 } }).unwrap();
+        db.create_index("events", "at").unwrap();
+        let found = db.find("events", &mk_document! { "at": Value::Timestamp { time: 10, inc: 1 } }).unwrap();
+        assert_eq!(found.len(), 1);
+        let found = db.find("events", &mk_document! {
+            "at": mk_document! { "$gt": Value::Timestamp { time: 10, inc: 1 } },
+        }).unwrap();
+        assert_eq!(found.len(), 1);
+    }
+
+    #[test]
+    fn test_first_and_last() {
+        let mut db = prepare_db("test-first-and-last");
+        db.create_collection("test").unwrap();
+        assert!(db.first("test").unwrap().is_none());
+        assert!(db.last("test").unwrap().is_none());
+
+        let mut collection = db.collection("test").unwrap();
+        for i in 0..1000 {
+            collection.insert(&mut mk_document! { "_id": i, "content": i.to_string() }).unwrap();
+        }
+
+        assert_eq!(db.first("test").unwrap().unwrap().get("content").unwrap(), &Value::from("0"));
+        assert_eq!(db.last("test").unwrap().unwrap().get("content").unwrap(), &Value::from("999"));
+
+        db.delete("test", &mk_document! { "_id": 999 }).unwrap();
+        assert_eq!(db.last("test").unwrap().unwrap().pkey_id().unwrap(), Value::Int(998));
+
+        assert!(db.first("not-exist").is_err());
+    }
+
+    #[test]
+    fn test_compact_collection() {
+        let mut db = prepare_db("test-compact-collection");
+        let mut collection = db.create_collection("test").unwrap();
+        for i in 0..1000 {
+            collection.insert(&mut mk_document! {
+                "name": i.to_string(),
+                "odd": i % 2,
+            }).unwrap();
+        }
+        db.create_index("test", "name").unwrap();
+        db.delete("test", &mk_document! { "odd": 1 }).unwrap();
+        let mut other = db.create_collection("other").unwrap();
+        other.insert(&mut mk_document! { "name": "other" }).unwrap();
+
+        let free_pages_before = db.stats().unwrap().free_pages;
+        db.compact_collection("test").unwrap();
+        assert!(db.stats().unwrap().free_pages > free_pages_before);
+        assert_eq!(db.verify_integrity().unwrap(), Vec::<String>::new());
+
+        assert_eq!(db.list_indexes("test").unwrap(), vec!["name".to_string()]);
+        assert_eq!(db.collection("test").unwrap().count().unwrap(), 500);
+        assert_eq!(db.count_documents("test", &mk_document! {}).unwrap(), 500);
+        let doc = db.find_one("test", &mk_document! { "name": "998" }).unwrap().unwrap();
+        assert_eq!(doc.get("odd").unwrap(), &Value::Int(0));
+        assert!(db.find_one("test", &mk_document! { "name": "999" }).unwrap().is_none());
+        assert_eq!(db.find("other", &mk_document! {}).unwrap().len(), 1);
+
+        // the collection keeps working after the rebuilding
+        db.collection("test").unwrap().insert(&mut mk_document! { "name": "new" }).unwrap();
+        assert_eq!(db.delete("test", &mk_document! { "name": "0" }).unwrap(), 1);
+        assert_eq!(db.verify_integrity().unwrap(), Vec::<String>::new());
+
+        db.create_collection("empty").unwrap();
+        db.compact_collection("empty").unwrap();
+        assert_eq!(db.count_documents("empty", &mk_document! {}).unwrap(), 0);
+
+        assert!(db.compact_collection("not-exist").is_err());
+    }
+
+    #[test]
+    fn test_truncate_collection() {
+        let mut db = prepare_db("test-truncate-collection");
+        let mut collection = db.create_collection("test").unwrap();
+        for i in 0..1000 {
+            collection.insert(&mut mk_document! {
+                "name": i.to_string(),
+            }).unwrap();
+        }
+        db.create_index("test", "name").unwrap();
+        let mut other = db.create_collection("other").unwrap();
+        other.insert(&mut mk_document! { "name": "other" }).unwrap();
+
+        let free_pages_before = db.stats().unwrap().free_pages;
+        assert_eq!(db.truncate_collection("test").unwrap(), 1000);
+        assert!(db.stats().unwrap().free_pages > free_pages_before);
+        assert_eq!(db.verify_integrity().unwrap(), Vec::<String>::new());
+
+        assert_eq!(db.list_indexes("test").unwrap(), vec!["name".to_string()]);
+        assert_eq!(db.collection("test").unwrap().count().unwrap(), 0);
+        assert_eq!(db.count_documents("test", &mk_document! {}).unwrap(), 0);
+        assert!(db.find_one("test", &mk_document! { "name": "1" }).unwrap().is_none());
+        assert_eq!(db.find("other", &mk_document! {}).unwrap().len(), 1);
+
+        // the index is still used after the truncating
+        db.collection("test").unwrap().insert(&mut mk_document! { "name": "1" }).unwrap();
+        let doc = db.find_one("test", &mk_document! { "name": "1" }).unwrap();
+        assert!(doc.is_some());
+        assert_eq!(db.collection("test").unwrap().count().unwrap(), 1);
+        assert_eq!(db.verify_integrity().unwrap(), Vec::<String>::new());
+
+        db.create_collection("empty").unwrap();
+        assert_eq!(db.truncate_collection("empty").unwrap(), 0);
+
+        assert!(db.truncate_collection("not-exist").is_err());
+    }
+
+    #[test]
+    fn test_cancellation() {
+        let mut db = prepare_db("test-cancellation");
+        let mut collection = db.create_collection("test").unwrap();
+        for i in 0..1000 {
+            collection.insert(&mut mk_document! {
+                "name": i.to_string(),
+                "odd": i % 2,
+            }).unwrap();
+        }
+
+        let cancel_flag = AtomicBool::new(false);
+        let options = OperationOptions {
+            deadline: Some(Instant::now() + Duration::from_secs(3600)),
+            cancel_flag: Some(&cancel_flag),
+        };
+        let filter = mk_document! { "odd": 1 };
+        assert_eq!(db.find_cancellable("test", &filter, &options).unwrap().len(), 500);
+        assert_eq!(db.count_documents_cancellable("test", &filter, &options).unwrap(), 500);
+
+        cancel_flag.store(true, Ordering::Relaxed);
+        assert!(matches!(db.find_cancellable("test", &filter, &options), Err(DbErr::Cancelled)));
+        assert!(matches!(db.count_documents_cancellable("test", &filter, &options), Err(DbErr::Cancelled)));
+        assert!(matches!(db.compact_cancellable(&options), Err(DbErr::Cancelled)));
+
+        let options = OperationOptions {
+            deadline: Some(Instant::now()),
+            ..Default::default()
+        };
+        assert!(matches!(db.find_cancellable("test", &filter, &options), Err(DbErr::Cancelled)));
+
+        // the query answered by the index is cancelled too
+        db.create_index("test", "odd").unwrap();
+        assert_eq!(db.explain("test", &filter).unwrap(), QueryPlan::Index("odd".into()));
+        assert!(matches!(db.find_cancellable("test", &filter, &options), Err(DbErr::Cancelled)));
+        cancel_flag.store(false, Ordering::Relaxed);
+        let options = OperationOptions {
+            cancel_flag: Some(&cancel_flag),
+            ..Default::default()
+        };
+        assert_eq!(db.find_cancellable("test", &filter, &options).unwrap().len(), 500);
+        cancel_flag.store(true, Ordering::Relaxed);
+        assert!(matches!(db.find_cancellable("test", &filter, &options), Err(DbErr::Cancelled)));
+
+        // nothing is changed by the cancelled operations
+        let mut compacted_path = env::temp_dir();
+        compacted_path.push("test-cancellation.db.compact");
+        assert!(!compacted_path.exists());
+        assert_eq!(db.count_documents("test", &filter).unwrap(), 500);
+        db.collection("test").unwrap().insert(&mut mk_document! { "name": "new" }).unwrap();
+        db.compact_cancellable(&OperationOptions::default()).unwrap();
+        assert_eq!(db.collection("test").unwrap().count().unwrap(), 1001);
+        assert_eq!(db.verify_integrity().unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut db = create_and_return_db_with_items("test-stats", 100);
+        db.create_collection("empty").unwrap();
+        let mut collection = db.collection("large").unwrap();
+        let large_content: Vec<u8> = vec![7; 10000];
+        collection.insert(&mut mk_document! { "content": large_content }).unwrap();
+
+        let stats = db.stats().unwrap();
+        assert_eq!(stats.page_size, 4096);
+        assert_eq!(stats.free_pages, 0);
+        assert_eq!(stats.overflow_pages, 3);
+        assert_eq!(stats.collections.len(), 3);
+        let counts: Vec<(&str, u64)> = stats.collections.iter().map(|col| (col.name.as_str(), col.count)).collect();
+        assert_eq!(counts, vec![("empty", 0), ("large", 1), ("test", 100)]);
+
+        db.delete_collection("large").unwrap();
+        let after_delete = db.stats().unwrap();
+        assert_eq!(after_delete.overflow_pages, 0);
+        assert!(after_delete.free_pages >= 3);
+        assert_eq!(after_delete.total_pages, stats.total_pages);
+    }
+
+    #[test]
+    fn test_collection_size_bytes() {
+        let mut db = create_and_return_db_with_items("test-collection-size-bytes", 100);
+        db.create_collection("empty").unwrap();
+        let mut collection = db.collection("large").unwrap();
+        let large_content: Vec<u8> = vec![7; 10000];
+        collection.insert(&mut mk_document! { "content": large_content }).unwrap();
+
+        // the root page only
+        assert_eq!(db.collection_size_bytes("empty").unwrap(), 4096);
+        // the root page, the data page and 3 overflow pages
+        assert_eq!(db.collection_size_bytes("large").unwrap(), 5 * 4096);
+
+        let size = db.collection_size_bytes("test").unwrap();
+        assert_eq!(size % 4096, 0);
+        assert!(size > 4096);
+
+        db.delete("test", &mk_document! {}).unwrap();
+        assert!(db.collection_size_bytes("test").unwrap() < size);
+
+        assert!(db.collection_size_bytes("not-exist").is_err());
+    }
+
+    #[test]
+    fn test_reclaim_overflow_pages() {
+        let mut db = prepare_db("test-reclaim-overflow-pages");
+        let mut collection = db.create_collection("test").unwrap();
+        collection.insert(&mut mk_document! { "_id": 0 }).unwrap();
+        let large_content: Vec<u8> = vec![7; 10000];
+        collection.insert(&mut mk_document! { "_id": 1, "content": large_content.clone() }).unwrap();
+
+        let before_delete = db.stats().unwrap();
+        assert_eq!(before_delete.overflow_pages, 3);
+
+        assert_eq!(db.delete("test", &mk_document! { "_id": 1 }).unwrap(), 1);
+        let after_delete = db.stats().unwrap();
+        assert_eq!(after_delete.overflow_pages, 0);
+        assert_eq!(after_delete.free_pages, before_delete.free_pages + 3);
+
+        // the freed pages are reused by the next chain
+        let mut collection = db.collection("test").unwrap();
+        collection.insert(&mut mk_document! { "_id": 2, "content": large_content }).unwrap();
+        let before_update = db.stats().unwrap();
+        assert_eq!(before_update.overflow_pages, 3);
+        assert_eq!(before_update.free_pages, before_delete.free_pages);
+        assert_eq!(before_update.total_pages, after_delete.total_pages);
+
+        let update = mk_document! {
+            "$set": mk_document! { "content": "small" },
+        };
+        assert_eq!(db.update("test", &mk_document! { "_id": 2 }, &update).unwrap(), 1);
+        let after_update = db.stats().unwrap();
+        assert_eq!(after_update.overflow_pages, 0);
+        assert_eq!(after_update.free_pages, before_update.free_pages + 3);
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let mut db = create_and_return_db_with_items("test-checkpoint", 100);
+
+        let mut journal_path = env::temp_dir();
+        journal_path.push("test-checkpoint.db.journal");
+        assert!(std::fs::metadata(journal_path.as_path()).unwrap().len() > 64);
+
+        db.checkpoint().unwrap();
+        assert_eq!(std::fs::metadata(journal_path.as_path()).unwrap().len(), 64);
+        assert_eq!(db.collection("test").unwrap().count().unwrap(), 100);
+
+        db.start_transaction(Some(TransactionType::Write)).unwrap();
+        db.collection("test").unwrap().insert(&mut mk_document! { "content": "in transaction" }).unwrap();
+        assert!(db.checkpoint().is_err());
+        db.commit().unwrap();
+        db.checkpoint().unwrap();
+        assert_eq!(db.collection("test").unwrap().count().unwrap(), 101);
+    }
+
+    #[test]
+    fn test_dump_and_import_collection() {
+        let mut db = prepare_db("test-dump-collection");
+        db.create_collection("test").unwrap();
+        for i in 0..200 {
+            let mut doc = mk_document! {
+                "name": format!("name-{}", i),
+                "num": i,
+                "tags": mk_array![i, "tag"],
+                "nested": mk_document! {
+                    "big": (i as i64) << 40,
+                    "double": i as f64 / 2.0,
+                },
+            };
+            db.insert("test", &mut doc).unwrap();
+        }
+
+        let mut buffer = Vec::new();
+        db.dump_collection("test", &mut buffer).unwrap();
+
+        // the first record is framed by its length
+        let first_len = u32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as usize;
+        assert!(Document::from_standard_bytes(&buffer[0..first_len]).is_ok());
+
+        assert_eq!(db.import_collection("restored", buffer.as_slice()).unwrap(), 200);
+
+        let origin = db.find("test", &mk_document! {}).unwrap();
+        let restored = db.find("restored", &mk_document! {}).unwrap();
+        assert_eq!(origin.len(), restored.len());
+        for (origin, restored) in origin.iter().zip(restored.iter()) {
+            assert_eq!(origin.to_bytes().unwrap(), restored.to_bytes().unwrap());
+        }
+
+        // a broken record imports nothing
+        let truncated = &buffer[0..(buffer.len() - 3)];
+        assert!(db.import_collection("broken", truncated).is_err());
+        assert!(!db.exists_collection("broken").unwrap());
+
+        assert!(matches!(db.dump_collection("not-exist", Vec::new()), Err(DbErr::CollectionNotFound(_))));
+    }
+
+    #[test]
+    fn test_backup_to() {
+        let mut db = create_and_return_db_with_items("test-backup-origin", 1000);
+        db.create_index("test", "content").unwrap();
+        db.delete("test", &mk_document! { "content": "7" }).unwrap();
+
+        let mut backup_path = env::temp_dir();
+        backup_path.push("test-backup-target.db");
+        let _ = std::fs::remove_file(backup_path.as_path());
+
+        // the journal is not checkpointed
+        db.backup_to(backup_path.as_path()).unwrap();
+
+        let mut db_path = env::temp_dir();
+        db_path.push("test-backup-origin.db");
+        assert!(matches!(db.backup_to(db_path.as_path()), Err(DbErr::ValidationError(_))));
+
+        db.start_transaction(Some(TransactionType::Read)).unwrap();
+        assert!(matches!(db.backup_to(backup_path.as_path()), Err(DbErr::StartTransactionInAnotherTransaction)));
+        db.rollback().unwrap();
+
+        let mut backup = Database::open(backup_path.as_path()).unwrap();
+        assert_eq!(backup.verify_integrity().unwrap(), Vec::<String>::new());
+        assert_eq!(backup.count_documents("test", &mk_document! {}).unwrap(), 999);
+        assert!(backup.find("test", &mk_document! { "content": "7" }).unwrap().is_empty());
+        assert_eq!(backup.find("test", &mk_document! { "content": "8" }).unwrap().len(), 1);
+        assert_eq!(backup.list_indexes("test").unwrap(), db.list_indexes("test").unwrap());
+
+        // the backup is independent
+        backup.insert("test", &mut mk_document! { "content": "7" }).unwrap();
+        assert!(db.find("test", &mk_document! { "content": "7" }).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_backup_memory() {
+        let mut db = Database::open_memory().unwrap();
+        db.create_collection("test").unwrap();
+        db.insert("test", &mut mk_document! { "_id": 1, "name": "memory" }).unwrap();
+
+        let mut backup_path = env::temp_dir();
+        backup_path.push("test-backup-memory.db");
+        db.backup_to(backup_path.as_path()).unwrap();
+
+        let mut backup = Database::open(backup_path.as_path()).unwrap();
+        assert_eq!(backup.find_by_id("test", &Value::from(1)).unwrap().unwrap().get("name").unwrap(), &Value::from("memory"));
+    }
+
+    #[test]
+    fn test_query_cursor() {
+        let mut db = prepare_db("test-query-cursor");
+        db.create_collection("test").unwrap();
+        for i in 0..1000 {
+            db.insert("test", &mut mk_document! { "_id": i, "num": i % 10 }).unwrap();
+        }
+
+        let ids: Vec<Value> = db.query("test", &mk_document! { "num": 3 }).unwrap()
+            .map(|doc| doc.unwrap().get("_id").unwrap().clone())
+            .take(5)
+            .collect();
+        assert_eq!(ids, vec![Value::from(3), Value::from(13), Value::from(23), Value::from(33), Value::from(43)]);
+
+        // the transaction is finished when the cursor is dropped
+        db.insert("test", &mut mk_document! { "_id": 1000, "num": 3 }).unwrap();
+
+        let mut cursor = db.query("test", &mk_document! { "num": 3 }).unwrap();
+        assert_eq!(cursor.count_remaining().unwrap(), 101);
+        assert_eq!(cursor.by_ref().take(2).count(), 2);
+        assert_eq!(cursor.count_remaining().unwrap(), 99);
+        assert_eq!(cursor.next().unwrap().unwrap().get("_id").unwrap(), &Value::from(23));
+        assert_eq!(cursor.by_ref().count(), 98);
+        assert!(cursor.next().is_none());
+        assert_eq!(cursor.count_remaining().unwrap(), 0);
+
+        // rewind to the beginning after the cursor is exhausted
+        cursor.reset();
+        assert_eq!(cursor.count_remaining().unwrap(), 101);
+        assert_eq!(cursor.next().unwrap().unwrap().get("_id").unwrap(), &Value::from(3));
+        assert_eq!(cursor.by_ref().count(), 100);
+        drop(cursor);
+
+        let mut cursor = db.query("test", &mk_document! {}).unwrap();
+        assert_eq!(cursor.count_remaining().unwrap(), 1001);
+        assert_eq!(cursor.by_ref().take(10).count(), 10);
+        assert_eq!(cursor.count_remaining().unwrap(), 991);
+        assert_eq!(cursor.next().unwrap().unwrap().get("_id").unwrap(), &Value::from(10));
+        cursor.reset();
+        assert_eq!(cursor.by_ref().count(), 1001);
+        drop(cursor);
+
+        db.insert("test", &mut mk_document! { "_id": 1001, "num": 4 }).unwrap();
+
+        let count = db.query("test", &mk_document! { "num": mk_document! { "$gte": 8 } }).unwrap()
+            .filter(|doc| doc.as_ref().unwrap().get("_id").unwrap().unwrap_int() < 100)
+            .count();
+        assert_eq!(count, 20);
+
+        assert!(matches!(db.query("not-exist", &mk_document! {}), Err(DbErr::CollectionNotFound(_))));
+    }
+
+    #[test]
+    fn test_distinct() {
+        let mut db = prepare_db("test-distinct");
+        db.create_collection("test").unwrap();
+        for i in 0..100 {
+            db.insert("test", &mut mk_document! { "_id": i, "num": i % 5, "info": mk_document! { "name": "n" } }).unwrap();
+        }
+        db.insert("test", &mut mk_document! { "_id": 100, "num": 3.0 }).unwrap();
+        db.insert("test", &mut mk_document! { "_id": 101, "num": "text" }).unwrap();
+        db.insert("test", &mut mk_document! { "_id": 102, "num": mk_array![7, 1] }).unwrap();
+        db.insert("test", &mut mk_document! { "_id": 103 }).unwrap();
+
+        let values = db.distinct("test", "num", &mk_document! {}).unwrap();
+        assert_eq!(values, vec![
+            Value::from(0), Value::from(1), Value::from(2), Value::from(3),
+            Value::from(4), Value::from(7), Value::from("text"),
+        ]);
+
+        let values = db.distinct("test", "num", &mk_document! { "_id": mk_document! { "$lt": 3 } }).unwrap();
+        assert_eq!(values, vec![Value::from(0), Value::from(1), Value::from(2)]);
+
+        let values = db.distinct("test", "info.name", &mk_document! {}).unwrap();
+        assert_eq!(values, vec![Value::from("n")]);
+
+        assert!(db.distinct("test", "not-exist", &mk_document! {}).unwrap().is_empty());
+        assert!(matches!(db.distinct("not-exist", "num", &mk_document! {}), Err(DbErr::CollectionNotFound(_))));
+    }
+
+    #[test]
+    fn test_group_count_and_sum() {
+        let mut db = prepare_db("test-group-count-and-sum");
+        db.create_collection("test").unwrap();
+        for i in 0..30 {
+            db.insert("test", &mut mk_document! { "_id": i, "kind": i % 3, "amount": i }).unwrap();
+        }
+        db.insert("test", &mut mk_document! { "_id": 30, "kind": 1, "amount": 0.5 }).unwrap();
+        db.insert("test", &mut mk_document! { "_id": 31, "kind": 2, "amount": "text" }).unwrap();
+        db.insert("test", &mut mk_document! { "_id": 32, "amount": 7 }).unwrap();
+
+        let counts = db.group_count("test", "kind").unwrap();
+        assert_eq!(counts, vec![
+            (Value::Null, 1),
+            (Value::from(0), 10),
+            (Value::from(1), 11),
+            (Value::from(2), 11),
+        ]);
+
+        let sums = db.group_sum("test", "kind", "amount").unwrap();
+        assert_eq!(sums, vec![
+            (Value::Null, Value::from(7)),
+            (Value::from(0), Value::from(135)),
+            (Value::from(1), Value::from(145.5)),
+            (Value::from(2), Value::from(155)),
+        ]);
+
+        assert!(matches!(db.group_count("not-exist", "kind"), Err(DbErr::CollectionNotFound(_))));
+    }
+
+    #[test]
+    fn test_update_numeric_operators() {
+        let mut db = prepare_db("test-update-numeric-operators");
+        db.create_collection("test").unwrap();
+        db.insert("test", &mut mk_document! { "_id": 1, "views": 10, "price": 2.5, "name": "a" }).unwrap();
+
+        let find_doc = |db: &mut Database| db.find_by_id("test", &Value::from(1)).unwrap().unwrap();
+
+        db.update("test", &mk_document! {}, &mk_document! {
+            "$inc": mk_document! { "views": 1, "price": 1, "clicks": 5 },
+        }).unwrap();
+        let doc = find_doc(&mut db);
+        assert_eq!(doc.get("views").unwrap(), &Value::Int(11));
+        assert_eq!(doc.get("price").unwrap(), &Value::Double(3.5));
+        assert_eq!(doc.get("clicks").unwrap(), &Value::Int(5));
+
+        db.update("test", &mk_document! {}, &mk_document! {
+            "$mul": mk_document! { "views": 0.5, "price": 2, "missing": 3 },
+        }).unwrap();
+        let doc = find_doc(&mut db);
+        assert_eq!(doc.get("views").unwrap(), &Value::Double(5.5));
+        assert_eq!(doc.get("price").unwrap(), &Value::Double(7.0));
+        assert_eq!(doc.get("missing").unwrap(), &Value::Int(0));
+
+        db.update("test", &mk_document! {}, &mk_document! {
+            "$min": mk_document! { "views": 3, "low": 1 },
+            "$max": mk_document! { "price": 6, "clicks": 9, "high": 2 },
+        }).unwrap();
+        let doc = find_doc(&mut db);
+        assert_eq!(doc.get("views").unwrap(), &Value::Int(3));
+        assert_eq!(doc.get("low").unwrap(), &Value::Int(1));
+        assert_eq!(doc.get("price").unwrap(), &Value::Double(7.0));
+        assert_eq!(doc.get("clicks").unwrap(), &Value::Int(9));
+        assert_eq!(doc.get("high").unwrap(), &Value::Int(2));
+
+        // promoted to double instead of overflowing
+        db.update("test", &mk_document! {}, &mk_document! {
+            "$set": mk_document! { "big": i64::MAX },
+        }).unwrap();
+        db.update("test", &mk_document! {}, &mk_document! {
+            "$inc": mk_document! { "big": 1 },
+        }).unwrap();
+        let doc = find_doc(&mut db);
+        assert_eq!(doc.get("big").unwrap(), &Value::Double(i64::MAX as f64 + 1.0));
+
+        let result = db.update("test", &mk_document! {}, &mk_document! {
+            "$inc": mk_document! { "name": 1 },
+        });
+        assert!(matches!(result, Err(DbErr::FieldTypeUnexpected(_))));
+        let result = db.update("test", &mk_document! {}, &mk_document! {
+            "$inc": mk_document! { "other": "1" },
+        });
+        assert!(matches!(result, Err(DbErr::FieldTypeUnexpected(_))));
+    }
+
+    #[test]
+    fn test_update_unset_and_rename() {
+        let mut db = prepare_db("test-update-unset-and-rename");
+        db.create_collection("test").unwrap();
+        db.insert("test", &mut mk_document! { "_id": 1, "a": 1, "b": 2, "c": 3 }).unwrap();
+
+        db.update("test", &mk_document! {}, &mk_document! {
+            "$unset": mk_document! { "a": "", "not-exist": "" },
+        }).unwrap();
+        let doc = db.find_by_id("test", &Value::from(1)).unwrap().unwrap();
+        assert!(doc.get("a").is_none());
+        assert_eq!(doc.len(), 3);
+
+        // overwrite the existing field, and renaming a missing field is a no-op
+        db.update("test", &mk_document! {}, &mk_document! {
+            "$rename": mk_document! { "b": "c", "not-exist": "d" },
+        }).unwrap();
+        let doc = db.find_by_id("test", &Value::from(1)).unwrap().unwrap();
+        assert!(doc.get("b").is_none());
+        assert!(doc.get("d").is_none());
+        assert_eq!(doc.get("c").unwrap(), &Value::Int(2));
+
+        let result = db.update("test", &mk_document! {}, &mk_document! {
+            "$rename": mk_document! { "c": "c" },
+        });
+        assert!(matches!(result, Err(DbErr::InvalidQuery(_))));
+        let result = db.update("test", &mk_document! {}, &mk_document! {
+            "$rename": mk_document! { "c": "_id" },
+        });
+        assert!(matches!(result, Err(DbErr::UnableToUpdatePrimaryKey)));
+        let result = db.update("test", &mk_document! {}, &mk_document! {
+            "$unset": mk_document! { "_id": "" },
+        });
+        assert!(matches!(result, Err(DbErr::UnableToUpdatePrimaryKey)));
+
+        let doc = db.find_by_id("test", &Value::from(1)).unwrap().unwrap();
+        assert_eq!(doc.get("c").unwrap(), &Value::Int(2));
+    }
+
+    #[test]
+    fn test_update_push_and_pull() {
+        let mut db = prepare_db("test-update-push-and-pull");
+        db.create_collection("test").unwrap();
+        db.insert("test", &mut mk_document! { "_id": 1, "tags": mk_array!["a", "x", "b", "x"], "name": "n" }).unwrap();
+
+        db.update("test", &mk_document! {}, &mk_document! {
+            "$push": mk_document! { "tags": "c", "new_tags": 1 },
+        }).unwrap();
+        let doc = db.find_by_id("test", &Value::from(1)).unwrap().unwrap();
+        assert_eq!(doc.get("tags").unwrap(), &Value::from(mk_array!["a", "x", "b", "x", "c"]));
+        assert_eq!(doc.get("new_tags").unwrap(), &Value::from(mk_array![1]));
+
+        db.update("test", &mk_document! {}, &mk_document! {
+            "$pull": mk_document! { "tags": "x", "new_tags": 1.0, "not-exist": 1 },
+        }).unwrap();
+        let doc = db.find_by_id("test", &Value::from(1)).unwrap().unwrap();
+        assert_eq!(doc.get("tags").unwrap(), &Value::from(mk_array!["a", "b", "c"]));
+        assert_eq!(doc.get("new_tags").unwrap(), &Value::from(Array::new()));
+        assert!(doc.get("not-exist").is_none());
+
+        let result = db.update("test", &mk_document! {}, &mk_document! {
+            "$push": mk_document! { "name": "x" },
+        });
+        assert!(matches!(result, Err(DbErr::FieldTypeUnexpected(_))));
+        let result = db.update("test", &mk_document! {}, &mk_document! {
+            "$pull": mk_document! { "name": "n" },
+        });
+        assert!(matches!(result, Err(DbErr::FieldTypeUnexpected(_))));
+    }
+
+    #[test]
+    fn test_estimated_document_count() {
+        let mut db = prepare_db("test-estimated-document-count");
+        db.create_collection("test").unwrap();
+        assert_eq!(db.estimated_document_count("test").unwrap(), Some(0));
+
+        for i in 0..100 {
+            db.insert("test", &mut mk_document! { "_id": i }).unwrap();
+        }
+        db.delete("test", &mk_document! { "_id": mk_document! { "$lt": 10 } }).unwrap();
+        assert_eq!(db.estimated_document_count("test").unwrap(), Some(90));
+
+        db.start_transaction(None).unwrap();
+        db.insert("test", &mut mk_document! { "_id": 100 }).unwrap();
+        db.rollback().unwrap();
+        assert_eq!(db.estimated_document_count("test").unwrap(), Some(90));
+
+        assert!(matches!(db.estimated_document_count("not-exist"), Err(DbErr::CollectionNotFound(_))));
+    }
+
+    #[test]
+    fn test_replace_one() {
+        let mut db = prepare_db("test-replace-one");
+        db.create_collection("test").unwrap();
+        db.create_index("test", "name").unwrap();
+        for i in 0..10 {
+            db.insert("test", &mut mk_document! { "_id": i, "name": format!("n{}", i), "age": i }).unwrap();
+        }
+
+        let big_content = "x".repeat(10000);
+        let replaced = db.replace_one("test", &mk_document! { "age": 3 }, Rc::new(mk_document! {
+            "_id": 100,
+            "name": "big",
+            "content": big_content.clone(),
+        })).unwrap();
+        assert!(replaced);
+
+        let doc = db.find_by_id("test", &Value::from(3)).unwrap().unwrap();
+        assert_eq!(doc.get("name").unwrap().unwrap_string(), "big");
+        assert_eq!(doc.get("content").unwrap().unwrap_string(), big_content);
+        assert!(doc.get("age").is_none());
+        assert!(db.find_by_id("test", &Value::from(100)).unwrap().is_none());
+
+        // the old index entry is removed
+        assert!(db.find("test", &mk_document! { "name": "n3" }).unwrap().is_empty());
+        assert_eq!(db.find("test", &mk_document! { "name": "big" }).unwrap().len(), 1);
+
+        // replaced with a smaller one, the overflow pages of the old one are freed
+        let replaced = db.replace_one("test", &mk_document! { "name": "big" }, Rc::new(mk_document! {
+            "name": "small",
+        })).unwrap();
+        assert!(replaced);
+        let doc = db.find_by_id("test", &Value::from(3)).unwrap().unwrap();
+        assert_eq!(doc.get("_id").unwrap(), &Value::from(3));
+        assert!(doc.get("content").is_none());
+
+        assert!(!db.replace_one("test", &mk_document! { "age": 100 }, Rc::new(mk_document! {})).unwrap());
+        assert_eq!(db.count_documents("test", &mk_document! {}).unwrap(), 10);
+        assert!(db.verify_integrity().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_sync_policy() {
+        let db = prepare_db("test-sync-policy-default");
+        assert_eq!(db.sync_policy(), SyncPolicy::Normal);
+        drop(db);
+
+        for (name, policy) in [("test-sync-policy-full", SyncPolicy::Full), ("test-sync-policy-off", SyncPolicy::Off)] {
+            let config = Config {
+                journal_full_size: 10,
+                sync_policy: policy,
+                ..Default::default()
+            };
+            let mut db = prepare_db_with_config(name, config);
+            assert_eq!(db.sync_policy(), policy);
+
+            db.create_collection("test").unwrap();
+            for i in 0..50 {
+                db.insert("test", &mut mk_document! { "_id": i }).unwrap();
+            }
+            drop(db);
+
+            let mut db_path = env::temp_dir();
+            db_path.push(String::from(name) + ".db");
+            let mut db = Database::open(db_path).unwrap();
+            assert_eq!(db.count_documents("test", &mk_document! {}).unwrap(), 50);
+        }
+    }
+
+    fn check_snapshot(db_name: &str, journal_mode: JournalMode) {
+        let config = Config {
+            journal_mode,
+            journal_full_size: 10,
+            ..Default::default()
+        };
+        let mut db = prepare_db_with_config(db_name, config);
+        db.create_collection("test").unwrap();
+        for i in 0..100 {
+            db.insert("test", &mut mk_document! { "_id": i, "num": i % 10 }).unwrap();
+        }
+
+        let snapshot = db.snapshot().unwrap();
+
+        // the writes after the snapshot, the journal is full in the write-ahead log
+        for i in 100..200 {
+            db.insert("test", &mut mk_document! { "_id": i, "num": i }).unwrap();
+        }
+        db.update("test", &mk_document! { "_id": 3 }, &mk_document! {
+            "$set": mk_document! { "num": 100 },
+        }).unwrap();
+        db.delete("test", &mk_document! { "_id": 4 }).unwrap();
+        db.create_collection("other").unwrap();
+
+        let mut count = 0;
+        let mut cursor = snapshot.query(&mut db, "test", &mk_document! {}).unwrap();
+        for doc in cursor.by_ref().take(50) {
+            doc.unwrap();
+            count += 1;
+        }
+        assert_eq!(cursor.count_remaining().unwrap(), 50);
+        drop(cursor);
+        assert_eq!(count, 50);
+
+        // written between the reads of the snapshot
+        db.insert("test", &mut mk_document! { "_id": 200 }).unwrap();
+
+        assert_eq!(snapshot.count_documents(&mut db, "test", &mk_document! {}).unwrap(), 100);
+        assert_eq!(snapshot.distinct(&mut db, "test", "num", &mk_document! {}).unwrap().len(), 10);
+        let doc = snapshot.find_by_id(&mut db, "test", &Value::from(3)).unwrap().unwrap();
+        assert_eq!(doc.get("num").unwrap().unwrap_int(), 3);
+        assert!(snapshot.find_by_id(&mut db, "test", &Value::from(4)).unwrap().is_some());
+        assert!(snapshot.find_one(&mut db, "test", &mk_document! { "_id": 150 }).unwrap().is_none());
+        assert!(matches!(snapshot.find(&mut db, "other", &mk_document! {}), Err(DbErr::CollectionNotFound(_))));
+
+        // the database sees the newest state
+        assert_eq!(db.count_documents("test", &mk_document! {}).unwrap(), 200);
+        let doc = db.find_by_id("test", &Value::from(3)).unwrap().unwrap();
+        assert_eq!(doc.get("num").unwrap().unwrap_int(), 100);
+        assert_eq!(db.find("other", &mk_document! {}).unwrap().len(), 0);
+
+        // the checkpoints are held off
+        assert!(matches!(db.checkpoint(), Err(DbErr::Busy)));
+
+        let mut other = Database::open_memory().unwrap();
+        other.create_collection("test").unwrap();
+        assert!(matches!(snapshot.count_documents(&mut other, "test", &mk_document! {}), Err(DbErr::SnapshotNotOfDatabase)));
+
+        db.start_transaction(Some(TransactionType::Write)).unwrap();
+        assert!(matches!(snapshot.count_documents(&mut db, "test", &mk_document! {}), Err(DbErr::StartTransactionInAnotherTransaction)));
+        db.rollback().unwrap();
+
+        drop(snapshot);
+        db.checkpoint().unwrap();
+        assert_eq!(db.count_documents("test", &mk_document! {}).unwrap(), 200);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        check_snapshot("test-snapshot", JournalMode::Wal);
+    }
+
+    #[test]
+    fn test_snapshot_shadow() {
+        check_snapshot("test-snapshot-shadow", JournalMode::Shadow);
+    }
+
+    #[test]
+    fn test_checkpoint_interval() {
+        let mut journal_path = env::temp_dir();
+        journal_path.push("test-checkpoint-interval.db.journal");
+
+        // checkpointed at every commit
+        let config = Config {
+            journal_full_size: 10000,
+            checkpoint_interval: Some(Duration::ZERO),
+            ..Default::default()
+        };
+        let mut db = prepare_db_with_config("test-checkpoint-interval", config);
+        db.create_collection("test").unwrap();
+        for i in 0..10 {
+            db.collection("test").unwrap().insert(&mut mk_document! { "_id": i }).unwrap();
+            assert_eq!(std::fs::metadata(journal_path.as_path()).unwrap().len(), 64);
+        }
+        drop(db);
+
+        // the commits are batched until the interval is elapsed
+        let config = Config {
+            journal_full_size: 10000,
+            checkpoint_interval: Some(Duration::from_secs(3600)),
+            ..Default::default()
+        };
+        let mut db = prepare_db_with_config("test-checkpoint-interval", config);
+        db.create_collection("test").unwrap();
+        for i in 0..10 {
+            db.collection("test").unwrap().insert(&mut mk_document! { "_id": i }).unwrap();
+        }
+        assert!(std::fs::metadata(journal_path.as_path()).unwrap().len() > 64);
+        assert_eq!(db.collection("test").unwrap().count().unwrap(), 10);
+    }
+
+    #[test]
+    fn test_open_read_only() {
+        let db = create_and_return_db_with_items("test-read-only", 100);
+        let committed_path = copy_db_files_as_crashed("test-read-only", "test-read-only-committed");
+        drop(db);
+
+        let mut db_path = env::temp_dir();
+        db_path.push("test-read-only.db");
+        let mut journal_path = env::temp_dir();
+        journal_path.push("test-read-only.db.journal");
+        assert!(!journal_path.exists());
+
+        let mut db = Database::open_read_only(db_path.as_path()).unwrap();
+        assert_eq!(db.collection("test").unwrap().count().unwrap(), 100);
+        assert_eq!(db.find("test", &mk_document! { "content": "1" }).unwrap().len(), 1);
+
+        assert!(matches!(db.create_collection("test-2"), Err(DbErr::ReadOnly)));
+        assert!(matches!(db.collection("test-2"), Err(DbErr::ReadOnly)));
+        assert!(matches!(db.collection("test").unwrap().insert(&mut mk_document! { "content": "new" }), Err(DbErr::ReadOnly)));
+        assert!(matches!(db.update("test", &mk_document! {}, &mk_document! {
+            "$set": mk_document! { "content": "changed" },
+        }), Err(DbErr::ReadOnly)));
+        assert!(matches!(db.delete("test", &mk_document! {}), Err(DbErr::ReadOnly)));
+        assert!(matches!(db.start_transaction(Some(TransactionType::Write)), Err(DbErr::ReadOnly)));
+        assert!(matches!(db.checkpoint(), Err(DbErr::ReadOnly)));
+        assert_eq!(db.collection("test").unwrap().count().unwrap(), 100);
+
+        // another reader of the same file
+        let mut db2 = Database::open_read_only(db_path.as_path()).unwrap();
+        assert_eq!(db2.collection("test").unwrap().count().unwrap(), 100);
+        drop(db2);
+        drop(db);
+        assert!(!journal_path.exists());
+
+        // the committed frames in the journal are visible, the journal is untouched
+        let mut committed_journal_path = committed_path.clone();
+        committed_journal_path.set_file_name("test-read-only-committed.db.journal");
+        let journal_len = std::fs::metadata(committed_journal_path.as_path()).unwrap().len();
+        let db_len = std::fs::metadata(committed_path.as_path()).unwrap().len();
+        assert!(journal_len > 64);
+
+        let mut db = Database::open_read_only(committed_path.as_path()).unwrap();
+        assert_eq!(db.collection("test").unwrap().count().unwrap(), 100);
+        drop(db);
+        assert_eq!(std::fs::metadata(committed_journal_path.as_path()).unwrap().len(), journal_len);
+        assert_eq!(std::fs::metadata(committed_path.as_path()).unwrap().len(), db_len);
+
+        let mut not_exist_path = env::temp_dir();
+        not_exist_path.push("test-read-only-not-exist.db");
+        let _ = std::fs::remove_file(not_exist_path.as_path());
+        assert!(Database::open_read_only(not_exist_path.as_path()).is_err());
+        assert!(!not_exist_path.exists());
+    }
+
+    #[test]
+    fn test_open_memory() {
+        let mut db = Database::open_memory().unwrap();
+        let mut collection = db.create_collection("test").unwrap();
+        for i in 0..2000 {
+            collection.insert(&mut mk_document! { "_id": i, "content": i.to_string() }).unwrap();
+        }
+        assert_eq!(collection.count().unwrap(), 2000);
+
+        let doc = db.find_by_id("test", &Value::from(1500)).unwrap().unwrap();
+        assert_eq!(doc.get("content").unwrap().unwrap_string(), "1500");
+
+        assert_eq!(db.update("test", &mk_document! { "_id": 10 }, &mk_document! {
+            "$set": mk_document! { "content": "changed" },
+        }).unwrap(), 1);
+        assert_eq!(db.delete("test", &mk_document! {
+            "_id": mk_document! { "$lt": 1000 },
+        }).unwrap(), 1000);
+
+        let mut txn = db.transaction().unwrap();
+        txn.collection("test").unwrap().insert(&mut mk_document! { "_id": -1 }).unwrap();
+        txn.rollback().unwrap();
+        assert_eq!(db.collection("test").unwrap().count().unwrap(), 1000);
+
+        db.checkpoint().unwrap();
+        db.compact().unwrap();
+        assert_eq!(db.collection("test").unwrap().count().unwrap(), 1000);
+        assert!(db.find_by_id("test", &Value::from(10)).unwrap().is_none());
+        assert_eq!(db.stats().unwrap().free_pages, 0);
+
+        drop(db);
+        assert!(!std::path::Path::new(":memory:").exists());
+        assert!(!std::path::Path::new(":memory:.journal").exists());
+    }
+
+    #[test]
+    fn test_find_in_and_nin() {
+        let mut db = prepare_db("test-find-in-nin");
+        let mut collection = db.create_collection("test").unwrap();
+
+        let statuses = [Value::from("a"), Value::from("b"), Value::from("c"), Value::from(1), mk_array![ "a" ].into()];
+        for (i, status) in statuses.iter().enumerate() {
+            collection.insert(&mut mk_document! { "_id": i, "status": status.clone() }).unwrap();
+        }
+        collection.insert(&mut mk_document! { "_id": 5 }).unwrap();
+
+        let ids_of = |docs: Vec<Rc<Document>>| -> Vec<i64> {
+            docs.iter().map(|doc| doc.pkey_id().unwrap().unwrap_int()).collect()
+        };
+
+        let result = db.find("test", &mk_document! {
+            "status": mk_document! { "$in": mk_array![ "a", "b" ] },
+        }).unwrap();
+        assert_eq!(ids_of(result), vec![0, 1]);
+
+        // the arrays are compared by equality, the numbers of different types are equal
+        let result = db.find("test", &mk_document! {
+            "status": mk_document! { "$in": mk_array![ mk_array![ "a" ], 1.0 ] },
+        }).unwrap();
+        assert_eq!(ids_of(result), vec![3, 4]);
+
+        let result = db.find("test", &mk_document! {
+            "status": mk_document! { "$nin": mk_array![ "a", "b" ] },
+        }).unwrap();
+        assert_eq!(ids_of(result), vec![2, 3, 4, 5]);
+
+        let result = db.find("test", &mk_document! {
+            "status": mk_document! { "$nin": mk_array![ "a", "b" ] },
+            "_id": mk_document! { "$lt": 5 },
+        }).unwrap();
+        assert_eq!(ids_of(result), vec![2, 3, 4]);
+
+        let result = db.find("test", &mk_document! {
+            "status": mk_document! { "$in": mk_array![] },
+        }).unwrap();
+        assert!(result.is_empty());
+
+        assert!(db.find("test", &mk_document! {
+            "status": mk_document! { "$nin": "a" },
+        }).is_err());
+    }
+
+    #[test]
+    fn test_find_ne() {
+        let mut db = prepare_db("test-find-ne");
+        let mut collection = db.create_collection("test").unwrap();
+        for i in 0..4 {
+            collection.insert(&mut mk_document! { "_id": i, "a": i % 2 }).unwrap();
         }
-        db.commit().unwrap();
+        collection.insert(&mut mk_document! { "_id": 4 }).unwrap();
+        collection.insert(&mut mk_document! { "_id": 5, "n": mk_document! { "x": 1 } }).unwrap();
+
+        let ids_of = |docs: Vec<Rc<Document>>| -> Vec<i64> {
+            docs.iter().map(|doc| doc.pkey_id().unwrap().unwrap_int()).collect()
+        };
+
+        // the same as MongoDB, the documents missing the field match
+        let result = db.find("test", &mk_document! {
+            "a": mk_document! { "$ne": 1 },
+        }).unwrap();
+        assert_eq!(ids_of(result), vec![0, 2, 4, 5]);
+
+        let result = db.find("test", &mk_document! {
+            "a": mk_document! { "$ne": 1 },
+            "_id": mk_document! { "$lt": 4 },
+        }).unwrap();
+        assert_eq!(ids_of(result), vec![0, 2]);
+
+        let result = db.find("test", &mk_document! {
+            "n.x": mk_document! { "$ne": 1 },
+        }).unwrap();
+        assert_eq!(ids_of(result), vec![0, 1, 2, 3, 4]);
     }
 
     #[test]
-    fn test_rollback() {
-        let mut db = prepare_db("test-rollback");
+    fn test_find_with_logic_operators() {
+        let mut db = prepare_db("test-find-logic");
         let mut collection = db.create_collection("test").unwrap();
+        for i in 0..10 {
+            collection.insert(&mut mk_document! { "_id": i, "a": i % 2, "b": i % 3 }).unwrap();
+        }
+        collection.insert(&mut mk_document! { "_id": 10 }).unwrap();
 
-        assert_eq!(collection.count().unwrap(), 0);
+        let mut find_ids = |filter: Document| -> Vec<i64> {
+            db.find("test", &filter).unwrap().iter()
+                .map(|doc| doc.pkey_id().unwrap().unwrap_int()).collect()
+        };
 
-        db.start_transaction(None).unwrap();
+        assert_eq!(find_ids(mk_document! {
+            "$or": mk_array![ mk_document! { "a": 1 }, mk_document! { "b": 0 } ],
+        }), vec![0, 1, 3, 5, 6, 7, 9]);
 
-        let mut collection = db.collection("test").unwrap();
-        for i in 0..10{
-            let content = i.to_string();
-            let mut new_doc = mk_document! {
-                "_id": i,
-                "content": content,
-            };
-            collection.insert(new_doc.as_mut()).unwrap();
+        assert_eq!(find_ids(mk_document! {
+            "$and": mk_array![ mk_document! { "a": 1 }, mk_document! { "b": 0 } ],
+        }), vec![3, 9]);
+
+        // the conditions after the "$or" are checked too
+        assert_eq!(find_ids(mk_document! {
+            "$or": mk_array![ mk_document! { "a": 1 }, mk_document! { "b": 0 } ],
+            "_id": mk_document! { "$gt": 4, "$lt": 10 },
+        }), vec![5, 6, 7, 9]);
+
+        assert_eq!(find_ids(mk_document! {
+            "$and": mk_array![
+                mk_document! {
+                    "$or": mk_array![ mk_document! { "a": 0 }, mk_document! { "b": 1 } ],
+                },
+                mk_document! {
+                    "$or": mk_array![ mk_document! { "b": 1 }, mk_document! { "_id": 8 } ],
+                },
+            ],
+        }), vec![1, 4, 7, 8]);
+
+        assert_eq!(find_ids(mk_document! {
+            "$or": mk_array![
+                mk_document! {
+                    "$and": mk_array![ mk_document! { "a": 0 }, mk_document! { "b": 2 } ],
+                },
+                mk_document! { "_id": 1 },
+            ],
+        }), vec![1, 2, 8]);
+
+        // the documents missing the field match "$not"
+        assert_eq!(find_ids(mk_document! {
+            "b": mk_document! {
+                "$not": mk_document! { "$lt": 2 },
+            },
+        }), vec![2, 5, 8, 10]);
+
+        assert_eq!(find_ids(mk_document! {
+            "$not": mk_document! { "a": 0, "b": 0 },
+        }), vec![1, 2, 3, 4, 5, 7, 8, 9, 10]);
+
+        assert_eq!(find_ids(mk_document! {
+            "$not": mk_document! {
+                "$or": mk_array![ mk_document! { "a": 1 }, mk_document! { "b": 1 } ],
+            },
+            "a": mk_document! { "$gte": 0 },
+        }), vec![0, 2, 6, 8]);
+
+        let invalid_filters = vec![
+            mk_document! { "$or": mk_document! { "a": 1 } },
+            mk_document! { "$or": mk_array![] },
+            mk_document! { "$and": mk_array![ 1, 2 ] },
+            mk_document! { "$not": 1 },
+            mk_document! { "a": mk_document! { "$not": 1 } },
+        ];
+        for filter in invalid_filters {
+            assert!(matches!(db.find("test", &filter), Err(DbErr::InvalidQuery(_))));
         }
-        assert_eq!(collection.count().unwrap(), 10);
+    }
 
-        db.rollback().unwrap();
+    #[test]
+    fn test_find_exists() {
+        let mut db = prepare_db("test-find-exists");
+        let mut collection = db.create_collection("test").unwrap();
+        collection.insert(&mut mk_document! { "_id": 0, "name": "Vincent" }).unwrap();
+        collection.insert(&mut mk_document! { "_id": 1, "name": Value::Null }).unwrap();
+        collection.insert(&mut mk_document! { "_id": 2 }).unwrap();
+        collection.insert(&mut mk_document! { "_id": 3, "info": mk_document! { "name": "Chan" } }).unwrap();
 
-        let mut collection = db.collection("test").unwrap();
-        assert_eq!(collection.count().unwrap(), 0);
+        let mut find_ids = |filter: Document| -> Vec<i64> {
+            db.find("test", &filter).unwrap().iter()
+                .map(|doc| doc.pkey_id().unwrap().unwrap_int()).collect()
+        };
+
+        assert_eq!(find_ids(mk_document! {
+            "name": mk_document! { "$exists": true },
+        }), vec![0, 1]);
+        assert_eq!(find_ids(mk_document! {
+            "name": mk_document! { "$exists": false },
+        }), vec![2, 3]);
+        assert_eq!(find_ids(mk_document! {
+            "info.name": mk_document! { "$exists": true },
+        }), vec![3]);
+        assert_eq!(find_ids(mk_document! {
+            "name": mk_document! { "$exists": false },
+            "info": mk_document! { "$exists": false },
+        }), vec![2]);
+        assert_eq!(find_ids(mk_document! {
+            "$or": mk_array![
+                mk_document! { "name": mk_document! { "$exists": false } },
+                mk_document! { "name": "Vincent" },
+            ],
+        }), vec![0, 2, 3]);
+
+        assert!(matches!(db.find("test", &mk_document! {
+            "name": mk_document! { "$exists": 1 },
+        }), Err(DbErr::InvalidQuery(_))));
     }
 
     #[test]
-    fn test_create_collection_with_number_pkey() {
-        let mut db = {
-            let mut db = prepare_db("test-number-pkey");
-            let mut collection = db.create_collection("test").unwrap();
+    #[cfg(feature = "regex")]
+    fn test_find_regex() {
+        let mut db = prepare_db("test-find-regex");
+        let mut collection = db.create_collection("test").unwrap();
+        let names = [Value::from("John"), Value::from("Joe"), Value::from("Vincent"), Value::from(10), Value::Null];
+        for (i, name) in names.iter().enumerate() {
+            collection.insert(&mut mk_document! { "_id": i, "name": name.clone() }).unwrap();
+        }
 
-            for i in 0..TEST_SIZE {
-                let content = i.to_string();
-                let mut new_doc = mk_document! {
-                    "_id": i,
-                    "content": content,
-                };
-                collection.insert(new_doc.as_mut()).unwrap();
-            }
+        let result = db.find("test", &mk_document! {
+            "name": mk_document! { "$regex": "^Jo" },
+        }).unwrap();
+        let ids: Vec<i64> = result.iter().map(|doc| doc.pkey_id().unwrap().unwrap_int()).collect();
+        assert_eq!(ids, vec![0, 1]);
 
-            db
+        let result = db.find("test", &mk_document! {
+            "name": mk_document! { "$regex": "n$" },
+            "_id": mk_document! { "$gt": 0 },
+        }).unwrap();
+        assert!(result.is_empty());
+
+        let err = db.find("test", &mk_document! {
+            "name": mk_document! { "$regex": "(Jo" },
+        }).unwrap_err();
+        assert!(matches!(err, DbErr::InvalidQuery(msg) if msg.contains("unclosed group")));
+    }
+
+    #[test]
+    fn test_exists_collection() {
+        let mut db = prepare_db("test-exists-collection");
+        assert!(!db.exists_collection("test").unwrap());
+
+        db.create_collection("test").unwrap();
+        db.create_collection("test-2").unwrap();
+        assert!(db.exists_collection("test").unwrap());
+        assert!(db.exists_collection("test-2").unwrap());
+        assert!(!db.exists_collection("test-3").unwrap());
+
+        assert!(matches!(db.create_collection("test"), Err(DbErr::CollectionAlreadyExists(name)) if name == "test"));
+        assert_eq!(db.ctx.query_all_meta().unwrap().len(), 2);
+
+        assert!(db.delete_collection("test").unwrap());
+        assert!(!db.exists_collection("test").unwrap());
+        db.create_collection("test").unwrap();
+        assert!(db.exists_collection("test").unwrap());
+    }
+
+    #[test]
+    fn test_rename_collection() {
+        let mut db = create_and_return_db_with_items("test-rename-collection", 100);
+        db.create_index("test", "content").unwrap();
+        db.create_collection("test-2").unwrap();
+
+        assert!(matches!(db.rename_collection("test", "test-2"), Err(DbErr::CollectionAlreadyExists(_))));
+        assert!(matches!(db.rename_collection("not-exist", "test-3"), Err(DbErr::CollectionNotFound(_))));
+
+        db.rename_collection("test", "renamed").unwrap();
+        assert!(!db.exists_collection("test").unwrap());
+        assert!(db.exists_collection("renamed").unwrap());
+        assert_eq!(db.count_documents("renamed", &mk_document! {}).unwrap(), 100);
+        assert_eq!(db.find("renamed", &mk_document! { "content": "42" }).unwrap().len(), 1);
+        assert_eq!(db.list_indexes("renamed").unwrap(), vec![String::from("content")]);
+
+        drop(db);
+
+        let mut db_path = env::temp_dir();
+        db_path.push("test-rename-collection.db");
+        let mut db = Database::open(db_path.as_path()).unwrap();
+        assert!(db.exists_collection("renamed").unwrap());
+        assert!(!db.exists_collection("test").unwrap());
+        assert_eq!(db.count_documents("renamed", &mk_document! {}).unwrap(), 100);
+        db.create_collection("test").unwrap();
+        assert_eq!(db.count_documents("test", &mk_document! {}).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_max_document_size() {
+        let config = Config {
+            max_document_size: 10000,
+            ..Default::default()
         };
+        let mut db = prepare_db_with_config("test-max-document-size", config);
+        assert_eq!(db.max_document_size(), 10000);
+        assert_eq!(Database::open_memory().unwrap().max_document_size(), 16 * 1024 * 1024);
 
-        let mut collection = db.collection("test").unwrap();
+        let mut collection = db.create_collection("test").unwrap();
+        collection.insert(&mut mk_document! { "_id": 0, "content": "a".repeat(9000) }).unwrap();
 
-        let count = collection.count().unwrap();
-        assert_eq!(TEST_SIZE, count as usize);
+        let result = collection.insert(&mut mk_document! { "_id": 1, "content": "a".repeat(10000) });
+        assert!(matches!(result, Err(DbErr::DocumentTooLarge { size, max: 10000 }) if size > 10000));
 
-        let all = collection.find_all( ).unwrap();
+        let result = db.update("test", &mk_document! { "_id": 0 }, &mk_document! {
+            "$set": mk_document! { "content": "b".repeat(20000) },
+        });
+        assert!(matches!(result, Err(DbErr::DocumentTooLarge { .. })));
 
-        assert_eq!(TEST_SIZE, all.len())
+        let found = db.find_by_id("test", &Value::from(0)).unwrap().unwrap();
+        assert_eq!(found.get("content").unwrap().unwrap_string().len(), 9000);
+        assert_eq!(db.count_documents("test", &mk_document! {}).unwrap(), 1);
     }
 
     #[test]
-    fn test_find() {
-        let mut db = create_and_return_db_with_items("test-find", TEST_SIZE);
-        let mut collection = db.collection("test").unwrap();
+    fn test_find_with_range_operators() {
+        let mut db = prepare_db("test-find-range");
+        let mut collection = db.create_collection("test").unwrap();
 
-        let result = collection.find(
-            &mk_document! {
-                "content": "3",
-            }
-        ).unwrap();
+        for i in 0..30 {
+            let mut doc = mk_document! {
+                "age": i,
+            };
+            collection.insert(doc.as_mut()).unwrap();
+        }
+        let mut doc = mk_document! {
+            "age": "unknown",
+        };
+        collection.insert(doc.as_mut()).unwrap();
 
-        assert_eq!(result.len(), 1);
+        let result = db.find("test", &mk_document! {
+            "age": mk_document! {
+                "$gt": 18,
+            },
+        }).unwrap();
+        assert_eq!(result.len(), 11);
 
-        let one = result[0].clone();
-        assert_eq!(one.get("content").unwrap().unwrap_string(), "3");
+        let result = db.find("test", &mk_document! {
+            "age": mk_document! {
+                "$gte": 10,
+                "$lt": 20,
+            },
+        }).unwrap();
+        assert_eq!(result.len(), 10);
+
+        let result = db.find("test", &mk_document! {
+            "age": mk_document! {
+                "$lte": 2.5,
+            },
+        }).unwrap();
+        assert_eq!(result.len(), 3);
+
+        let result = db.find("test", &mk_document! {
+            "age": mk_document! {
+                "$gt": "a",
+            },
+        }).unwrap();
+        assert_eq!(result.len(), 1);
     }
 
     #[test]
@@ -564,11 +4274,377 @@ mod tests {
             collection.insert(data.as_mut()).unwrap();
         }
 
+        // the values of different types are ordered by the types
         let mut data = mk_document! {
             "name": "what",
             "user_id": 3,
         };
-        collection.insert(data.as_mut()).expect_err("not comparable");
+        collection.insert(data.as_mut()).unwrap();
+
+        let result = collection.find(&mk_document! { "user_id": 3 }).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].get("name").unwrap(), &Value::from("what"));
+        let result = collection.find(&mk_document! { "user_id": "3" }).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].get("name").unwrap(), &Value::from("3"));
+    }
+
+    #[test]
+    fn test_index_dotted_field() {
+        let mut db = prepare_db("test-index-dotted-field");
+        let mut collection = db.create_collection("test").unwrap();
+        for i in 0..10 {
+            collection.insert(&mut mk_document! { "_id": i, "n": mk_document! { "x": i % 5 } }).unwrap();
+        }
+        collection.insert(&mut mk_document! { "_id": 10, "n": 3 }).unwrap();
+
+        // the existing documents are indexed, and the new ones
+        db.create_index("test", "n.x").unwrap();
+        db.collection("test").unwrap().insert(&mut mk_document! { "_id": 11, "n": mk_document! { "x": 3 } }).unwrap();
+
+        let filter = mk_document! { "n.x": 3 };
+        assert_eq!(db.explain("test", &filter).unwrap(), QueryPlan::Index("n.x".into()));
+        let ids: Vec<i64> = db.find("test", &filter).unwrap().iter().map(|doc| doc.pkey_id().unwrap().unwrap_int()).collect();
+        assert_eq!(ids, vec![3, 8, 11]);
+
+        assert_eq!(db.delete("test", &mk_document! { "_id": 8 }).unwrap(), 1);
+        assert_eq!(db.find("test", &filter).unwrap().len(), 2);
+
+        db.create_index_compound("test", &["n.x", "_id"]).unwrap();
+        let filter = mk_document! { "n.x": 3, "_id": 11 };
+        assert_eq!(db.find("test", &filter).unwrap().len(), 1);
+        assert_eq!(db.verify_integrity().unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_index_numbers() {
+        let mut db = prepare_db("test-index-numbers");
+        let mut collection = db.create_collection("test").unwrap();
+        collection.insert(&mut mk_document! { "_id": 0, "a": 1 }).unwrap();
+        collection.insert(&mut mk_document! { "_id": 1, "a": 1.0 }).unwrap();
+        collection.insert(&mut mk_document! { "_id": 2, "a": 1.5 }).unwrap();
+        db.create_index("test", "a").unwrap();
+        db.collection("test").unwrap().insert(&mut mk_document! { "_id": 3, "a": 2.5 }).unwrap();
+        db.collection("test").unwrap().insert(&mut mk_document! { "_id": 4, "a": f64::NAN }).unwrap();
+        // the values of different types are ordered by the type first
+        db.collection("test").unwrap().insert(&mut mk_document! { "_id": 5, "a": "1" }).unwrap();
+
+        let ids_of = |docs: Vec<Rc<Document>>| -> Vec<i64> {
+            docs.iter().map(|doc| doc.pkey_id().unwrap().unwrap_int()).collect()
+        };
+
+        // the numbers are indexed by the numeric value
+        let filter = mk_document! { "a": 1.0 };
+        assert_eq!(db.explain("test", &filter).unwrap(), QueryPlan::Index("a".into()));
+        assert_eq!(ids_of(db.find("test", &filter).unwrap()), vec![0, 1]);
+        assert_eq!(ids_of(db.find("test", &mk_document! { "a": 1 }).unwrap()), vec![0, 1]);
+        assert_eq!(ids_of(db.find("test", &mk_document! { "a": 2.5 }).unwrap()), vec![3]);
+        assert_eq!(ids_of(db.find("test", &mk_document! { "a": "1" }).unwrap()), vec![5]);
+
+        // NaN equals nothing, it's not looked up in the index
+        let filter = mk_document! { "a": f64::NAN };
+        assert_eq!(db.explain("test", &filter).unwrap(), QueryPlan::FullScan);
+        assert!(db.find("test", &filter).unwrap().is_empty());
+
+        assert_eq!(db.delete("test", &mk_document! { "_id": 1 }).unwrap(), 1);
+        assert_eq!(ids_of(db.find("test", &mk_document! { "a": 1 }).unwrap()), vec![0]);
+        assert_eq!(db.verify_integrity().unwrap(), Vec::<String>::new());
+
+        // a Double is not a primary key
+        let mut doc = mk_document! { "_id": 1.5 };
+        assert!(matches!(db.insert("test", &mut doc), Err(DbErr::NotAValidKeyType(_))));
+    }
+
+    #[test]
+    fn test_find_by_index() {
+        let mut db = prepare_db("test-find-by-index");
+        let mut collection = db.create_collection("test").unwrap();
+
+        for i in 0..100 {
+            let mut doc = mk_document! {
+                "_id": i,
+                "group": (i % 10).to_string(),
+                "content": i.to_string(),
+            };
+            collection.insert(doc.as_mut()).unwrap();
+        }
+
+        // the existing documents are indexed
+        db.create_index("test", "group").unwrap();
+        db.create_index("test", "group").expect_err("index exists");
+
+        let filter = mk_document! { "group": "3" };
+        let result = db.find("test", &filter).unwrap();
+        assert_eq!(result.len(), 10);
+        for (i, doc) in result.iter().enumerate() {
+            assert_eq!(doc.pkey_id().unwrap().unwrap_int(), (i * 10 + 3) as i64);
+        }
+
+        let filter = mk_document! { "group": "3", "content": "13" };
+        assert_eq!(db.find("test", &filter).unwrap().len(), 1);
+
+        let filter = mk_document! { "group": 3 };
+        assert!(db.find("test", &filter).unwrap().is_empty());
+
+        // the index is updated with the documents
+        let filter = mk_document! { "_id": 13 };
+        let update = mk_document! {
+            "$set": mk_document! { "group": "new" },
+        };
+        assert_eq!(db.update("test", &filter, &update).unwrap(), 1);
+
+        let filter = mk_document! { "group": "3" };
+        assert_eq!(db.find("test", &filter).unwrap().len(), 9);
+        let filter = mk_document! { "group": "new" };
+        assert_eq!(db.find("test", &filter).unwrap().len(), 1);
+
+        let filter = mk_document! { "group": "5" };
+        assert_eq!(db.delete("test", &filter).unwrap(), 10);
+        assert!(db.find("test", &filter).unwrap().is_empty());
+
+        let mut doc = mk_document! {
+            "_id": 100,
+            "group": "5",
+        };
+        db.collection("test").unwrap().insert(doc.as_mut()).unwrap();
+        assert_eq!(db.find("test", &filter).unwrap().len(), 1);
+
+        let empty = mk_document! {};
+        assert_eq!(db.count_documents("test", &empty).unwrap(), 91);
+
+        assert!(db.delete_collection("test").unwrap());
+    }
+
+    #[test]
+    fn test_explain() {
+        let mut db = prepare_db("test-explain");
+        db.create_collection("test").unwrap();
+        let docs: Vec<Rc<Document>> = (0..50).map(|i| Rc::new(mk_document! {
+            "_id": i,
+            "group": i % 5,
+            "city": format!("city-{}", i % 3),
+            "age": i % 7,
+        })).collect();
+        db.insert_many("test", docs).unwrap();
+
+        let filter = mk_document! { "group": 3 };
+        assert_eq!(db.explain("test", &filter).unwrap(), QueryPlan::FullScan);
+
+        db.create_index("test", "group").unwrap();
+        db.create_index_compound("test", &["city", "age"]).unwrap();
+
+        assert_eq!(db.explain("test", &filter).unwrap(), QueryPlan::Index("group".into()));
+        assert_eq!(db.find("test", &filter).unwrap().len(), 10);
+
+        let filter = mk_document! { "_id": 3, "group": 3 };
+        assert_eq!(db.explain("test", &filter).unwrap(), QueryPlan::PrimaryKey);
+        assert_eq!(db.find("test", &filter).unwrap().len(), 1);
+
+        let filter = mk_document! { "city": "city-1", "group": 2 };
+        assert_eq!(db.explain("test", &filter).unwrap(), QueryPlan::Index("group".into()));
+
+        let filter = mk_document! { "city": "city-1" };
+        assert_eq!(db.explain("test", &filter).unwrap(), QueryPlan::CompoundIndex {
+            index: "city,age".into(),
+            fields: vec!["city".into()],
+        });
+
+        let filter = mk_document! { "city": "city-1", "age": 4, "group": 1 };
+        assert_eq!(db.explain("test", &filter).unwrap(), QueryPlan::CompoundIndex {
+            index: "city,age".into(),
+            fields: vec!["city".into(), "age".into()],
+        });
+        let ids: Vec<i64> = db.find("test", &filter).unwrap().iter().map(|doc| doc.pkey_id().unwrap().unwrap_int()).collect();
+        assert_eq!(ids, vec![46]);
+
+        let filter = mk_document! { "age": 4 };
+        assert_eq!(db.explain("test", &filter).unwrap(), QueryPlan::FullScan);
+
+        let filter = mk_document! {
+            "group": mk_document! { "$gt": 2 },
+        };
+        assert_eq!(db.explain("test", &filter).unwrap(), QueryPlan::FullScan);
+        assert_eq!(db.find("test", &filter).unwrap().len(), 20);
+
+        assert!(matches!(db.explain("not-exist", &filter), Err(DbErr::CollectionNotFound(_))));
+    }
+
+    #[test]
+    fn test_compound_index() {
+        let mut db = prepare_db("test-compound-index");
+        db.create_collection("test").unwrap();
+
+        let docs: Vec<Rc<Document>> = (0..200).map(|i| {
+            let mut doc = mk_document! {
+                "_id": i,
+                "city": format!("city-{}", i % 5),
+            };
+            // some documents miss the second field
+            if i % 7 != 0 {
+                doc.insert("age".into(), Value::from(i % 20));
+            }
+            Rc::new(doc)
+        }).collect();
+        db.insert_many("test", docs.clone()).unwrap();
+
+        db.create_index_compound("test", &["city", "age"]).unwrap();
+        db.create_index_compound("test", &["city", "age"]).expect_err("index exists");
+        db.create_index_compound("test", &["city"]).expect_err("one field");
+        db.create_index_compound("test", &["city", "city"]).expect_err("duplicated field");
+        assert_eq!(db.list_indexes("test").unwrap(), vec!["city,age".to_string()]);
+
+        // documents missing all the fields are not indexed
+        let mut doc = mk_document! { "_id": 200 };
+        db.insert("test", &mut doc).unwrap();
+
+        let meta = db.ctx.get_collection_meta_by_name("test").unwrap();
+        let filters = vec![
+            mk_document! { "city": "city-3" },
+            mk_document! { "city": "city-3", "age": 8 },
+            mk_document! { "age": 8, "city": "city-3", "_id_not_indexed": 1 },
+            mk_document! { "city": "city-9" },
+            mk_document! { "city": 3 },
+        ];
+        for filter in &filters {
+            let expected: Vec<i64> = docs.iter()
+                .filter(|doc| filter.iter().all(|(key, value)| doc.get(key) == Some(value)))
+                .map(|doc| doc.pkey_id().unwrap().unwrap_int())
+                .collect();
+
+            let by_index = db.ctx.find_by_index(meta.id, meta.meta_version, filter).unwrap().expect("the index is used");
+            let ids: Vec<i64> = by_index.iter().map(|doc| doc.pkey_id().unwrap().unwrap_int()).collect();
+            assert_eq!(ids, expected, "filter: {}", filter);
+        }
+
+        // the second field alone can't use the index
+        let filter = mk_document! { "age": 8 };
+        assert!(db.ctx.find_by_index(meta.id, meta.meta_version, &filter).unwrap().is_none());
+        assert_eq!(db.find("test", &filter).unwrap().len(), 8);
+
+        // the index is updated with the documents
+        let update = mk_document! {
+            "$set": mk_document! { "age": 100 },
+        };
+        assert_eq!(db.update("test", &mk_document! { "_id": 13 }, &update).unwrap(), 1);
+        assert_eq!(db.find("test", &mk_document! { "city": "city-3", "age": 13 }).unwrap().len(), 8);
+        assert_eq!(db.find("test", &mk_document! { "city": "city-3", "age": 100 }).unwrap().len(), 1);
+        assert_eq!(db.delete("test", &mk_document! { "city": "city-3" }).unwrap(), 40);
+        assert!(db.find("test", &mk_document! { "city": "city-3", "age": 100 }).unwrap().is_empty());
+        assert!(db.verify_integrity().unwrap().is_empty());
+
+        db.compact().unwrap();
+        assert_eq!(db.find("test", &mk_document! { "city": "city-4", "age": 4 }).unwrap().len(), 9);
+        assert!(db.verify_integrity().unwrap().is_empty());
+
+        assert!(db.drop_index("test", "city,age").unwrap());
+        assert!(db.list_indexes("test").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_unique_compound_index() {
+        let mut db = prepare_db("test-unique-compound-index");
+        db.create_collection("test").unwrap();
+
+        let meta = db.ctx.get_collection_meta_by_name("test").unwrap();
+        let options = mk_document! { "unique": true };
+        db.ctx.create_index_compound(meta.id, &["first", "last"], Some(&options)).unwrap();
+
+        let mut doc = mk_document! { "first": "Ada", "last": "Lovelace" };
+        db.insert("test", &mut doc).unwrap();
+        let mut doc = mk_document! { "first": "Ada", "last": "Byron" };
+        db.insert("test", &mut doc).unwrap();
+        let mut doc = mk_document! { "first": "Ada", "last": "Lovelace" };
+        assert!(matches!(db.insert("test", &mut doc), Err(DbErr::DuplicateKey { .. })));
+
+        // the missing field is Null
+        let mut doc = mk_document! { "first": "Ada" };
+        db.insert("test", &mut doc).unwrap();
+        let mut doc = mk_document! { "first": "Ada" };
+        assert!(matches!(db.insert("test", &mut doc), Err(DbErr::DuplicateKey { .. })));
+
+        let mut doc = mk_document! { "first": "Ada", "last": mk_document! { "name": "Lovelace" } };
+        assert!(matches!(db.insert("test", &mut doc), Err(DbErr::NotAValidKeyType(_))));
+
+        assert_eq!(db.find("test", &mk_document! { "first": "Ada" }).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_create_unique_index() {
+        let mut db = create_and_return_db_with_items("test-create-unique-index", 10);
+        db.create_unique_index("test", "content").unwrap();
+
+        let mut doc = mk_document! {
+            "content": "3",
+        };
+        let err = db.collection("test").unwrap().insert(doc.as_mut()).expect_err("duplicate key");
+        match err {
+            DbErr::DuplicateKey { field, value } => {
+                assert_eq!(field, "content");
+                assert_eq!(value.unwrap_string(), "3");
+            }
+            _ => panic!("unexpected error: {}", err),
+        }
+
+        // the document with the duplicated key is not inserted
+        let empty = mk_document! {};
+        assert_eq!(db.count_documents("test", &empty).unwrap(), 10);
+
+        let filter = mk_document! { "content": "4" };
+        let update = mk_document! {
+            "$set": mk_document! { "content": "5" },
+        };
+        db.update("test", &filter, &update).expect_err("duplicate key");
+        assert_eq!(db.find("test", &filter).unwrap().len(), 1);
+
+        let mut doc = mk_document! {
+            "content": "10",
+        };
+        db.collection("test").unwrap().insert(doc.as_mut()).unwrap();
+
+        // the collection contains duplicated values
+        let mut collection = db.create_collection("dup").unwrap();
+        for _ in 0..2 {
+            let mut doc = mk_document! {
+                "name": "Vincent",
+            };
+            collection.insert(doc.as_mut()).unwrap();
+        }
+        match db.create_unique_index("dup", "name") {
+            Err(DbErr::DuplicateKey { value, .. }) => assert_eq!(value.unwrap_string(), "Vincent"),
+            _ => panic!("unique index should not be created"),
+        }
+        db.create_index("dup", "name").unwrap();
+    }
+
+    #[test]
+    fn test_drop_index() {
+        let mut db = create_and_return_db_with_items("test-drop-index", 100);
+        assert!(db.list_indexes("test").unwrap().is_empty());
+
+        db.create_index("test", "content").unwrap();
+        db.create_unique_index("test", "_id").unwrap();
+        assert_eq!(db.list_indexes("test").unwrap(), vec!["_id", "content"]);
+
+        assert!(db.drop_index("test", "content").unwrap());
+        assert!(!db.drop_index("test", "content").unwrap());
+        assert!(!db.drop_index("test", "not-exist").unwrap());
+        assert_eq!(db.list_indexes("test").unwrap(), vec!["_id"]);
+
+        // the collection is scanned without the index
+        let filter = mk_document! { "content": "3" };
+        assert_eq!(db.find("test", &filter).unwrap().len(), 1);
+
+        let mut doc = mk_document! {
+            "content": "3",
+        };
+        db.collection("test").unwrap().insert(doc.as_mut()).unwrap();
+        assert_eq!(db.find("test", &filter).unwrap().len(), 2);
+
+        // the index can be created again
+        db.create_index("test", "content").unwrap();
+        assert_eq!(db.find("test", &filter).unwrap().len(), 2);
+
+        assert!(db.list_indexes("not-exist").is_err());
     }
 
     #[test]
@@ -630,3 +4706,83 @@ mod tests {
     }
 
 }
+
+#[cfg(all(feature = "nightly", test))]
+mod bench {
+    extern crate test;
+
+    use std::env;
+    use polodb_bson::mk_document;
+    use crate::{Database, Config, JournalMode};
+
+    fn open_bench_db(db_name: &str, journal_mode: JournalMode) -> Database {
+        let mut db_path = env::temp_dir();
+        db_path.push(String::from(db_name) + ".db");
+        let mut journal_path = env::temp_dir();
+        journal_path.push(String::from(db_name) + ".db.journal");
+        let _ = std::fs::remove_file(db_path.as_path());
+        let _ = std::fs::remove_file(journal_path.as_path());
+
+        let config = Config {
+            journal_mode,
+            ..Default::default()
+        };
+        let mut db = Database::open_with_config(db_path.as_path(), config).unwrap();
+        db.create_collection("test").unwrap();
+        db
+    }
+
+    // every insertion is committed
+    fn bench_insert(b: &mut test::Bencher, db_name: &str, journal_mode: JournalMode) {
+        let mut db = open_bench_db(db_name, journal_mode);
+        let mut collection = db.collection("test").unwrap();
+        let mut id: i64 = 0;
+        b.iter(|| {
+            collection.insert(&mut mk_document! {
+                "_id": id,
+                "content": "hello world",
+            }).unwrap();
+            id += 1;
+        });
+    }
+
+    // 100 insertions in a transaction
+    fn bench_transaction(b: &mut test::Bencher, db_name: &str, journal_mode: JournalMode) {
+        let mut db = open_bench_db(db_name, journal_mode);
+        let mut id: i64 = 0;
+        b.iter(|| {
+            let mut txn = db.transaction().unwrap();
+            let mut collection = txn.collection("test").unwrap();
+            for _ in 0..100 {
+                collection.insert(&mut mk_document! {
+                    "_id": id,
+                    "content": "hello world",
+                }).unwrap();
+                id += 1;
+            }
+            drop(collection);
+            txn.commit().unwrap();
+        });
+    }
+
+    #[bench]
+    fn insert_wal(b: &mut test::Bencher) {
+        bench_insert(b, "bench-insert-wal", JournalMode::Wal);
+    }
+
+    #[bench]
+    fn insert_shadow(b: &mut test::Bencher) {
+        bench_insert(b, "bench-insert-shadow", JournalMode::Shadow);
+    }
+
+    #[bench]
+    fn transaction_wal(b: &mut test::Bencher) {
+        bench_transaction(b, "bench-transaction-wal", JournalMode::Wal);
+    }
+
+    #[bench]
+    fn transaction_shadow(b: &mut test::Bencher) {
+        bench_transaction(b, "bench-transaction-shadow", JournalMode::Shadow);
+    }
+
+}
@@ -1,7 +1,7 @@
 use std::fmt;
 use polodb_bson::Value;
 use crate::vm::{VM, VmState};
-use crate::DbResult;
+use crate::{DbResult, OperationOptions};
 
 /**
  * A VM wrapper for Rust-level API
@@ -35,16 +35,39 @@ impl<'a> DbHandle<'a> {
         self.0.execute()
     }
 
+    #[inline]
+    pub fn rewind(&mut self) {
+        self.0.rewind()
+    }
+
+    #[inline]
+    pub fn count_remaining(&mut self, is_full_scan: bool) -> DbResult<usize> {
+        self.0.count_remaining(is_full_scan)
+    }
+
     #[inline]
     pub fn commit_and_close_vm(self) -> DbResult<()> {
         self.0.commit_and_close()
     }
 
+    /// The operation fails with `DbErr::Cancelled` when the options are triggered,
+    /// see [OperationOptions](./struct.OperationOptions.html).
+    #[inline]
+    pub fn set_options(&mut self, options: OperationOptions<'a>) {
+        self.0.set_options(options)
+    }
+
     #[inline]
     pub fn set_rollback_on_drop(&mut self, value: bool) {
         self.0.set_rollback_on_drop(value)
     }
 
+    /// The read transaction of the snapshot is finished when the handle is dropped.
+    #[inline]
+    pub(crate) fn set_finish_read_view_on_drop(&mut self, value: bool) {
+        self.0.set_finish_read_view_on_drop(value)
+    }
+
 }
 
 impl<'a> fmt::Display for DbHandle<'a> {
@@ -0,0 +1,596 @@
+//! The conversions between the documents and the types implementing the traits of serde,
+//! needs the feature `serde`.
+//!
+//! The fields of a struct are the keys of the document, the nested structs are the documents,
+//! the sequences are the arrays.
+//!
+//! `None` is stored as `Null`, skip it by `#[serde(skip_serializing_if = "Option::is_none")]`.
+//! Both `Null` and a missing field are read as `None`.
+//!
+//! A unit variant of an enum is stored as the string of the name,
+//! the other variants are stored as a document with the name as the only key.
+use std::fmt;
+use std::rc::Rc;
+use serde::ser::{self, Serialize};
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+use polodb_bson::{Document, Array, Value};
+use polodb_bson::linked_hash_map::Iter;
+use crate::{DbErr, DbResult};
+
+/// Convert the value into a document, the value must be a struct or a map.
+pub fn to_document<T: Serialize + ?Sized>(value: &T) -> DbResult<Document> {
+    match value.serialize(ValueSerializer)? {
+        Value::Document(doc) => Ok(Rc::try_unwrap(doc).unwrap_or_else(|doc| doc.as_ref().clone())),
+        value => Err(DbErr::SerdeError(format!("expected a document, actual: {}", value))),
+    }
+}
+
+/// Convert the document into the value.
+pub fn from_document<T: DeserializeOwned>(doc: &Document) -> DbResult<T> {
+    T::deserialize(DocumentDeserializer(doc))
+}
+
+impl ser::Error for DbErr {
+
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        DbErr::SerdeError(msg.to_string())
+    }
+
+}
+
+impl de::Error for DbErr {
+
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        DbErr::SerdeError(msg.to_string())
+    }
+
+}
+
+fn mk_variant_doc(variant: &str, value: Value) -> Value {
+    let mut doc = Document::new_without_id();
+    doc.insert(variant.into(), value);
+    Value::Document(Rc::new(doc))
+}
+
+struct ValueSerializer;
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = DbErr;
+
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = TupleVariantSerializer;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = MapSerializer;
+    type SerializeStructVariant = StructVariantSerializer;
+
+    fn serialize_bool(self, v: bool) -> DbResult<Value> {
+        Ok(Value::Boolean(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> DbResult<Value> {
+        Ok(Value::Int(v as i64))
+    }
+
+    fn serialize_i16(self, v: i16) -> DbResult<Value> {
+        Ok(Value::Int(v as i64))
+    }
+
+    fn serialize_i32(self, v: i32) -> DbResult<Value> {
+        Ok(Value::Int(v as i64))
+    }
+
+    fn serialize_i64(self, v: i64) -> DbResult<Value> {
+        Ok(Value::Int(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> DbResult<Value> {
+        Ok(Value::Int(v as i64))
+    }
+
+    fn serialize_u16(self, v: u16) -> DbResult<Value> {
+        Ok(Value::Int(v as i64))
+    }
+
+    fn serialize_u32(self, v: u32) -> DbResult<Value> {
+        Ok(Value::Int(v as i64))
+    }
+
+    fn serialize_u64(self, v: u64) -> DbResult<Value> {
+        if v > i64::MAX as u64 {
+            return Err(DbErr::SerdeError(format!("the integer {} is out of the range of i64", v)));
+        }
+        Ok(Value::Int(v as i64))
+    }
+
+    fn serialize_f32(self, v: f32) -> DbResult<Value> {
+        Ok(Value::Double(v as f64))
+    }
+
+    fn serialize_f64(self, v: f64) -> DbResult<Value> {
+        Ok(Value::Double(v))
+    }
+
+    fn serialize_char(self, v: char) -> DbResult<Value> {
+        Ok(Value::String(v.to_string().into()))
+    }
+
+    fn serialize_str(self, v: &str) -> DbResult<Value> {
+        Ok(Value::String(v.into()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> DbResult<Value> {
+        Ok(Value::Binary(v.into()))
+    }
+
+    fn serialize_none(self) -> DbResult<Value> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> DbResult<Value> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> DbResult<Value> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> DbResult<Value> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _variant_index: u32, variant: &'static str) -> DbResult<Value> {
+        Ok(Value::String(variant.into()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> DbResult<Value> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self, _name: &'static str, _variant_index: u32, variant: &'static str, value: &T
+    ) -> DbResult<Value> {
+        Ok(mk_variant_doc(variant, value.serialize(ValueSerializer)?))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> DbResult<SeqSerializer> {
+        Ok(SeqSerializer { arr: Array::new() })
+    }
+
+    fn serialize_tuple(self, len: usize) -> DbResult<SeqSerializer> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> DbResult<SeqSerializer> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self, _name: &'static str, _variant_index: u32, variant: &'static str, _len: usize
+    ) -> DbResult<TupleVariantSerializer> {
+        Ok(TupleVariantSerializer { variant, arr: Array::new() })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> DbResult<MapSerializer> {
+        Ok(MapSerializer { doc: Document::new_without_id(), key: None })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> DbResult<MapSerializer> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self, _name: &'static str, _variant_index: u32, variant: &'static str, _len: usize
+    ) -> DbResult<StructVariantSerializer> {
+        Ok(StructVariantSerializer { variant, doc: Document::new_without_id() })
+    }
+
+}
+
+struct SeqSerializer {
+    arr: Array,
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Value;
+    type Error = DbErr;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> DbResult<()> {
+        self.arr.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> DbResult<Value> {
+        Ok(Value::Array(Rc::new(self.arr)))
+    }
+
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Value;
+    type Error = DbErr;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> DbResult<()> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> DbResult<Value> {
+        ser::SerializeSeq::end(self)
+    }
+
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Value;
+    type Error = DbErr;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> DbResult<()> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> DbResult<Value> {
+        ser::SerializeSeq::end(self)
+    }
+
+}
+
+struct TupleVariantSerializer {
+    variant: &'static str,
+    arr:     Array,
+}
+
+impl ser::SerializeTupleVariant for TupleVariantSerializer {
+    type Ok = Value;
+    type Error = DbErr;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> DbResult<()> {
+        self.arr.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> DbResult<Value> {
+        Ok(mk_variant_doc(self.variant, Value::Array(Rc::new(self.arr))))
+    }
+
+}
+
+struct MapSerializer {
+    doc: Document,
+    key: Option<String>,
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = Value;
+    type Error = DbErr;
+
+    // the keys of a document are strings
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> DbResult<()> {
+        match key.serialize(ValueSerializer)? {
+            Value::String(key) => {
+                self.key = Some(key.to_string());
+                Ok(())
+            }
+            key => Err(DbErr::SerdeError(format!("the key of a map must be a string, actual: {}", key))),
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> DbResult<()> {
+        let key = self.key.take().expect("serialize the value before the key");
+        self.doc.insert(key, value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> DbResult<Value> {
+        Ok(Value::Document(Rc::new(self.doc)))
+    }
+
+}
+
+impl ser::SerializeStruct for MapSerializer {
+    type Ok = Value;
+    type Error = DbErr;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> DbResult<()> {
+        self.doc.insert(key.into(), value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> DbResult<Value> {
+        ser::SerializeMap::end(self)
+    }
+
+}
+
+struct StructVariantSerializer {
+    variant: &'static str,
+    doc:     Document,
+}
+
+impl ser::SerializeStructVariant for StructVariantSerializer {
+    type Ok = Value;
+    type Error = DbErr;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> DbResult<()> {
+        self.doc.insert(key.into(), value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> DbResult<Value> {
+        Ok(mk_variant_doc(self.variant, Value::Document(Rc::new(self.doc))))
+    }
+
+}
+
+struct DocumentDeserializer<'a>(&'a Document);
+
+impl<'de, 'a> de::Deserializer<'de> for DocumentDeserializer<'a> {
+    type Error = DbErr;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> DbResult<V::Value> {
+        visitor.visit_map(MapDeserializer { iter: self.0.iter(), value: None })
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> DbResult<V::Value> {
+        visitor.visit_some(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+
+}
+
+struct ValueDeserializer<'a>(&'a Value);
+
+impl<'de, 'a> de::Deserializer<'de> for ValueDeserializer<'a> {
+    type Error = DbErr;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> DbResult<V::Value> {
+        match self.0 {
+            Value::Null => visitor.visit_unit(),
+            Value::Double(v) => visitor.visit_f64(*v),
+            Value::Boolean(v) => visitor.visit_bool(*v),
+            Value::Int(v) => visitor.visit_i64(*v),
+            Value::String(v) => visitor.visit_str(v),
+            Value::ObjectId(oid) => visitor.visit_string(oid.to_hex()),
+            Value::Array(arr) => visitor.visit_seq(SeqDeserializer { arr, index: 0 }),
+            Value::Document(doc) => DocumentDeserializer(doc).deserialize_any(visitor),
+            Value::Binary(bytes) => visitor.visit_bytes(bytes),
+            Value::UTCDateTime(datetime) => visitor.visit_u64(datetime.timestamp()),
+            Value::Timestamp { time, inc } => visitor.visit_u64((*time as u64) << 32 | (*inc as u64)),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> DbResult<V::Value> {
+        match self.0 {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> DbResult<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self, _name: &'static str, _variants: &'static [&'static str], visitor: V
+    ) -> DbResult<V::Value> {
+        match self.0 {
+            Value::String(variant) => visitor.visit_enum(variant.as_ref().into_deserializer()),
+            Value::Document(doc) if doc.len() == 1 => {
+                let (variant, value) = doc.iter().next().unwrap();
+                visitor.visit_enum(EnumDeserializer { variant, value })
+            }
+            value => Err(DbErr::SerdeError(format!("expected a string or a document with one key for the enum, actual: {}", value))),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+
+}
+
+struct SeqDeserializer<'a> {
+    arr:   &'a Array,
+    index: usize,
+}
+
+impl<'de, 'a> de::SeqAccess<'de> for SeqDeserializer<'a> {
+    type Error = DbErr;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> DbResult<Option<T::Value>> {
+        match self.arr.get(self.index) {
+            Some(value) => {
+                self.index += 1;
+                seed.deserialize(ValueDeserializer(value)).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.arr.len() as usize - self.index)
+    }
+
+}
+
+struct MapDeserializer<'a> {
+    iter:  Iter<'a, String, Value>,
+    value: Option<&'a Value>,
+}
+
+impl<'de, 'a> de::MapAccess<'de> for MapDeserializer<'a> {
+    type Error = DbErr;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> DbResult<Option<K::Value>> {
+        match self.iter.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(key.as_str().into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> DbResult<V::Value> {
+        let value = self.value.take().expect("deserialize the value before the key");
+        seed.deserialize(ValueDeserializer(value))
+    }
+
+}
+
+struct EnumDeserializer<'a> {
+    variant: &'a str,
+    value:   &'a Value,
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for EnumDeserializer<'a> {
+    type Error = DbErr;
+    type Variant = ValueDeserializer<'a>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> DbResult<(V::Value, ValueDeserializer<'a>)> {
+        let deserializer: de::value::StrDeserializer<DbErr> = self.variant.into_deserializer();
+        let variant = seed.deserialize(deserializer)?;
+        Ok((variant, ValueDeserializer(self.value)))
+    }
+
+}
+
+impl<'de, 'a> de::VariantAccess<'de> for ValueDeserializer<'a> {
+    type Error = DbErr;
+
+    fn unit_variant(self) -> DbResult<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> DbResult<T::Value> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> DbResult<V::Value> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, _fields: &'static [&'static str], visitor: V) -> DbResult<V::Value> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use serde::{Serialize, Deserialize};
+    use polodb_bson::{Value, mk_document, mk_array};
+    use crate::DbErr;
+    use crate::doc_serde::{to_document, from_document};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Role {
+        Admin,
+        Guest(String),
+        Member { level: u32 },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Address {
+        city: String,
+        zip:  Option<String>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct User {
+        #[serde(rename = "_id")]
+        id:        i64,
+        name:      String,
+        score:     f64,
+        active:    bool,
+        tags:      Vec<String>,
+        address:   Address,
+        roles:     Vec<Role>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        nickname:  Option<String>,
+        extra:     HashMap<String, i32>,
+    }
+
+    fn mk_user() -> User {
+        User {
+            id: 1,
+            name: "Vincent".into(),
+            score: 99.5,
+            active: true,
+            tags: vec!["a".into(), "b".into()],
+            address: Address {
+                city: "Shanghai".into(),
+                zip: None,
+            },
+            roles: vec![Role::Admin, Role::Guest("x".into()), Role::Member { level: 3 }],
+            nickname: None,
+            extra: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let user = mk_user();
+        let doc = to_document(&user).unwrap();
+        assert_eq!(doc.get("_id").unwrap(), &Value::Int(1));
+        assert_eq!(doc.get("name").unwrap(), &Value::from("Vincent"));
+        assert_eq!(doc.get_path("address.city").unwrap(), &Value::from("Shanghai"));
+
+        // None is stored as Null, unless it's skipped
+        assert_eq!(doc.get_path("address.zip").unwrap(), &Value::Null);
+        assert!(doc.get("nickname").is_none());
+
+        let back: User = from_document(&doc).unwrap();
+        assert_eq!(back, user);
+    }
+
+    #[test]
+    fn test_from_document() {
+        let doc = mk_document! {
+            "_id": 2,
+            "name": "Alice",
+            "score": 60,
+            "active": false,
+            "tags": mk_array![],
+            "address": mk_document! {
+                "city": "Paris",
+            },
+            "roles": mk_array![ "Admin" ],
+            "nickname": "ali",
+            "extra": mk_document! {
+                "x": 1,
+            },
+        };
+        let user: User = from_document(&doc).unwrap();
+        assert_eq!(user.score, 60.0);
+        assert_eq!(user.address.zip, None);
+        assert_eq!(user.roles, vec![Role::Admin]);
+        assert_eq!(user.nickname.as_deref(), Some("ali"));
+        assert_eq!(user.extra.get("x"), Some(&1));
+
+        let doc = mk_document! {
+            "_id": 2,
+            "name": 3,
+        };
+        assert!(matches!(from_document::<User>(&doc), Err(DbErr::SerdeError(_))));
+    }
+
+    #[test]
+    fn test_not_a_document() {
+        assert!(matches!(to_document(&vec![1, 2]), Err(DbErr::SerdeError(_))));
+
+        let mut map = HashMap::new();
+        map.insert(1, 2);
+        assert!(matches!(to_document(&map), Err(DbErr::SerdeError(_))));
+    }
+
+}
@@ -20,6 +20,8 @@ pub struct FullDump {
     pub meta_pid:       u32,
     pub free_list_pid:  u32,
     pub free_list_size: u32,
+    // the pages after it have never been allocated
+    pub null_page_bar:  u32,
     pub page_size:      u32,
     pub pages:          Vec<PageDump>,
 }
@@ -58,6 +58,43 @@ impl fmt::Display for UnexpectedHeader {
 
 }
 
+/// The location where the corrupt bytes are found.
+#[derive(Debug)]
+pub struct UnsupportedValueTypeStruct {
+    pub field: String,
+    pub ty: String,
+}
+
+pub(crate) fn mk_unsupported_value_type<T: Into<String>>(field: &str, ty: T) -> DbErr {
+    DbErr::UnsupportedValueType(Box::new(UnsupportedValueTypeStruct {
+        field: field.into(),
+        ty: ty.into(),
+    }))
+}
+
+#[derive(Debug)]
+pub struct DataMalformedStruct {
+    pub page_id: u32,
+    pub offset: usize,
+    pub reason: String,
+}
+
+pub(crate) fn mk_data_malformed<T: Into<String>>(page_id: u32, offset: usize, reason: T) -> DbErr {
+    DbErr::DataMalformed(Box::new(DataMalformedStruct {
+        page_id,
+        offset,
+        reason: reason.into(),
+    }))
+}
+
+impl fmt::Display for DataMalformedStruct {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "page_id: {}, offset: {}, {}", self.page_id, self.offset, self.reason)
+    }
+
+}
+
 #[derive(Debug)]
 pub struct InvalidFieldStruct {
     pub field_type: &'static str,
@@ -73,16 +110,23 @@ pub fn mk_invalid_query_field(name: String, path: String) -> Box<InvalidFieldStr
     })
 }
 
+/// The errors of the database.
+///
+/// New variants may be added in the future versions,
+/// so a `match` over it must contain a wildcard arm.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum DbErr {
     UnexpectedIdType(u8, u8),
     NotAValidKeyType(String),
     InvalidField(Box<InvalidFieldStruct>),
+    InvalidQuery(String),
     ValidationError(String),
     InvalidOrderOfIndex(String),
     IndexAlreadyExists(String),
     FieldTypeUnexpected(Box<FieldTypeUnexpectedStruct>),
     ParseError(String),
+    SerdeError(String),
     IOErr(Box<io::Error>),
     UTF8Err(Box<std::str::Utf8Error>),
     BsonErr(Box<BsonErr>),
@@ -93,9 +137,12 @@ pub enum DbErr {
     PageSpaceNotEnough,
     DataHasNoPrimaryKey,
     ChecksumMismatch,
+    JournalChecksumMismatch,
     JournalPageSizeMismatch(u32, u32),
     SaltMismatch,
     PageMagicMismatch(u32),
+    PageChecksumMismatch { page_id: u32 },
+    UnsupportedFileVersion { found: u32, supported: u32 },
     ItemSizeGreaterThanExpected,
     CollectionNotFound(String),
     CollectionIdNotFound(u32),
@@ -114,8 +161,21 @@ pub enum DbErr {
     IncrementNullField,
     VmIsHalt,
     MetaVersionMismatched(u32, u32),
-    CollectionAlreadyExits(String),
+    CollectionAlreadyExists(String),
     UnableToUpdatePrimaryKey,
+    DuplicateKey { field: String, value: Box<Value> },
+    IllegalPageSize(u32),
+    PageSizeMismatch(u32, u32),
+    IllegalBTreeOrder(u32, u32),
+    BTreeOrderMismatch(u32, u32),
+    InvalidObjectId(String),
+    DataMalformed(Box<DataMalformedStruct>),
+    ReadOnly,
+    DocumentTooLarge { size: usize, max: usize },
+    UnsupportedValueType(Box<UnsupportedValueTypeStruct>),
+    Cancelled,
+    MmapNotSupported,
+    SnapshotNotOfDatabase,
     Busy
 }
 
@@ -127,37 +187,42 @@ impl fmt::Display for DbErr {
                 let expected = ty_int::to_str(*expected_ty);
                 let actual = ty_int::to_str(*actual_ty);
 
-                write!(f, "UnexpectedIdType(expected: {}, actual: {})", expected, actual)
+                write!(f, "unexpected type of id, expected: {}, actual: {}", expected, actual)
             }
 
             DbErr::NotAValidKeyType(ty_name) => write!(f, "type {} is not a valid key type", ty_name),
+            DbErr::InvalidQuery(reason) => write!(f, "invalid query: {}", reason),
             DbErr::InvalidField(st) =>
                 write!(f, "the {} field name: \"{}\" is invalid, path: {}",
                        st.field_type, st.field_name, st.path.as_ref().unwrap_or(&String::from("<None>"))),
-            DbErr::ValidationError(reason) => write!(f, "ValidationError: {}", reason),
+            DbErr::ValidationError(reason) => write!(f, "validation error: {}", reason),
             DbErr::InvalidOrderOfIndex(index_key_name) => write!(f, "invalid order of index: {}", index_key_name),
             DbErr::IndexAlreadyExists(index_key_name) => write!(f, "index for {} already exists", index_key_name),
             DbErr::FieldTypeUnexpected(st) => write!(f, "{}", st),
-            DbErr::ParseError(reason) => write!(f, "ParseError: {}", reason),
-            DbErr::IOErr(io_err) => write!(f, "IOErr: {}", io_err),
+            DbErr::ParseError(reason) => write!(f, "parse error: {}", reason),
+            DbErr::SerdeError(reason) => write!(f, "serde error: {}", reason),
+            DbErr::IOErr(io_err) => write!(f, "io error: {}", io_err),
             DbErr::UTF8Err(utf8_err) => utf8_err.fmt(f),
             DbErr::BsonErr(bson_err) => write!(f, "bson error: {}", bson_err),
             DbErr::DataSizeTooLarge(expected, actual) =>
-                write!(f, "DataSizeTooLarge(expected: {}, actual: {})", expected, actual),
-            DbErr::DecodeEOF => write!(f, "DecodeEOF"),
-            DbErr::DataOverflow => write!(f, "DataOverflow"),
+                write!(f, "data size is too large, expected: {}, actual: {}", expected, actual),
+            DbErr::DecodeEOF => write!(f, "unexpected end of data while decoding"),
+            DbErr::DataOverflow => write!(f, "data overflow"),
             DbErr::DataExist(value) => write!(f, "item with primary key exists, key: {}", value),
             DbErr::PageSpaceNotEnough => write!(f, "the space of page is not enough"),
-            DbErr::DataHasNoPrimaryKey => write!(f, "DataHasNoPrimaryKey"),
+            DbErr::DataHasNoPrimaryKey => write!(f, "the data has no primary key"),
             DbErr::ChecksumMismatch => write!(f, "journal's checksum is mismatch with data, database maybe corrupt"),
+            DbErr::JournalChecksumMismatch => write!(f, "checksum of a committed frame in journal is mismatched, journal is corrupt"),
             DbErr::JournalPageSizeMismatch(expect, actual) => {
                 write!(f, "journal's page size is mismatch with database. expect:{}, actual: {}", expect, actual)
             },
-            DbErr::SaltMismatch => write!(f, "SaltMismatch"),
-            DbErr::PageMagicMismatch(pid) => write!(f, "PageMagicMismatch({})", pid),
+            DbErr::SaltMismatch => write!(f, "journal's salt is mismatch with the database"),
+            DbErr::PageMagicMismatch(pid) => write!(f, "magic number of page {} is mismatched", pid),
+            DbErr::PageChecksumMismatch { page_id } => write!(f, "checksum of page {} is mismatched", page_id),
+            DbErr::UnsupportedFileVersion { found, supported } => write!(f, "the version of the file format is {}, only {} and below are supported", found, supported),
             DbErr::ItemSizeGreaterThanExpected => write!(f, "the size of the item is greater than expected"),
             DbErr::CollectionNotFound(name) => write!(f, "collection \"{}\" not found", name),
-            DbErr::CollectionIdNotFound(id) => write!(f, "colleciton id {} not found", id),
+            DbErr::CollectionIdNotFound(id) => write!(f, "collection id {} not found", id),
             DbErr::MetaPageIdError => write!(f, "meta page id should not be zero"),
             DbErr::CannotWriteDbWithoutTransaction => write!(f, "cannot write database without transaction"),
             DbErr::StartTransactionInAnotherTransaction => write!(f, "start transaction in another transaction"),
@@ -174,8 +239,52 @@ impl fmt::Display for DbErr {
             DbErr::VmIsHalt => write!(f, "Vm can not execute because it's halt"),
             DbErr::MetaVersionMismatched(expected, actual) => write!(f, "meta version mismatched, expect: {}, actual: {}", expected, actual),
             DbErr::Busy => write!(f, "database busy"),
-            DbErr::CollectionAlreadyExits(name) => write!(f, "collection name '{}' already exists", name),
+            DbErr::CollectionAlreadyExists(name) => write!(f, "collection name '{}' already exists", name),
             DbErr::UnableToUpdatePrimaryKey => write!(f, "it's illegal to update '_id' field"),
+            DbErr::DuplicateKey { field, value } => write!(f, "duplicate key for unique index '{}', value: {}", field, value),
+            DbErr::IllegalPageSize(page_size) => write!(f, "illegal page size: {}, expect a power of two in [4096, 32768]", page_size),
+            DbErr::InvalidObjectId(content) => write!(f, "invalid ObjectId: \"{}\"", content),
+            DbErr::DataMalformed(st) => write!(f, "data malformed, database maybe corrupt: {}", st),
+            DbErr::DocumentTooLarge { size, max } => write!(f, "the document is too large, size: {}, max: {}", size, max),
+            DbErr::UnsupportedValueType(st) => write!(f, "the value of field '{}' can't be stored: {}", st.field, st.ty),
+            DbErr::ReadOnly => write!(f, "the database is opened as read-only"),
+            DbErr::Cancelled => write!(f, "the operation is cancelled"),
+            DbErr::MmapNotSupported => write!(f, "the memory mapping is only supported with the feature \"mmap\" on unix"),
+            DbErr::SnapshotNotOfDatabase => write!(f, "the snapshot is not taken from this database"),
+            DbErr::PageSizeMismatch(expect, actual) => {
+                write!(f, "page size is mismatch with the database file. expect: {}, actual: {}", expect, actual)
+            },
+            DbErr::IllegalBTreeOrder(order, max) => write!(f, "illegal B-tree order: {}, expect a number in [3, {}]", order, max),
+            DbErr::BTreeOrderMismatch(expect, actual) => {
+                write!(f, "B-tree order is mismatch with the database file. expect: {}, actual: {}", expect, actual)
+            },
+        }
+    }
+
+}
+
+impl DbErr {
+
+    /// The kind of the IO error, None returns if it's not an IO error.
+    ///
+    /// It helps to distinguish the causes, e.g. `NotFound` from `PermissionDenied`.
+    pub fn io_error_kind(&self) -> Option<io::ErrorKind> {
+        match self {
+            DbErr::IOErr(io_err) => Some(io_err.kind()),
+            _ => None,
+        }
+    }
+
+}
+
+impl std::error::Error for DbErr {
+
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DbErr::IOErr(io_err) => Some(io_err.as_ref()),
+            DbErr::UTF8Err(utf8_err) => Some(utf8_err.as_ref()),
+            DbErr::BsonErr(bson_err) => Some(bson_err.as_ref()),
+            _ => None,
         }
     }
 
@@ -184,7 +293,10 @@ impl fmt::Display for DbErr {
 impl From<BsonErr> for DbErr {
 
     fn from(error: BsonErr) -> Self {
-        DbErr::BsonErr(Box::new(error))
+        match error {
+            BsonErr::InvalidObjectId(content) => DbErr::InvalidObjectId(content),
+            _ => DbErr::BsonErr(Box::new(error)),
+        }
     }
 
 }
@@ -215,4 +327,45 @@ mod tests {
         assert_eq!(size, 32);
     }
 
+    #[test]
+    fn test_invalid_object_id() {
+        let err: DbErr = polodb_bson::ObjectId::from_hex("not-an-id").unwrap_err().into();
+        assert!(matches!(err, DbErr::InvalidObjectId(content) if content == "not-an-id"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_invalid_object_id_from_json() {
+        let json = serde_json::json!({ "_id": { "$oid": "xyz" } });
+        let err: DbErr = polodb_bson::Document::from_json(&json).unwrap_err().into();
+        assert!(matches!(err, DbErr::InvalidObjectId(content) if content == "xyz"));
+    }
+
+    #[test]
+    fn test_error_source() {
+        use std::error::Error;
+
+        let io_err = std::io::Error::new(std::io::ErrorKind::NotFound, "no such file");
+        let err: DbErr = io_err.into();
+        assert_eq!(err.to_string(), "io error: no such file");
+        let source = err.source().unwrap();
+        assert_eq!(source.downcast_ref::<std::io::Error>().unwrap().kind(), std::io::ErrorKind::NotFound);
+        assert_eq!(err.io_error_kind(), Some(std::io::ErrorKind::NotFound));
+
+        let err = DbErr::CollectionNotFound("test".into());
+        assert_eq!(err.to_string(), "collection \"test\" not found");
+        assert!(err.source().is_none());
+        assert!(err.io_error_kind().is_none());
+
+        // the kind is kept through the page layer
+        let mut db_path = std::env::temp_dir();
+        db_path.push("test-error-source-not-exist");
+        db_path.push("test.db");
+        let err = crate::Database::open(db_path.as_path()).err().unwrap();
+        assert_eq!(err.io_error_kind(), Some(std::io::ErrorKind::NotFound));
+
+        let boxed: Box<dyn Error> = Box::new(DbErr::MetaPageIdError);
+        assert_eq!(boxed.to_string(), "meta page id should not be zero");
+    }
+
 }
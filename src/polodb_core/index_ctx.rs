@@ -1,12 +1,17 @@
 use std::rc::Rc;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::borrow::Borrow;
-use polodb_bson::{Document, Value, mk_document, mk_array};
+use polodb_bson::{Document, Value, Array, mk_document, mk_array};
 use crate::meta_doc_helper::{meta_doc_key, MetaDocEntry};
 use crate::DbResult;
 use crate::error::{DbErr, mk_field_name_type_unexpected};
 use crate::page::PageHandler;
-use crate::btree::{BTreePageInsertWrapper, InsertBackwardItem, BTreePageDeleteWrapper};
+use crate::cursor::Cursor;
+use crate::btree::{
+    BTreePageInsertWrapper, InsertBackwardItem, BTreePageDeleteWrapper,
+    BTreePageSearchWrapper, BTreeSearchResult, BTreeNodeDataItem, delete_all_helper, bulk_load,
+};
 
 pub(crate) struct IndexCtx {
     key_to_entry: HashMap<String, IndexEntry>,
@@ -26,13 +31,17 @@ impl IndexCtx {
         let indexes = doc.get(meta_doc_key::INDEXES)?;
 
         let meta_doc: &Rc<Document> = indexes.unwrap_document();
-        if meta_doc.is_empty() {
+        IndexCtx::from_indexes_doc(meta_doc)
+    }
+
+    pub fn from_indexes_doc(indexes_doc: &Document) -> Option<IndexCtx> {
+        if indexes_doc.is_empty() {
             return None;
         }
 
         let mut result = IndexCtx::new();
 
-        for (key, options) in meta_doc.iter() {
+        for (key, options) in indexes_doc.iter() {
             let options_doc = options.unwrap_document();
             let entry = IndexEntry::from_option_doc(options_doc.borrow());
             result.key_to_entry.insert(key.clone(), entry);
@@ -53,31 +62,112 @@ impl IndexCtx {
 
     pub fn insert_index_by_content(&mut self, doc: &Document, primary_key: &Value, is_ctx_changed: &mut bool, page_handler: &mut PageHandler) -> DbResult<()> {
         for (key, entry) in &mut self.key_to_entry {
-            if let Some(value) = doc.get(key) {
+            if let Some(value) = entry.index_value_of(key, doc) {
                 // index exist, and value exist
-                entry.insert_index(value, primary_key.clone(), is_ctx_changed, page_handler)?;
+                entry.insert_index(key, &value, primary_key.clone(), is_ctx_changed, page_handler)?;
             }
         }
 
         Ok(())
     }
 
+    // build the empty indexes from all the documents of the collection at once,
+    // much faster than inserting them one by one
+    pub fn bulk_load_by_contents(&self, docs: &[Rc<Document>], page_handler: &mut PageHandler) -> DbResult<()> {
+        for (key, entry) in &self.key_to_entry {
+            entry.bulk_load(key, docs, page_handler)?;
+        }
+
+        Ok(())
+    }
+
     pub fn delete_index_by_content(&self, doc: &Document, page_handler: &mut PageHandler) -> DbResult<()> {
+        let primary_key = doc.pkey_id().unwrap();
         for (key, entry) in &self.key_to_entry {
-            if let Some(value) = doc.get(key) {
-                entry.remove_index(value, page_handler)?;
+            if let Some(value) = entry.index_value_of(key, doc) {
+                entry.remove_index(&value, &primary_key, page_handler)?;
             }
         }
 
         Ok(())
     }
 
+    #[inline]
+    pub fn has_index(&self, key: &str) -> bool {
+        self.key_to_entry.contains_key(key)
+    }
+
+    // find the primary keys of the documents whose value of the key is `value`,
+    // None returns if the key is not indexed
+    pub fn find_pkeys_by_index(&self, key: &str, value: &Value, page_handler: &mut PageHandler) -> DbResult<Option<Vec<Value>>> {
+        let entry = match self.key_to_entry.get(key) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+
+        let keys = entry.find_primary_keys(value, page_handler)?;
+        Ok(Some(keys))
+    }
+
+    // find the compound index whose leading fields are compared for equality in the query,
+    // the one with the most fields matched is chosen,
+    // the key of the index and the matched fields return
+    pub fn find_compound_index_by_query(&self, query: &Document) -> Option<(&str, &[String])> {
+        let mut result: Option<(&str, &[String])> = None;
+
+        for (key, entry) in &self.key_to_entry {
+            let fields = match &entry.fields {
+                Some(fields) => fields,
+                None => continue,
+            };
+
+            let matched_count = fields.iter()
+                .take_while(|field| matches!(query.get(field), Some(value) if is_index_lookup_value(value)))
+                .count();
+
+            let is_better = match &result {
+                Some((_, best_fields)) => matched_count > best_fields.len(),
+                None => matched_count > 0,
+            };
+            if is_better {
+                result = Some((key.as_str(), &fields[..matched_count]));
+            }
+        }
+
+        result
+    }
+
+    // find the primary keys of the documents whose leading fields of the compound index
+    // equal to the `prefix`, None returns if the key is not a compound index
+    pub fn find_pkeys_by_compound_index(&self, key: &str, prefix: &[Value], page_handler: &mut PageHandler) -> DbResult<Option<Vec<Value>>> {
+        let entry = match self.key_to_entry.get(key) {
+            Some(entry) if entry.fields.is_some() => entry,
+            _ => return Ok(None),
+        };
+
+        let keys = entry.find_primary_keys_by_prefix(prefix, page_handler)?;
+        Ok(Some(keys))
+    }
+
+    // free all the pages of the indexes
+    pub fn delete_all(&self, page_handler: &mut PageHandler) -> DbResult<()> {
+        for entry in self.key_to_entry.values() {
+            delete_all_helper::delete_all_by_root_pid(page_handler, entry.root_pid)?;
+        }
+
+        Ok(())
+    }
+
 }
 
 struct IndexEntry {
     name:     Option<String>,
     unique:   bool,
     root_pid: u32,
+
+    // the fields of the compound index, in order,
+    // None for the index on the single field of the key
+    fields:   Option<Vec<String>>,
 }
 
 impl IndexEntry {
@@ -88,11 +178,15 @@ impl IndexEntry {
         });
         let unique = doc.get(meta_doc_key::index::UNIQUE).unwrap().unwrap_boolean();
         let root_pid = doc.get(meta_doc_key::index::ROOT_PID).unwrap().unwrap_int();
+        let fields = doc.get(meta_doc_key::index::FIELDS).map(|val| {
+            val.unwrap_array().iter().map(|field| field.unwrap_string().to_string()).collect()
+        });
 
         IndexEntry {
             name,
             unique,
             root_pid: root_pid as u32,
+            fields,
         }
     }
 
@@ -103,17 +197,68 @@ impl IndexEntry {
         }
         result.insert(meta_doc_key::index::UNIQUE.into(), Value::Boolean(self.unique));
         result.insert(meta_doc_key::index::ROOT_PID.into(), Value::Int(self.root_pid as i64));
+        if let Some(fields) = &self.fields {
+            result.insert(meta_doc_key::index::FIELDS.into(), mk_fields_array(fields).into());
+        }
         result
     }
 
-    // store (data_value -> primary_key)
+    // the value stored in the index for the document,
+    // the dotted field is read from the embedded documents
+    //
+    // For the compound index, it's the array of the values of the fields in order,
+    // a missing field is Null, which is less than any other value.
+    // The document is not indexed if all the fields are missing.
+    fn index_value_of(&self, key: &str, doc: &Document) -> Option<Value> {
+        let fields = match &self.fields {
+            Some(fields) => fields,
+            None => return doc.get_path(key).cloned(),
+        };
+
+        let mut arr = Array::new();
+        let mut is_all_missing = true;
+        for field in fields {
+            match doc.get_path(field) {
+                Some(value) => {
+                    is_all_missing = false;
+                    arr.push(value.clone());
+                }
+                None => arr.push(Value::Null),
+            }
+        }
+
+        if is_all_missing {
+            return None;
+        }
+
+        Some(arr.into())
+    }
+
+    // store (data_value -> [primary_key])
+    //
+    // several documents may share the same value,
+    // the primary key is appended to the list of the existing entry.
+    // For a unique index, DuplicateKey returns if the entry exists.
     fn insert_index(
-        &mut self, data_value: &Value, primary_key: Value,
+        &mut self, field: &str, data_value: &Value, primary_key: Value,
         is_changed: &mut bool,
         page_handler: &mut PageHandler) -> DbResult<()> {
 
-        if !data_value.is_valid_key_type() {
-            return Err(DbErr::NotAValidKeyType(data_value.ty_name().into()));
+        check_index_value(data_value)?;
+
+        if self.unique && !self.find_primary_keys(data_value, page_handler)?.is_empty() {
+            return Err(DbErr::DuplicateKey {
+                field: field.into(),
+                value: Box::new(data_value.clone()),
+            });
+        }
+
+        if let Some(item) = self.find_index_entry(data_value, page_handler)? {
+            let entry_doc = page_handler.get_doc_from_ticket(&item.data_ticket)?.unwrap();
+            let mut keys = IndexEntry::primary_keys_of_entry_doc(&entry_doc);
+            keys.push(primary_key);
+
+            return self.replace_index_entry(data_value, keys, &item, page_handler);
         }
 
         let mut insert_wrapper = BTreePageInsertWrapper::new(page_handler, self.root_pid);
@@ -130,6 +275,110 @@ impl IndexEntry {
         Ok(())
     }
 
+    // sort the values of the field, the primary keys of the same value
+    // are merged into one entry, in the order of the documents
+    fn bulk_load(&self, field: &str, docs: &[Rc<Document>], page_handler: &mut PageHandler) -> DbResult<()> {
+        let mut pairs: Vec<(Value, Value)> = Vec::with_capacity(docs.len());
+        for doc in docs {
+            if let Some(value) = self.index_value_of(field, doc) {
+                check_index_value(&value)?;
+                pairs.push((value, doc.pkey_id().unwrap()));
+            }
+        }
+
+        // the sort is stable, so the order of the primary keys is kept
+        pairs.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let mut entry_docs: Vec<Document> = Vec::new();
+        let mut pairs_iter = pairs.into_iter().peekable();
+        while let Some((data_value, primary_key)) = pairs_iter.next() {
+            let mut keys = vec![primary_key];
+            while let Some((next_value, _)) = pairs_iter.peek() {
+                if next_value.value_cmp(&data_value)? != Ordering::Equal {
+                    break;
+                }
+                if self.unique {
+                    return Err(DbErr::DuplicateKey {
+                        field: field.into(),
+                        value: Box::new(data_value),
+                    });
+                }
+                keys.push(pairs_iter.next().unwrap().1);
+            }
+
+            entry_docs.push(IndexEntry::mk_index_entry_doc_with_keys(&data_value, keys));
+        }
+
+        bulk_load(page_handler, self.root_pid, &entry_docs)
+    }
+
+    // replace the entry of the data_value with the new primary keys,
+    // the old data is freed after the B-tree item is replaced,
+    // because the key of a complex item is read from the data while searching
+    fn replace_index_entry(&self, data_value: &Value, keys: Vec<Value>, old_item: &BTreeNodeDataItem, page_handler: &mut PageHandler) -> DbResult<()> {
+        let index_entry_doc = IndexEntry::mk_index_entry_doc_with_keys(data_value, keys);
+
+        let mut insert_wrapper = BTreePageInsertWrapper::new(page_handler, self.root_pid);
+        insert_wrapper.insert_item(&index_entry_doc, true)?;
+
+        page_handler.free_data_ticket(&old_item.data_ticket)?;
+
+        Ok(())
+    }
+
+    fn find_index_entry(&self, data_value: &Value, page_handler: &mut PageHandler) -> DbResult<Option<BTreeNodeDataItem>> {
+        let mut search_wrapper = BTreePageSearchWrapper::new(page_handler, self.root_pid);
+        match search_wrapper.search(data_value)? {
+            BTreeSearchResult::Found(item) => Ok(Some(item)),
+            BTreeSearchResult::NotFound(_) => Ok(None),
+        }
+    }
+
+    fn find_primary_keys(&self, data_value: &Value, page_handler: &mut PageHandler) -> DbResult<Vec<Value>> {
+        let item = match self.find_index_entry(data_value, page_handler)? {
+            Some(item) => item,
+            None => return Ok(vec![]),
+        };
+
+        let entry_doc = page_handler.get_doc_from_ticket(&item.data_ticket)?.unwrap();
+
+        Ok(IndexEntry::primary_keys_of_entry_doc(&entry_doc))
+    }
+
+    // the entries of the compound index are sorted by the fields in order,
+    // so the entries with the same leading fields are adjacent
+    fn find_primary_keys_by_prefix(&self, prefix: &[Value], page_handler: &mut PageHandler) -> DbResult<Vec<Value>> {
+        let mut lower_bound = Array::new();
+        for value in prefix {
+            lower_bound.push(value.clone());
+        }
+        let lower_bound: Value = lower_bound.into();
+
+        if prefix.len() == self.fields.as_ref().unwrap().len() {
+            return self.find_primary_keys(&lower_bound, page_handler);
+        }
+
+        let mut cursor = Cursor::new(page_handler.btree_order, self.root_pid);
+        cursor.reset_by_lower_bound(page_handler, &lower_bound)?;
+
+        let mut result = vec![];
+        while let Some(entry_doc) = cursor.next(page_handler)? {
+            let is_prefix_matched = match entry_doc.get(meta_doc_key::ID) {
+                Some(Value::Array(arr)) => prefix.iter().enumerate().all(|(index, value)| {
+                    matches!(arr.get(index).map(|field| field.value_cmp(value)), Some(Ok(Ordering::Equal)))
+                }),
+                _ => false,
+            };
+            if !is_prefix_matched {
+                break;
+            }
+
+            result.extend(IndexEntry::primary_keys_of_entry_doc(&entry_doc));
+        }
+
+        Ok(result)
+    }
+
     fn handle_backward_item(&mut self, meta_doc: &mut Document, backward_item: &InsertBackwardItem, page_handler: &mut PageHandler) -> DbResult<()> {
         let new_root_id = page_handler.alloc_page_id()?;
 
@@ -151,12 +400,92 @@ impl IndexEntry {
         }
     }
 
-    fn remove_index(&self, data_value: &Value, page_handler: &mut PageHandler) -> DbResult<()> {
-        let mut delete_wrapper = BTreePageDeleteWrapper::new(page_handler, self.root_pid);
-        let _result = delete_wrapper.delete_item(data_value)?;
-        Ok(())
+    fn mk_index_entry_doc_with_keys(data_value: &Value, keys: Vec<Value>) -> Document {
+        let mut arr = Array::new();
+        for key in keys {
+            arr.push(key);
+        }
+
+        mk_document! {
+            "_id": data_value.clone(),
+            "keys": arr,
+        }
+    }
+
+    fn primary_keys_of_entry_doc(entry_doc: &Document) -> Vec<Value> {
+        match entry_doc.get("keys") {
+            Some(Value::Array(arr)) => arr.iter().cloned().collect(),
+            _ => vec![],
+        }
+    }
+
+    fn remove_index(&self, data_value: &Value, primary_key: &Value, page_handler: &mut PageHandler) -> DbResult<()> {
+        let item = match self.find_index_entry(data_value, page_handler)? {
+            Some(item) => item,
+            None => return Ok(()),
+        };
+
+        let entry_doc = page_handler.get_doc_from_ticket(&item.data_ticket)?.unwrap();
+        let old_keys = IndexEntry::primary_keys_of_entry_doc(&entry_doc);
+
+        let keys: Vec<Value> = old_keys
+            .into_iter()
+            .filter(|key| key != primary_key)
+            .collect();
+
+        if keys.is_empty() {
+            let mut delete_wrapper = BTreePageDeleteWrapper::new(page_handler, self.root_pid);
+            delete_wrapper.delete_item(data_value)?;
+            delete_wrapper.flush_pages()?;
+            return Ok(());
+        }
+
+        self.replace_index_entry(data_value, keys, &item, page_handler)
+    }
+
+}
+
+// the value of a single field index must be a valid key type,
+// the values of the compound index can be Null as well
+// the numbers are indexed by the numeric value,
+// so an Int and a Double of the same value share the entry
+#[inline]
+fn is_index_value_type(value: &Value) -> bool {
+    value.is_valid_key_type() || matches!(value, Value::Double(_))
+}
+
+// the values of the query which can be looked up in the index,
+// NaN equals nothing in the query, so it's left to the scan
+pub(crate) fn is_index_lookup_value(value: &Value) -> bool {
+    match value {
+        Value::Double(d) => !d.is_nan(),
+        _ => value.is_valid_key_type(),
     }
+}
 
+fn check_index_value(value: &Value) -> DbResult<()> {
+    if let Value::Array(arr) = value {
+        for field_value in arr.iter() {
+            if !matches!(field_value, Value::Null) && !is_index_value_type(field_value) {
+                return Err(DbErr::NotAValidKeyType(field_value.ty_name().into()));
+            }
+        }
+        return Ok(());
+    }
+
+    if !is_index_value_type(value) {
+        return Err(DbErr::NotAValidKeyType(value.ty_name().into()));
+    }
+
+    Ok(())
+}
+
+pub(crate) fn mk_fields_array(fields: &[String]) -> Array {
+    let mut arr = Array::new();
+    for field in fields {
+        arr.push(Value::from(field.as_str()));
+    }
+    arr
 }
 
 macro_rules! match_and_merge_option {
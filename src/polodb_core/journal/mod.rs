@@ -3,7 +3,6 @@ mod transaction;
 
 pub use transaction::TransactionType;
 
-use std::fs::File;
 use std::path::{Path, PathBuf};
 use std::collections::BTreeMap;
 use std::io::{Seek, Write, SeekFrom, Read};
@@ -13,7 +12,8 @@ use frame_header::FrameHeader;
 use transaction::TransactionState;
 use crc64fast::Digest;
 use crate::page::RawPage;
-use crate::DbResult;
+use crate::storage::Storage;
+use crate::{DbResult, JournalMode, SyncPolicy};
 use crate::error::DbErr;
 use crate::dump::{JournalDump, JournalFrameDump};
 
@@ -21,19 +21,33 @@ use crate::dump::{JournalDump, JournalFrameDump};
 use std::os::windows::io::AsRawHandle;
 
 static HEADER_DESP: &str       = "PoloDB Journal v0.2";
+static SHADOW_HEADER_DESP: &str = "PoloDB Shadow Journal v0.2";
 const JOURNAL_DATA_BEGIN: u64 = 64;
 const FRAME_HEADER_SIZE: u64  = 40;
 
-// name:       32 bytes
+// name:       32 bytes, the mode of the journal
 // version:    4bytes(offset 32)
 // page_size:  4bytes(offset 36)
 // salt_1:     4bytes(offset 40)
 // salt_2:     4bytes(offset 44)
 // checksum before 48:   8bytes(offset 48)
 // data begin: 64 bytes
+//
+// The journal is a write-ahead log: the written pages are appended as frames,
+// the last frame of a transaction is marked as committed.
+// The reads find the newest frame of the page before the database file,
+// the frames are copied to the database file at the checkpoint.
+// After a crash, only the frames of the committed transactions are loaded and replayed.
+//
+// In the shadow mode, the written pages are kept in the memory until the commit,
+// the original pages are appended as frames, all of them have the size of the database
+// before the transaction. The changed pages are written to the database file in place,
+// then the journal is truncated. After a crash, the frames are written back, the same as
+// the replay of the write-ahead log.
 pub struct JournalManager {
     file_path:        PathBuf,
-    journal_file:     File,
+    journal_file:     Storage,
+    mode:             JournalMode,
     version:          [u8; 4],
     page_size:        u32,
     salt1:            u32,
@@ -64,18 +78,46 @@ fn crc64(bytes: &[u8]) -> u64 {
 
 impl JournalManager {
 
-    pub fn open(path: &Path, page_size: u32, db_file_size: u64) -> DbResult<JournalManager> {
+    /// The `mode` is used if the journal is created,
+    /// an existing journal is loaded by the mode it was written with.
+    pub fn open(path: &Path, page_size: u32, db_file_size: u64, mode: JournalMode) -> DbResult<JournalManager> {
         let journal_file = std::fs::OpenOptions::new()
             .create(true)
+            .truncate(false)
             .write(true)
             .read(true)
             .open(path)?;
-        let meta = journal_file.metadata()?;
+
+        JournalManager::from_storage(path, Storage::File(journal_file), page_size, db_file_size, mode)
+    }
+
+    /// Open the journal without changing anything on the disk.
+    ///
+    /// The content of the journal file is copied into the memory if it exists,
+    /// only the frames committed before opening are loaded.
+    pub fn open_read_only(path: &Path, page_size: u32, db_file_size: u64, mode: JournalMode) -> DbResult<JournalManager> {
+        let data = match std::fs::read(path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+
+        JournalManager::from_storage(path, Storage::new_memory(data), page_size, db_file_size, mode)
+    }
+
+    /// The journal of the database in the memory.
+    pub fn open_memory(path: &Path, page_size: u32, db_file_size: u64, mode: JournalMode) -> DbResult<JournalManager> {
+        JournalManager::from_storage(path, Storage::new_memory(Vec::new()), page_size, db_file_size, mode)
+    }
+
+    fn from_storage(path: &Path, journal_file: Storage, page_size: u32, db_file_size: u64, mode: JournalMode) -> DbResult<JournalManager> {
+        let journal_file_len = journal_file.len()?;
 
         let file_path: PathBuf = path.to_path_buf();
         let mut result = JournalManager {
             file_path,
             journal_file,
+            mode,
             version: [0, 0, 1, 0],
             page_size,
             db_file_size,
@@ -87,14 +129,17 @@ impl JournalManager {
             count: 0,
         };
 
-        if meta.len() == 0 {  // init the file
+        if journal_file_len == 0 {  // init the file
             result.init_header_to_file()?;
         } else {
             result.read_and_check_from_file()?;
         }
 
         result.journal_file.seek(SeekFrom::Start(JOURNAL_DATA_BEGIN))?;
-        result.load_all_pages(meta.len())?;
+        match result.mode {
+            JournalMode::Wal => result.load_all_pages(journal_file_len)?,
+            JournalMode::Shadow => result.load_shadow_frames(journal_file_len)?,
+        }
 
         Ok(result)
     }
@@ -106,11 +151,13 @@ impl JournalManager {
     }
 
     fn write_header_to_file(&mut self) -> DbResult<()> {
-        let mut header48: Vec<u8> = vec![];
-        header48.resize(48, 0);
+        let mut header48: Vec<u8> = vec![0; 48];
 
         // copy title
-        let title_bytes = HEADER_DESP.as_bytes();
+        let title_bytes = match self.mode {
+            JournalMode::Wal => HEADER_DESP.as_bytes(),
+            JournalMode::Shadow => SHADOW_HEADER_DESP.as_bytes(),
+        };
         header48[0..title_bytes.len()].copy_from_slice(title_bytes);
 
         // copy version
@@ -148,6 +195,12 @@ impl JournalManager {
             return Err(DbErr::ChecksumMismatch);
         }
 
+        self.mode = if header48.starts_with(SHADOW_HEADER_DESP.as_bytes()) {
+            JournalMode::Shadow
+        } else {
+            JournalMode::Wal
+        };
+
         // copy version
         self.version.copy_from_slice(&header48[32..36]);
 
@@ -188,12 +241,12 @@ impl JournalManager {
     }
 
     #[inline]
-    fn full_frame_size(&self) -> u64 {
+    pub(crate) fn full_frame_size(&self) -> u64 {
         (self.page_size as u64) + FRAME_HEADER_SIZE
     }
 
     fn load_all_pages(&mut self, file_size: u64) -> DbResult<()> {
-        let mut current_pos = self.journal_file.seek(SeekFrom::Current(0))?;
+        let mut current_pos = self.journal_file.stream_position()?;
         let frame_size = self.full_frame_size();
 
         while current_pos + frame_size <= file_size {
@@ -201,14 +254,17 @@ impl JournalManager {
                 self.new_write_state();
             }
 
-            let mut buffer = vec![];
-            buffer.resize(frame_size as usize, 0);
+            let mut buffer = vec![0; frame_size as usize];
 
             self.journal_file.read_exact(&mut buffer)?;
 
             let is_commit = Cell::new(false);
             match self.check_and_load_frame(current_pos, &buffer, &is_commit) {
                 Ok(()) => (),
+                Err(DbErr::ChecksumMismatch) if self.has_commit_frame_after(current_pos + frame_size, file_size)? => {
+                    // the broken frame belongs to a committed transaction
+                    return Err(DbErr::JournalChecksumMismatch);
+                }
                 Err(DbErr::SaltMismatch) |
                 Err(DbErr::ChecksumMismatch) => {
                     self.journal_file.set_len(current_pos)?;  // trim the tail
@@ -220,7 +276,7 @@ impl JournalManager {
 
             let state = self.transaction_state.as_mut().unwrap();
             state.frame_count += 1;
-            current_pos = self.journal_file.seek(SeekFrom::Current(0))?;
+            current_pos = self.journal_file.stream_position()?;
 
             if is_commit.get() {
                 self.merge_transaction_state();
@@ -235,6 +291,42 @@ impl JournalManager {
         Ok(())
     }
 
+    // the frames of the shadow journal are the original pages of the commit unfinished,
+    // all of them are loaded to be written back
+    fn load_shadow_frames(&mut self, file_size: u64) -> DbResult<()> {
+        let mut current_pos = self.journal_file.stream_position()?;
+        let frame_size = self.full_frame_size();
+        let mut buffer = vec![0; frame_size as usize];
+
+        while current_pos + frame_size <= file_size {
+            self.journal_file.read_exact(&mut buffer)?;
+
+            let frame_header = match self.check_frame(&buffer) {
+                Ok(frame_header) => frame_header,
+                Err(DbErr::ChecksumMismatch) if self.has_commit_frame_after(current_pos + frame_size, file_size)? => {
+                    // the database file may be written after the frame
+                    return Err(DbErr::JournalChecksumMismatch);
+                }
+                Err(DbErr::SaltMismatch) |
+                Err(DbErr::ChecksumMismatch) => {
+                    // the database file is written after all the frames are appended,
+                    // the torn tail is trimmed
+                    self.journal_file.set_len(current_pos)?;
+                    self.journal_file.seek(SeekFrom::End(0))?;
+                    break;
+                }
+                Err(err) => return Err(err),
+            };
+
+            self.offset_map.insert(frame_header.page_id, current_pos);
+            self.db_file_size = frame_header.db_size;
+            self.count += 1;
+            current_pos += frame_size;
+        }
+
+        Ok(())
+    }
+
     fn recover_file_and_state(&mut self) -> DbResult<()> {
         self.transaction_state = None;
         let frame_size = FRAME_HEADER_SIZE + (self.page_size as u64);
@@ -244,7 +336,35 @@ impl JournalManager {
         Ok(())
     }
 
-    fn check_and_load_frame(&mut self, current_pos: u64, bytes: &[u8], is_commit: &Cell<bool>) -> DbResult<()> {
+    // A torn tail is left if the process dies while appending the frames,
+    // the frames after the last commit are abandoned, it's not a corruption.
+    //
+    // If a valid commit frame is found after the broken frame,
+    // the committed data is broken.
+    fn has_commit_frame_after(&mut self, begin_pos: u64, file_size: u64) -> DbResult<bool> {
+        let frame_size = self.full_frame_size();
+        let mut current_pos = begin_pos;
+        let mut buffer = vec![0; frame_size as usize];
+        let mut result = false;
+
+        self.journal_file.seek(SeekFrom::Start(current_pos))?;
+        while current_pos + frame_size <= file_size {
+            self.journal_file.read_exact(&mut buffer)?;
+            if let Ok(frame_header) = self.check_frame(&buffer) {
+                if frame_header.db_size != 0 {
+                    result = true;
+                    break;
+                }
+            }
+            current_pos += frame_size;
+        }
+
+        self.journal_file.seek(SeekFrom::Start(begin_pos))?;
+        Ok(result)
+    }
+
+    // check the checksums and the salts of the frame
+    fn check_frame(&self, bytes: &[u8]) -> DbResult<FrameHeader> {
         let frame_header = FrameHeader::from_bytes(&bytes[0..24]);
         let checksum1 = {
             let mut buffer: [u8; 8] = [0; 8];
@@ -274,12 +394,21 @@ impl JournalManager {
             return Err(DbErr::SaltMismatch);
         }
 
-        // load frame
-        self.offset_map.insert(frame_header.page_id, current_pos);
+        Ok(frame_header)
+    }
+
+    fn check_and_load_frame(&mut self, current_pos: u64, bytes: &[u8], is_commit: &Cell<bool>) -> DbResult<()> {
+        let frame_header = self.check_frame(bytes)?;
+
+        // load frame into the state of the transaction,
+        // they are merged when the commit frame is read,
+        // or abandoned if the transaction is not committed
+        let state = self.transaction_state.as_mut().unwrap();
+        state.offset_map.insert(frame_header.page_id, current_pos);
 
         // is a commit frame
         if frame_header.db_size != 0 {
-            self.db_file_size = frame_header.db_size;
+            state.db_file_size = frame_header.db_size;
             is_commit.set(true);
         }
         Ok(())
@@ -312,7 +441,7 @@ impl JournalManager {
 
     fn update_last_frame(&mut self) -> DbResult<()> {
         let full_frame_size = self.full_frame_size();
-        let begin_loc = self.journal_file.seek(SeekFrom::End((full_frame_size as i64) * -1))?;
+        let begin_loc = self.journal_file.seek(SeekFrom::End(-(full_frame_size as i64)))?;
         let mut data: [u8; FRAME_HEADER_SIZE as usize] = [0; FRAME_HEADER_SIZE as usize];
         self.journal_file.read_exact(&mut data)?;
         let mut frame_header = FrameHeader::from_bytes(&data);
@@ -361,7 +490,11 @@ impl JournalManager {
             _ => return Err(DbErr::CannotWriteDbWithoutTransaction),
         }
 
-        let start_pos = self.journal_file.seek(SeekFrom::Current(0))?;
+        // the position may be moved by reading pages,
+        // the frame must be appended after the last frame
+        let frame_count = self.transaction_state.as_ref().unwrap().frame_count as u64;
+        let start_pos = JOURNAL_DATA_BEGIN + self.full_frame_size() * frame_count;
+        self.journal_file.seek(SeekFrom::Start(start_pos))?;
 
         let frame_header = FrameHeader {
             page_id: raw_page.page_id,
@@ -381,7 +514,7 @@ impl JournalManager {
         state.offset_map.insert(raw_page.page_id, start_pos);
         state.frame_count += 1;
 
-        let expected_db_size = (raw_page.page_id as u64) * (self.page_size as u64);
+        let expected_db_size = (raw_page.page_id as u64 + 1) * (self.page_size as u64);
         if expected_db_size > state.db_file_size {
             state.db_file_size = expected_db_size;
         }
@@ -391,12 +524,131 @@ impl JournalManager {
         Ok(())
     }
 
+    /// Keep the page written in the shadow mode until the commit.
+    pub(crate) fn put_shadow_page(&mut self, raw_page: &RawPage) -> DbResult<()> {
+        let state = match &mut self.transaction_state {
+            Some(state) if state.ty == TransactionType::Write => state,
+            _ => return Err(DbErr::CannotWriteDbWithoutTransaction),
+        };
+
+        state.shadow_pages.insert(raw_page.page_id, raw_page.clone());
+
+        let expected_db_size = (raw_page.page_id as u64 + 1) * (self.page_size as u64);
+        if expected_db_size > state.db_file_size {
+            state.db_file_size = expected_db_size;
+        }
+
+        Ok(())
+    }
+
+    /// Commit the transaction of the shadow mode, the count of the frames
+    /// of the original pages returns, `None` if nothing is written.
+    ///
+    /// The original pages are appended to the journal, and the changed ones are written
+    /// to the database file, the transaction is committed when the journal is truncated.
+    /// Unless the policy is `SyncPolicy::Off`, the journal is synced before writing
+    /// the database file, and the database file is synced before truncating the journal.
+    pub(crate) fn commit_shadow(&mut self, db_file: &mut Storage, sync_policy: SyncPolicy) -> DbResult<Option<u32>> {
+        debug_assert!(self.mode == JournalMode::Shadow);
+
+        let state = match self.transaction_state.take() {
+            Some(state) => state,
+            None => return Err(DbErr::CannotWriteDbWithoutTransaction),
+        };
+        if state.ty == TransactionType::Read || state.shadow_pages.is_empty() {
+            self.unlock_file()?;
+            return Ok(None);
+        }
+
+        let sync = sync_policy != SyncPolicy::Off;
+        let page_size = self.page_size as u64;
+
+        let frame_count = self.append_original_pages(db_file, state.shadow_pages.keys().copied())?;
+        if sync && frame_count > 0 {
+            self.journal_file.sync_data()?;
+        }
+
+        db_file.set_len(state.db_file_size)?;
+        for (page_id, page) in &state.shadow_pages {
+            page.sync_to_file(db_file, (*page_id as u64) * page_size)?;
+        }
+        db_file.flush()?;
+        if sync {
+            db_file.sync_data()?;
+        }
+
+        self.db_file_size = state.db_file_size;
+        self.checkpoint_finished()?;
+        if sync_policy == SyncPolicy::Full {
+            self.journal_file.sync_data()?;
+        }
+        self.unlock_file()?;
+
+        crate::polo_log!("commit shadow pages: {}, original frames: {}", state.shadow_pages.len(), frame_count);
+
+        Ok(Some(frame_count))
+    }
+
+    /// Append the original pages in the database file as the frames,
+    /// the new pages beyond the end of the database have nothing to restore.
+    pub(crate) fn append_original_pages<I: Iterator<Item = u32>>(&mut self, db_file: &mut Storage, page_ids: I) -> DbResult<u32> {
+        let page_size = self.page_size as u64;
+        let mut frame_count: u32 = 0;
+        self.journal_file.seek(SeekFrom::Start(JOURNAL_DATA_BEGIN))?;
+        for page_id in page_ids {
+            let offset = (page_id as u64) * page_size;
+            if offset + page_size > self.db_file_size {
+                continue;
+            }
+
+            let mut original = RawPage::new(page_id, self.page_size);
+            original.read_from_file(db_file, offset)?;
+
+            let frame_header = FrameHeader {
+                page_id,
+                db_size: self.db_file_size,
+                salt1: self.salt1,
+                salt2: self.salt2,
+            };
+            self.append_frame_header(&frame_header, crc64(&original.data))?;
+            self.journal_file.write_all(&original.data)?;
+            frame_count += 1;
+        }
+        self.count = frame_count;
+        Ok(frame_count)
+    }
+
+    /// Switch the mode of the empty journal, the header is rewritten.
+    pub(crate) fn set_mode(&mut self, mode: JournalMode) -> DbResult<()> {
+        debug_assert!(self.count == 0 && self.transaction_state.is_none());
+        if self.mode == mode {
+            return Ok(());
+        }
+        self.mode = mode;
+        self.write_header_to_file()
+    }
+
+    #[inline]
+    pub(crate) fn mode(&self) -> JournalMode {
+        self.mode
+    }
+
+    /// The size of the database file, the pages written by the transaction are not counted.
+    #[inline]
+    pub(crate) fn committed_db_size(&self) -> u64 {
+        self.db_file_size
+    }
+
     pub(crate) fn read_page(&mut self, page_id: u32) -> std::io::Result<Option<RawPage>> {
         let offset = match &self.transaction_state {
 
             // currently in transaction state
             // find it in state firstly
             Some(state) => {
+                if let Some(page) = state.shadow_pages.get(&page_id) {
+                    return Ok(Some(page.clone()));
+                }
+
                 match state.offset_map.get(&page_id) {
                     Some(offset) => *offset,
 
@@ -420,6 +672,14 @@ impl JournalManager {
 
         };
 
+        let result = self.read_frame_page(page_id, offset)?;
+
+        Ok(Some(result))
+    }
+
+    /// Read the page of the frame at `offset`,
+    /// the frames are not moved until the checkpoint.
+    pub(crate) fn read_frame_page(&mut self, page_id: u32, offset: u64) -> std::io::Result<RawPage> {
         let data_offset = offset + FRAME_HEADER_SIZE;
 
         self.journal_file.seek(SeekFrom::Start(data_offset))?;
@@ -429,12 +689,37 @@ impl JournalManager {
 
         crate::polo_log!("read page from journal, page_id: {}, data_offset:\t\t0x{:0>8X}", page_id, offset);
 
-        Ok(Some(result))
+        Ok(result)
+    }
+
+    /// The pages written by the transaction of the shadow mode,
+    /// they are written to the database file in place at the commit.
+    pub(crate) fn shadow_page_ids(&self) -> Vec<u32> {
+        match &self.transaction_state {
+            Some(state) => state.shadow_pages.keys().copied().collect(),
+            None => vec![],
+        }
     }
 
-    pub(crate) fn checkpoint_journal(&mut self, db_file: &mut File) -> DbResult<()> {
+    /// Write the pages of the journal into the database file.
+    ///
+    /// All the frames are verified by the checksums before writing anything,
+    /// if a frame is broken, `DbErr::JournalChecksumMismatch` returns,
+    /// the database file and the journal are not changed.
+    /// Copy the committed pages to the database file and truncate the journal,
+    /// the database file is synced before truncating if `sync` is true.
+    pub(crate) fn checkpoint_journal(&mut self, db_file: &mut Storage, sync: bool) -> DbResult<()> {
         debug_assert!(self.transaction_state.is_none());
 
+        let mut buffer = vec![0; self.full_frame_size() as usize];
+        for offset in self.offset_map.values() {
+            self.journal_file.seek(SeekFrom::Start(*offset))?;
+            self.journal_file.read_exact(&mut buffer)?;
+            if self.check_frame(&buffer).is_err() {
+                return Err(DbErr::JournalChecksumMismatch);
+            }
+        }
+
         db_file.set_len(self.db_file_size)?;
 
         for (page_id, offset) in &self.offset_map {
@@ -450,11 +735,17 @@ impl JournalManager {
 
         db_file.flush()?;  // only checkpoint flush the file
 
+        // the journal is truncated later,
+        // the pages must be on the disk before that
+        if sync {
+            db_file.sync_data()?;
+        }
+
         self.checkpoint_finished()
     }
 
     fn plus_salt1(&mut self) {
-        if self.salt1 == u32::max_value() {
+        if self.salt1 == u32::MAX {
             self.salt1 = 0;
             return;
         }
@@ -504,7 +795,8 @@ impl JournalManager {
         }
 
         let transaction_ty = self.merge_transaction_state();
-        if transaction_ty == TransactionType::Write {
+        // nothing is written since the journal is checkpointed, no frame to update
+        if transaction_ty == TransactionType::Write && self.count > 0 {
             self.update_last_frame()?;
         }
         self.unlock_file()?;
@@ -512,6 +804,13 @@ impl JournalManager {
         Ok(())
     }
 
+    /// Sync the frames appended to the disk.
+    #[inline]
+    pub(crate) fn sync(&self) -> DbResult<()> {
+        self.journal_file.sync_data()?;
+        Ok(())
+    }
+
     pub(crate) fn rollback(&mut self) -> DbResult<()> {
         if self.transaction_state.is_none() {
             return Err(DbErr::RollbackNotInTransaction);
@@ -542,7 +841,10 @@ impl JournalManager {
         use winapi::um::minwinbase::{LOCKFILE_EXCLUSIVE_LOCK, LOCKFILE_FAIL_IMMEDIATELY};
         use winapi::ctypes;
 
-        let handle = self.journal_file.as_raw_handle();
+        let handle = match self.journal_file.as_file() {
+            Some(file) => file.as_raw_handle(),
+            None => return Ok(()),
+        };
 
         let bl = unsafe {
             let overlapped: *mut OVERLAPPED = libc::malloc(std::mem::size_of::<OVERLAPPED>()).cast::<OVERLAPPED>();
@@ -569,7 +871,10 @@ impl JournalManager {
         use winapi::um::minwinbase::LOCKFILE_FAIL_IMMEDIATELY;
         use winapi::ctypes;
 
-        let handle = self.journal_file.as_raw_handle();
+        let handle = match self.journal_file.as_file() {
+            Some(file) => file.as_raw_handle(),
+            None => return Ok(()),
+        };
 
         let bl = unsafe {
             let overlapped: *mut OVERLAPPED = libc::malloc(std::mem::size_of::<OVERLAPPED>()).cast::<OVERLAPPED>();
@@ -592,7 +897,10 @@ impl JournalManager {
         use winapi::um::minwinbase::OVERLAPPED;
         use winapi::ctypes;
 
-        let handle = self.journal_file.as_raw_handle();
+        let handle = match self.journal_file.as_file() {
+            Some(file) => file.as_raw_handle(),
+            None => return Ok(()),
+        };
 
         let bl = unsafe {
             let overlapped: *mut OVERLAPPED = libc::malloc(std::mem::size_of::<OVERLAPPED>()).cast::<OVERLAPPED>();
@@ -614,7 +922,10 @@ impl JournalManager {
         use std::os::unix::prelude::*;
         use libc::{flock, LOCK_EX, LOCK_NB};
 
-        let fd = self.journal_file.as_raw_fd();
+        let fd = match self.journal_file.as_file() {
+            Some(file) => file.as_raw_fd(),
+            None => return Ok(()),
+        };
         let result = unsafe {
             flock(fd, LOCK_EX | LOCK_NB)
         };
@@ -631,7 +942,10 @@ impl JournalManager {
         use std::os::unix::prelude::*;
         use libc::{flock, LOCK_SH, LOCK_NB};
 
-        let fd = self.journal_file.as_raw_fd();
+        let fd = match self.journal_file.as_file() {
+            Some(file) => file.as_raw_fd(),
+            None => return Ok(()),
+        };
         let result = unsafe {
             flock(fd, LOCK_SH | LOCK_NB)
         };
@@ -650,7 +964,10 @@ impl JournalManager {
         use std::os::unix::prelude::*;
         use libc::{flock, LOCK_UN, LOCK_NB};
 
-        let fd = self.journal_file.as_raw_fd();
+        let fd = match self.journal_file.as_file() {
+            Some(file) => file.as_raw_fd(),
+            None => return Ok(()),
+        };
         let result = unsafe {
             flock(fd, LOCK_UN | LOCK_NB)
         };
@@ -716,9 +1033,10 @@ impl JournalManager {
 
 #[cfg(test)]
 mod tests {
-    use crate::journal::JournalManager;
+    use crate::journal::{JournalManager, JOURNAL_DATA_BEGIN, FRAME_HEADER_SIZE};
     use crate::page::RawPage;
-    use crate::TransactionType;
+    use crate::storage::Storage;
+    use crate::{TransactionType, DbErr, JournalMode, SyncPolicy};
 
     static TEST_PAGE_LEN: u32 = 100;
 
@@ -737,7 +1055,7 @@ mod tests {
     #[test]
     fn test_journal() {
         let _ = std::fs::remove_file("/tmp/test-journal");
-        let mut journal_manager = JournalManager::open("/tmp/test-journal".as_ref(), 4096, 4096).unwrap();
+        let mut journal_manager = JournalManager::open("/tmp/test-journal".as_ref(), 4096, 4096, JournalMode::Wal).unwrap();
 
         journal_manager.start_transaction(TransactionType::Write).unwrap();
 
@@ -770,7 +1088,7 @@ mod tests {
         let _ = std::fs::remove_file(TEST_FILE);
         let mem_count;
         {
-            let mut journal_manager = JournalManager::open(TEST_FILE.as_ref(), 4096, 4096).unwrap();
+            let mut journal_manager = JournalManager::open(TEST_FILE.as_ref(), 4096, 4096, JournalMode::Wal).unwrap();
 
             journal_manager.start_transaction(TransactionType::Write).unwrap();
 
@@ -788,8 +1106,204 @@ mod tests {
             mem_count = journal_manager.count;
         }
 
-        let journal_manager = JournalManager::open(TEST_FILE.as_ref(), 4096, 4096).unwrap();
+        let journal_manager = JournalManager::open(TEST_FILE.as_ref(), 4096, 4096, JournalMode::Wal).unwrap();
         assert_eq!(mem_count, journal_manager.count);
     }
 
+    fn write_journal_with_pages(path: &str, committed: u32, uncommitted: u32) {
+        let _ = std::fs::remove_file(path);
+        let mut journal_manager = JournalManager::open(path.as_ref(), 4096, 4096, JournalMode::Wal).unwrap();
+
+        journal_manager.start_transaction(TransactionType::Write).unwrap();
+        for i in 0..committed {
+            journal_manager.append_raw_page(&make_raw_page(i)).unwrap();
+        }
+        journal_manager.commit().unwrap();
+
+        if uncommitted > 0 {
+            journal_manager.start_transaction(TransactionType::Write).unwrap();
+            for i in 0..uncommitted {
+                journal_manager.append_raw_page(&make_raw_page(committed + i)).unwrap();
+            }
+        }
+    }
+
+    fn corrupt_byte(path: &str, offset: u64) {
+        use std::io::{Seek, SeekFrom, Read, Write};
+        let mut file = std::fs::OpenOptions::new().read(true).write(true).open(path).unwrap();
+        let mut buffer: [u8; 1] = [0];
+        file.seek(SeekFrom::Start(offset)).unwrap();
+        file.read_exact(&mut buffer).unwrap();
+        buffer[0] = !buffer[0];
+        file.seek(SeekFrom::Start(offset)).unwrap();
+        file.write_all(&buffer).unwrap();
+    }
+
+    #[test]
+    fn test_torn_tail() {
+        const TEST_FILE: &str = "/tmp/test-journal-torn-tail";
+        let frame_size = 4096 + FRAME_HEADER_SIZE;
+
+        // the last frame is broken
+        write_journal_with_pages(TEST_FILE, 3, 2);
+        corrupt_byte(TEST_FILE, JOURNAL_DATA_BEGIN + frame_size * 4 + 100);
+        let journal_manager = JournalManager::open(TEST_FILE.as_ref(), 4096, 4096, JournalMode::Wal).unwrap();
+        assert_eq!(journal_manager.count, 3);
+        drop(journal_manager);
+
+        // the last frame is partially written
+        write_journal_with_pages(TEST_FILE, 3, 2);
+        let file = std::fs::OpenOptions::new().write(true).open(TEST_FILE).unwrap();
+        file.set_len(JOURNAL_DATA_BEGIN + frame_size * 4 + 1000).unwrap();
+        drop(file);
+        let journal_manager = JournalManager::open(TEST_FILE.as_ref(), 4096, 4096, JournalMode::Wal).unwrap();
+        assert_eq!(journal_manager.count, 3);
+        assert_eq!(journal_manager.offset_map.len(), 3);
+    }
+
+    #[test]
+    fn test_committed_frame_broken() {
+        const TEST_FILE: &str = "/tmp/test-journal-committed-broken";
+        let frame_size = 4096 + FRAME_HEADER_SIZE;
+
+        write_journal_with_pages(TEST_FILE, 3, 0);
+        corrupt_byte(TEST_FILE, JOURNAL_DATA_BEGIN + frame_size + 100);
+        let result = JournalManager::open(TEST_FILE.as_ref(), 4096, 4096, JournalMode::Wal);
+        assert!(matches!(result, Err(DbErr::JournalChecksumMismatch)));
+    }
+
+    #[test]
+    fn test_checkpoint_verify_checksum() {
+        const TEST_FILE: &str = "/tmp/test-journal-checkpoint-verify";
+        const DB_FILE: &str = "/tmp/test-journal-checkpoint-verify.db";
+        let frame_size = 4096 + FRAME_HEADER_SIZE;
+
+        write_journal_with_pages(TEST_FILE, 3, 0);
+        let mut journal_manager = JournalManager::open(TEST_FILE.as_ref(), 4096, 4096, JournalMode::Wal).unwrap();
+        corrupt_byte(TEST_FILE, JOURNAL_DATA_BEGIN + frame_size * 2 + 100);
+
+        let _ = std::fs::remove_file(DB_FILE);
+        let db_file = std::fs::OpenOptions::new().create(true).truncate(true).read(true).write(true).open(DB_FILE).unwrap();
+        let mut db_file = Storage::File(db_file);
+        assert!(matches!(journal_manager.checkpoint_journal(&mut db_file, true), Err(DbErr::JournalChecksumMismatch)));
+        assert_eq!(db_file.metadata().unwrap().len(), 0);
+    }
+
+    fn make_db_file(page_count: u32) -> (Storage, Vec<RawPage>) {
+        let mut db_file = Storage::new_memory(Vec::new());
+        let pages: Vec<RawPage> = (0..page_count).map(make_raw_page).collect();
+        for page in &pages {
+            page.sync_to_file(&mut db_file, (page.page_id as u64) * 4096).unwrap();
+        }
+        (db_file, pages)
+    }
+
+    fn read_db_page(db_file: &mut Storage, page_id: u32) -> RawPage {
+        let mut page = RawPage::new(page_id, 4096);
+        page.read_from_file(db_file, (page_id as u64) * 4096).unwrap();
+        page
+    }
+
+    #[test]
+    fn test_shadow_commit() {
+        const TEST_FILE: &str = "/tmp/test-journal-shadow-commit";
+        let _ = std::fs::remove_file(TEST_FILE);
+
+        let (mut db_file, pages) = make_db_file(4);
+        let mut journal_manager = JournalManager::open(TEST_FILE.as_ref(), 4096, 4096 * 4, JournalMode::Shadow).unwrap();
+
+        journal_manager.start_transaction(TransactionType::Write).unwrap();
+        let changed = make_raw_page(1);
+        let appended = make_raw_page(5);
+        journal_manager.put_shadow_page(&changed).unwrap();
+        journal_manager.put_shadow_page(&appended).unwrap();
+        assert_eq!(journal_manager.read_page(1).unwrap().unwrap().data, changed.data);
+        assert_eq!(journal_manager.record_db_size(), 4096 * 6);
+        assert_eq!(journal_manager.committed_db_size(), 4096 * 4);
+
+        // only the original of the page 1 is kept
+        let frame_count = journal_manager.commit_shadow(&mut db_file, SyncPolicy::Normal).unwrap();
+        assert_eq!(frame_count, Some(1));
+        assert_eq!(journal_manager.len(), 0);
+        assert_eq!(journal_manager.committed_db_size(), 4096 * 6);
+        assert_eq!(std::fs::metadata(TEST_FILE).unwrap().len(), JOURNAL_DATA_BEGIN);
+
+        assert_eq!(db_file.len().unwrap(), 4096 * 6);
+        assert_eq!(read_db_page(&mut db_file, 0).data, pages[0].data);
+        assert_eq!(read_db_page(&mut db_file, 1).data, changed.data);
+        assert_eq!(read_db_page(&mut db_file, 5).data, appended.data);
+
+        // nothing is written by a read transaction
+        journal_manager.start_transaction(TransactionType::Read).unwrap();
+        assert_eq!(journal_manager.commit_shadow(&mut db_file, SyncPolicy::Normal).unwrap(), None);
+        drop(journal_manager);
+
+        // the mode is stored in the header
+        let journal_manager = JournalManager::open(TEST_FILE.as_ref(), 4096, 4096 * 6, JournalMode::Wal).unwrap();
+        assert_eq!(journal_manager.mode(), JournalMode::Shadow);
+    }
+
+    // the process dies after the original pages are appended,
+    // and a part of the changed pages are written to the database file
+    fn write_hot_shadow_journal(path: &str, db_file: &mut Storage) {
+        let _ = std::fs::remove_file(path);
+        let mut journal_manager = JournalManager::open(path.as_ref(), 4096, 4096 * 4, JournalMode::Shadow).unwrap();
+        journal_manager.start_transaction(TransactionType::Write).unwrap();
+        assert_eq!(journal_manager.append_original_pages(db_file, [1, 2, 6].iter().copied()).unwrap(), 2);
+
+        db_file.set_len(4096 * 7).unwrap();
+        make_raw_page(1).sync_to_file(db_file, 4096).unwrap();
+        make_raw_page(6).sync_to_file(db_file, 4096 * 6).unwrap();
+    }
+
+    #[test]
+    fn test_shadow_recovery() {
+        const TEST_FILE: &str = "/tmp/test-journal-shadow-recovery";
+
+        let (mut db_file, pages) = make_db_file(4);
+        write_hot_shadow_journal(TEST_FILE, &mut db_file);
+
+        let mut journal_manager = JournalManager::open(TEST_FILE.as_ref(), 4096, 4096 * 7, JournalMode::Wal).unwrap();
+        assert_eq!(journal_manager.mode(), JournalMode::Shadow);
+        assert_eq!(journal_manager.len(), 2);
+        assert_eq!(journal_manager.committed_db_size(), 4096 * 4);
+        // the original pages are read before the recovery
+        assert_eq!(journal_manager.read_page(1).unwrap().unwrap().data, pages[1].data);
+
+        journal_manager.checkpoint_journal(&mut db_file, true).unwrap();
+        assert_eq!(journal_manager.len(), 0);
+        assert_eq!(db_file.len().unwrap(), 4096 * 4);
+        for page in &pages {
+            assert_eq!(read_db_page(&mut db_file, page.page_id).data, page.data);
+        }
+
+        // switched to the mode of the config after the recovery
+        journal_manager.set_mode(JournalMode::Wal).unwrap();
+        drop(journal_manager);
+        let journal_manager = JournalManager::open(TEST_FILE.as_ref(), 4096, 4096 * 4, JournalMode::Shadow).unwrap();
+        assert_eq!(journal_manager.mode(), JournalMode::Wal);
+    }
+
+    #[test]
+    fn test_shadow_torn_tail() {
+        const TEST_FILE: &str = "/tmp/test-journal-shadow-torn-tail";
+        let frame_size = 4096 + FRAME_HEADER_SIZE;
+
+        // the database file is not written before the journal is complete
+        let (mut db_file, _) = make_db_file(4);
+        write_hot_shadow_journal(TEST_FILE, &mut db_file);
+        corrupt_byte(TEST_FILE, JOURNAL_DATA_BEGIN + frame_size + 100);
+        let journal_manager = JournalManager::open(TEST_FILE.as_ref(), 4096, 4096 * 7, JournalMode::Shadow).unwrap();
+        assert_eq!(journal_manager.len(), 1);
+        assert_eq!(std::fs::metadata(TEST_FILE).unwrap().len(), JOURNAL_DATA_BEGIN + frame_size);
+        drop(journal_manager);
+
+        // the pages after the broken one may be written back
+        let (mut db_file, _) = make_db_file(4);
+        write_hot_shadow_journal(TEST_FILE, &mut db_file);
+        corrupt_byte(TEST_FILE, JOURNAL_DATA_BEGIN + 100);
+        let result = JournalManager::open(TEST_FILE.as_ref(), 4096, 4096 * 7, JournalMode::Shadow);
+        assert!(matches!(result, Err(DbErr::JournalChecksumMismatch)));
+    }
+
 }
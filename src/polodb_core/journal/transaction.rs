@@ -1,4 +1,5 @@
 use std::collections::BTreeMap;
+use crate::page::RawPage;
 
 #[derive(Eq, PartialEq, Copy, Clone)]
 pub enum TransactionType {
@@ -9,6 +10,8 @@ pub enum TransactionType {
 pub(super) struct TransactionState {
     pub(super) ty: TransactionType,
     pub(super) offset_map: BTreeMap<u32, u64>,
+    // the pages written in the shadow mode
+    pub(super) shadow_pages: BTreeMap<u32, RawPage>,
     pub(super) frame_count: u32,
     pub(super) db_file_size: u64,
 }
@@ -19,6 +22,7 @@ impl TransactionState {
         TransactionState {
             ty,
             offset_map: BTreeMap::new(),
+            shadow_pages: BTreeMap::new(),
             frame_count,
             db_file_size,
         }
@@ -1,5 +1,6 @@
 #![cfg_attr(docsrs, deny(broken_intra_doc_links))]
 #![cfg_attr(docsrs, feature(doc_cfg))]
+#![cfg_attr(all(feature = "nightly", test), feature(test))]
 
 //! PoloDB is an embedded JSON-based database.
 //!
@@ -26,22 +27,45 @@ mod btree;
 mod page;
 mod journal;
 mod vm;
+mod query;
 mod error;
 mod cursor;
 
 pub mod db;
+mod shared_db;
+mod transaction;
+mod snapshot;
 mod data_ticket;
 mod index_ctx;
 mod meta_doc_helper;
 mod context;
 mod db_handle;
 pub mod dump;
+mod stats;
+mod bulk_write;
+mod storage;
+mod migration;
+mod oplog;
 mod config;
+mod options;
 mod macros;
+#[cfg(feature = "serde")]
+mod doc_serde;
 
-pub use db::{Database, DbResult};
-pub use config::Config;
+pub use db::{Database, DbResult, UpdateResult, QueryCursor};
+pub use shared_db::SharedDatabase;
+pub use transaction::Transaction;
+pub use snapshot::Snapshot;
+pub use config::{Config, SyncPolicy, JournalMode};
+pub use options::OperationOptions;
+pub use oplog::OPLOG_COLLECTION_NAME;
 pub use journal::TransactionType;
 pub use context::DbContext;
 pub use db_handle::DbHandle;
 pub use error::DbErr;
+pub use page::{PageCacheStats, PageMetrics};
+pub use stats::{DbStats, CollectionStats};
+pub use bulk_write::{WriteOp, BulkResult};
+pub use query::QueryPlan;
+#[cfg(feature = "serde")]
+pub use doc_serde::{to_document, from_document};
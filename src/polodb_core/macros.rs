@@ -2,7 +2,7 @@
 #[macro_export]
 macro_rules! polo_log (
     ($($arg:tt)+) => {
-        if cfg!(log) {
+        if cfg!(feature = "log") {
             eprintln!($($arg)*);
         }
     }
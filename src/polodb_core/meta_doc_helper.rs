@@ -9,6 +9,7 @@ use crate::error::DbErr;
 ///   name: String,
 ///   root_pid: Int,
 ///   flags: Int,
+///   count: Int,
 /// }
 ///
 /// flags indicates:
@@ -30,6 +31,7 @@ impl MetaDocEntry {
             "name": name.clone(),
             "root_pid": root_pid,
             "flags": 0,
+            "count": 0,
         };
         MetaDocEntry {
             name,
@@ -45,11 +47,16 @@ impl MetaDocEntry {
         }
     }
 
-    #[allow(dead_code)]
     pub(crate) fn name(&self) -> &str {
         self.name.as_str()
     }
 
+    pub(crate) fn set_name(&mut self, name: &str) {
+        let doc_mut = Rc::get_mut(&mut self.doc).unwrap();
+        doc_mut.insert(meta_doc_key::NAME.into(), Value::from(name));
+        self.name = name.into();
+    }
+
     pub(crate) fn root_pid(&self) -> u32 {
         self.doc.get(meta_doc_key::ROOT_PID).unwrap().unwrap_int() as u32
     }
@@ -68,6 +75,18 @@ impl MetaDocEntry {
         doc_mut.insert(meta_doc_key::FLAGS.into(), Value::from(flags));
     }
 
+    /// The count of the documents, updated with the documents in the same transaction.
+    ///
+    /// The collections created before the count is stored return `None`.
+    pub(crate) fn count(&self) -> Option<u64> {
+        self.doc.get(meta_doc_key::COUNT).map(|count| count.unwrap_int() as u64)
+    }
+
+    pub(crate) fn set_count(&mut self, count: u64) {
+        let doc_mut = Rc::get_mut(&mut self.doc).unwrap();
+        doc_mut.insert(meta_doc_key::COUNT.into(), Value::from(count));
+    }
+
     #[inline]
     fn key_ty(&self) -> u8 {
         (self.flags() & KEY_TY_FLAG) as u8
@@ -112,12 +131,14 @@ pub(crate) mod meta_doc_key {
     pub(crate) static NAME: &str     = "name";
     pub(crate) static FLAGS: &str    = "flags";
     pub(crate) static INDEXES: &str  = "indexes";
+    pub(crate) static COUNT: &str    = "count";
 
     pub(crate) mod index {
         pub(crate) static NAME: &str = "name";
         pub(crate) static V: &str    = "v";
         pub(crate) static UNIQUE: &str = "unique";
         pub(crate) static ROOT_PID: &str = "root_pid";
+        pub(crate) static FIELDS: &str = "fields";
 
     }
 
@@ -0,0 +1,70 @@
+use crate::DbResult;
+use crate::error::DbErr;
+use crate::journal::TransactionType;
+use crate::page::PageHandler;
+use crate::page::header_page_wrapper::{self, HeaderPageWrapper};
+
+type Migration = fn(&mut PageHandler) -> DbResult<()>;
+
+// the oldest version can be upgraded
+const MIN_FORMAT_VERSION: u32 = 1;
+
+// MIGRATIONS[i] upgrades a file from version `MIN_FORMAT_VERSION + i` to the next one,
+// a new entry is appended when FORMAT_VERSION is bumped.
+static MIGRATIONS: &[Migration] = &[
+    migrate_v1_to_v2,
+];
+
+// version 1 has the same layout as version 2, only the version is bumped
+fn migrate_v1_to_v2(_page_handler: &mut PageHandler) -> DbResult<()> {
+    Ok(())
+}
+
+/// Upgrade the file to the current format version by running the migrations in order.
+///
+/// All the migrations and the new version in the header are written in one transaction,
+/// if it's interrupted, the journal is discarded when the database is opened again,
+/// so the file is left at the old version, and the migrations run again.
+pub(crate) fn migrate(page_handler: &mut PageHandler) -> DbResult<()> {
+    let first_page = page_handler.get_first_page()?;
+    let version = HeaderPageWrapper::from_raw_page(first_page).get_format_version();
+    if version == header_page_wrapper::FORMAT_VERSION {
+        return Ok(());
+    }
+
+    debug_assert_eq!(MIN_FORMAT_VERSION + MIGRATIONS.len() as u32, header_page_wrapper::FORMAT_VERSION);
+    if version < MIN_FORMAT_VERSION {
+        return Err(DbErr::UnsupportedFileVersion {
+            found: version,
+            supported: header_page_wrapper::FORMAT_VERSION,
+        });
+    }
+
+    if page_handler.config().read_only {
+        return Err(DbErr::ReadOnly);
+    }
+
+    crate::polo_log!("migrate the file from version {} to {}", version, header_page_wrapper::FORMAT_VERSION);
+
+    page_handler.start_transaction(TransactionType::Write)?;
+
+    match run_migrations(page_handler, version) {
+        Ok(()) => page_handler.commit(),
+        Err(err) => {
+            page_handler.rollback()?;
+            Err(err)
+        }
+    }
+}
+
+fn run_migrations(page_handler: &mut PageHandler, from_version: u32) -> DbResult<()> {
+    let start = (from_version - MIN_FORMAT_VERSION) as usize;
+    for migration in &MIGRATIONS[start..] {
+        migration(page_handler)?;
+    }
+
+    let first_page = page_handler.get_first_page()?;
+    let mut header_wrapper = HeaderPageWrapper::from_raw_page(first_page);
+    header_wrapper.set_version(&header_page_wrapper::FORMAT_VERSION.to_be_bytes());
+    page_handler.pipeline_write_page(&header_wrapper.0)
+}
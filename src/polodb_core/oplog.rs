@@ -0,0 +1,35 @@
+use polodb_bson::{Document, Value};
+
+/// The name of the collection the oplog is stored in,
+/// it's created when the database is opened with [Config::oplog](./struct.Config.html#structfield.oplog).
+///
+/// An entry of the oplog looks like:
+///
+/// ```text
+/// {
+///     "_id": Timestamp(<time>, <inc>),
+///     "op": "insert" | "update" | "delete",
+///     "collection": <the name of the collection>,
+///     "doc_id": <the _id of the document>,
+/// }
+/// ```
+///
+/// The entries are keyed by increasing timestamps, in the order of the changes.
+/// The changes of the oplog collection itself are not recorded,
+/// so the old entries can be deleted from it directly.
+pub const OPLOG_COLLECTION_NAME: &str = "$oplog";
+
+pub(crate) mod op_name {
+    pub const INSERT: &str = "insert";
+    pub const UPDATE: &str = "update";
+    pub const DELETE: &str = "delete";
+}
+
+pub(crate) fn mk_oplog_entry(timestamp: Value, op: &str, col_name: &str, doc_id: &Value) -> Document {
+    let mut doc = Document::new_without_id();
+    doc.insert("_id".into(), timestamp);
+    doc.insert("op".into(), op.into());
+    doc.insert("collection".into(), col_name.into());
+    doc.insert("doc_id".into(), doc_id.clone());
+    doc
+}
@@ -0,0 +1,54 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+use crate::{DbResult, DbErr};
+
+/// The options of a long-running operation, to stop it before it's finished,
+/// e.g. [Database::find_cancellable](./struct.Database.html#method.find_cancellable).
+///
+/// The operation fails with `DbErr::Cancelled` after the `deadline` is passed,
+/// or the `cancel_flag` is set by another thread. They are checked when the cursor
+/// moves to another page of the B-tree instead of every document,
+/// so the operation may stop a little later than the deadline.
+///
+/// # Example
+///
+/// ```rust
+/// use std::time::{Duration, Instant};
+/// use polodb_core::{Database, OperationOptions};
+/// use polodb_bson::mk_document;
+///
+/// let mut db = Database::open_memory().unwrap();
+/// db.create_collection("test").unwrap();
+///
+/// let options = OperationOptions {
+///     deadline: Some(Instant::now() + Duration::from_secs(1)),
+///     ..Default::default()
+/// };
+/// let result = db.find_cancellable("test", &mk_document! {}, &options).unwrap();
+/// assert!(result.is_empty());
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OperationOptions<'a> {
+    /// The operation is cancelled after the time.
+    pub deadline:    Option<Instant>,
+    /// The operation is cancelled after the flag is set to true.
+    pub cancel_flag: Option<&'a AtomicBool>,
+}
+
+impl<'a> OperationOptions<'a> {
+
+    pub(crate) fn check_cancelled(&self) -> DbResult<()> {
+        if let Some(flag) = self.cancel_flag {
+            if flag.load(Ordering::Relaxed) {
+                return Err(DbErr::Cancelled);
+            }
+        }
+        if let Some(deadline) = self.deadline {
+            if Instant::now() >= deadline {
+                return Err(DbErr::Cancelled);
+            }
+        }
+        Ok(())
+    }
+
+}
@@ -31,6 +31,13 @@ impl DataPageWrapper {
         }
     }
 
+    // the max size of an item can be put into an empty page,
+    // the larger one should be stored in the overflow pages
+    #[inline]
+    pub(crate) fn max_item_size(page_size: u32) -> u32 {
+        page_size - DATA_PAGE_HEADER_SIZE - 4
+    }
+
     pub(crate) fn from_raw(raw_page: RawPage) -> DataPageWrapper {
         let bar_len = raw_page.get_u16(6);
 
@@ -56,7 +63,7 @@ impl DataPageWrapper {
         let data_size = data.len() as u32;
         let last_bar = self.get_last_bar();
         let begin_bar = (last_bar as u32) - data_size;
-        self.page.seek(begin_bar as u32);
+        self.page.seek(begin_bar);
         self.page.put(data);
 
         self.append_bar(begin_bar as u16);
@@ -82,6 +89,12 @@ impl DataPageWrapper {
         Some(&self.page.data[(begin_bar as usize)..(end_bar as usize)])
     }
 
+    // the offset of the item in the page
+    #[inline]
+    pub(crate) fn item_offset(&self, index: u32) -> usize {
+        self.get_bars_by_index(index).0 as usize
+    }
+
     fn get_bars_by_index(&self, index: u32) -> (u16, u16) {
         let begin_bar = self.page.get_u16(DATA_PAGE_HEADER_SIZE + index * 2);
         let end_bar = if index == 0 {
@@ -95,7 +108,7 @@ impl DataPageWrapper {
 
     fn append_bar(&mut self, bar: u16) {
         let index = DATA_PAGE_HEADER_SIZE + self.bar_len() * 2;
-        self.page.seek(index as u32);
+        self.page.seek(index);
         self.page.put_u16(bar);
     }
 
@@ -161,7 +174,7 @@ impl DataPageWrapper {
             return self.page.len() as u16;
         }
         let last_bar_index = DATA_PAGE_HEADER_SIZE + (self.bar_len() - 1) * 2;
-        self.page.get_u16(last_bar_index as u32)
+        self.page.get_u16(last_bar_index)
     }
 
     #[inline]
@@ -250,8 +263,8 @@ mod tests {
 
         for i in 0..4 {
             let mut first_item: [u8; 4] = [0; 4];
-            for j in 0..4 {
-                first_item[j] = (i + j) as u8;
+            for (j, item) in first_item.iter_mut().enumerate() {
+                *item = (i + j) as u8;
             }
             wrapper.put(&first_item);
         }
@@ -269,8 +282,8 @@ mod tests {
 
         for i in 0..4 {
             let mut first_item: [u8; 4] = [0; 4];
-            for j in 0..4 {
-                first_item[j] = (i + j) as u8;
+            for (j, item) in first_item.iter_mut().enumerate() {
+                *item = (i + j) as u8;
             }
             wrapper.put(&first_item);
         }
@@ -1,25 +1,37 @@
 use super::RawPage;
 
 static HEADER_DESP: &str          = "PoloDB Format v0.2";
+const VERSION_OFFSET: u32         = 32;
+/// The version of the file format stamped in the files created,
+/// the files with a greater version can't be opened.
+pub(crate) const FORMAT_VERSION: u32 = 2;
 const SECTOR_SIZE_OFFSET: u32     = 40;
 const PAGE_SIZE_OFFSET: u32       = 44;
 const NULL_PAGE_BAR_OFFSET: u32   = 48;
 const META_PAGE_ID: u32           = 52;
 const META_VERSION_OFFSET: u32    = 56;
 const META_ID_COUNTER_OFFSET: u32 = 60;
+const OID_TIMESTAMP_OFFSET: u32   = 64;
+const OID_COUNTER_OFFSET: u32     = 72;
+const BTREE_ORDER_OFFSET: u32     = 76;
+const PAGE_CHECKSUM_OFFSET: u32   = 80;
 pub const FREE_LIST_OFFSET: u32   = 2048;
 const FREE_LIST_PAGE_LINK_OFFSET: u32 = 2048 + 4;
 pub const HEADER_FREE_LIST_MAX_SIZE: usize = (2048 - 8) / 4;
 
 /**
  * Offset 0 (32 bytes) : "PoloDB Format v0.2";
- * Offset 32 (8 bytes) : Version 0.0.0.2;
+ * Offset 32 (4 bytes) : FormatVersion(0.0.0.2, read as a big-endian u32);
  * Offset 40 (4 bytes) : SectorSize;
  * Offset 44 (4 bytes) : PageSize;
  * Offset 48 (4 bytes) : NullPageBarId;
  * Offset 52 (4 bytes) : MetaPageId(usually 1);
  * Offset 56 (4 bytes) : MetaVersionId;
  * Offset 60 (4 bytes) : MetaIdCounter;
+ * Offset 64 (8 bytes) : ObjectIdTimestamp(the timestamp of the last generated ObjectId);
+ * Offset 72 (4 bytes) : ObjectIdCounter(the counter of the next generated ObjectId);
+ * Offset 76 (4 bytes) : BTreeOrder(the max items of a B-tree node, 0 if it's derived from the page size);
+ * Offset 80 (4 bytes) : PageChecksum(1 if the pages store the checksums);
  *
  * Free list offset: 2048;
  * | 4b   | 4b                  | 4b     | 4b    | ... |
@@ -33,9 +45,9 @@ impl HeaderPageWrapper {
         let raw_page = RawPage::new(page_id, page_size);
         let mut wrapper = HeaderPageWrapper::from_raw_page(raw_page);
         wrapper.set_title(HEADER_DESP);
-        wrapper.set_version(&[0, 0, 0, 2]);
+        wrapper.set_version(&FORMAT_VERSION.to_be_bytes());
         wrapper.set_sector_size(4096);
-        wrapper.set_page_size(4096);
+        wrapper.set_page_size(page_size);
         wrapper.set_meta_page_id(1);
         wrapper.set_null_page_bar(2);
         wrapper
@@ -62,17 +74,23 @@ impl HeaderPageWrapper {
     }
 
     pub(crate) fn set_version(&mut self, version: &[u8]) {
-        self.0.seek(32);
+        self.0.seek(VERSION_OFFSET);
         self.0.put(version);
     }
 
     #[allow(dead_code)]
     pub(crate) fn get_version(&self) -> [u8; 4] {
         let mut version: [u8; 4] = [0; 4];
-        version[..4].clone_from_slice(&self.0.data[32..(4 + 32)]);
+        let offset = VERSION_OFFSET as usize;
+        version[..4].clone_from_slice(&self.0.data[offset..(offset + 4)]);
         version
     }
 
+    #[inline]
+    pub(crate) fn get_format_version(&self) -> u32 {
+        self.0.get_u32(VERSION_OFFSET)
+    }
+
     #[inline]
     pub(crate) fn set_sector_size(&mut self, sector_size: u32) {
         self.0.seek(SECTOR_SIZE_OFFSET);
@@ -92,8 +110,7 @@ impl HeaderPageWrapper {
     }
 
     #[inline]
-    #[allow(dead_code)]
-    pub(crate) fn get_page_size(&mut self) -> u32 {
+    pub(crate) fn get_page_size(&self) -> u32 {
         self.0.get_u32(PAGE_SIZE_OFFSET)
     }
 
@@ -141,6 +158,50 @@ impl HeaderPageWrapper {
         self.0.put_u32(data);
     }
 
+    #[inline]
+    pub(crate) fn get_oid_timestamp(&self) -> u64 {
+        self.0.get_u64(OID_TIMESTAMP_OFFSET)
+    }
+
+    #[inline]
+    pub(crate) fn set_oid_timestamp(&mut self, timestamp: u64) {
+        self.0.seek(OID_TIMESTAMP_OFFSET);
+        self.0.put_u64(timestamp);
+    }
+
+    #[inline]
+    pub(crate) fn get_oid_counter(&self) -> u32 {
+        self.0.get_u32(OID_COUNTER_OFFSET)
+    }
+
+    #[inline]
+    pub(crate) fn set_oid_counter(&mut self, counter: u32) {
+        self.0.seek(OID_COUNTER_OFFSET);
+        self.0.put_u32(counter);
+    }
+
+    #[inline]
+    pub(crate) fn get_btree_order(&self) -> u32 {
+        self.0.get_u32(BTREE_ORDER_OFFSET)
+    }
+
+    #[inline]
+    pub(crate) fn set_btree_order(&mut self, order: u32) {
+        self.0.seek(BTREE_ORDER_OFFSET);
+        self.0.put_u32(order);
+    }
+
+    #[inline]
+    pub(crate) fn get_page_checksum(&self) -> bool {
+        self.0.get_u32(PAGE_CHECKSUM_OFFSET) != 0
+    }
+
+    #[inline]
+    pub(crate) fn set_page_checksum(&mut self, enabled: bool) {
+        self.0.seek(PAGE_CHECKSUM_OFFSET);
+        self.0.put_u32(enabled as u32);
+    }
+
     #[inline]
     pub(crate) fn get_free_list_size(&self) -> u32 {
         self.0.get_u32(FREE_LIST_OFFSET)
@@ -204,6 +265,14 @@ mod tests {
         let test_page_size = 222;
         wrapper.set_page_size(test_page_size);
         assert_eq!(wrapper.get_page_size(), test_page_size);
+
+        let test_btree_order = 33;
+        wrapper.set_btree_order(test_btree_order);
+        assert_eq!(wrapper.get_btree_order(), test_btree_order);
+
+        assert!(!wrapper.get_page_checksum());
+        wrapper.set_page_checksum(true);
+        assert!(wrapper.get_page_checksum());
     }
 
 }
@@ -0,0 +1,21 @@
+/// The counters of the pages read and written, see [Database::page_metrics](./db/struct.Database.html#method.page_metrics).
+///
+/// They are counted since the database is opened, or the last
+/// [reset](./db/struct.Database.html#method.reset_page_metrics).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PageMetrics {
+    /// The count of the pages read from the database file.
+    pub pages_read:            u64,
+    /// The count of the pages read from the journal.
+    pub journal_pages_read:    u64,
+    /// The count of the pages written, every page is appended to the journal.
+    pub pages_written:         u64,
+    /// The bytes appended to the journal, including the headers of the frames.
+    pub journal_bytes_written: u64,
+    /// The count of the pages allocated, from the free list or the end of the file.
+    pub pages_allocated:       u64,
+    /// The count of reading found in the page cache.
+    pub cache_hits:            u64,
+    /// The count of reading not found in the page cache.
+    pub cache_misses:          u64,
+}
@@ -0,0 +1,65 @@
+use std::fs::File;
+use std::io;
+use memmap2::Mmap;
+
+/// A read-only mapping of the whole database file, enabled by the feature `mmap`.
+///
+/// The pages are only written to the file at the checkpoint, by `write` through the file,
+/// which is visible through a shared mapping on the platforms with a unified buffer cache.
+/// The file grows when the pages are allocated, the pages beyond the end of the mapping
+/// are mapped again with the new length of the file.
+///
+/// Accessing a mapped page after the file is truncated by another process raises `SIGBUS`,
+/// the database file must not be modified outside the database while it's opened.
+pub(crate) struct FileMapping {
+    mmap: Option<Mmap>,
+}
+
+impl FileMapping {
+
+    pub(crate) fn new() -> FileMapping {
+        FileMapping {
+            mmap: None,
+        }
+    }
+
+    /// Copy the bytes at `offset` into `buf`, the file is mapped again if it's grown.
+    ///
+    /// Returns false if the range is beyond the end of the file,
+    /// the caller reads it by the buffered IO instead.
+    pub(crate) fn read_at(&mut self, file: &File, offset: u64, buf: &mut [u8]) -> io::Result<bool> {
+        let end = offset + buf.len() as u64;
+        let mapped_len = self.mmap.as_ref().map_or(0, |mmap| mmap.len() as u64);
+        if end > mapped_len {
+            let file_len = file.metadata()?.len();
+            if end > file_len {
+                return Ok(false);
+            }
+            self.remap(file)?;
+        }
+
+        let mmap = self.mmap.as_ref().unwrap();
+        buf.copy_from_slice(&mmap[(offset as usize)..(end as usize)]);
+
+        Ok(true)
+    }
+
+    /// Drop the mapping, it's made again at the next reading.
+    ///
+    /// Called after the length of the file is changed by the database itself.
+    pub(crate) fn reset(&mut self) {
+        self.mmap = None;
+    }
+
+    fn remap(&mut self, file: &File) -> io::Result<()> {
+        self.mmap = None;
+
+        // the file is only written by the database, through the file instead of the mapping,
+        // and it's not truncated while it's mapped, see the SIGBUS above
+        let mmap = unsafe { Mmap::map(file)? };
+        self.mmap = Some(mmap);
+
+        Ok(())
+    }
+
+}
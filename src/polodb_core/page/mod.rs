@@ -2,17 +2,26 @@
 pub(crate) mod header_page_wrapper;
 mod page_handler;
 mod pagecache;
+mod metrics;
 mod data_page_wrapper;
 mod free_list_data_wrapper;
+mod overflow_data_wrapper;
+mod read_view;
+#[cfg(all(unix, feature = "mmap"))]
+mod mmap;
 
-pub(crate) use page_handler::{PageHandler, TransactionState};
+pub(crate) use page_handler::{PageHandler, TransactionState, MEMORY_DB_PATH};
 pub(crate) use free_list_data_wrapper::FreeListDataWrapper;
+pub(crate) use read_view::ReadView;
+pub use pagecache::PageCacheStats;
+pub use metrics::PageMetrics;
 
-use std::fs::File;
 use std::io::{Seek, SeekFrom, Write, Read};
+use crc64fast::Digest;
 use crate::DbResult;
 use crate::error::{DbErr};
 
+#[derive(Clone, Copy)]
 #[repr(u8)]
 pub(crate) enum PageType {
     Undefined = 0,
@@ -54,7 +63,11 @@ impl PageType {
 
 }
 
-#[derive(Debug)]
+// the bytes in [12, 16) are not used by any type of the pages,
+// the checksum of the page is stored there if it's enabled
+const PAGE_CHECKSUM_OFFSET: u32 = 12;
+
+#[derive(Debug, Clone)]
 pub(crate) struct RawPage {
     pub page_id:    u32,
     pub data:       Vec<u8>,
@@ -64,8 +77,7 @@ pub(crate) struct RawPage {
 impl RawPage {
 
     pub fn new(page_id: u32, size: u32) -> RawPage {
-        let mut v: Vec<u8> = Vec::new();
-        v.resize(size as usize, 0);
+        let v: Vec<u8> = vec![0; size as usize];
         RawPage {
             page_id,
             data: v,
@@ -160,18 +172,42 @@ impl RawPage {
         u64::from_be_bytes(buffer)
     }
 
-    pub fn sync_to_file(&self, file: &mut File, offset: u64) -> std::io::Result<()> {
+    pub fn sync_to_file<W: Write + Seek>(&self, file: &mut W, offset: u64) -> std::io::Result<()> {
         file.seek(SeekFrom::Start(offset))?;
         file.write_all(self.data.as_slice())?;
         Ok(())
     }
 
-    pub fn read_from_file(&mut self, file: &mut File, offset: u64) -> std::io::Result<()> {
+    pub fn read_from_file<R: Read + Seek>(&mut self, file: &mut R, offset: u64) -> std::io::Result<()> {
         file.seek(SeekFrom::Start(offset))?;
         file.read_exact(self.data.as_mut_slice())?;
         Ok(())
     }
 
+    // the low 32 bits of the crc64 of the page, the checksum field is counted as zero
+    fn calc_checksum(&self) -> u32 {
+        let begin = PAGE_CHECKSUM_OFFSET as usize;
+        let mut digest = Digest::new();
+        digest.write(&self.data[0..begin]);
+        digest.write(&[0; 4]);
+        digest.write(&self.data[(begin + 4)..]);
+        digest.sum64() as u32
+    }
+
+    pub fn write_checksum(&mut self) {
+        let checksum = self.calc_checksum();
+        self.seek(PAGE_CHECKSUM_OFFSET);
+        self.put_u32(checksum);
+    }
+
+    // a page never written is full of zero
+    pub fn check_checksum(&self) -> bool {
+        if self.data.iter().all(|byte| *byte == 0) {
+            return true;
+        }
+        self.get_u32(PAGE_CHECKSUM_OFFSET) == self.calc_checksum()
+    }
+
     #[inline]
     pub fn seek(&mut self, pos: u32) {
         self.pos = pos;
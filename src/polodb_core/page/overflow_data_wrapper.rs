@@ -0,0 +1,163 @@
+use crate::page::{RawPage, PageType};
+use crate::DbResult;
+use crate::error::mk_data_malformed;
+
+const SIZE_OFFSET: u32 = 4;
+const NEXT_PAGE_OFFSET: u32 = 8;
+const DATA_FRAGMENT_OFFSET: u32 = 16;
+
+// the first byte of an encoded document is a type or zero,
+// so it never conflicts with the flag
+const OVERFLOW_TICKET_FLAG: u8 = 0xFF;
+pub(crate) const OVERFLOW_TICKET_SIZE: usize = 9;
+
+/**
+ * The data which is too large for a data page is split into a chain of overflow pages,
+ * only a ticket pointing to the chain is stored in the data page.
+ *
+ * Offset 0 (2 bytes): magic number
+ *
+ * Offset 4 (4 bytes): size of the fragment in current page
+ * Offset 8 (4 bytes): next page of the chain, 0 for the last page
+ * Offset 16: data begin
+ */
+pub(crate) struct OverflowDataWrapper {
+    page: RawPage,
+}
+
+impl OverflowDataWrapper {
+
+    pub(crate) fn init(page_id: u32, page_size: u32) -> OverflowDataWrapper {
+        let mut raw_page = RawPage::new(page_id, page_size);
+        let page_type = PageType::OverflowData;
+        raw_page.put(&page_type.to_magic());
+
+        OverflowDataWrapper {
+            page: raw_page,
+        }
+    }
+
+    pub(crate) fn from_raw(raw_page: RawPage) -> OverflowDataWrapper {
+        OverflowDataWrapper {
+            page: raw_page
+        }
+    }
+
+    /// Check the magic and the size of the fragment read from the file,
+    /// so the corrupt bytes are reported instead of a panic.
+    pub(crate) fn validate(&self) -> DbResult<()> {
+        let magic = PageType::OverflowData.to_magic();
+        if self.page.data[0..2] != magic {
+            return Err(mk_data_malformed(self.page.page_id, 0, "unexpected header for overflow page"));
+        }
+
+        let size = self.page.get_u32(SIZE_OFFSET) as usize;
+        if size > OverflowDataWrapper::fragment_capacity(self.page.len()) {
+            return Err(mk_data_malformed(self.page.page_id, SIZE_OFFSET as usize, format!(
+                "the size of fragment {} is greater than the capacity", size)));
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    pub(crate) fn fragment_capacity(page_size: u32) -> usize {
+        (page_size - DATA_FRAGMENT_OFFSET) as usize
+    }
+
+    pub(crate) fn put_fragment(&mut self, data: &[u8]) {
+        debug_assert!(data.len() <= OverflowDataWrapper::fragment_capacity(self.page.len()));
+        self.page.seek(SIZE_OFFSET);
+        self.page.put_u32(data.len() as u32);
+        self.page.seek(DATA_FRAGMENT_OFFSET);
+        self.page.put(data);
+    }
+
+    pub(crate) fn fragment(&self) -> &[u8] {
+        let size = self.page.get_u32(SIZE_OFFSET) as usize;
+        let begin = DATA_FRAGMENT_OFFSET as usize;
+        &self.page.data[begin..(begin + size)]
+    }
+
+    #[inline]
+    pub(crate) fn next_pid(&self) -> u32 {
+        self.page.get_u32(NEXT_PAGE_OFFSET)
+    }
+
+    pub(crate) fn set_next_pid(&mut self, value: u32) {
+        self.page.seek(NEXT_PAGE_OFFSET);
+        self.page.put_u32(value);
+    }
+
+    #[inline]
+    pub(crate) fn borrow_page(&self) -> &RawPage {
+        &self.page
+    }
+
+}
+
+/// | 1 byte | 4 bytes    | 4 bytes            |
+/// | 0xFF   | total size | first page of chain |
+pub(crate) fn mk_overflow_ticket(size: u32, pid: u32) -> [u8; OVERFLOW_TICKET_SIZE] {
+    let mut result = [0; OVERFLOW_TICKET_SIZE];
+    result[0] = OVERFLOW_TICKET_FLAG;
+    result[1..5].copy_from_slice(&size.to_be_bytes());
+    result[5..9].copy_from_slice(&pid.to_be_bytes());
+    result
+}
+
+/// Return the total size and the first page of the chain
+/// if the bytes are an overflow ticket.
+pub(crate) fn parse_overflow_ticket(bytes: &[u8]) -> Option<(u32, u32)> {
+    if bytes.len() != OVERFLOW_TICKET_SIZE || bytes[0] != OVERFLOW_TICKET_FLAG {
+        return None;
+    }
+
+    let mut buffer: [u8; 4] = [0; 4];
+    buffer.copy_from_slice(&bytes[1..5]);
+    let size = u32::from_be_bytes(buffer);
+    buffer.copy_from_slice(&bytes[5..9]);
+    let pid = u32::from_be_bytes(buffer);
+
+    Some((size, pid))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::page::{RawPage, PageType};
+    use crate::page::overflow_data_wrapper::{OverflowDataWrapper, mk_overflow_ticket, parse_overflow_ticket, SIZE_OFFSET};
+    use crate::DbErr;
+
+    #[test]
+    fn test_overflow_ticket() {
+        let ticket = mk_overflow_ticket(100000, 42);
+        assert_eq!(parse_overflow_ticket(&ticket), Some((100000, 42)));
+        assert_eq!(parse_overflow_ticket(&[0x16, 0, 0, 0, 0, 0, 0, 0, 0]), None);
+        assert_eq!(parse_overflow_ticket(&[0xFF, 0]), None);
+    }
+
+    #[test]
+    fn test_put_fragment() {
+        let mut wrapper = OverflowDataWrapper::init(3, 4096);
+        let data: Vec<u8> = (0..OverflowDataWrapper::fragment_capacity(4096)).map(|i| i as u8).collect();
+        wrapper.put_fragment(&data);
+        wrapper.set_next_pid(4);
+        assert_eq!(wrapper.fragment(), data.as_slice());
+        assert_eq!(wrapper.next_pid(), 4);
+        assert!(wrapper.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_corrupt_page() {
+        let wrapper = OverflowDataWrapper::from_raw(RawPage::new(5, 4096));
+        assert!(matches!(wrapper.validate(), Err(DbErr::DataMalformed(st)) if st.page_id == 5 && st.offset == 0));
+
+        let mut raw_page = RawPage::new(6, 4096);
+        raw_page.put(&PageType::OverflowData.to_magic());
+        raw_page.seek(SIZE_OFFSET);
+        raw_page.put_u32(5000);
+        let wrapper = OverflowDataWrapper::from_raw(raw_page);
+        assert!(matches!(wrapper.validate(), Err(DbErr::DataMalformed(st)) if st.page_id == 6 && st.offset == 4));
+    }
+
+}
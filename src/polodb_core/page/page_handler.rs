@@ -1,23 +1,38 @@
-use std::fs::{File, Metadata};
+use std::fs::Metadata;
 use std::cell::Cell;
 use std::collections::BTreeMap;
 use std::ops::Bound::{Included, Unbounded};
 use std::rc::Rc;
+use std::sync::{Arc, Weak};
 use std::path::{Path, PathBuf};
+use std::time::Instant;
 use polodb_bson::Document;
-use super::RawPage;
-use super::pagecache::PageCache;
+use super::{RawPage, PageType, ReadView};
+use super::pagecache::{PageCache, PageCacheStats};
+use super::PageMetrics;
 use super::header_page_wrapper;
 use super::header_page_wrapper::HeaderPageWrapper;
 use crate::journal::{JournalManager, TransactionType};
 use crate::dump::JournalDump;
-use crate::{DbResult, Config};
-use crate::error::DbErr;
+use crate::storage::Storage;
+use crate::{DbResult, Config, SyncPolicy, JournalMode};
+use crate::error::{DbErr, mk_data_malformed};
 use crate::page::data_page_wrapper::DataPageWrapper;
 use crate::data_ticket::DataTicket;
+use crate::btree::cal_item_size;
 use crate::page::free_list_data_wrapper::FreeListDataWrapper;
+use crate::page::overflow_data_wrapper::{OverflowDataWrapper, OVERFLOW_TICKET_SIZE, mk_overflow_ticket, parse_overflow_ticket};
+#[cfg(all(unix, feature = "mmap"))]
+use super::mmap::FileMapping;
 
 const PRESERVE_WRAPPER_MIN_REMAIN_SIZE: u32 = 16;
+// the path shown for the database in the memory, it's never opened
+pub(crate) const MEMORY_DB_PATH: &str = ":memory:";
+const MIN_PAGE_SIZE: u32 = 4096;
+// the offsets in the data page are u16
+const MAX_PAGE_SIZE: u32 = 32768;
+// a node holds at least one item after the deletion
+const MIN_BTREE_ORDER: u32 = 3;
 
 #[derive(Eq, PartialEq, Copy, Clone)]
 pub(crate) enum TransactionState {
@@ -28,17 +43,29 @@ pub(crate) enum TransactionState {
 }
 
 pub(crate) struct PageHandler {
-    file:                     File,
+    file:                     Storage,
+    // None if Config::mmap is off, or the database is in the memory
+    #[cfg(all(unix, feature = "mmap"))]
+    mapping:                  Option<FileMapping>,
 
     pub page_size:            u32,
+    pub btree_order:          u32,
+    page_checksum:            bool,
     page_cache:               Box<PageCache>,
+    metrics:                  PageMetrics,
     journal_manager:          Box<JournalManager>,
+    last_checkpoint:          Instant,
 
     data_page_map:            BTreeMap<u32, Vec<u32>>,
 
     transaction_state:        TransactionState,
 
-    config:                   Rc<Config>,
+    // the reads are served from it between start_read_view and finish_read_view
+    read_view:                Option<Arc<ReadView>>,
+    // the views pinned by the snapshots, the checkpoints are held off while any of them is alive
+    read_views:               Vec<Weak<ReadView>>,
+
+    config:                   Arc<Config>,
 
 }
 
@@ -49,34 +76,74 @@ pub(crate) struct AutoStartResult {
 
 impl PageHandler {
 
-    fn read_first_block(file: &mut File, page_size: u32) -> std::io::Result<RawPage> {
+    fn read_first_block(file: &mut Storage, page_size: u32) -> std::io::Result<RawPage> {
         let mut raw_page = RawPage::new(0, page_size);
         raw_page.read_from_file(file, 0)?;
         Ok(raw_page)
     }
 
-    fn force_write_first_block(file: &mut File, page_size: u32) -> std::io::Result<RawPage> {
-        let wrapper = HeaderPageWrapper::init(0, page_size);
+    fn force_write_first_block(file: &mut Storage, page_size: u32, btree_order: u32, page_checksum: bool) -> std::io::Result<RawPage> {
+        let mut wrapper = HeaderPageWrapper::init(0, page_size);
+        wrapper.set_btree_order(btree_order);
+        wrapper.set_page_checksum(page_checksum);
         wrapper.0.sync_to_file(file, 0)?;
         Ok(wrapper.0)
     }
 
-    fn init_db(file: &mut File, page_size: u32, init_block_count: u64) -> std::io::Result<(RawPage, u32, u64)> {
-        let meta = file.metadata()?;
-        let file_len = meta.len();
-        if file_len < page_size as u64 {
+    // returns the first page, the block count, the size of the file and the order of the B-tree
+    fn init_db(file: &mut Storage, page_size: u32, config: &Config) -> DbResult<(RawPage, u32, u64, u32)> {
+        let init_block_count = config.init_block_count;
+        let read_only = config.read_only;
+        let file_len = file.len()?;
+        if file_len < MIN_PAGE_SIZE as u64 {
+            // an empty file can't be initialized without writing
+            if read_only {
+                return Err(DbErr::ReadOnly);
+            }
+
             let expected_file_size: u64 = (page_size as u64) * init_block_count;
             file.set_len(expected_file_size)?;
-            let first_page = PageHandler::force_write_first_block(file, page_size)?;
-            Ok((first_page, init_block_count as u32, expected_file_size))
+            let btree_order = config.btree_order.unwrap_or_else(|| cal_item_size(page_size));
+            let first_page = PageHandler::force_write_first_block(file, page_size, btree_order, config.page_checksum)?;
+            Ok((first_page, init_block_count as u32, expected_file_size, btree_order))
         } else {
+            // the header fields are at the beginning of the first page,
+            // read them with the min page size to check the page size of the file
+            let header_page = PageHandler::read_first_block(file, MIN_PAGE_SIZE)?;
+            let header_wrapper = HeaderPageWrapper::from_raw_page(header_page);
+
+            // the pages of a file from a newer version may be laid out differently
+            let file_version = header_wrapper.get_format_version();
+            if file_version > header_page_wrapper::FORMAT_VERSION {
+                return Err(DbErr::UnsupportedFileVersion {
+                    found: file_version,
+                    supported: header_page_wrapper::FORMAT_VERSION,
+                });
+            }
+
+            let file_page_size = header_wrapper.get_page_size();
+            if file_page_size != page_size {
+                return Err(DbErr::PageSizeMismatch(page_size, file_page_size));
+            }
+
+            // the files created before the order is stored are derived from the page size
+            let btree_order = match header_wrapper.get_btree_order() {
+                0 => cal_item_size(page_size),
+                order => order,
+            };
+            if let Some(expect) = config.btree_order {
+                if expect != btree_order {
+                    return Err(DbErr::BTreeOrderMismatch(expect, btree_order));
+                }
+            }
+
             let block_count = file_len / (page_size as u64);
             let first_page = PageHandler::read_first_block(file, page_size)?;
-            Ok((first_page, block_count as u32, file_len))
+            Ok((first_page, block_count as u32, file_len, btree_order))
         }
     }
 
-    fn mk_journal_path(db_path: &Path) -> PathBuf {
+    pub(crate) fn mk_journal_path(db_path: &Path) -> PathBuf {
         let mut buf = db_path.to_path_buf();
         let filename = buf.file_name().unwrap().to_str().unwrap();
         let new_filename = String::from(filename) + ".journal";
@@ -86,38 +153,138 @@ impl PageHandler {
 
     #[allow(dead_code)]
     pub fn new(path: &Path, page_size: u32) -> DbResult<PageHandler> {
-        let config = Rc::new(Config::default());
+        let config = Arc::new(Config::default());
         PageHandler::with_config(path, page_size, config)
     }
 
-    pub fn with_config(path: &Path, page_size: u32, config: Rc<Config>) -> DbResult<PageHandler> {
-        let mut file = std::fs::OpenOptions::new()
-            .create(true)
-            .write(true)
-            .read(true)
-            .open(path)?;
+    fn check_page_size(page_size: u32) -> DbResult<()> {
+        if !page_size.is_power_of_two() || !(MIN_PAGE_SIZE..=MAX_PAGE_SIZE).contains(&page_size) {
+            return Err(DbErr::IllegalPageSize(page_size));
+        }
+        Ok(())
+    }
+
+    // every item of a node must fit in the page
+    fn check_btree_order(page_size: u32, btree_order: Option<u32>) -> DbResult<()> {
+        if let Some(order) = btree_order {
+            if !(MIN_BTREE_ORDER..=cal_item_size(page_size)).contains(&order) {
+                return Err(DbErr::IllegalBTreeOrder(order, cal_item_size(page_size)));
+            }
+        }
+        Ok(())
+    }
+
+    // the mapping is only made with the feature "mmap" on unix
+    fn check_mmap(config: &Config) -> DbResult<()> {
+        if config.mmap && cfg!(not(all(unix, feature = "mmap"))) {
+            return Err(DbErr::MmapNotSupported);
+        }
+        Ok(())
+    }
 
-        let (_, _, db_file_size) = PageHandler::init_db(&mut file, page_size, config.init_block_count)?;
+    pub fn with_config(path: &Path, page_size: u32, config: Arc<Config>) -> DbResult<PageHandler> {
+        PageHandler::check_page_size(page_size)?;
+        PageHandler::check_btree_order(page_size, config.btree_order)?;
+        PageHandler::check_mmap(&config)?;
 
+        let file = if config.read_only {
+            std::fs::OpenOptions::new()
+                .read(true)
+                .open(path)?
+        } else {
+            std::fs::OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .read(true)
+                .open(path)?
+        };
+        let mut file = Storage::File(file);
+
+        let (first_page, _, db_file_size, btree_order) = PageHandler::init_db(&mut file, page_size, &config)?;
+
+        // the header and the checksums of the journal are checked when it's opened,
+        // only the frames of the committed transactions are loaded.
+        //
+        // If the process died before the checkpoint, the committed frames remain,
+        // replay them into the database file before serving reads.
+        //
+        // A shadow journal left by a crash holds the original pages of the commit unfinished,
+        // they are written back the same way, the database is restored to the last commit.
+        // The journal is switched to the mode of the config after it's empty.
+        //
+        // A read-only database never writes the file,
+        // the frames are read from the journal kept in the memory.
         let journal_file_path: PathBuf = PageHandler::mk_journal_path(path);
-        let journal_manager = JournalManager::open(&journal_file_path, page_size, db_file_size)?;
+        let journal_manager = if config.read_only {
+            JournalManager::open_read_only(&journal_file_path, page_size, db_file_size, config.journal_mode)?
+        } else {
+            let mut journal_manager = JournalManager::open(&journal_file_path, page_size, db_file_size, config.journal_mode)?;
+            if journal_manager.len() > 0 {
+                crate::polo_log!("replay {} frames of journal", journal_manager.len());
+                journal_manager.checkpoint_journal(&mut file, config.sync_policy != SyncPolicy::Off)?;
+            }
+            journal_manager.set_mode(config.journal_mode)?;
+            journal_manager
+        };
+
+        // the flag in the header decides, the pages of an existing file are written with it
+        let page_checksum = HeaderPageWrapper::from_raw_page(first_page).get_page_checksum();
 
+        Ok(PageHandler::from_storage(file, page_size, btree_order, page_checksum, journal_manager, config))
+    }
+
+    /// The pages and the journal are kept in the memory,
+    /// all of them are lost when the database is dropped.
+    pub fn open_memory(page_size: u32, config: Arc<Config>) -> DbResult<PageHandler> {
+        PageHandler::check_page_size(page_size)?;
+        PageHandler::check_btree_order(page_size, config.btree_order)?;
+
+        let mut file = Storage::new_memory(Vec::new());
+        let (first_page, _, db_file_size, btree_order) = PageHandler::init_db(&mut file, page_size, &config)?;
+
+        let journal_file_path = PageHandler::mk_journal_path(Path::new(MEMORY_DB_PATH));
+        let journal_manager = JournalManager::open_memory(&journal_file_path, page_size, db_file_size, config.journal_mode)?;
+
+        let page_checksum = HeaderPageWrapper::from_raw_page(first_page).get_page_checksum();
+
+        Ok(PageHandler::from_storage(file, page_size, btree_order, page_checksum, journal_manager, config))
+    }
+
+    fn from_storage(file: Storage, page_size: u32, btree_order: u32, page_checksum: bool,
+                    journal_manager: JournalManager, config: Arc<Config>) -> PageHandler {
         let page_cache = PageCache::new_default(page_size);
 
-        Ok(PageHandler {
+        #[cfg(all(unix, feature = "mmap"))]
+        let mapping = if config.mmap && matches!(file, Storage::File(_)) {
+            Some(FileMapping::new())
+        } else {
+            None
+        };
+
+        PageHandler {
             file,
+            #[cfg(all(unix, feature = "mmap"))]
+            mapping,
 
             page_size,
+            btree_order,
+            page_checksum,
             page_cache: Box::new(page_cache),
+            metrics: PageMetrics::default(),
             journal_manager: Box::new(journal_manager),
+            last_checkpoint: Instant::now(),
 
             data_page_map: BTreeMap::new(),
 
             transaction_state: TransactionState::NoTrans,
 
+            read_view: None,
+            read_views: Vec::new(),
+
             config,
 
-        })
+        }
     }
 
     pub(crate) fn auto_start_transaction(&mut self, ty: TransactionType) -> DbResult<AutoStartResult> {
@@ -207,7 +374,7 @@ impl PageHandler {
             return;
         }
 
-        if wrapper.bar_len() >= (u16::max_value() as u32) / 2 {  // len too large
+        if wrapper.bar_len() >= (u16::MAX as u32) / 2 {  // len too large
             return;
         }
 
@@ -223,16 +390,51 @@ impl PageHandler {
         }
     }
 
+    // the page is freed, it can't be distributed anymore
+    fn remove_from_data_page_map(&mut self, pid: u32) {
+        self.data_page_map.retain(|_, vector| {
+            vector.retain(|item| *item != pid);
+            !vector.is_empty()
+        });
+    }
+
     // 1. write to journal, if success
     //    - 2. checkpoint journal, if full
     // 3. write to page_cache
+    //
+    // In the shadow mode, the page is kept by the journal until the commit.
     pub fn pipeline_write_page(&mut self, page: &RawPage) -> DbResult<()> {
-        self.journal_manager.as_mut().append_raw_page(page)?;
+        if self.config.read_only {
+            return Err(DbErr::ReadOnly);
+        }
+
+        self.metrics.pages_written += 1;
+
+        // the header page is not checked, the flag of the checksums is in it
+        if self.page_checksum && page.page_id != 0 {
+            let mut page = page.clone();
+            page.write_checksum();
+            self.journal_write_page(&page)?;
+            self.page_cache.insert_to_cache(&page);
+            return Ok(());
+        }
+
+        self.journal_write_page(page)?;
 
         self.page_cache.insert_to_cache(page);
         Ok(())
     }
 
+    fn journal_write_page(&mut self, page: &RawPage) -> DbResult<()> {
+        match self.journal_manager.mode() {
+            JournalMode::Wal => {
+                self.metrics.journal_bytes_written += self.journal_manager.full_frame_size();
+                self.journal_manager.as_mut().append_raw_page(page)
+            }
+            JournalMode::Shadow => self.journal_manager.as_mut().put_shadow_page(page),
+        }
+    }
+
     #[inline]
     fn pipeline_write_null_page(&mut self, page_id: u32) -> DbResult<()> {
         let page = RawPage::new(page_id, self.page_size);
@@ -243,13 +445,23 @@ impl PageHandler {
     // 2. read from journal, if none
     // 3. read from main db
     pub fn pipeline_read_page(&mut self, page_id: u32) -> Result<RawPage, DbErr> {
+        if let Some(view) = &self.read_view {
+            let view = view.clone();
+            return self.read_page_in_view(&view, page_id);
+        }
+
         if let Some(page) = self.page_cache.get_from_cache(page_id) {
             crate::polo_log!("read page from cache, page_id: {}", page_id);
+            self.metrics.cache_hits += 1;
 
             return Ok(page);
         }
 
+        self.metrics.cache_misses += 1;
+
         if let Some(page) = self.journal_manager.read_page(page_id)? {
+            self.metrics.journal_pages_read += 1;
+
             // find in journal, insert to cache
             self.page_cache.insert_to_cache(&page);
 
@@ -259,8 +471,18 @@ impl PageHandler {
         let offset = (page_id as u64) * (self.page_size as u64);
         let mut result = RawPage::new(page_id, self.page_size);
 
-        if self.journal_manager.record_db_size() >= offset + (self.page_size as u64) {
-            result.read_from_file(&mut self.file, offset)?;
+        // the new pages of the shadow mode are not in the file until the commit
+        let db_size = match self.journal_manager.mode() {
+            JournalMode::Wal => self.journal_manager.record_db_size(),
+            JournalMode::Shadow => self.journal_manager.committed_db_size(),
+        };
+        if db_size >= offset + (self.page_size as u64) {
+            self.read_page_from_file(&mut result, offset)?;
+            self.metrics.pages_read += 1;
+
+            if self.page_checksum && page_id != 0 && !result.check_checksum() {
+                return Err(DbErr::PageChecksumMismatch { page_id });
+            }
         }
 
         self.page_cache.insert_to_cache(&result);
@@ -270,23 +492,268 @@ impl PageHandler {
         Ok(result)
     }
 
+    // the cache holds the newest pages, the pages of the view are not cached
+    fn read_page_in_view(&mut self, view: &ReadView, page_id: u32) -> DbResult<RawPage> {
+        if let Some(page) = view.saved_page(page_id) {
+            return Ok(page);
+        }
+
+        if let Some(offset) = view.journal_offset(page_id) {
+            self.metrics.journal_pages_read += 1;
+            return Ok(self.journal_manager.read_frame_page(page_id, offset)?);
+        }
+
+        let offset = (page_id as u64) * (self.page_size as u64);
+        let mut result = RawPage::new(page_id, self.page_size);
+        if view.db_size() >= offset + (self.page_size as u64) {
+            self.read_page_from_file(&mut result, offset)?;
+            self.metrics.pages_read += 1;
+
+            if self.page_checksum && page_id != 0 && !result.check_checksum() {
+                return Err(DbErr::PageChecksumMismatch { page_id });
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Pin the committed state for a snapshot, the transaction in progress is not included.
+    pub(crate) fn pin_read_view(&mut self) -> Arc<ReadView> {
+        let view = Arc::new(ReadView::new(
+            self.journal_manager.offset_map.clone(),
+            self.journal_manager.committed_db_size(),
+        ));
+        self.read_views.retain(|view| view.strong_count() > 0);
+        self.read_views.push(Arc::downgrade(&view));
+        view
+    }
+
+    fn has_read_views(&self) -> bool {
+        self.read_views.iter().any(|view| view.strong_count() > 0)
+    }
+
+    /// Forget the views pinned, they can't be read anymore, and the journal can be checkpointed.
+    pub(crate) fn release_read_views(&mut self) {
+        self.read_views.clear();
+    }
+
+    /// Start a read transaction, the pages are read from the view until
+    /// [finish_read_view](#method.finish_read_view).
+    pub(crate) fn start_read_view(&mut self, view: &Arc<ReadView>) -> DbResult<()> {
+        let is_pinned = self.read_views.iter().any(|pinned| std::ptr::eq(pinned.as_ptr(), Arc::as_ptr(view)));
+        if !is_pinned {
+            return Err(DbErr::SnapshotNotOfDatabase);
+        }
+        if self.transaction_state != TransactionState::NoTrans {
+            return Err(DbErr::StartTransactionInAnotherTransaction);
+        }
+        self.start_transaction(TransactionType::Read)?;
+        self.transaction_state = TransactionState::User;
+        self.read_view = Some(view.clone());
+        Ok(())
+    }
+
+    pub(crate) fn finish_read_view(&mut self) -> DbResult<()> {
+        if self.read_view.take().is_none() {
+            return Ok(());
+        }
+        self.transaction_state = TransactionState::NoTrans;
+        self.commit()
+    }
+
+    // the pages are going to be written to the database file in place,
+    // the views alive keep the original ones
+    fn save_originals_for_views(&mut self) -> DbResult<()> {
+        let views: Vec<Arc<ReadView>> = self.read_views.iter().filter_map(Weak::upgrade).collect();
+        if views.is_empty() {
+            return Ok(());
+        }
+
+        for page_id in self.journal_manager.shadow_page_ids() {
+            if !views.iter().any(|view| view.needs_original(page_id, self.page_size)) {
+                continue;
+            }
+
+            let mut original = RawPage::new(page_id, self.page_size);
+            self.read_page_from_file(&mut original, (page_id as u64) * (self.page_size as u64))?;
+            for view in &views {
+                if view.needs_original(page_id, self.page_size) {
+                    view.save_original(original.clone());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(all(unix, feature = "mmap"))]
+    fn read_page_from_file(&mut self, page: &mut RawPage, offset: u64) -> DbResult<()> {
+        if let (Some(mapping), Storage::File(file)) = (&mut self.mapping, &self.file) {
+            if mapping.read_at(file, offset, page.data.as_mut_slice())? {
+                return Ok(());
+            }
+        }
+        page.read_from_file(&mut self.file, offset)?;
+        Ok(())
+    }
+
+    #[cfg(not(all(unix, feature = "mmap")))]
+    #[inline]
+    fn read_page_from_file(&mut self, page: &mut RawPage, offset: u64) -> DbResult<()> {
+        page.read_from_file(&mut self.file, offset)?;
+        Ok(())
+    }
+
+    /// Resize the page cache, the cached pages are dropped.
+    /// Passing 0 disables the cache.
+    ///
+    /// The cache is write-through: [pipeline_write_page](#method.pipeline_write_page)
+    /// overrides the cached page, so it never returns a stale page.
+    pub fn set_cache_capacity(&mut self, pages: usize) {
+        *self.page_cache = PageCache::new(pages, self.page_size);
+    }
+
+    pub fn cache_stats(&self) -> PageCacheStats {
+        PageCacheStats {
+            capacity: self.page_cache.capacity(),
+            hits: self.metrics.cache_hits,
+            misses: self.metrics.cache_misses,
+        }
+    }
+
+    #[inline]
+    pub fn metrics(&self) -> PageMetrics {
+        self.metrics
+    }
+
+    #[inline]
+    pub fn reset_metrics(&mut self) {
+        self.metrics = PageMetrics::default();
+    }
+
     pub(crate) fn get_doc_from_ticket(&mut self, data_ticket: &DataTicket) -> DbResult<Option<Rc<Document>>> {
         let page = self.pipeline_read_page(data_ticket.pid)?;
         let wrapper = DataPageWrapper::from_raw(page);
+        if wrapper.borrow_page().data[0..2] != PageType::Data.to_magic() {
+            return Err(mk_data_malformed(data_ticket.pid, 0, "unexpected header for data page"));
+        }
+        if data_ticket.index as u32 >= wrapper.bar_len() {
+            return Err(mk_data_malformed(data_ticket.pid, 6, format!(
+                "index {} of the ticket is greater than length {}", data_ticket.index, wrapper.bar_len())));
+        }
+
         let bytes = wrapper.get(data_ticket.index as u32);
         if let Some(bytes) = bytes {
-            let doc = Document::from_bytes(bytes)?;
+            let doc = match parse_overflow_ticket(bytes) {
+                Some((size, pid)) => {
+                    let overflow_bytes = self.read_overflow_data(size, pid)?;
+                    Document::from_bytes(&overflow_bytes)
+                        .map_err(|err| mk_data_malformed(pid, 0, err.to_string()))?
+                }
+                None => {
+                    let offset = wrapper.item_offset(data_ticket.index as u32);
+                    Document::from_bytes(bytes)
+                        .map_err(|err| mk_data_malformed(data_ticket.pid, offset, err.to_string()))?
+                }
+            };
             return Ok(Some(Rc::new(doc)));
         }
         Ok(None)
     }
 
+    /// Return the count of the overflow pages used by the item of the ticket,
+    /// 0 if the item is stored in the data page.
+    pub(crate) fn overflow_page_count_of_ticket(&mut self, data_ticket: &DataTicket) -> DbResult<u32> {
+        let page = self.pipeline_read_page(data_ticket.pid)?;
+        let wrapper = DataPageWrapper::from_raw(page);
+        let size = match wrapper.get(data_ticket.index as u32).and_then(parse_overflow_ticket) {
+            Some((size, _)) => size as usize,
+            None => return Ok(0),
+        };
+
+        let fragment_capacity = OverflowDataWrapper::fragment_capacity(self.page_size);
+        Ok(size.div_ceil(fragment_capacity) as u32)
+    }
+
     pub(crate) fn store_doc(&mut self, doc: &Document) -> DbResult<DataTicket> {
         let bytes = doc.to_bytes()?;
+        if bytes.len() > self.config.max_document_size {
+            return Err(DbErr::DocumentTooLarge {
+                size: bytes.len(),
+                max: self.config.max_document_size,
+            });
+        }
+        if bytes.len() as u32 > DataPageWrapper::max_item_size(self.page_size) {
+            let overflow_ticket = self.store_overflow_data(&bytes)?;
+            return self.store_bytes(&overflow_ticket);
+        }
+        self.store_bytes(&bytes)
+    }
+
+    // split the data into a chain of overflow pages,
+    // return the ticket to put into the data page
+    fn store_overflow_data(&mut self, bytes: &[u8]) -> DbResult<[u8; OVERFLOW_TICKET_SIZE]> {
+        let fragment_capacity = OverflowDataWrapper::fragment_capacity(self.page_size);
+        let fragments: Vec<&[u8]> = bytes.chunks(fragment_capacity).collect();
+
+        let mut pids = Vec::with_capacity(fragments.len());
+        for _ in 0..fragments.len() {
+            pids.push(self.alloc_page_id()?);
+        }
+
+        for (index, fragment) in fragments.iter().enumerate() {
+            let mut wrapper = OverflowDataWrapper::init(pids[index], self.page_size);
+            wrapper.put_fragment(fragment);
+            wrapper.set_next_pid(pids.get(index + 1).cloned().unwrap_or(0));
+            self.pipeline_write_page(wrapper.borrow_page())?;
+        }
+
+        crate::polo_log!("store overflow data, size: {}, pages: {:?}", bytes.len(), pids);
+
+        Ok(mk_overflow_ticket(bytes.len() as u32, pids[0]))
+    }
+
+    fn read_overflow_data(&mut self, size: u32, pid: u32) -> DbResult<Vec<u8>> {
+        let mut result = Vec::with_capacity(size as usize);
+        let mut current_pid = pid;
+        while current_pid != 0 {
+            let page = self.pipeline_read_page(current_pid)?;
+            let wrapper = OverflowDataWrapper::from_raw(page);
+            wrapper.validate()?;
+            result.extend_from_slice(wrapper.fragment());
+            if result.len() > size as usize {
+                return Err(mk_data_malformed(current_pid, 0, format!(
+                    "the data of overflow chain is larger than {} bytes", size)));
+            }
+            current_pid = wrapper.next_pid();
+        }
+
+        if result.len() != size as usize {
+            return Err(mk_data_malformed(pid, 0, format!(
+                "the size of overflow chain is {}, expected: {}", result.len(), size)));
+        }
+
+        Ok(result)
+    }
+
+    fn free_overflow_data(&mut self, pid: u32) -> DbResult<()> {
+        let mut pids = vec![];
+        let mut current_pid = pid;
+        while current_pid != 0 {
+            let page = self.pipeline_read_page(current_pid)?;
+            let wrapper = OverflowDataWrapper::from_raw(page);
+            wrapper.validate()?;
+            pids.push(current_pid);
+            current_pid = wrapper.next_pid();
+        }
+        self.free_pages(&pids)
+    }
+
+    fn store_bytes(&mut self, bytes: &[u8]) -> DbResult<DataTicket> {
         let mut wrapper = self.distribute_data_page_wrapper(bytes.len() as u32)?;
         let index = wrapper.bar_len() as u16;
         let pid = wrapper.pid();
-        wrapper.put(&bytes);
+        wrapper.put(bytes);
 
         self.pipeline_write_page(wrapper.borrow_page())?;
 
@@ -306,10 +773,18 @@ impl PageHandler {
         let bytes = wrapper.get(data_ticket.index as u32).unwrap().to_vec();
         wrapper.remove(data_ticket.index as u32);
         if wrapper.is_empty() {
+            self.remove_from_data_page_map(data_ticket.pid);
             self.free_page(data_ticket.pid)?;
         }
         let page = wrapper.consume_page();
         self.pipeline_write_page(&page)?;
+
+        if let Some((size, pid)) = parse_overflow_ticket(&bytes) {
+            let overflow_bytes = self.read_overflow_data(size, pid)?;
+            self.free_overflow_data(pid)?;
+            return Ok(overflow_bytes);
+        }
+
         Ok(bytes)
     }
 
@@ -318,6 +793,23 @@ impl PageHandler {
         self.free_pages(&[pid])
     }
 
+    /// Count the pages in the header and all the free list pages.
+    pub(crate) fn free_page_count(&mut self) -> DbResult<u32> {
+        let first_page = self.pipeline_read_page(0)?;
+        let first_page_wrapper = HeaderPageWrapper::from_raw_page(first_page);
+
+        let mut result = first_page_wrapper.get_free_list_size();
+        let mut current_pid = first_page_wrapper.get_free_list_page_id();
+        while current_pid != 0 {
+            let page = self.pipeline_read_page(current_pid)?;
+            let wrapper = FreeListDataWrapper::from_raw(page);
+            result += wrapper.size();
+            current_pid = wrapper.next_pid();
+        }
+
+        Ok(result)
+    }
+
     // for test
     #[allow(dead_code)]
     fn first_page_free_list_pid_and_size(&mut self) -> DbResult<(u32, u32)> {
@@ -416,9 +908,31 @@ impl PageHandler {
         (self.journal_manager.len() as u64) >= self.config.journal_full_size
     }
 
-    #[inline]
+    // the interval is elapsed since the last checkpoint
+    fn is_checkpoint_due(&self) -> bool {
+        match self.config.checkpoint_interval {
+            Some(interval) => self.journal_manager.len() > 0 && self.last_checkpoint.elapsed() >= interval,
+            None => false,
+        }
+    }
+
+    /// `DbErr::Busy` returns if any snapshot is alive, its pages may be in the journal.
     pub fn checkpoint_journal(&mut self) -> DbResult<()> {
-        self.journal_manager.checkpoint_journal(&mut self.file)
+        if self.config.read_only {
+            return Err(DbErr::ReadOnly);
+        }
+        if self.has_read_views() {
+            return Err(DbErr::Busy);
+        }
+        let sync = self.config.sync_policy != SyncPolicy::Off;
+        self.journal_manager.checkpoint_journal(&mut self.file, sync)?;
+        // the length of the file is set at the checkpoint
+        #[cfg(all(unix, feature = "mmap"))]
+        if let Some(mapping) = &mut self.mapping {
+            mapping.reset();
+        }
+        self.last_checkpoint = Instant::now();
+        Ok(())
     }
 
     fn try_get_free_page_id(&mut self) -> DbResult<Option<u32>> {
@@ -470,6 +984,8 @@ impl PageHandler {
     }
 
     pub fn alloc_page_id(&mut self) -> DbResult<u32> {
+        self.metrics.pages_allocated += 1;
+
         let page_id = match self.try_get_free_page_id()? {
             Some(page_id) =>  {
                 self.pipeline_write_null_page(page_id)?;
@@ -506,6 +1022,21 @@ impl PageHandler {
         Ok(null_page_bar)
     }
 
+    #[inline]
+    pub(crate) fn is_memory(&self) -> bool {
+        matches!(self.file, Storage::Memory(_))
+    }
+
+    #[inline]
+    pub(crate) fn is_read_only(&self) -> bool {
+        self.config.read_only
+    }
+
+    #[inline]
+    pub(crate) fn config(&self) -> &Config {
+        self.config.as_ref()
+    }
+
     #[inline]
     pub fn journal_file_path(&self) -> &Path {
         self.journal_manager.path()
@@ -513,6 +1044,9 @@ impl PageHandler {
 
     #[inline]
     pub fn start_transaction(&mut self, ty: TransactionType) -> DbResult<()> {
+        if ty == TransactionType::Write && self.config.read_only {
+            return Err(DbErr::ReadOnly);
+        }
         self.journal_manager.start_transaction(ty)
     }
 
@@ -523,6 +1057,9 @@ impl PageHandler {
 
     #[inline]
     fn upgrade_read_transaction_to_write(&mut self) -> DbResult<()> {
+        if self.config.read_only {
+            return Err(DbErr::ReadOnly);
+        }
         self.journal_manager.upgrade_read_transaction_to_write()
     }
 
@@ -537,20 +1074,47 @@ impl PageHandler {
     }
 
     pub fn commit(&mut self) -> DbResult<()> {
+        if self.journal_manager.mode() == JournalMode::Shadow {
+            return self.commit_shadow();
+        }
+
+        let is_write = self.journal_manager.transaction_type() == Some(TransactionType::Write);
         self.journal_manager.commit()?;
-        if self.is_journal_full() {
+        if is_write && self.config.sync_policy == SyncPolicy::Full {
+            self.journal_manager.sync()?;
+        }
+        // the frames pinned by the snapshots are kept, the journal grows meanwhile
+        if !self.config.read_only && !self.has_read_views() && (self.is_journal_full() || self.is_checkpoint_due()) {
             self.checkpoint_journal()?;
             crate::polo_log!("checkpoint journal finished");
         }
         Ok(())
     }
 
+    // the pages are written to the database file in place, no checkpoint is needed
+    fn commit_shadow(&mut self) -> DbResult<()> {
+        self.save_originals_for_views()?;
+
+        let frame_count = match self.journal_manager.commit_shadow(&mut self.file, self.config.sync_policy)? {
+            Some(frame_count) => frame_count,
+            None => return Ok(()),
+        };
+        self.metrics.journal_bytes_written += (frame_count as u64) * self.journal_manager.full_frame_size();
+        // the length of the file may be changed
+        #[cfg(all(unix, feature = "mmap"))]
+        if let Some(mapping) = &mut self.mapping {
+            mapping.reset();
+        }
+        Ok(())
+    }
+
     // after the rollback
     // all the cache are wrong
     // cleat it
     pub fn rollback(&mut self) -> DbResult<()> {
         self.journal_manager.rollback()?;
-        self.page_cache = Box::new(PageCache::new_default(self.page_size));
+        *self.page_cache = PageCache::new(self.page_cache.capacity(), self.page_size);
+        self.data_page_map.clear();
         Ok(())
     }
 
@@ -573,20 +1137,21 @@ impl PageHandler {
 #[cfg(test)]
 mod test {
     use std::env;
+    use std::path::PathBuf;
+    use polodb_bson::mk_document;
     use crate::page::PageHandler;
+    use crate::page::overflow_data_wrapper::OverflowDataWrapper;
     use crate::TransactionType;
     use std::collections::HashSet;
 
     const TEST_FREE_LIST_SIZE: usize = 10000;
-    const DB_NAME: &str = "test-page-handler";
 
-    #[test]
-    fn test_free_list() {
+    fn prepare_db_path(db_name: &str) -> PathBuf {
         let mut db_path = env::temp_dir();
         let mut journal_path = env::temp_dir();
 
-        let db_filename = String::from(DB_NAME) + ".db";
-        let journal_filename = String::from(DB_NAME) + ".db.journal";
+        let db_filename = String::from(db_name) + ".db";
+        let journal_filename = String::from(db_name) + ".db.journal";
 
         db_path.push(db_filename);
         journal_path.push(journal_filename);
@@ -594,6 +1159,13 @@ mod test {
         let _ = std::fs::remove_file(db_path.as_path());
         let _ = std::fs::remove_file(journal_path);
 
+        db_path
+    }
+
+    #[test]
+    fn test_free_list() {
+        let db_path = prepare_db_path("test-page-handler");
+
         let mut page_handler = PageHandler::new(db_path.as_ref(), 4096).unwrap();
         page_handler.start_transaction(TransactionType::Write).unwrap();
 
@@ -609,15 +1181,13 @@ mod test {
             id.push(pid);
         }
 
-        let mut counter = 0;
-        for i in id {
-            page_handler.free_page(i).expect(&*format!("free page failed: {}", i));
+        for (counter, i) in id.into_iter().enumerate() {
+            page_handler.free_page(i).unwrap_or_else(|_| panic!("free page failed: {}", i));
             freed_pid.insert(i);
             let (free_pid, free_size) = page_handler.first_page_free_list_pid_and_size().unwrap();
             if free_pid == 0 {
                 assert_eq!(free_size as usize, counter + 1);
             }
-            counter += 1;
         }
 
         let mut counter = 0;
@@ -637,4 +1207,42 @@ mod test {
         assert!(rate > 0.99, "rate {} too low, pages leak", rate);
     }
 
+    #[test]
+    fn test_overflow_chain_round_trip() {
+        let db_path = prepare_db_path("test-overflow-chain");
+        let fragment_capacity = OverflowDataWrapper::fragment_capacity(4096);
+
+        let mut page_handler = PageHandler::new(db_path.as_ref(), 4096).unwrap();
+        page_handler.start_transaction(TransactionType::Write).unwrap();
+
+        let mut tickets = vec![];
+        for blob_size in &[fragment_capacity * 3, fragment_capacity * 5 + 1, 100000] {
+            let blob: Vec<u8> = (0..*blob_size).map(|i| (i * 13 % 251) as u8).collect();
+            let doc = mk_document! {
+                "_id": *blob_size as i64,
+                "blob": blob,
+            };
+            let bytes = doc.to_bytes().unwrap();
+
+            let ticket = page_handler.store_doc(&doc).unwrap();
+            let page_count = page_handler.overflow_page_count_of_ticket(&ticket).unwrap();
+            assert!(page_count > 3);
+            assert_eq!(page_count as usize, bytes.len().div_ceil(fragment_capacity));
+
+            tickets.push((ticket, bytes));
+        }
+
+        page_handler.commit().unwrap();
+        drop(page_handler);
+
+        // the chains are read from the file
+        let mut page_handler = PageHandler::new(db_path.as_ref(), 4096).unwrap();
+        page_handler.start_transaction(TransactionType::Read).unwrap();
+        for (ticket, bytes) in &tickets {
+            let doc = page_handler.get_doc_from_ticket(ticket).unwrap().unwrap();
+            assert_eq!(&doc.to_bytes().unwrap(), bytes);
+        }
+        page_handler.commit().unwrap();
+    }
+
 }
@@ -3,6 +3,8 @@ use std::collections::HashMap;
 use std::ptr::null_mut;
 use crate::page::RawPage;
 
+const DEFAULT_CACHE_CAPACITY: usize = 1024;
+
 struct LruNode {
     prev:      *mut LruNode,
     next:      *mut LruNode,
@@ -53,10 +55,7 @@ impl LruMap {
 
     pub fn find(&mut self, key: u32) -> Option<u32> {
         let from_map = self.data.remove(&key);
-        let node: Box<LruNode> = match from_map {
-            Some(own) => own,
-            None => return None,
-        };
+        let node: Box<LruNode> = from_map?;
 
         let result = node.as_ref().value;
 
@@ -67,7 +66,7 @@ impl LruMap {
                     return Some(result)
                 }
 
-                let mut ptr: *mut LruNode = Box::into_raw(node);
+                let ptr: *mut LruNode = Box::into_raw(node);
                 (*(*ptr).prev).next = (*ptr).next; // prev link to next
 
                 if !(*ptr).next.is_null() {  // not a tail
@@ -135,11 +134,18 @@ impl LruMap {
 
     pub fn remove_tail(&mut self) -> Option<(u32, u32)> {
         let len = self.len();
-        if len <= 1 {
+        if len == 0 {
+            return None;
+        }
+
+        if len == 1 {
+            let (key, value) = unsafe {
+                ((*self.end).key, (*self.end).value)
+            };
             self.start = null_mut();
             self.end = null_mut();
             self.data.clear();
-            return None;
+            return Some((key, value));
         }
 
         let (key, value) = unsafe {
@@ -154,10 +160,7 @@ impl LruMap {
     }
 
     pub fn remove(&mut self, key: u32) -> Option<u32> {
-        let ptr: &LruNode = match self.data.get(&key) {
-            Some(node_ref) => node_ref,
-            None => return None,
-        };
+        let ptr: &LruNode = self.data.get(&key)?;
 
         let len = self.data.len();
         if len <= 1 {
@@ -173,7 +176,7 @@ impl LruMap {
 
         let result = ptr.value;
         unsafe {
-            let mut node = &*ptr;
+            let node = ptr;
 
             if node.prev.is_null() {  // head
                 (*node.next).prev = null_mut();
@@ -208,6 +211,17 @@ impl LruMap {
 
 }
 
+/// The statistics of the page cache, see [Database::page_cache_stats](./db/struct.Database.html#method.page_cache_stats).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PageCacheStats {
+    /// The max count of pages in the cache.
+    pub capacity: usize,
+    /// The count of reading found in the cache.
+    pub hits:     u64,
+    /// The count of reading from the journal or the main file.
+    pub misses:   u64,
+}
+
 pub(crate) struct PageCache {
     page_count: usize,
     page_size:  u32,
@@ -218,7 +232,12 @@ pub(crate) struct PageCache {
 impl PageCache {
 
     pub fn new_default(page_size: u32) -> PageCache {
-        Self::new(1024, page_size)
+        Self::new(DEFAULT_CACHE_CAPACITY, page_size)
+    }
+
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.page_count
     }
 
     pub fn new(page_count: usize, page_size: u32) -> PageCache {
@@ -237,14 +256,11 @@ impl PageCache {
     }
 
     pub(crate) fn get_from_cache(&mut self, page_id: u32) -> Option<RawPage> {
-        let index = match self.lru_map.find(page_id) {
-            Some(index) => index,
-            None => return None,
-        };
+        let index = self.lru_map.find(page_id)?;
         let offset: usize = (index as usize) * (self.page_size as usize);
         let mut result = RawPage::new(page_id, self.page_size);
         unsafe {
-            result.copy_from_ptr(self.data.add(offset as usize));
+            result.copy_from_ptr(self.data.add(offset));
         }
         Some(result)
     }
@@ -260,6 +276,11 @@ impl PageCache {
     }
 
     pub(crate) fn insert_to_cache(&mut self, page: &RawPage) {
+        // the cache is disabled
+        if self.page_count == 0 {
+            return;
+        }
+
         match self.lru_map.find(page.page_id) {
             Some(index) => {  // override
                 let offset = (index as usize) * (self.page_size as usize);
@@ -281,6 +302,10 @@ impl PageCache {
 
 }
 
+// The cache owns the memory of the pages and all the nodes of the LRU map,
+// no pointer is shared out of it, so it's safe to move it to another thread.
+unsafe impl Send for PageCache {}
+
 impl Drop for PageCache {
 
     fn drop (&mut self) {
@@ -328,6 +353,19 @@ mod tests {
         }
     }
 
+    #[test]
+    fn page_cache_small_capacity() {
+        let mut page_cache = PageCache::new(1, 4096);
+        page_cache.insert_to_cache(&make_raw_page(0));
+        page_cache.insert_to_cache(&make_raw_page(1));
+        assert!(page_cache.get_from_cache(0).is_none());
+        assert!(page_cache.get_from_cache(1).is_some());
+
+        let mut page_cache = PageCache::new(0, 4096);
+        page_cache.insert_to_cache(&make_raw_page(0));
+        assert!(page_cache.get_from_cache(0).is_none());
+    }
+
     static TEST_PAGE_LEN: u32 = 10;
 
     #[test]
@@ -358,7 +396,7 @@ mod tests {
         }
 
         for i in 0..3 {
-            if let Some(_) = page_cache.get_from_cache(i) {
+            if page_cache.get_from_cache(i).is_some() {
                 panic!("removed");
             };
         }
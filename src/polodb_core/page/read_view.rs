@@ -0,0 +1,59 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use super::RawPage;
+
+/// The committed state of the database pinned by a [Snapshot](../../struct.Snapshot.html).
+///
+/// In the write-ahead log, the frames are never overwritten before the checkpoint,
+/// so the index of the committed frames and the size of the database at the pin
+/// are enough to read the pages of that time, the checkpoints are held off meanwhile.
+///
+/// In the shadow mode, the pages are written to the database file in place at every commit,
+/// the original pages are copied into the view before they are overwritten.
+pub(crate) struct ReadView {
+    // page_id => the offset of the newest frame committed at the pin,
+    // the frames appended later are ignored
+    journal_offsets: BTreeMap<u32, u64>,
+    db_size:         u64,
+    // the original pages overwritten in the shadow mode
+    saved_pages:     Mutex<BTreeMap<u32, RawPage>>,
+}
+
+impl ReadView {
+
+    pub(crate) fn new(journal_offsets: BTreeMap<u32, u64>, db_size: u64) -> ReadView {
+        ReadView {
+            journal_offsets,
+            db_size,
+            saved_pages: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn journal_offset(&self, page_id: u32) -> Option<u64> {
+        self.journal_offsets.get(&page_id).copied()
+    }
+
+    #[inline]
+    pub(crate) fn db_size(&self) -> u64 {
+        self.db_size
+    }
+
+    pub(crate) fn saved_page(&self, page_id: u32) -> Option<RawPage> {
+        self.saved_pages.lock().unwrap().get(&page_id).cloned()
+    }
+
+    /// The page of the database file is going to be overwritten,
+    /// true if the view needs the original one.
+    pub(crate) fn needs_original(&self, page_id: u32, page_size: u32) -> bool {
+        let end = (page_id as u64 + 1) * (page_size as u64);
+        end <= self.db_size
+            && !self.journal_offsets.contains_key(&page_id)
+            && !self.saved_pages.lock().unwrap().contains_key(&page_id)
+    }
+
+    pub(crate) fn save_original(&self, page: RawPage) {
+        self.saved_pages.lock().unwrap().entry(page.page_id).or_insert(page);
+    }
+
+}
@@ -0,0 +1,275 @@
+use std::cmp::Ordering;
+use polodb_bson::{Value, Document};
+use crate::vm::DbOp;
+use crate::index_ctx::{IndexCtx, is_index_lookup_value};
+
+/// Map a comparison operator of the query document to the op of the VM.
+pub(crate) fn cmp_op_of_name(name: &str) -> Option<DbOp> {
+    let op = match name {
+        "$eq"  => DbOp::Equal,
+        "$gt"  => DbOp::Greater,
+        "$gte" => DbOp::GreaterEqual,
+        "$lt"  => DbOp::Less,
+        "$lte" => DbOp::LessEqual,
+        _ => return None,
+    };
+    Some(op)
+}
+
+/// Check if the value of the field satisfies the comparison op.
+///
+/// The values of different types are not comparable,
+/// in this case false returns, the document doesn't match. NaN matches nothing.
+///
+/// Arrays and documents only support the equality,
+/// they are equal if all the items are equal in order.
+pub(crate) fn value_matches_cmp(op: DbOp, field: &Value, operand: &Value) -> bool {
+    if field.ty_rank() != operand.ty_rank() || is_nan(field) || is_nan(operand) {
+        return false;
+    }
+
+    let ord = field.cmp(operand);
+
+    if let Value::Array(_) | Value::Document(_) = field {
+        return matches!(op, DbOp::Equal) && ord == Ordering::Equal;
+    }
+
+    matches!((op, ord),
+        (DbOp::Equal, Ordering::Equal) |
+        (DbOp::Greater, Ordering::Greater) |
+        (DbOp::GreaterEqual, Ordering::Equal) |
+        (DbOp::GreaterEqual, Ordering::Greater) |
+        (DbOp::Less, Ordering::Less) |
+        (DbOp::LessEqual, Ordering::Equal) |
+        (DbOp::LessEqual, Ordering::Less)
+    )
+}
+
+#[inline]
+fn is_nan(value: &Value) -> bool {
+    matches!(value, Value::Double(num) if num.is_nan())
+}
+
+/// Compare two documents by the field, a document missing the field is the lowest.
+///
+/// The field can be a dotted path of the nested documents.
+pub(crate) fn doc_field_cmp(doc1: &Document, doc2: &Document, field: &str) -> Ordering {
+    match (doc1.get_path(field), doc2.get_path(field)) {
+        (Some(value1), Some(value2)) => value1.cmp(value2),
+        (Some(_), None) => Ordering::Greater,
+        (None, Some(_)) => Ordering::Less,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// Build a new document containing only the fields listed in `fields`.
+///
+/// The `_id` is always kept even if it's not listed.
+/// The fields which don't exist in the document are ignored.
+pub(crate) fn project_document(doc: &Document, fields: &[&str]) -> Document {
+    let mut result = Document::new_without_id();
+
+    if let Some(id) = doc.get("_id") {
+        result.insert("_id".into(), id.clone());
+    }
+
+    for field in fields {
+        if *field == "_id" {
+            continue;
+        }
+        if let Some(value) = doc.get(field) {
+            result.insert((*field).into(), value.clone());
+        }
+    }
+
+    result
+}
+
+/// Build the document to insert when an upsert matches nothing.
+///
+/// The equality fields of the `filter` and the fields of `$set` in `update` are used,
+/// the fields of `$set` override the ones of the `filter`.
+pub(crate) fn mk_upsert_document(filter: &Document, update: &Document) -> Document {
+    let mut result = Document::new_without_id();
+
+    for (key, value) in filter.iter() {
+        if key.starts_with('$') {
+            continue;
+        }
+
+        match value {
+            Value::Document(doc) => {
+                if let Some(eq_value) = doc.get("$eq") {
+                    result.insert(key.clone(), eq_value.clone());
+                } else if !doc.iter().any(|(key, _)| key.starts_with('$')) {
+                    result.insert(key.clone(), value.clone());
+                }
+            }
+
+            _ => {
+                result.insert(key.clone(), value.clone());
+            }
+
+        }
+    }
+
+    if let Some(Value::Document(set_doc)) = update.get("$set") {
+        for (key, value) in set_doc.iter() {
+            result.insert(key.clone(), value.clone());
+        }
+    }
+
+    result
+}
+
+/// The strategy to find the documents matching a filter,
+/// returned by [Database::explain](crate::Database::explain).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryPlan {
+    /// Descend the B-tree of the collection by the `_id` of the filter.
+    PrimaryKey,
+
+    /// Descend the index on the field, the documents found are checked by the other fields.
+    Index(String),
+
+    /// Scan the entries of the compound index whose leading fields are equal to the filter,
+    /// the documents found are checked by the other fields.
+    CompoundIndex {
+        index:  String,
+        fields: Vec<String>,
+    },
+
+    /// Scan all the documents of the collection.
+    FullScan,
+}
+
+/// Choose the strategy to answer the filter with the indexes of the collection.
+///
+/// The indexes are used only when all the fields of the filter are equality comparisons.
+/// A compound index matching several fields is more selective than a single field,
+/// so it's preferred, otherwise the index on a single field is used.
+pub(crate) fn plan_query(query: &Document, index_ctx: Option<&IndexCtx>) -> QueryPlan {
+    if let Some(id_value) = query.pkey_id() {
+        if id_value.is_valid_key_type() {
+            return QueryPlan::PrimaryKey;
+        }
+        return QueryPlan::FullScan;
+    }
+
+    let index_ctx = match index_ctx {
+        Some(index_ctx) => index_ctx,
+        None => return QueryPlan::FullScan,
+    };
+
+    let is_all_equality = query.iter().all(|(key, value)| {
+        !key.starts_with('$') && !matches!(value, Value::Document(_) | Value::Array(_))
+    });
+    if !is_all_equality {
+        return QueryPlan::FullScan;
+    }
+
+    let single_index = query.iter().find(|(key, value)| {
+        index_ctx.has_index(key) && is_index_lookup_value(value)
+    });
+    let compound_index = index_ctx.find_compound_index_by_query(query);
+
+    match (single_index, compound_index) {
+        (_, Some((index, fields))) if fields.len() > 1 => QueryPlan::CompoundIndex {
+            index: index.into(),
+            fields: fields.to_vec(),
+        },
+
+        (Some((key, _)), _) => QueryPlan::Index(key.clone()),
+
+        (None, Some((index, fields))) => QueryPlan::CompoundIndex {
+            index: index.into(),
+            fields: fields.to_vec(),
+        },
+
+        (None, None) => QueryPlan::FullScan,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+    use polodb_bson::{Value, mk_document};
+    use crate::vm::DbOp;
+    use crate::query::{cmp_op_of_name, value_matches_cmp, doc_field_cmp, project_document, mk_upsert_document};
+
+    #[test]
+    fn test_cmp_op_dispatch() {
+        let op = cmp_op_of_name("$gt").unwrap();
+        assert!(value_matches_cmp(op, &Value::from(20), &Value::from(18)));
+        assert!(!value_matches_cmp(op, &Value::from(18), &Value::from(18)));
+        assert!(value_matches_cmp(DbOp::GreaterEqual, &Value::from(18), &Value::from(18)));
+        assert!(value_matches_cmp(DbOp::Less, &Value::from("abc"), &Value::from("abd")));
+        assert!(value_matches_cmp(DbOp::LessEqual, &Value::from(1.5), &Value::from(2)));
+        assert!(cmp_op_of_name("$unknown").is_none());
+    }
+
+    #[test]
+    fn test_mixed_type_not_match() {
+        for op in &[DbOp::Equal, DbOp::Greater, DbOp::GreaterEqual, DbOp::Less, DbOp::LessEqual] {
+            assert!(!value_matches_cmp(*op, &Value::from(18), &Value::from("18")));
+            assert!(!value_matches_cmp(*op, &Value::from(f64::NAN), &Value::from(f64::NAN)));
+        }
+    }
+
+    #[test]
+    fn test_doc_field_cmp() {
+        let doc1 = mk_document! { "a": 1 };
+        let doc2 = mk_document! { "b": 1 };
+        let doc3 = mk_document! {};
+        assert_eq!(doc_field_cmp(&doc1, &doc2, "a"), Ordering::Greater);
+        assert_eq!(doc_field_cmp(&doc3, &doc2, "a"), Ordering::Equal);
+        assert_eq!(doc_field_cmp(&doc2, &doc1, "b"), Ordering::Greater);
+
+        let doc4 = mk_document! { "a": mk_document! { "b": 2 } };
+        let doc5 = mk_document! { "a": mk_document! { "b": 1 } };
+        assert_eq!(doc_field_cmp(&doc4, &doc5, "a.b"), Ordering::Greater);
+        assert_eq!(doc_field_cmp(&doc1, &doc5, "a.b"), Ordering::Less);
+    }
+
+    #[test]
+    fn test_project_document() {
+        let doc = mk_document! {
+            "_id": 3,
+            "name": "Vincent",
+            "age": 32,
+            "content": "a very long text",
+        };
+
+        let projected = project_document(&doc, &["name", "not-exist"]);
+        assert_eq!(projected.len(), 2);
+        assert_eq!(projected.pkey_id().unwrap().unwrap_int(), 3);
+        assert_eq!(projected.get("name").unwrap().unwrap_string(), "Vincent");
+
+        let projected = project_document(&doc, &["_id", "age"]);
+        assert_eq!(projected.len(), 2);
+        assert!(projected.get("content").is_none());
+    }
+
+    #[test]
+    fn test_mk_upsert_document() {
+        let filter = mk_document! {
+            "name": "Vincent",
+            "age": mk_document! { "$gt": 18 },
+            "city": mk_document! { "$eq": "Guangzhou" },
+            "count": 1,
+        };
+        let update = mk_document! {
+            "$set": mk_document! { "count": 2, "job": "developer" },
+            "$inc": mk_document! { "visited": 1 },
+        };
+
+        let doc = mk_upsert_document(&filter, &update);
+        assert_eq!(doc.len(), 4);
+        assert_eq!(doc.get("name").unwrap().unwrap_string(), "Vincent");
+        assert_eq!(doc.get("city").unwrap().unwrap_string(), "Guangzhou");
+        assert_eq!(doc.get("count").unwrap().unwrap_int(), 2);
+        assert_eq!(doc.get("job").unwrap().unwrap_string(), "developer");
+        assert!(doc.get("age").is_none());
+    }
+
+}
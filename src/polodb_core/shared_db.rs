@@ -0,0 +1,146 @@
+use std::rc::Rc;
+use std::sync::{Mutex, MutexGuard};
+use polodb_bson::{Document, Value};
+use crate::{Database, DbResult};
+
+/// A database which can be shared between threads behind an `Arc`.
+///
+/// All the operations are serialized by a mutex, only one thread can get access
+/// to the database at the same time, even for the reading operations,
+/// because reading changes the page cache and the state of the transaction.
+///
+/// Every method is executed in its own transaction, so:
+/// - A reader never sees the partial result of a writer,
+///   it sees the data before or after the whole write.
+/// - Two writers never interleave, the second one runs after the first one commits or rolls back.
+///
+/// If a thread panics while holding the lock, the mutex is poisoned,
+/// and the following calls panic too.
+///
+/// Use [lock](#method.lock) to get the [Database](./db/struct.Database.html) for the other methods,
+/// or several operations in one user transaction.
+///
+/// # Example
+///
+/// ```rust
+/// use std::thread;
+/// use polodb_core::Database;
+/// use polodb_bson::mk_document;
+///
+/// let db = Database::open_shared("/tmp/test-shared-polo.db").unwrap();
+/// db.create_collection("test").unwrap_or(());
+///
+/// let db2 = db.clone();
+/// let handle = thread::spawn(move || {
+///     let mut doc = mk_document! {
+///         "name": "Vincent",
+///     };
+///     db2.insert("test", &mut doc).unwrap();
+/// });
+/// handle.join().unwrap();
+/// ```
+pub struct SharedDatabase {
+    inner: Mutex<Database>,
+}
+
+impl SharedDatabase {
+
+    pub(crate) fn new(db: Database) -> SharedDatabase {
+        SharedDatabase {
+            inner: Mutex::new(db),
+        }
+    }
+
+    /// Lock the database, other threads are blocked until the guard is dropped.
+    pub fn lock(&self) -> MutexGuard<'_, Database> {
+        self.inner.lock().expect("the shared database is poisoned")
+    }
+
+    pub fn create_collection(&self, name: &str) -> DbResult<()> {
+        self.lock().create_collection(name)?;
+        Ok(())
+    }
+
+    /// Insert the document to the collection named `col_name`,
+    /// the collection is created if it doesn't exist.
+    pub fn insert(&self, col_name: &str, doc: &mut Document) -> DbResult<bool> {
+        let mut db = self.lock();
+        let mut collection = db.collection(col_name)?;
+        collection.insert(doc)
+    }
+
+    pub fn insert_many(&self, col_name: &str, docs: Vec<Rc<Document>>) -> DbResult<Vec<Value>> {
+        self.lock().insert_many(col_name, docs)
+    }
+
+    pub fn find(&self, col_name: &str, filter: &Document) -> DbResult<Vec<Rc<Document>>> {
+        self.lock().find(col_name, filter)
+    }
+
+    pub fn find_one(&self, col_name: &str, filter: &Document) -> DbResult<Option<Rc<Document>>> {
+        self.lock().find_one(col_name, filter)
+    }
+
+    pub fn find_by_id(&self, col_name: &str, id: &Value) -> DbResult<Option<Rc<Document>>> {
+        self.lock().find_by_id(col_name, id)
+    }
+
+    pub fn count_documents(&self, col_name: &str, filter: &Document) -> DbResult<u64> {
+        self.lock().count_documents(col_name, filter)
+    }
+
+    pub fn update(&self, col_name: &str, filter: &Document, update: &Document) -> DbResult<usize> {
+        self.lock().update(col_name, filter, update)
+    }
+
+    pub fn delete(&self, col_name: &str, filter: &Document) -> DbResult<usize> {
+        self.lock().delete(col_name, filter)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::thread;
+    use polodb_bson::mk_document;
+    use crate::Database;
+
+    #[test]
+    fn test_shared_database() {
+        let mut db_path = env::temp_dir();
+        db_path.push("test-shared-db.db");
+        let mut journal_path = env::temp_dir();
+        journal_path.push("test-shared-db.db.journal");
+        let _ = std::fs::remove_file(db_path.as_path());
+        let _ = std::fs::remove_file(journal_path.as_path());
+
+        let db = Database::open_shared(db_path.as_path().to_str().unwrap()).unwrap();
+        db.create_collection("test").unwrap();
+
+        let mut handles = vec![];
+        for i in 0..4 {
+            let db = db.clone();
+            handles.push(thread::spawn(move || {
+                for j in 0..50 {
+                    let mut doc = mk_document! {
+                        "thread": i,
+                        "content": j,
+                    };
+                    db.insert("test", &mut doc).unwrap();
+                }
+            }));
+        }
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let empty = mk_document! {};
+        assert_eq!(db.count_documents("test", &empty).unwrap(), 200);
+
+        let filter = mk_document! { "thread": 2 };
+        assert_eq!(db.find("test", &filter).unwrap().len(), 50);
+    }
+
+}
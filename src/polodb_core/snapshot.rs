@@ -0,0 +1,102 @@
+use std::rc::Rc;
+use std::sync::Arc;
+use polodb_bson::{Document, Value};
+use crate::{Database, DbResult, QueryCursor};
+use crate::page::ReadView;
+
+/// The committed state of the database pinned by [Database::snapshot](./db/struct.Database.html#method.snapshot).
+///
+/// All the reads through the snapshot see the state when it's taken,
+/// including the cursors returned by [query](#method.query), the database can be written meanwhile.
+/// The reads take the database as the argument, it must be the one the snapshot is taken from,
+/// otherwise `DbErr::SnapshotNotOfDatabase` returns.
+/// Nothing can be written through a snapshot.
+///
+/// In the write-ahead log, the index of the frames committed is copied when the snapshot is taken,
+/// the reads ignore the frames appended later. The frames are not moved until the checkpoint,
+/// so the checkpoints are held off until the snapshot is dropped.
+/// In the shadow mode, the pages are written to the database file in place at the commit,
+/// the original ones are copied into the snapshot before they are overwritten.
+///
+/// The pin is only kept by this database, the checkpoints of other processes are not held off.
+///
+/// # Cost
+///
+/// - The index of the journal is copied, an entry for every page in the journal.
+/// - In the write-ahead log, the journal is not checkpointed while the snapshot is alive,
+///   it grows with every page written, even beyond `Config::journal_full_size`.
+///   [Database::checkpoint](./db/struct.Database.html#method.checkpoint) and
+///   [Database::compact](./db/struct.Database.html#method.compact) fail with `DbErr::Busy` meanwhile.
+/// - In the shadow mode, every page overwritten after the snapshot is taken is kept in the memory
+///   by the snapshot, up to the size of the whole database.
+/// - The pages read through the snapshot are not cached.
+///
+/// Everything is released when the snapshot is dropped, keep it as short as possible.
+///
+/// # Example
+///
+/// ```rust
+/// use polodb_core::Database;
+/// use polodb_bson::mk_document;
+///
+/// let mut db = Database::open_memory().unwrap();
+/// db.create_collection("test").unwrap();
+///
+/// let snapshot = db.snapshot().unwrap();
+/// db.insert("test", &mut mk_document! { "_id": 1 }).unwrap();
+///
+/// let count = snapshot.query(&mut db, "test", &mk_document! {}).unwrap().count();
+/// assert_eq!(count, 0);
+/// assert_eq!(db.count_documents("test", &mk_document! {}).unwrap(), 1);
+/// ```
+pub struct Snapshot {
+    view: Arc<ReadView>,
+}
+
+impl Snapshot {
+
+    pub(crate) fn new(view: Arc<ReadView>) -> Snapshot {
+        Snapshot {
+            view,
+        }
+    }
+
+    /// See [Database::find](./db/struct.Database.html#method.find).
+    #[inline]
+    pub fn find(&self, db: &mut Database, col_name: &str, filter: &Document) -> DbResult<Vec<Rc<Document>>> {
+        db.with_read_view(&self.view, |db| db.find(col_name, filter))
+    }
+
+    /// See [Database::find_one](./db/struct.Database.html#method.find_one).
+    #[inline]
+    pub fn find_one(&self, db: &mut Database, col_name: &str, filter: &Document) -> DbResult<Option<Rc<Document>>> {
+        db.with_read_view(&self.view, |db| db.find_one(col_name, filter))
+    }
+
+    /// See [Database::find_by_id](./db/struct.Database.html#method.find_by_id).
+    #[inline]
+    pub fn find_by_id(&self, db: &mut Database, col_name: &str, id: &Value) -> DbResult<Option<Rc<Document>>> {
+        db.with_read_view(&self.view, |db| db.find_by_id(col_name, id))
+    }
+
+    /// See [Database::query](./db/struct.Database.html#method.query).
+    ///
+    /// The cursor borrows the database, nothing can be written until it's dropped.
+    #[inline]
+    pub fn query<'a>(&self, db: &'a mut Database, col_name: &str, filter: &Document) -> DbResult<QueryCursor<'a>> {
+        db.query_in_view(&self.view, col_name, filter)
+    }
+
+    /// See [Database::count_documents](./db/struct.Database.html#method.count_documents).
+    #[inline]
+    pub fn count_documents(&self, db: &mut Database, col_name: &str, filter: &Document) -> DbResult<u64> {
+        db.with_read_view(&self.view, |db| db.count_documents(col_name, filter))
+    }
+
+    /// See [Database::distinct](./db/struct.Database.html#method.distinct).
+    #[inline]
+    pub fn distinct(&self, db: &mut Database, col_name: &str, field: &str, filter: &Document) -> DbResult<Vec<Value>> {
+        db.with_read_view(&self.view, |db| db.distinct(col_name, field, filter))
+    }
+
+}
@@ -0,0 +1,24 @@
+
+/// The statistics of the database returned by [Database::stats](./db/struct.Database.html#method.stats).
+#[derive(Debug, Clone)]
+pub struct DbStats {
+    pub page_size:      u32,
+
+    /// The count of the pages allocated, including the header page and the free pages.
+    pub total_pages:    u32,
+
+    /// The count of the pages in the free list, they are reused before allocating new pages.
+    pub free_pages:     u32,
+
+    /// The count of the pages storing the documents which are too large for a data page.
+    pub overflow_pages: u32,
+
+    /// The collections sorted by the names.
+    pub collections:    Vec<CollectionStats>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CollectionStats {
+    pub name:           String,
+    pub count:          u64,
+}
@@ -0,0 +1,130 @@
+use std::fs::{File, Metadata};
+use std::io::{self, Read, Write, Seek, SeekFrom, Cursor};
+
+/// The bytes behind the database or the journal,
+/// a file on the disk or a buffer in the memory.
+///
+/// The journal of a read-only database is kept in the memory,
+/// so nothing is written to the disk.
+pub(crate) enum Storage {
+    File(File),
+    Memory(Cursor<Vec<u8>>),
+}
+
+impl Storage {
+
+    #[inline]
+    pub(crate) fn new_memory(data: Vec<u8>) -> Storage {
+        Storage::Memory(Cursor::new(data))
+    }
+
+    pub(crate) fn len(&self) -> io::Result<u64> {
+        match self {
+            Storage::File(file) => Ok(file.metadata()?.len()),
+            Storage::Memory(cursor) => Ok(cursor.get_ref().len() as u64),
+        }
+    }
+
+    pub(crate) fn set_len(&mut self, size: u64) -> io::Result<()> {
+        match self {
+            Storage::File(file) => file.set_len(size),
+            Storage::Memory(cursor) => {
+                cursor.get_mut().resize(size as usize, 0);
+                Ok(())
+            }
+        }
+    }
+
+    pub(crate) fn sync_data(&self) -> io::Result<()> {
+        match self {
+            Storage::File(file) => file.sync_data(),
+            Storage::Memory(_) => Ok(()),
+        }
+    }
+
+    pub(crate) fn metadata(&self) -> io::Result<Metadata> {
+        match self {
+            Storage::File(file) => file.metadata(),
+            Storage::Memory(_) => Err(io::Error::other("no metadata for the memory storage")),
+        }
+    }
+
+    /// The file to lock, `None` for the memory,
+    /// which can't be shared with other processes.
+    #[inline]
+    pub(crate) fn as_file(&self) -> Option<&File> {
+        match self {
+            Storage::File(file) => Some(file),
+            Storage::Memory(_) => None,
+        }
+    }
+
+}
+
+impl Read for Storage {
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Storage::File(file) => file.read(buf),
+            Storage::Memory(cursor) => cursor.read(buf),
+        }
+    }
+
+}
+
+impl Write for Storage {
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Storage::File(file) => file.write(buf),
+            Storage::Memory(cursor) => cursor.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Storage::File(file) => file.flush(),
+            Storage::Memory(cursor) => cursor.flush(),
+        }
+    }
+
+}
+
+impl Seek for Storage {
+
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Storage::File(file) => file.seek(pos),
+            Storage::Memory(cursor) => cursor.seek(pos),
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write, Seek, SeekFrom};
+    use crate::storage::Storage;
+
+    #[test]
+    fn test_memory_storage() {
+        let mut storage = Storage::new_memory(Vec::new());
+        storage.set_len(16).unwrap();
+        assert_eq!(storage.len().unwrap(), 16);
+
+        storage.seek(SeekFrom::Start(20)).unwrap();
+        storage.write_all(&[1, 2, 3, 4]).unwrap();
+        assert_eq!(storage.len().unwrap(), 24);
+
+        let mut buffer = [0; 4];
+        storage.seek(SeekFrom::Start(20)).unwrap();
+        storage.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, [1, 2, 3, 4]);
+
+        storage.set_len(8).unwrap();
+        assert_eq!(storage.len().unwrap(), 8);
+        assert!(storage.as_file().is_none());
+        assert!(storage.metadata().is_err());
+    }
+
+}
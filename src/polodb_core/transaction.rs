@@ -0,0 +1,82 @@
+use std::ops::{Deref, DerefMut};
+use crate::{Database, DbResult, TransactionType};
+
+/// A write transaction started by [Database::transaction](./db/struct.Database.html#method.transaction).
+///
+/// The guard dereferences to the [Database](./db/struct.Database.html),
+/// all the operations through it are executed in the transaction.
+/// The pages written are kept in the journal until [commit](#method.commit),
+/// so all the changes appear after committing, or none of them if the process crashes before.
+///
+/// The transaction is rolled back if the guard is dropped without committing.
+///
+/// # Example
+///
+/// ```rust
+/// use polodb_core::Database;
+/// use polodb_bson::mk_document;
+///
+/// let mut db = Database::open("/tmp/test-transaction-doc.db").unwrap();
+///
+/// let mut txn = db.transaction().unwrap();
+/// let mut collection = txn.collection("test").unwrap();
+/// collection.insert(&mut mk_document! { "name": "Vincent" }).unwrap();
+/// collection.insert(&mut mk_document! { "name": "Chan" }).unwrap();
+/// txn.commit().unwrap();
+/// ```
+pub struct Transaction<'a> {
+    db: &'a mut Database,
+    finished: bool,
+}
+
+impl<'a> Transaction<'a> {
+
+    pub(crate) fn new(db: &'a mut Database) -> DbResult<Transaction<'a>> {
+        db.start_transaction(Some(TransactionType::Write))?;
+        Ok(Transaction {
+            db,
+            finished: false,
+        })
+    }
+
+    /// Commit all the changes of the transaction.
+    pub fn commit(mut self) -> DbResult<()> {
+        self.finished = true;
+        self.db.commit()
+    }
+
+    /// Discard all the changes of the transaction,
+    /// the pages in the journal are never written to the database file.
+    pub fn rollback(mut self) -> DbResult<()> {
+        self.finished = true;
+        self.db.rollback()
+    }
+
+}
+
+impl<'a> Deref for Transaction<'a> {
+    type Target = Database;
+
+    fn deref(&self) -> &Database {
+        self.db
+    }
+
+}
+
+impl<'a> DerefMut for Transaction<'a> {
+
+    fn deref_mut(&mut self) -> &mut Database {
+        self.db
+    }
+
+}
+
+impl<'a> Drop for Transaction<'a> {
+
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.db.rollback();  // ignored
+        }
+    }
+
+}
@@ -3,11 +3,28 @@ use super::label::{Label, LabelSlot, JumpTableRecord};
 use crate::vm::SubProgram;
 use crate::vm::op::DbOp;
 use crate::{DbResult, DbErr};
+use crate::query;
 use crate::error::{mk_field_name_type_unexpected, mk_invalid_query_field};
 
 const JUMP_TABLE_DEFAULT_SIZE: usize = 8;
 const PATH_DEFAULT_SIZE: usize = 8;
 
+// the value of "$and" and "$or" is a non-empty array of the sub queries
+fn unwrap_logic_array<'a>(op_name: &str, value: &'a Value) -> DbResult<&'a Array> {
+    match value {
+        Value::Array(arr) if !arr.is_empty() => Ok(arr.as_ref()),
+        Value::Array(_) => Err(DbErr::InvalidQuery(format!("the array of {} should not be empty", op_name))),
+        _ => Err(DbErr::InvalidQuery(format!("the value of {} should be an array, actual: {}", op_name, value.ty_name()))),
+    }
+}
+
+fn unwrap_logic_item<'a>(op_name: &str, value: &'a Value) -> DbResult<&'a Document> {
+    match value {
+        Value::Document(doc) => Ok(doc.as_ref()),
+        _ => Err(DbErr::InvalidQuery(format!("the items of {} should be documents, actual: {}", op_name, value.ty_name()))),
+    }
+}
+
 mod update_op {
     use polodb_bson::Value;
     use std::rc::Rc;
@@ -23,13 +40,14 @@ mod update_op {
             let clean_label = codegen.new_label();
             let next_element_label = codegen.new_label();
             let set_field_label = codegen.new_label();
+            let not_found_label = codegen.new_label();
 
             let rc_str: Rc<str> = key.as_str().into();
             let key_id_1 = codegen.push_static(Value::String(rc_str.clone()));
             let key_id_2 = codegen.push_static(Value::String(rc_str));
             let value_id = codegen.push_static(value.clone());
 
-            codegen.emit_goto2(DbOp::GetField, key_id_1, next_element_label);  // stack +1
+            codegen.emit_goto2(DbOp::GetField, key_id_1, not_found_label);  // stack +1
 
             codegen.emit_push_value(value_id);  // stack +2
 
@@ -61,6 +79,18 @@ mod update_op {
             codegen.emit(DbOp::Pop);
             codegen.emit(DbOp::Pop);
 
+            codegen.emit_goto(DbOp::Goto, next_element_label);
+
+            // the missing field is set to the value, the same as MongoDB
+            codegen.emit_label(not_found_label);
+
+            codegen.emit_push_value(value_id);
+
+            codegen.emit(DbOp::SetField);
+            codegen.emit_u32(key_id_2);
+
+            codegen.emit(DbOp::Pop);
+
             codegen.emit_label(next_element_label);
         }
 
@@ -163,18 +193,17 @@ impl Codegen {
                 continue;
             }
 
-            let key_static_id = self.push_static(Value::String(key.as_str().into()));
+            let field_size = self.recursively_get_field(key, close_label); // push the fields
             let value_static_id = self.push_static(value.clone());
-
-            self.emit_goto2(DbOp::GetField, key_static_id, close_label); // push a value1
             self.emit_push_value(value_static_id);  // push a value2
 
             self.emit(DbOp::Equal);
             // if not equal，go to next
             self.emit_goto(DbOp::IfFalse, close_label);
 
-            self.emit(DbOp::Pop); // pop a value2
-            self.emit(DbOp::Pop); // pop a value1
+            // pop the value2 and the fields
+            self.emit(DbOp::Pop2);
+            self.emit_u32((field_size + 1) as u32);
         }
 
         result_callback(self)?;
@@ -291,7 +320,7 @@ impl Codegen {
         for (index, item_doc_value) in arr.iter().enumerate() {
             let path_msg = format!("[{}]", index);
             path_hint!(self, path_msg.as_str(), {
-                let item_doc = crate::try_unwrap_document!("$and", item_doc_value);
+                let item_doc = unwrap_logic_item("$and", item_doc_value)?;
                 self.emit_standard_query_doc(
                     item_doc,
                     result_label, get_field_failed_label, not_found_label
@@ -306,10 +335,13 @@ impl Codegen {
                      arr: &Array,
                      result_label: Label, global_get_field_failed_label: Label, not_found_label: Label
     ) -> DbResult<()> {
+        // the conditions after the "$or" are checked if any item matches
+        let or_end_label = self.new_label();
+
         for (index, item_doc_value) in arr.iter().enumerate() {
             let path_msg = format!("[{}]", index);
             path_hint!(self, path_msg.as_str(), {
-                let item_doc = crate::try_unwrap_document!("$or", item_doc_value);
+                let item_doc = unwrap_logic_item("$or", item_doc_value)?;
                 if index == (arr.len() as usize) - 1 { // last item
                     for (key, value) in item_doc.iter() {
                         self.emit_query_tuple(key, value, result_label, global_get_field_failed_label, not_found_label)?;
@@ -331,13 +363,32 @@ impl Codegen {
                         local_get_field_failed_label,
                         local_get_field_failed_label
                     )?;
-                    // pass, goto result
-                    self.emit_goto(DbOp::Goto, result_label);
+                    // pass, skip the other items
+                    self.emit_goto(DbOp::Goto, or_end_label);
                     self.emit_label(go_next_label);
                 }
             });
         }
 
+        self.emit_label(or_end_label);
+
+        Ok(())
+    }
+
+    // The document matches if the sub query doesn't match,
+    // the missing fields make the sub query fail, so they match.
+    fn emit_logic_not<F>(&mut self, not_found_label: Label, emit_sub_query: F) -> DbResult<()> where
+        F: FnOnce(&mut Codegen, Label) -> DbResult<()> {
+
+        let sub_failed_label = self.new_label();
+
+        emit_sub_query(self, sub_failed_label)?;
+        // the sub query matches
+        self.emit_goto(DbOp::Goto, not_found_label);
+
+        self.emit_label(sub_failed_label);
+        self.emit(DbOp::RecoverStackPos);
+
         Ok(())
     }
 
@@ -350,12 +401,12 @@ impl Codegen {
                         result_label: Label,
                         get_field_failed_label: Label,
                         not_found_label: Label) -> DbResult<()> {
-        if key.chars().next().unwrap() == '$' {
+        if key.starts_with('$') {
             match key {
                 "$and" => {
-                    let sub_arr = crate::try_unwrap_array!("$and", value);
+                    let sub_arr = unwrap_logic_array("$and", value)?;
                     self.emit_logic_and(
-                        sub_arr.as_ref(),
+                        sub_arr,
                         result_label,
                         get_field_failed_label,
                         not_found_label
@@ -363,9 +414,9 @@ impl Codegen {
                 }
 
                 "$or" => {
-                    let sub_arr = crate::try_unwrap_array!("$or", value);
+                    let sub_arr = unwrap_logic_array("$or", value)?;
                     self.emit_logic_or(
-                        sub_arr.as_ref(),
+                        sub_arr,
                         result_label,
                         get_field_failed_label,
                         not_found_label
@@ -373,13 +424,16 @@ impl Codegen {
                 }
 
                 "$not" => {
-                    let sub_doc = crate::try_unwrap_document!("$not", value);
-                    // swap label
-                    let (get_field_failed_label, not_found_label) = (not_found_label, get_field_failed_label);
-                    return self.emit_query_tuple_document(
-                        key, &sub_doc,
-                        get_field_failed_label, not_found_label
-                    );
+                    let sub_doc = match value {
+                        Value::Document(doc) => doc.clone(),
+                        _ => return Err(DbErr::InvalidQuery(format!("the value of $not should be a document, actual: {}", value.ty_name()))),
+                    };
+                    self.emit_logic_not(not_found_label, |codegen, sub_failed_label| {
+                        codegen.emit_standard_query_doc(
+                            sub_doc.as_ref(),
+                            result_label, sub_failed_label, sub_failed_label
+                        )
+                    })?;
                 }
 
                 _ => return Err(DbErr::InvalidField(mk_invalid_query_field(self.last_key().into(), self.gen_path()))),
@@ -393,12 +447,9 @@ impl Codegen {
                     );
                 }
 
-                Value::Array(_) => return
-                    Err(DbErr::InvalidField(mk_invalid_query_field(self.last_key().into(), self.gen_path()))),
-
+                // the arrays are matched by equality too
                 _ => {
-                    let key_static_id = self.push_static(key.into());
-                    self.emit_goto2(DbOp::GetField, key_static_id, get_field_failed_label);
+                    let field_size = self.recursively_get_field(key, get_field_failed_label);
 
                     let value_static_id = self.push_static(value.clone());
                     self.emit_push_value(value_static_id);  // push a value2
@@ -407,8 +458,9 @@ impl Codegen {
                     // if not equal，go to next
                     self.emit_goto(DbOp::IfFalse, not_found_label);
 
-                    self.emit(DbOp::Pop); // pop a value2
-                    self.emit(DbOp::Pop); // pop a value1
+                    // pop the value2 and the fields
+                    self.emit(DbOp::Pop2);
+                    self.emit_u32((field_size + 1) as u32);
                 }
             }
         }
@@ -426,45 +478,85 @@ impl Codegen {
     }
 
     fn emit_query_tuple_document_kv(&mut self, key: &str, get_field_failed_label: Label, not_found_label: Label, sub_key: &str, sub_value: &Value) -> DbResult<()> {
-        match sub_key {
-            "$eq" => {
-                let field_size = self.recursively_get_field(key, get_field_failed_label);
+        if let Some(cmp_op) = query::cmp_op_of_name(sub_key) {
+            let field_size = self.recursively_get_field(key, get_field_failed_label);
 
-                let stat_val_id = self.push_static(sub_value.clone());
-                self.emit_push_value(stat_val_id);
-                self.emit(DbOp::Equal);
+            let stat_val_id = self.push_static(sub_value.clone());
+            self.emit_push_value(stat_val_id);
+            self.emit(cmp_op);
 
-                // if not equal，go to next
-                self.emit_goto(DbOp::IfFalse, not_found_label);
+            // if not matched，go to next
+            self.emit_goto(DbOp::IfFalse, not_found_label);
 
-                self.emit(DbOp::Pop2);
-                self.emit_u32((field_size + 1) as u32);
+            self.emit(DbOp::Pop2);
+            self.emit_u32((field_size + 1) as u32);
+
+            return Ok(());
+        }
+
+        match sub_key {
+            "$not" => {
+                let sub_doc = match sub_value {
+                    Value::Document(doc) => doc.clone(),
+                    _ => return Err(DbErr::InvalidQuery(format!("the value of $not should be a document, actual: {}", sub_value.ty_name()))),
+                };
+                self.emit_logic_not(not_found_label, |codegen, sub_failed_label| {
+                    codegen.emit_query_tuple_document(
+                        key, sub_doc.as_ref(),
+                        sub_failed_label, sub_failed_label
+                    )
+                })?;
             }
 
-            "$gt" => {
-                let field_size = self.recursively_get_field(key, get_field_failed_label);
+            // a field storing null exists
+            "$exists" => {
+                let expected = match sub_value {
+                    Value::Boolean(bl) => *bl,
+                    _ => return Err(DbErr::InvalidQuery(format!("the value of $exists should be a boolean, actual: {}", sub_value.ty_name()))),
+                };
 
-                let stat_val_id = self.push_static(sub_value.clone());
-                self.emit_push_value(stat_val_id);
-                self.emit(DbOp::Greater);
+                if expected {
+                    let field_size = self.recursively_get_field(key, get_field_failed_label);
 
-                self.emit_goto(DbOp::IfFalse, not_found_label);
+                    self.emit(DbOp::Pop2);
+                    self.emit_u32(field_size as u32);
+                } else {
+                    let field_missing_label = self.new_label();
 
-                self.emit(DbOp::Pop2);
-                self.emit_u32((field_size + 1) as u32);
+                    self.recursively_get_field(key, field_missing_label);
+                    self.emit_goto(DbOp::Goto, not_found_label);
+
+                    self.emit_label(field_missing_label);
+                    self.emit(DbOp::RecoverStackPos);
+                }
             }
 
-            "$gte" => {
+            // the values which are not strings don't match
+            #[cfg(feature = "regex")]
+            "$regex" => {
+                let pattern = match sub_value {
+                    Value::String(pattern) => pattern,
+                    _ => return Err(DbErr::InvalidQuery(format!("the value of $regex should be a string, actual: {}", sub_value.ty_name()))),
+                };
+                let regex = regex::Regex::new(pattern)
+                    .map_err(|err| DbErr::InvalidQuery(format!("invalid regex: {}", err)))?;
+                let regex_index = self.program.regex_list.len() as u32;
+                self.program.regex_list.push(regex);
+
                 let field_size = self.recursively_get_field(key, get_field_failed_label);
 
-                let stat_val_id = self.push_static(sub_value.clone());
-                self.emit_push_value(stat_val_id);
-                self.emit(DbOp::GreaterEqual);
+                self.emit(DbOp::Regex);
+                self.emit_u32(regex_index);
 
                 self.emit_goto(DbOp::IfFalse, not_found_label);
 
                 self.emit(DbOp::Pop2);
-                self.emit_u32((field_size + 1) as u32);
+                self.emit_u32(field_size as u32);
+            }
+
+            #[cfg(not(feature = "regex"))]
+            "$regex" => {
+                return Err(DbErr::InvalidQuery("$regex is not supported without the feature \"regex\"".into()));
             }
 
             // check the value is array
@@ -486,45 +578,28 @@ impl Codegen {
                 self.emit_u32((field_size + 1) as u32);
             }
 
-            "$lt" => {
-                let field_size = self.recursively_get_field(key, get_field_failed_label);
-
-                let stat_val_id = self.push_static(sub_value.clone());
-                self.emit_push_value(stat_val_id);
-                self.emit(DbOp::Less);
-
-                self.emit_goto(DbOp::IfFalse, not_found_label);
-
-                self.emit(DbOp::Pop2);
-                self.emit_u32((field_size + 1) as u32);
-            }
-
-            "$lte" => {
-                let field_size = self.recursively_get_field(key, get_field_failed_label);
-
-                let stat_val_id = self.push_static(sub_value.clone());
-                self.emit_push_value(stat_val_id);
-                self.emit(DbOp::LessEqual);
-
-                // less
-                self.emit_goto(DbOp::IfFalse, not_found_label);
-
-                self.emit(DbOp::Pop2);
-                self.emit_u32((field_size + 1) as u32);
-            }
-
+            // the same as MongoDB, the document missing the field matches
             "$ne" => {
-                let field_size = self.recursively_get_field(key, get_field_failed_label);
+                let field_missing_label = self.new_label();
+                let next_label = self.new_label();
+
+                let field_size = self.recursively_get_field(key, field_missing_label);
 
                 let stat_val_id = self.push_static(sub_value.clone());
                 self.emit_push_value(stat_val_id);
                 self.emit(DbOp::Equal);
 
                 // if equal，go to next
-                self.emit_goto(DbOp::IfFalse, not_found_label);
+                self.emit_goto(DbOp::IfTrue, not_found_label);
 
                 self.emit(DbOp::Pop2);
                 self.emit_u32((field_size + 1) as u32);
+                self.emit_goto(DbOp::Goto, next_label);
+
+                self.emit_label(field_missing_label);
+                self.emit(DbOp::RecoverStackPos);
+
+                self.emit_label(next_label);
             }
 
             "$nin" => {
@@ -535,7 +610,11 @@ impl Codegen {
                     ),
                 }
 
-                let field_size = self.recursively_get_field(key, get_field_failed_label);
+                // the same as MongoDB, the document missing the field matches
+                let field_missing_label = self.new_label();
+                let next_label = self.new_label();
+
+                let field_size = self.recursively_get_field(key, field_missing_label);
 
                 let stat_val_id = self.push_static(sub_value.clone());
                 self.emit_push_value(stat_val_id);
@@ -545,6 +624,12 @@ impl Codegen {
 
                 self.emit(DbOp::Pop2);
                 self.emit_u32((field_size + 1) as u32);
+                self.emit_goto(DbOp::Goto, next_label);
+
+                self.emit_label(field_missing_label);
+                self.emit(DbOp::RecoverStackPos);
+
+                self.emit_label(next_label);
             }
 
             "$size" => {
@@ -618,8 +703,20 @@ impl Codegen {
                     self.iterate_add_op(DbOp::MulField, doc.as_ref())?;
                 }
 
+                "$push" => {
+                    let doc = crate::try_unwrap_document!("$push", value);
+
+                    self.iterate_add_op(DbOp::PushField, doc.as_ref())?;
+                }
+
+                "$pull" => {
+                    let doc = crate::try_unwrap_document!("$pull", value);
+
+                    self.iterate_add_op(DbOp::PullField, doc.as_ref())?;
+                }
+
                 "$rename" => {
-                    let doc = crate::try_unwrap_document!("$set", value);
+                    let doc = crate::try_unwrap_document!("$rename", value);
 
                     for (key, value) in doc.iter() {
                         let new_name = match value {
@@ -630,6 +727,15 @@ impl Codegen {
                             }
                         };
 
+                        if key == "_id" || new_name.as_ref() == "_id" {
+                            return Err(DbErr::UnableToUpdatePrimaryKey);
+                        }
+
+                        // the field would be removed after moved to itself
+                        if key.as_str() == new_name.as_ref() {
+                            return Err(DbErr::InvalidQuery(format!("the source and the target of $rename should differ: {}", key)));
+                        }
+
                         self.emit_rename_field(key.as_str(), new_name.as_ref());
                     }
                 }
@@ -638,6 +744,10 @@ impl Codegen {
                     let doc = crate::try_unwrap_document!("$unset", value);
 
                     for (key, _) in doc.iter() {
+                        if key == "_id" {
+                            return Err(DbErr::UnableToUpdatePrimaryKey);
+                        }
+
                         self.emit_unset_field(key.as_str());
                     }
                 }
@@ -745,7 +855,7 @@ impl Codegen {
             self.program.instructions.extend_from_slice(&bytes);
             return;
         }
-        let bytes: [u8; 4] = (-1 as i32).to_le_bytes();
+        let bytes: [u8; 4] = (-1_i32).to_le_bytes();
         self.program.instructions.extend_from_slice(&bytes);
         self.jump_table.push(
             JumpTableRecord::new(record_loc, 1, label.pos())
@@ -764,7 +874,7 @@ impl Codegen {
             self.program.instructions.extend_from_slice(&bytes);
             return;
         }
-        let bytes2: [u8; 4] = (-1 as i32).to_le_bytes();
+        let bytes2: [u8; 4] = (-1_i32).to_le_bytes();
         self.program.instructions.extend_from_slice(&bytes2);
         self.jump_table.push(
             JumpTableRecord::new(record_loc, 5, label.pos())
@@ -4,16 +4,15 @@ mod codegen;
 mod label;
 
 pub(crate) use subprogram::SubProgram;
+pub(crate) use op::DbOp;
 
 use std::rc::Rc;
 use std::vec::Vec;
-use std::cmp::Ordering;
-use polodb_bson::Value;
-use op::DbOp;
+use polodb_bson::{Value, Array};
+use crate::query;
 use crate::cursor::Cursor;
 use crate::page::PageHandler;
-use crate::btree::{HEADER_SIZE, ITEM_SIZE};
-use crate::{TransactionType, DbResult, DbErr};
+use crate::{TransactionType, DbResult, DbErr, OperationOptions};
 use crate::error::mk_field_name_type_unexpected;
 use std::cell::Cell;
 
@@ -51,25 +50,14 @@ pub struct VM<'a> {
     stack:               Vec<Value>,
     pub(crate) program:  Box<SubProgram>,
     rollback_on_drop:    bool,
-}
-
-fn generic_cmp(op: DbOp, val1: &Value, val2: &Value) -> DbResult<bool> {
-    let ord = val1.value_cmp(val2)?;
-    let result = matches!((op, ord),
-        (DbOp::Equal, Ordering::Equal) |
-        (DbOp::Greater, Ordering::Greater) |
-        (DbOp::GreaterEqual, Ordering::Equal) |
-        (DbOp::GreaterEqual, Ordering::Greater) |
-        (DbOp::Less, Ordering::Less) |
-        (DbOp::LessEqual, Ordering::Equal) |
-        (DbOp::LessEqual, Ordering::Less)
-    );
-    Ok(result)
+    // the program reads the view of a snapshot
+    finish_read_view:    bool,
+    options:             OperationOptions<'a>,
 }
 
 impl<'a> VM<'a> {
 
-    pub(crate) fn new(page_handler: &mut PageHandler, program: Box<SubProgram>) -> VM {
+    pub(crate) fn new(page_handler: &mut PageHandler, program: Box<SubProgram>) -> VM<'_> {
         let stack = Vec::with_capacity(STACK_SIZE);
         let pc = program.instructions.as_ptr();
         VM {
@@ -83,12 +71,14 @@ impl<'a> VM<'a> {
             stack,
             program,
             rollback_on_drop: false,
+            finish_read_view: false,
+            options: OperationOptions::default(),
         }
     }
 
     #[inline]
     fn item_size(&self) -> u32 {
-        (self.page_handler.page_size - HEADER_SIZE) / ITEM_SIZE
+        self.page_handler.btree_order
     }
 
     fn auto_start_transaction(&mut self, ty: TransactionType) -> DbResult<()> {
@@ -111,7 +101,13 @@ impl<'a> VM<'a> {
         Ok(())
     }
 
+    #[inline]
+    pub(crate) fn set_options(&mut self, options: OperationOptions<'a>) {
+        self.options = options;
+    }
+
     fn reset_cursor(&mut self, is_empty: &Cell<bool>) -> DbResult<()> {
+        self.options.check_cancelled()?;
         let cursor = self.r1.as_mut().unwrap();
         cursor.reset(self.page_handler)?;
         if cursor.has_next() {
@@ -148,7 +144,12 @@ impl<'a> VM<'a> {
 
     fn next(&mut self) -> DbResult<()> {
         let cursor = self.r1.as_mut().unwrap();
+        let pid = cursor.current_pid();
         let _ = cursor.next(self.page_handler)?;
+        // checked when moving to another page instead of every document
+        if cursor.current_pid() != pid {
+            self.options.check_cancelled()?;
+        }
         match cursor.peek() {
             Some(ticket) => {
                 let doc = self.page_handler.get_doc_from_ticket(&ticket)?.unwrap();
@@ -200,9 +201,10 @@ impl<'a> VM<'a> {
 
             Some(Value::Int(original_int_value)) => {
                 let new_value = match value {
-                    Value::Int(inc_int_value) => {
-                        let new_value = *original_int_value + inc_int_value;
-                        Value::Int(new_value)
+                    Value::Int(inc_int_value) => match original_int_value.checked_add(inc_int_value) {
+                        Some(new_value) => Value::Int(new_value),
+                        // promote to double instead of overflowing
+                        None => Value::Double(*original_int_value as f64 + inc_int_value as f64),
                     }
 
                     Value::Double(inc_double_value) => {
@@ -242,6 +244,9 @@ impl<'a> VM<'a> {
             }
 
             None => {
+                if !matches!(value, Value::Int(_) | Value::Double(_)) {
+                    return Err(mk_field_name_type_unexpected(key, "number", value.ty_name()));
+                }
                 mut_doc.insert(key.into(), value);
             }
 
@@ -263,9 +268,9 @@ impl<'a> VM<'a> {
         match mut_doc.get(key) {
             Some(Value::Int(original_int_value)) => {
                 let new_value = match value {
-                    Value::Int(inc_int_value) => {
-                        let new_value = *original_int_value * inc_int_value;
-                        Value::Int(new_value)
+                    Value::Int(inc_int_value) => match original_int_value.checked_mul(inc_int_value) {
+                        Some(new_value) => Value::Int(new_value),
+                        None => Value::Double(*original_int_value as f64 * inc_int_value as f64),
                     }
 
                     Value::Double(inc_double_value) => {
@@ -304,14 +309,86 @@ impl<'a> VM<'a> {
                 return Err(mk_field_name_type_unexpected(key, "number", ty.ty_name()));
             }
 
+            // the same as MongoDB, the missing field is set to zero
             None => {
-                mut_doc.insert(key.into(), value);
+                let zero = match value {
+                    Value::Int(_) => Value::Int(0),
+                    Value::Double(_) => Value::Double(0.0),
+                    _ => {
+                        return Err(mk_field_name_type_unexpected(key, "number", value.ty_name()));
+                    }
+                };
+                mut_doc.insert(key.into(), zero);
             }
 
         }
         Ok(())
     }
 
+    fn push_field(&mut self, field_id: usize) -> DbResult<()> {
+        let key = self.program.static_values[field_id].unwrap_string();
+
+        let value_index = self.stack.len() - 1;
+        let doc_index = self.stack.len() - 2;
+
+        let value = self.stack[value_index].clone();
+
+        let doc = self.stack[doc_index].unwrap_document_mut();
+        let mut_doc = Rc::make_mut(doc);
+
+        match mut_doc.get(key) {
+            Some(Value::Array(arr)) => {
+                let mut new_arr = arr.as_ref().clone();
+                new_arr.push(value);
+                mut_doc.insert(key.into(), new_arr.into());
+            }
+
+            Some(ty) => {
+                return Err(mk_field_name_type_unexpected(key, "Array", ty.ty_name()));
+            }
+
+            None => {
+                let mut new_arr = Array::new();
+                new_arr.push(value);
+                mut_doc.insert(key.into(), new_arr.into());
+            }
+
+        }
+        Ok(())
+    }
+
+    fn pull_field(&mut self, field_id: usize) -> DbResult<()> {
+        let key = self.program.static_values[field_id].unwrap_string();
+
+        let value_index = self.stack.len() - 1;
+        let doc_index = self.stack.len() - 2;
+
+        let value = self.stack[value_index].clone();
+
+        let doc = self.stack[doc_index].unwrap_document_mut();
+        let mut_doc = Rc::make_mut(doc);
+
+        match mut_doc.get(key) {
+            Some(Value::Array(arr)) => {
+                let mut new_arr = Array::new();
+                for elm in arr.iter() {
+                    if *elm != value {
+                        new_arr.push(elm.clone());
+                    }
+                }
+                mut_doc.insert(key.into(), new_arr.into());
+            }
+
+            Some(ty) => {
+                return Err(mk_field_name_type_unexpected(key, "Array", ty.ty_name()));
+            }
+
+            None => (),
+
+        }
+        Ok(())
+    }
+
     fn unset_field(&mut self, field_id: u32) -> DbResult<()> {
         let key = self.program.static_values[field_id as usize].unwrap_string();
 
@@ -426,16 +503,16 @@ impl<'a> VM<'a> {
                         let key = self.borrow_static(key_stat_id as usize);
                         let key_name = key.unwrap_string();
                         let top = self.stack[self.stack.len() - 1].clone();
-                        let doc = match top {
-                            Value::Document(doc) => doc,
-                            _ => {
-                                let err = mk_field_name_type_unexpected(key_name, "Document", top.ty_name());
-                                self.state = VmState::Halt;
-                                return Err(err)
-                            }
+
+                        // a segment of the dotted path,
+                        // indexes into the array if it's numeric
+                        let field = match &top {
+                            Value::Document(doc) => doc.get(key_name),
+                            Value::Array(arr) => key_name.parse::<usize>().ok().and_then(|index| arr.get(index)),
+                            _ => None,
                         };
 
-                        match doc.get(key_name) {
+                        match field {
                             Some(val) => {
                                 self.stack.push(val.clone());
                                 self.pc = self.pc.add(9);
@@ -473,6 +550,22 @@ impl<'a> VM<'a> {
                         self.pc = self.pc.add(5);
                     }
 
+                    DbOp::PushField => {
+                        let filed_id = self.pc.add(1).cast::<u32>().read();
+
+                        try_vm!(self, self.push_field(filed_id as usize));
+
+                        self.pc = self.pc.add(5);
+                    }
+
+                    DbOp::PullField => {
+                        let filed_id = self.pc.add(1).cast::<u32>().read();
+
+                        try_vm!(self, self.pull_field(filed_id as usize));
+
+                        self.pc = self.pc.add(5);
+                    }
+
                     DbOp::SetField => {
                         let filed_id = self.pc.add(1).cast::<u32>().read();
 
@@ -506,6 +599,7 @@ impl<'a> VM<'a> {
                         let doc = top_value.unwrap_document();
 
                         self.r1.as_mut().unwrap().update_current(self.page_handler, doc.as_ref())?;
+                        self.r2 += 1;
 
                         self.pc = self.pc.add(1);
                     }
@@ -528,7 +622,7 @@ impl<'a> VM<'a> {
                         let val1 = &self.stack[self.stack.len() - 2];
                         let val2 = &self.stack[self.stack.len() - 1];
 
-                        let cmp = try_vm!(self, generic_cmp(op, val1, val2));
+                        let cmp = query::value_matches_cmp(op, val1, val2);
 
                         self.r0 = if cmp {
                             1
@@ -551,8 +645,7 @@ impl<'a> VM<'a> {
                         self.r0 = 0;
 
                         for item in top1.unwrap_array().iter() {
-                            let cmp_result = top2.value_cmp(item);
-                            if let Ok(Ordering::Equal) = cmp_result {
+                            if query::value_matches_cmp(DbOp::Equal, top2, item) {
                                 self.r0 = 1;
                                 break;
                             }
@@ -561,6 +654,19 @@ impl<'a> VM<'a> {
                         self.pc = self.pc.add(1);
                     }
 
+                    #[cfg(feature = "regex")]
+                    DbOp::Regex => {
+                        let regex_index = self.pc.add(1).cast::<u32>().read();
+                        let regex = &self.program.regex_list[regex_index as usize];
+
+                        self.r0 = match &self.stack[self.stack.len() - 1] {
+                            Value::String(content) => regex.is_match(content) as i32,
+                            _ => 0,
+                        };
+
+                        self.pc = self.pc.add(5);
+                    }
+
                     DbOp::OpenRead => {
                         let root_pid = self.pc.add(1).cast::<u32>().read();
 
@@ -615,6 +721,74 @@ impl<'a> VM<'a> {
         }
     }
 
+    /// Run the program again from the beginning, the cursor re-descends
+    /// to the leftmost leaf of the B-tree when it's opened.
+    pub(crate) fn rewind(&mut self) {
+        self.state = VmState::Init;
+        self.pc = self.program.instructions.as_ptr();
+        self.r0 = 0;
+        self.r1 = None;
+        self.r2 = 0;
+        self.r3 = 0;
+        self.stack.clear();
+    }
+
+    /// Count the rows after the current one, the position of the VM is kept.
+    /// If the program doesn't filter the documents, the items of the B-tree are counted
+    /// without reading the documents.
+    pub(crate) fn count_remaining(&mut self, is_full_scan: bool) -> DbResult<usize> {
+        let in_transaction = self.rollback_on_drop;
+        let saved_state = self.state;
+        let saved_pc = self.pc;
+        let saved_registers = (self.r0, self.r1.clone(), self.r2, self.r3);
+        let saved_stack = self.stack.clone();
+
+        let result = self.count_remaining_rows(is_full_scan);
+
+        self.state = saved_state;
+        self.pc = saved_pc;
+        (self.r0, self.r1, self.r2, self.r3) = saved_registers;
+        self.stack = saved_stack;
+
+        // the transaction is committed when the program is closed
+        if in_transaction && !self.rollback_on_drop {
+            self.auto_start_transaction(TransactionType::Read)?;
+        }
+
+        result
+    }
+
+    fn count_remaining_rows(&mut self, is_full_scan: bool) -> DbResult<usize> {
+        let mut count: usize = 0;
+        match self.state {
+            VmState::Halt => return Ok(0),
+
+            VmState::HasRow => (),
+
+            _ => {
+                self.execute()?;
+                if self.state != VmState::HasRow {
+                    return Ok(0);
+                }
+                count += 1;
+            }
+        }
+
+        if is_full_scan {
+            // the current item is the row returned
+            let cursor = self.r1.as_mut().unwrap();
+            return Ok(count + cursor.count_remaining(self.page_handler)? - 1);
+        }
+
+        loop {
+            self.execute()?;
+            if self.state != VmState::HasRow {
+                return Ok(count);
+            }
+            count += 1;
+        }
+    }
+
     pub(crate) fn commit_and_close(mut self) -> DbResult<()> {
         self.page_handler.auto_commit()?;
         self.rollback_on_drop = false;
@@ -625,18 +799,25 @@ impl<'a> VM<'a> {
         self.rollback_on_drop = value;
     }
 
+    pub(crate) fn set_finish_read_view_on_drop(&mut self, value: bool) {
+        self.finish_read_view = value;
+    }
+
 }
 
 impl<'a> Drop for VM<'a> {
 
     fn drop(&mut self) {
         if self.rollback_on_drop {
-            let _result = self.page_handler.rollback();
+            let _result = self.page_handler.auto_rollback();
             #[cfg(debug_assertions)]
             if let Err(err) = _result {
                 panic!("rollback fatal: {}", err);
             }
         }
+        if self.finish_read_view {
+            let _ = self.page_handler.finish_read_view();  // ignored
+        }
     }
 
 }
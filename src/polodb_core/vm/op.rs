@@ -99,7 +99,7 @@ pub enum DbOp {
     IncField,
 
     // multiple the field
-    // if not exists, set the value
+    // if not exists, set zero
     //
     // throw error if field is null
     //
@@ -110,6 +110,30 @@ pub enum DbOp {
     // op1. field_name_index: 4bytes
     MulField,
 
+    // append the value to the array field
+    // if not exists, set an array with the value
+    //
+    // throw error if field is not an array
+    //
+    // top-1 is the value to push
+    // top-2 is the doc to change
+    //
+    // 5 bytes
+    // op1. field_name_index: 4bytes
+    PushField,
+
+    // remove all the elements equal to the value from the array field
+    // if not exists, do nothing
+    //
+    // throw error if field is not an array
+    //
+    // top-1 is the value to push
+    // top-2 is the doc to change
+    //
+    // 5 bytes
+    // op1. field_name_index: 4bytes
+    PullField,
+
     // set the value of the field
     //
     // top-1 is the value to push
@@ -125,7 +149,7 @@ pub enum DbOp {
     // 1 byte
     ArraySize,
 
-    // update current item on cursor
+    // update current item on cursor, and increase the counter(r2)
     //
     // 1 byte
     UpdateCurrent,
@@ -153,6 +177,14 @@ pub enum DbOp {
     // the result is stored in r0
     In,
 
+    // check if the string on the top of the stack matches the regex,
+    // the result is stored in r0, false for the values which are not strings
+    //
+    // 5 bytes
+    // op1. regex_index: 4 bytes
+    #[cfg(feature = "regex")]
+    Regex,
+
     // open a cursor with op0 as root_pid
     //
     // 5 byes
@@ -10,6 +10,9 @@ pub(crate) struct SubProgram {
     pub(super) static_values:    Vec<Value>,
     pub(super) instructions:     Vec<u8>,
     pub(super) label_slots:      Vec<LabelSlot>,
+    // the patterns are compiled once when the query is compiled
+    #[cfg(feature = "regex")]
+    pub(super) regex_list:       Vec<regex::Regex>,
 }
 
 impl SubProgram {
@@ -19,6 +22,8 @@ impl SubProgram {
             static_values: Vec::with_capacity(32),
             instructions: Vec::with_capacity(256),
             label_slots: Vec::with_capacity(32),
+            #[cfg(feature = "regex")]
+            regex_list: Vec::new(),
         }
     }
 
@@ -44,7 +49,8 @@ impl SubProgram {
 
         codegen.emit_open_write(entry.root_pid());
 
-        codegen.emit_query_layout(query.unwrap(), |codegen| -> DbResult<()> {
+        let empty_query = Document::new_without_id();
+        codegen.emit_query_layout(query.unwrap_or(&empty_query), |codegen| -> DbResult<()> {
             codegen.emit_update_operation(update)?;
             codegen.emit(DbOp::Pop);
             Ok(())
@@ -204,6 +210,14 @@ impl fmt::Display for SubProgram {
                         pc += 1;
                     }
 
+                    #[cfg(feature = "regex")]
+                    DbOp::Regex => {
+                        let index = begin.add(pc + 1).cast::<u32>().read();
+                        let regex = &self.regex_list[index as usize];
+                        writeln!(f, "{}: Regex(\"{}\")", pc, regex.as_str())?;
+                        pc += 5;
+                    }
+
                     DbOp::OpenRead => {
                         let root_pid = begin.add(pc + 1).cast::<u32>().read();
                         writeln!(f, "{}: OpenRead({})", pc, root_pid)?;
@@ -253,6 +267,20 @@ impl fmt::Display for SubProgram {
                         pc += 5;
                     }
 
+                    DbOp::PushField => {
+                        let static_id = begin.add(pc + 1).cast::<u32>().read();
+                        let val = &self.static_values[static_id as usize];
+                        writeln!(f, "{}: PushField({})", pc, val)?;
+                        pc += 5;
+                    }
+
+                    DbOp::PullField => {
+                        let static_id = begin.add(pc + 1).cast::<u32>().read();
+                        let val = &self.static_values[static_id as usize];
+                        writeln!(f, "{}: PullField({})", pc, val)?;
+                        pc += 5;
+                    }
+
                     DbOp::SetField => {
                         let static_id = begin.add(pc + 1).cast::<u32>().read();
                         let val = &self.static_values[static_id as usize];
@@ -374,15 +402,13 @@ mod tests {
 83: PushValue("Vincent Chan")
 88: Equal
 89: FalseJump(37)
-94: Pop
-95: Pop
-96: GetField("age", 49)
-105: PushValue(32)
-110: Equal
-111: FalseJump(37)
-116: Pop
-117: Pop
-118: Goto(61)
+94: Pop2(2)
+99: GetField("age", 49)
+108: PushValue(32)
+113: Equal
+114: FalseJump(37)
+119: Pop2(2)
+124: Goto(61)
 "#;
         assert_eq!(expect, actual)
     }
@@ -415,11 +441,10 @@ mod tests {
 42: PushValue(32)
 47: Equal
 48: FalseJump(25)
-53: Pop
-54: Pop
-55: ResultRow
-56: Pop
-57: Goto(25)
+53: Pop2(2)
+58: ResultRow
+59: Pop
+60: Goto(25)
 "#;
         assert_eq!(expect, actual)
     }
@@ -475,15 +500,13 @@ mod tests {
 83: PushValue(6)
 88: Equal
 89: FalseJump(37)
-94: Pop
-95: Pop
-96: GetField("age", 49)
-105: PushValue(32)
-110: Equal
-111: FalseJump(37)
-116: Pop
-117: Pop
-118: Goto(61)
+94: Pop2(2)
+99: GetField("age", 49)
+108: PushValue(32)
+113: Equal
+114: FalseJump(37)
+119: Pop2(2)
+124: Goto(61)
 "#;
         assert_eq!(expect, actual)
     }
@@ -537,27 +560,27 @@ mod tests {
 73: SaveStackPos
 74: Goto(95)
 
-79: Label(7)
+79: Label(8)
 84: RecoverStackPos
-85: Goto(127)
+85: Goto(130)
 
-90: Label(8)
+90: Label(9)
 95: GetField("age", 84)
 104: PushValue(11)
 109: Equal
 110: FalseJump(84)
-115: Pop
-116: Pop
-117: Goto(61)
-
-122: Label(6)
-127: GetField("age", 49)
-136: PushValue(12)
-141: Equal
-142: FalseJump(37)
-147: Pop
-148: Pop
-149: Goto(61)
+115: Pop2(2)
+120: Goto(160)
+
+125: Label(7)
+130: GetField("age", 49)
+139: PushValue(12)
+144: Equal
+145: FalseJump(37)
+150: Pop2(2)
+
+155: Label(6)
+160: Goto(61)
 "#;
         assert_eq!(expect, actual);
     }
@@ -658,10 +681,10 @@ mod tests {
 
 0: OpenWrite(100)
 5: Rewind(30)
-10: Goto(196)
+10: Goto(217)
 
 15: Label(1)
-20: Next(196)
+20: Next(217)
 
 25: Label(5, "Close")
 30: Close
@@ -687,7 +710,7 @@ mod tests {
 83: PushValue(3)
 88: MulField("age")
 93: Pop
-94: GetField("age", 154)
+94: GetField("age", 159)
 103: PushValue(100)
 108: Less
 109: FalseJump(124)
@@ -699,32 +722,38 @@ mod tests {
 126: PushValue(100)
 131: SetField("age")
 136: Pop
-137: Goto(154)
+137: Goto(175)
 
 142: Label(6)
 147: Pop
 148: Pop
-
-149: Label(7)
-154: UnsetField("age")
-159: GetField("hello1", 184)
-168: SetField("hello2")
-173: Pop
-174: UnsetField("hello1")
-
-179: Label(9)
-184: UpdateCurrent
-185: Pop
-186: Goto(20)
-
-191: Label(0, "Compare")
-196: SaveStackPos
-197: GetField("_id", 49)
-206: PushValue(3)
-211: Greater
-212: FalseJump(37)
-217: Pop2(2)
-222: Goto(61)
+149: Goto(175)
+
+154: Label(9)
+159: PushValue(100)
+164: SetField("age")
+169: Pop
+
+170: Label(7)
+175: UnsetField("age")
+180: GetField("hello1", 205)
+189: SetField("hello2")
+194: Pop
+195: UnsetField("hello1")
+
+200: Label(10)
+205: UpdateCurrent
+206: Pop
+207: Goto(20)
+
+212: Label(0, "Compare")
+217: SaveStackPos
+218: GetField("_id", 49)
+227: PushValue(3)
+232: Greater
+233: FalseJump(37)
+238: Pop2(2)
+243: Goto(61)
 "#;
         assert_eq!(expect, actual);
     }
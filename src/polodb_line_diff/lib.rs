@@ -70,12 +70,12 @@ pub fn diff(a: &str, b: &str, splitter: &str) -> Vec<Diff> {
 
     let mut matrix: Vec<Vec<Item>> = make_matrix(a_lines.len() + 1, b_lines.len() + 1);
 
-    for i in 0..=a_lines.len() {
-        matrix[i][0].distance = i;
+    for (i, row) in matrix.iter_mut().enumerate() {
+        row[0].distance = i;
     }
 
-    for j in 0..=b_lines.len() {
-        matrix[0][j].distance = j;
+    for (j, item) in matrix[0].iter_mut().enumerate() {
+        item.distance = j;
     }
 
     for i in 1..=a_lines.len() {
@@ -169,7 +169,7 @@ impl std::fmt::Display for Diff {
 
 }
 
-fn backtracking(matrix: &Vec<Vec<Item>>, a_lines: &Vec<&str>, b_lines: &Vec<&str>, mut i: usize, mut j: usize) -> Vec<Diff> {
+fn backtracking(matrix: &[Vec<Item>], a_lines: &[&str], b_lines: &[&str], mut i: usize, mut j: usize) -> Vec<Diff> {
     let mut result: Vec<Diff> = vec![];
     while i > 0 && j > 0 {
         match matrix[i][j].op {
@@ -268,6 +268,7 @@ pub fn line_diff<T1: AsRef<str>, T2: AsRef<str>>(a: T1, b: T2) -> Vec<Diff> {
 }
 
 #[cfg(test)]
+#[allow(clippy::items_after_test_module)]
 mod tests {
     use crate::line_diff;
 